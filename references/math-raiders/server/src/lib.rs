@@ -13,11 +13,37 @@ use math_facts::{get_facts_for_grade, get_facts_for_grade_and_track, parse_fact_
 // Import bulk restore reducers for disaster recovery
 mod restore;
 
+// Per-player timezone / DST-aware day and week boundaries
+mod tz;
+
+// Overflow-safe Timestamp arithmetic
+mod time_math;
+
+// Bit-packed binary encoding for bulk restore payloads
+mod bitpack;
+
 // ==================== CONSTANTS ====================
 
 /// Maximum players per raid (supports up to 10-player squads)
 const MAX_PLAYERS_PER_RAID: usize = 10;
 
+/// World-boss events are a shared, drop-in spectacle - cap much higher than a squad raid
+const MAX_PLAYERS_PER_WORLD_BOSS: usize = 100;
+
+/// How often a new world-boss event opens (4 hours)
+const WORLD_BOSS_INTERVAL_SECS: u64 = 4 * 60 * 60;
+
+/// Matchmaking window before a world-boss event auto-starts, ready or not
+const WORLD_BOSS_MATCHMAKING_WINDOW_SECS: u64 = 120;
+
+/// Fixed boss_level for world-boss events - adaptive HP, Void Emperor visual
+/// (see encode_adaptive_boss) so it reads as the game's biggest fixed encounter
+const WORLD_BOSS_LEVEL: u8 = 108;
+
+/// Base world-boss HP contributed per connected session at spawn time, before
+/// real joiners' own contributions finalize it at auto_start_world_boss
+const WORLD_BOSS_HP_PER_SESSION: u32 = 50;
+
 /// Duration of 3-2-1-GO countdown before raid starts (seconds)
 const COUNTDOWN_DURATION_SECS: u64 = 4;
 
@@ -91,6 +117,122 @@ fn get_player(ctx: &ReducerContext) -> Result<Player, String> {
         .ok_or("Player not found".to_string())
 }
 
+// -------------------- Capability Authorization --------------------
+
+/// Parse a `per=<N><unit>` clause into a bucket size in microseconds
+/// Supported units: sec, min, hour
+fn parse_per_micros(value: &str) -> Option<u64> {
+    let (num_str, unit) = if let Some(n) = value.strip_suffix("hour") {
+        (n, 3_600u64)
+    } else if let Some(n) = value.strip_suffix("min") {
+        (n, 60u64)
+    } else if let Some(n) = value.strip_suffix("sec") {
+        (n, 1u64)
+    } else {
+        return None;
+    };
+    let seconds: u64 = num_str.parse().ok()?;
+    Some(seconds.saturating_mul(unit).saturating_mul(1_000_000))
+}
+
+/// Check that `arg_count` satisfies every `pnum<N` clause in `restrictions`
+fn check_pnum_restrictions(restrictions: &str, arg_count: usize) -> Result<(), String> {
+    for clause in restrictions.split_whitespace() {
+        if let Some(max_str) = clause.strip_prefix("pnum<") {
+            let max: usize = max_str.parse()
+                .map_err(|_| format!("Malformed restriction clause: {}", clause))?;
+            if arg_count >= max {
+                return Err(format!("Violated restriction: {} (got {} args)", clause, arg_count));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Find the `per=` (or `rate=`) clause in `restrictions` and return its bucket size in micros
+fn find_per_micros(restrictions: &str) -> Option<(String, u64)> {
+    for clause in restrictions.split_whitespace() {
+        if let Some(value) = clause.strip_prefix("per=") {
+            if let Some(micros) = parse_per_micros(value) {
+                return Some((clause.to_string(), micros));
+            }
+        } else if let Some(value) = clause.strip_prefix("rate=") {
+            // rate=N per minute ≡ per=(60/N)sec
+            if let Ok(n) = value.parse::<u64>() {
+                if n > 0 {
+                    let per_seconds = (60.0 / n as f64).ceil().max(1.0) as u64;
+                    return Some((clause.to_string(), per_seconds.saturating_mul(1_000_000)));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Capability-based authorization check for privileged reducers.
+///
+/// Full `authorized_worker` identities always pass (unrestricted trust).
+/// Otherwise, the caller must hold a `worker_capability` row scoped to
+/// `reducer_name`, and every clause in its `restrictions` must be satisfied:
+///   - `pnum<N` is checked immediately (no state needed)
+///   - `per=`/`rate=` is enforced with exact time division: the call is allowed
+///     only once per bucket (`now_micros / per_micros`), which is deterministic
+///     and needs no sliding-window bookkeeping
+pub(crate) fn check_capability(ctx: &ReducerContext, reducer_name: &str, arg_count: usize) -> Result<(), String> {
+    if ctx.db.authorized_worker().identity().find(&ctx.sender).is_some() {
+        return Ok(());
+    }
+
+    let mut grant = ctx.db.worker_capability()
+        .identity()
+        .filter(&ctx.sender)
+        .find(|g| g.reducer_name == reducer_name)
+        .ok_or_else(|| format!("No capability granted for reducer '{}'", reducer_name))?;
+
+    check_pnum_restrictions(&grant.restrictions, arg_count)?;
+
+    if let Some((clause, per_micros)) = find_per_micros(&grant.restrictions) {
+        let now_micros = ctx.timestamp.to_micros_since_unix_epoch() as u64;
+        let bucket = now_micros / per_micros.max(1);
+        if bucket == grant.last_bucket {
+            return Err(format!("Violated restriction: {} (already called this window)", clause));
+        }
+        grant.last_bucket = bucket;
+        ctx.db.worker_capability().id().update(grant);
+    }
+
+    Ok(())
+}
+
+/// Admin: grant (or replace) a narrowly-scoped capability for a worker identity.
+/// Only full `authorized_worker` identities can issue capabilities.
+#[reducer]
+pub fn grant_worker_capability(ctx: &ReducerContext, identity: Identity, reducer_name: String, restrictions: String) -> Result<(), String> {
+    if ctx.db.authorized_worker().identity().find(&ctx.sender).is_none() {
+        return Err("Unauthorized: only admins can grant capabilities".to_string());
+    }
+
+    // Manual uniqueness check: replace any existing grant for this (identity, reducer_name)
+    if let Some(existing) = ctx.db.worker_capability()
+        .identity()
+        .filter(&identity)
+        .find(|g| g.reducer_name == reducer_name)
+    {
+        ctx.db.worker_capability().id().delete(&existing.id);
+    }
+
+    ctx.db.worker_capability().insert(WorkerCapability {
+        id: 0,
+        identity,
+        reducer_name: reducer_name.clone(),
+        restrictions: restrictions.clone(),
+        last_bucket: 0,
+    });
+
+    log::info!("[CAPABILITY] granted identity:{} reducer:{} restrictions:\"{}\"", identity, reducer_name, restrictions);
+    Ok(())
+}
+
 /// Parse quest JSON data from player's quests field
 fn parse_quests(quests_json: &Option<String>) -> Value {
     quests_json.as_ref()
@@ -127,45 +269,24 @@ const WEEKLY_TIME_TARGET_SECS: u32 = 50 * 60;  // 50 minutes
 const DAILY_QUEST_REWARD: u32 = 400;
 const WEEKLY_QUEST_REWARD: u32 = 1500;
 
-/// Get today's start timestamp (midnight PST = 8am UTC)
-fn get_today_start(current: Timestamp) -> u64 {
-    const RESET_HOUR_UTC: u64 = 8;
-    let hour_in_micros = 60 * 60 * 1_000_000u64;
-    let day_in_micros = 24 * hour_in_micros;
-    let offset_micros = RESET_HOUR_UTC * hour_in_micros;
-    
-    let current_micros = current.to_micros_since_unix_epoch() as u64;
-    let current_offset = current_micros.saturating_sub(offset_micros);
-    let current_day = current_offset / day_in_micros;
-    
-    // Convert back to absolute timestamp
-    current_day * day_in_micros + offset_micros
+/// Get today's start timestamp in the player's local timezone (see tz module).
+/// Signed micros, not cast to u64 - a pre-epoch value must stay negative
+/// rather than wrap into a huge unsigned one.
+fn get_today_start(zone: &str, current: Timestamp) -> i64 {
+    tz::today_start_micros(zone, current)
 }
 
-/// Get this week's start timestamp (Monday midnight PST)
-fn get_week_start(current: Timestamp) -> u64 {
-    const RESET_HOUR_UTC: u64 = 8;
-    let hour_in_micros = 60 * 60 * 1_000_000u64;
-    let day_in_micros = 24 * hour_in_micros;
-    let week_in_micros = 7 * day_in_micros;
-    
-    // Unix epoch was Thursday, Monday = 4 days offset
-    let days_offset = 4u64;
-    let total_offset_micros = (days_offset * 24 + RESET_HOUR_UTC) * hour_in_micros;
-    
-    let current_micros = current.to_micros_since_unix_epoch() as u64;
-    let current_offset = current_micros.saturating_sub(total_offset_micros);
-    let current_week = current_offset / week_in_micros;
-    
-    current_week * week_in_micros + total_offset_micros
+/// Get this week's start timestamp (local Monday midnight, see tz module)
+fn get_week_start(zone: &str, current: Timestamp) -> i64 {
+    tz::week_start_micros(zone, current)
 }
 
 /// Calculate total play time from performance snapshots for a player since a given timestamp
-fn calculate_play_time_since(ctx: &ReducerContext, player_id: &str, since_micros: u64) -> u32 {
+fn calculate_play_time_since(ctx: &ReducerContext, player_id: &str, since_micros: i64) -> u32 {
     ctx.db.performance_snapshot()
         .player_id()
         .filter(&player_id.to_string())
-        .filter(|s| s.timestamp.to_micros_since_unix_epoch() as u64 >= since_micros)
+        .filter(|s| s.timestamp.to_micros_since_unix_epoch() >= since_micros)
         .map(|s| s.session_seconds)
         .sum()
 }
@@ -173,8 +294,8 @@ fn calculate_play_time_since(ctx: &ReducerContext, player_id: &str, since_micros
 /// Check and award time-based quest rewards
 /// Returns (daily_awarded, weekly_awarded) AP amounts
 fn check_and_award_time_quests(ctx: &ReducerContext, player: &mut Player, current: Timestamp) -> (u32, u32) {
-    let today_start = get_today_start(current);
-    let week_start = get_week_start(current);
+    let today_start = get_today_start(&player.timezone, current);
+    let week_start = get_week_start(&player.timezone, current);
     
     // Calculate total time played today and this week
     let daily_time = calculate_play_time_since(ctx, &player.id, today_start);
@@ -361,6 +482,120 @@ pub struct Player {
     
     /// Email for TimeBack events (required by API)
     pub email: Option<String>,
+
+    /// Abandon-tracking score (see raid_outcome / record_raid_outcome): increments
+    /// on Abandon outcomes, decays by one on Good outcomes. Not user-facing directly -
+    /// only its consequence (matchmaking_cooldown_until) is.
+    #[default(0u32)]
+    pub abandon_score: u32,
+
+    /// Set once abandon_score crosses the threshold; forces solo-only raids until
+    /// this time. None = no restriction. Gateway should surface this to explain why
+    /// multiplayer matchmaking is unavailable.
+    #[default(None::<Timestamp>)]
+    pub matchmaking_cooldown_until: Option<Timestamp>,
+
+    /// How many times this player has tripped the abandon threshold - escalates
+    /// the next cooldown duration (5min, 15min, then holds at 60min)
+    #[default(0u8)]
+    pub matchmaking_cooldown_strikes: u8,
+
+    /// Rolling 75th-percentile CQPM baseline for the player's current grade, used to
+    /// detect adaptive-HP sandbagging (see SANDBAG_RATIO_THRESHOLD). Only ratchets
+    /// upward - reset to 0.0 on a grade change so a genuine skill jump doesn't trip
+    /// the guard against the old grade's baseline (see recalculate_for_grade_change).
+    #[default(0.0f32)]
+    pub cqpm_baseline: f32,
+
+    /// Least-squares skill rating, recomputed incrementally after each raid - see
+    /// update_skill_ratings. Anchored around SKILL_RATING_ANCHOR; feeds into
+    /// calculate_player_contribution_with_context as a secondary multiplier on top
+    /// of the grade/DPM-based HP estimate.
+    #[default(1000.0f32)]
+    pub skill_rating: f32,
+
+    /// Day-bucket (see is_new_day) this player last claimed the daily first-victory
+    /// bonus - settle_raid_rewards checks this to grant DAILY_BONUS_MULTIPLIER_PCT
+    /// extra pool share on their first raid win of the day. None = never claimed.
+    /// Public on this table so clients can compare against "now" and show
+    /// "Daily Double active!" before the chest opens.
+    #[default(None::<Timestamp>)]
+    pub last_bonus_date: Option<Timestamp>,
+
+    /// Consecutive days the daily bonus has been claimed - adds
+    /// DAILY_BONUS_STREAK_BONUS_PCT_PER_DAY per day on top of the base multiplier,
+    /// capped at DAILY_BONUS_STREAK_BONUS_PCT_MAX. Resets to 1 on any gap longer
+    /// than one day since last_bonus_date.
+    #[default(0u8)]
+    pub daily_bonus_streak: u8,
+
+    /// Cumulative net bonus-target score across raids (see RaidPlayer.bonus_points/
+    /// wrong_count) - refresh_leaderboard uses this as a tiebreaker after
+    /// speed_percent. Can go negative on a rough stretch of wrong answers.
+    #[default(0i32)]
+    pub bonus_score: i32,
+
+    /// This player's FSRS target retention (see simulate_review_load) - the due
+    /// cutoff generate_adaptive_problem's weighting aims for, tuned per-player so
+    /// slower kids get lighter review loads and advanced kids push toward higher
+    /// retention. Recomputed every RETENTION_SIM_INTERVAL_PROBLEMS problems.
+    #[default(0.9f32)]
+    pub target_retention: f32,
+
+    /// Cached count of this player's fact_mastery rows at/above mastery level 5,
+    /// for the fact pool of the grade they were in when last tallied. Maintained
+    /// incrementally by update_fact_mastery (see mastery_aggregate_delta) instead
+    /// of rescanning fact_mastery on every read - see get_player_mastery_stats.
+    #[default(0u32)]
+    pub mastered_fact_count: u32,
+
+    /// Cached count of distinct facts this player has ever attempted. Maintained
+    /// alongside mastered_fact_count.
+    #[default(0u32)]
+    pub facts_seen_count: u32,
+
+    /// Running sum of FSRS stability (see FactMastery.stability) across this
+    /// player's fact_mastery rows - a rough proxy for overall memory health,
+    /// maintained incrementally alongside mastered_fact_count.
+    #[default(0.0f32)]
+    pub stability_sum: f32,
+
+    /// Cached division (I-IV, "" for legendary, "IV" for unranked) - refreshed
+    /// alongside rank whenever mastered_fact_count changes. See calculate_division.
+    #[default(None::<String>)]
+    pub division: Option<String>,
+
+    /// Hidden Elo-style rating vs. the boss, updated every raid this player
+    /// finishes (solo or multiplayer) - see update_elo_rating. Distinct from
+    /// skill_rating: that's a least-squares rating of players relative to each
+    /// other (squad-only); this is an absolute rating of the player against an
+    /// opponent derived from boss difficulty, so it also runs for solo raids.
+    #[default(1200.0f32)]
+    pub elo_rating: f32,
+
+    /// Peak elo_rating ever reached - only ratchets upward, same as
+    /// best_response_ms's pattern. Exposed on LeaderboardEntry. Grouping
+    /// multiplayer players by rating band isn't wired up: this codebase has
+    /// no automated matchmaking (rooms are joined by room code - see the
+    /// "auto-matchmaking removed for safety" note near join_room), so there's
+    /// no queue for a band to feed into yet.
+    #[default(1200.0f32)]
+    pub best_elo_rating: f32,
+
+    /// IANA zone name (see tz module's ZONE_TABLE) used to compute this
+    /// player's local daily/weekly reset boundaries - see is_new_day,
+    /// is_new_week, calculate_days_between. Unrecognized values fall back to
+    /// the old fixed "midnight PST" behavior rather than erroring.
+    pub timezone: String,
+
+    /// Adaptive per-player difficulty on a DIFFICULTY_LEVEL_FLOOR..=CEILING
+    /// scale, nudged after every raid by update_difficulty_level (a bounded
+    /// proportional feedback step, not a random walk). Biases fact selection
+    /// within the player's grade - see difficulty_level_shift and
+    /// generate_adaptive_problem - it never changes which grade's facts are
+    /// in play, only where within that pool a strong/struggling player lands.
+    #[default(88i32)]
+    pub difficulty_level: i32,
 }
 
 // ==================== VIEWS ====================
@@ -405,12 +640,60 @@ pub struct TimebackEventQueue {
     /// When to retry (NULL = now)
     #[index(btree)]
     pub next_retry_at: Option<Timestamp>,
-    
+
     /// Last error if failed
     pub last_error: Option<String>,
-    
+
     /// When successfully sent
     pub sent_at: Option<Timestamp>,
+
+    /// Minute-epoch (unix time / TIMEBACK_RETRY_BUCKET_SECS) this event becomes
+    /// due - next_retry_at (or created_at, for a fresh event) floored to the
+    /// bucket. Paired with `claimed`, this is a bucketed expiration queue:
+    /// dispatch_due_timeback_events only has to look at the small unclaimed
+    /// set instead of scanning every historical row in this ever-growing table.
+    #[index(btree)]
+    pub due_epoch: u64,
+
+    /// Flipped true by dispatch_due_timeback_events once this event's
+    /// due_epoch bucket arrives - the worker's slice is claimed && !sent,
+    /// not the whole table. Re-cleared to false by mark_event_sent whenever
+    /// a failed send re-buckets the event into the future.
+    #[index(btree)]
+    pub claimed: bool,
+}
+
+/// Dead-lettered TimeBack events: exceeded max retries, or `classify_error`
+/// judged the failure permanent (e.g. a 4xx rejection no amount of retrying
+/// will fix). Parked here instead of retried forever, with the full attempt
+/// history preserved so an operator can inspect what went wrong and decide
+/// whether to `requeue_dead_letter` once the underlying issue is resolved.
+#[table(name = timeback_dead_letter, public)]
+pub struct TimebackDeadLetter {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+
+    /// Original timeback_event_queue row id, for traceability
+    pub original_event_id: u64,
+
+    #[index(btree)]
+    pub player_id: String,
+
+    /// JSON payload, unchanged from the original queue row
+    pub payload: String,
+
+    /// Attempts made before giving up
+    pub attempts: u8,
+
+    /// Last error seen before dead-lettering
+    pub last_error: Option<String>,
+
+    /// When the event was originally enqueued
+    pub created_at: Timestamp,
+
+    /// When it was moved to the dead letter table
+    pub dead_lettered_at: Timestamp,
 }
 
 /// Authorized identities that can access protected tables and admin reducers
@@ -421,6 +704,110 @@ pub struct AuthorizedWorker {
     pub identity: Identity,
 }
 
+/// Fine-grained capability grant for a worker identity on a specific reducer
+/// Inspired by Core Lightning's "runes": lets operators hand out narrowly-scoped
+/// identities (e.g. "may drain the TimeBack queue once every 5 seconds") instead
+/// of the all-or-nothing trust that `authorized_worker` grants.
+/// Note: No multi-column unique constraint support, so (identity, reducer_name)
+/// uniqueness is enforced manually in grant_worker_capability.
+#[table(name = worker_capability)]
+pub struct WorkerCapability {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+
+    #[index(btree)]
+    pub identity: Identity,
+
+    #[index(btree)]
+    pub reducer_name: String,
+
+    /// Space-separated restriction clauses, e.g. "per=5sec pnum<2"
+    /// Supported clauses:
+    ///   per=5sec|1min|1hour - exact time-division throttle (see check_capability)
+    ///   rate=60             - shorthand for per=(60/N)sec, N calls/minute
+    ///   pnum<N               - reject calls with N or more arguments
+    pub restrictions: String,
+
+    /// Last bucket index (now_micros / per_micros) this capability was allowed to fire in
+    #[default(0u64)]
+    pub last_bucket: u64,
+}
+
+/// Tracks progress of a chunked bulk restore (see begin_restore/restore_chunk/
+/// finish_restore in restore.rs) - client picks session_id so a retried
+/// begin_restore after a dropped connection resumes the same session instead
+/// of starting a duplicate one.
+#[table(name = restore_session)]
+pub struct RestoreSession {
+    #[primary_key]
+    pub session_id: String,
+
+    /// Which bulk_restore_* table this session is importing into -
+    /// "player", "fact_mastery", or "performance_snapshot"
+    pub table_name: String,
+
+    pub chunks_expected: u32,
+
+    /// Next chunk_index restore_chunk will accept - chunks below this were
+    /// already applied, so re-sending one is a no-op rather than an error
+    pub chunks_received: u32,
+
+    pub rows_restored: u32,
+
+    pub started_at: Timestamp,
+
+    #[index(btree)]
+    pub last_chunk_at: Timestamp,
+
+    #[default(false)]
+    pub completed: bool,
+}
+
+/// How long an incomplete restore_session can go without a chunk before
+/// restore_session_gc treats it as abandoned and deletes it
+const RESTORE_SESSION_STALE_SECS: u64 = 60 * 60;
+
+/// Schedule table for restore_session_gc - same recurring-interval pattern as
+/// cleanup_schedule/announcement_prune_schedule
+#[table(name = restore_session_gc_schedule, scheduled(restore_session_gc))]
+pub struct RestoreSessionGcSchedule {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+
+    pub scheduled_at: ScheduleAt,
+}
+
+/// Deletes incomplete restore_session rows that haven't received a chunk in
+/// RESTORE_SESSION_STALE_SECS - a dropped admin-panel upload otherwise leaves
+/// the session parked forever, blocking a fresh begin_restore under the same id
+#[reducer]
+pub fn restore_session_gc(ctx: &ReducerContext, _schedule: RestoreSessionGcSchedule) {
+    if ctx.sender != ctx.identity() {
+        log::warn!("Client {} attempted to call restore_session_gc", ctx.sender);
+        return;
+    }
+
+    let mut removed = 0u32;
+    for session in ctx.db.restore_session().iter() {
+        if session.completed {
+            continue;
+        }
+        let age_secs = ctx.timestamp.duration_since(session.last_chunk_at)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        if age_secs > RESTORE_SESSION_STALE_SECS {
+            ctx.db.restore_session().session_id().delete(&session.session_id);
+            removed += 1;
+        }
+    }
+
+    if removed > 0 {
+        log::info!("[RESTORE] garbage-collected {} stale restore session(s)", removed);
+    }
+}
+
 /// Active raid session
 #[table(name = raid, public)]
 pub struct Raid {
@@ -465,6 +852,51 @@ pub struct Raid {
     /// None for legacy raids or after countdown completes
     #[default(None::<Timestamp>)]
     pub countdown_started_at: Option<Timestamp>,
+
+    /// Index into this boss's ordered boss_phase rows for the phase currently in
+    /// effect (0 = opening phase). Derived from boss_hp/boss_max_hp - see
+    /// recompute_raid_phase. Works for adaptive bosses too since boss_max_hp
+    /// already holds the raid's personalized starting HP, not BOSS_HP_VALUES.
+    #[default(0u8)]
+    pub current_phase_index: u8,
+
+    /// True for the single open world-boss event raid (room_code None, no leader,
+    /// anyone can join_world_boss and it auto-starts - see spawn_world_boss).
+    /// Private solo/room raids also have room_code None, so this flag - not
+    /// room_code - is what distinguishes a world boss from a solo raid.
+    #[default(false)]
+    pub is_public: bool,
+
+    /// True for a two-team PvP room (set via set_pvp_mode, private rooms only).
+    /// Teams still fight the same shared boss - see assign_team and team_a_damage/
+    /// team_b_damage - the "win" is which team out-contributed the other.
+    #[default(false)]
+    pub is_pvp: bool,
+
+    /// Cumulative damage dealt by team 0 this raid - see submit_answer
+    #[default(0u32)]
+    pub team_a_damage: u32,
+
+    /// Cumulative damage dealt by team 1 this raid - see submit_answer
+    #[default(0u32)]
+    pub team_b_damage: u32,
+
+    /// Next offset to assign in this raid's replay_raid event stream - see
+    /// record_raid_event / RaidEvent. Monotonically increasing, never reused.
+    #[default(0u64)]
+    pub event_offset: u64,
+
+    /// When the boss-tick enrage mechanic engaged for this raid (BOSS_TICK_GRACE_PERIOD_SECS
+    /// after started_at) - None before that grace period elapses. Shifted forward by the
+    /// pause duration on resume, same as started_at, so paused time isn't held against it.
+    #[default(None::<Timestamp>)]
+    pub enrage_started_at: Option<Timestamp>,
+
+    /// Current per-tick boss HP regen, as a percent of boss_max_hp - see fire_boss_tick.
+    /// 0 until enrage engages, then starts at BOSS_TICK_BASE_REGEN_PCT and escalates
+    /// while the raid's active players keep falling behind pace.
+    #[default(0.0f32)]
+    pub boss_tick_rate_pct: f32,
 }
 
 #[derive(SpacetimeType, Debug, Clone, PartialEq)]
@@ -533,948 +965,3479 @@ pub struct RaidPlayer {
     /// Format: "7x8,3x4,5x6" - used to prevent repeats
     pub recent_problems: String,
     
-    /// Pre-calculated chest bonus (None = not calculated yet or already claimed)
-    pub pending_chest_bonus: Option<u32>,
-    
     /// Track selected for this raid (None = all facts for grade, "ALL" = explicit all selection)
     pub track: Option<String>,
+
+    /// When this player was last marked inactive (disconnect/leave), for abandon-detection's
+    /// reconnect grace window. Cleared back to None whenever they reconnect.
+    #[default(None::<Timestamp>)]
+    pub inactive_since: Option<Timestamp>,
+
+    /// When this player last submitted an answer, reset to the raid's real start time in
+    /// countdown_complete. A connected-but-idle player whose answer this falls too far
+    /// behind gets marked inactive by check_idle_players, same as if they'd disconnected.
+    pub last_answered_at: Timestamp,
+
+    /// Team assignment for PvP rooms (0 or 1) - see assign_team. None outside
+    /// is_pvp raids, where teams don't apply.
+    #[default(None::<u8>)]
+    pub team: Option<u8>,
+
+    /// Consecutive first-attempt correct answers right now (see submit_answer's
+    /// streak damage bonus) - resets to 0 on a miss or a retry, never on a timeout
+    /// alone (the timeout itself inserts a wrong PlayerAnswer, which resets it)
+    #[default(0u32)]
+    pub current_streak: u32,
+
+    /// Highest current_streak reached this raid - folded into the streak bonus
+    /// in settle_raid_rewards instead of rewarding only the streak at raid end
+    #[default(0u32)]
+    pub peak_streak: u32,
+
+    /// Set once by mark_player_inactive_in_raid and never cleared, even on
+    /// reconnect (unlike inactive_since) - lets settle_raid_rewards dock a player
+    /// who bailed mid-raid and came back, not just one who's still gone at the end
+    #[default(false)]
+    pub disconnected_mid_raid: bool,
+
+    /// Consecutive fast-correct (first-attempt, under get_fast_threshold_ms) answers
+    /// right now - see BONUS_TARGET_FAST_STREAK_N. Resets on a miss, a retry, or a
+    /// correct-but-not-fast answer. Distinct from current_streak, which doesn't
+    /// care about speed.
+    #[default(0u32)]
+    pub fast_streak: u32,
+
+    /// Raid-scoped bonus-target score: +BONUS_TARGET_POINTS_PER_BURST per
+    /// fast_streak burst triggered, read by settle_raid_rewards/refresh_leaderboard
+    #[default(0u32)]
+    pub bonus_points: u32,
+
+    /// Raid-scoped count of wrong first-attempt answers - each one subtracts
+    /// BONUS_TARGET_WRONG_PENALTY from this player's net bonus score
+    #[default(0u32)]
+    pub wrong_count: u32,
+
+    /// Live combo buffs/debuffs, resolved into the damage calc by
+    /// apply_buffs_to_damage (see calculate_damage). Expired entries are swept
+    /// out each answer rather than on their own schedule.
+    pub active_buffs: Vec<ActiveBuff>,
 }
 
-/// Math problem presented to players
-#[table(
-    name = problem, 
-    public,
-    index(name = idx_raid_player, btree(columns = [raid_id, player_id]))
-)]
-pub struct Problem {
+/// Kind of combo modifier tracked in RaidPlayer.active_buffs - see
+/// apply_buffs_to_damage and calculate_damage
+#[derive(SpacetimeType, Debug, Clone, Copy, PartialEq)]
+pub enum BuffKind {
+    Combo,    // Escalating damage bonus from a sustained answer streak
+    Focus,    // Doubles crit chance, earned by a fast-answer streak
+    Stagger,  // Halves damage, applied by a wrong answer, cleared on the next correct one
+}
+
+/// One active buff/debuff on a raid_player - see BuffKind and apply_buffs_to_damage.
+/// `magnitude` is a percentage for Combo/Stagger (damage bonus/penalty) and a
+/// flat percentage-point crit-chance add for Focus
+#[derive(SpacetimeType, Debug, Clone, Copy, PartialEq)]
+pub struct ActiveBuff {
+    pub kind: BuffKind,
+    pub magnitude: f32,
+    pub expires_at: Timestamp,
+}
+
+/// A player watching a raid's room code without participating - see join_as_spectator.
+/// Deliberately separate from raid_player: spectators never count toward
+/// active_player_count, MAX_PLAYERS_PER_RAID, readiness, or pause_raid_if_empty.
+#[table(name = spectator, public)]
+pub struct Spectator {
     #[primary_key]
     #[auto_inc]
     pub id: u64,
-    
-    /// Which raid this problem belongs to
-    #[index(btree)]
-    pub raid_id: u64,
-    
-    /// Which player this problem is for
+
     #[index(btree)]
     pub player_id: String,
-    
-    /// Left operand
-    pub left_operand: u8,
-    
-    /// Right operand  
-    pub right_operand: u8,
-    
-    /// Operation (for now just multiplication)
-    pub operation: Operation,
-    
-    /// Correct answer
-    pub answer: u16,
-    
-    /// When this problem was shown
-    pub issued_at: Timestamp,
-    
-    /// Problem sequence number in raid
-    pub sequence: u32,
-}
 
-#[derive(SpacetimeType, Debug, Clone, PartialEq)]
-pub enum Operation {
-    Add,
-    Subtract,
-    Multiply,
-    Divide,
-}
+    #[index(btree)]
+    pub raid_id: u64,
 
-impl Operation {
-    /// Compute the result of applying this operation to two operands
-    pub fn compute(&self, left: u8, right: u8) -> i16 {
-        match self {
-            Operation::Add => (left as i16) + (right as i16),
-            Operation::Subtract => (left as i16) - (right as i16),
-            Operation::Multiply => (left as i16) * (right as i16),
-            Operation::Divide => {
-                // For division, we ensure no division by zero
-                // This should be handled by fact generation, but let's be safe
-                if right == 0 {
-                    0
-                } else {
-                    (left as i16) / (right as i16)
-                }
-            }
-        }
-    }
-    
-    /// Get the display symbol for this operation
-    pub fn symbol(&self) -> &'static str {
-        match self {
-            Operation::Add => "+",
-            Operation::Subtract => "-",
-            Operation::Multiply => "×",
-            Operation::Divide => "÷",
-        }
-    }
+    pub joined_at: Timestamp,
 }
 
-/// Single attempt record for fact mastery tracking
-#[derive(SpacetimeType, Clone, Debug)]
-pub struct AttemptRecord {
-    /// Response time in milliseconds
-    pub time_ms: u32,
-    /// Whether answer was correct
-    pub correct: bool,
-    /// When this attempt occurred
-    pub timestamp: Timestamp,
+/// How a player's participation in a (multiplayer) raid resolved, for abandon tracking.
+/// See classify_raid_outcomes / record_raid_outcome near the pause/resume helpers.
+#[derive(SpacetimeType, Debug, Clone, Copy, PartialEq)]
+pub enum RaidOutcomeKind {
+    Good,    // Played to the raid's end (victory/defeat) or reconnected within the grace window
+    Abandon, // Went inactive and never reconnected before the raid ended
+    NoShow,  // Matched but never readied up - never actually played
 }
 
-/// Player's answer to a problem
-#[table(name = player_answer, public)]  // Client needs to subscribe
-pub struct PlayerAnswer {
+/// Per-player outcome record for a completed multiplayer raid, used to maintain
+/// Player.abandon_score. One row per (player, raid) - solo raids never insert here.
+#[table(name = raid_outcome, public)]
+pub struct RaidOutcome {
     #[primary_key]
     #[auto_inc]
     pub id: u64,
-    
-    #[index(btree)]
-    pub problem_id: u64,
-    
+
     #[index(btree)]
     pub player_id: String,
-    
-    /// Response time in milliseconds
-    pub response_ms: u32,
-    
-    /// Whether answer was correct
-    pub is_correct: bool,
-    
-    /// Damage dealt (0 if incorrect)
-    pub damage: u32,
+
+    #[index(btree)]
+    pub raid_id: u64,
+
+    pub outcome: RaidOutcomeKind,
+
+    pub recorded_at: Timestamp,
 }
 
-/// Track player performance on specific multiplication facts for automaticity training
-#[table(name = fact_mastery, public)]  // Needs public for client subscriptions
-pub struct FactMastery {
+/// Contribution-scored post-battle summary for one player in one completed raid -
+/// see settle_raid_rewards. One row per (raid, player), inserted once when the raid
+/// ends; clients read this for the results screen instead of re-deriving it from
+/// RaidPlayer's raw counters.
+#[table(name = raid_reward, public)]
+pub struct RaidReward {
     #[primary_key]
     #[auto_inc]
     pub id: u64,
-    
-    /// Player this record belongs to
+
+    #[index(btree)]
+    pub raid_id: u64,
+
     #[index(btree)]
     pub player_id: String,
-    
-    /// Normalized fact key (e.g., "3x7" where first number is always smaller)
+
+    /// Share of RAID_REWARD_CONTRIBUTION_POOL proportional to this player's damage
+    /// fraction, scaled by their accuracy
+    pub contribution_points: u32,
+
+    /// Landed the blow that dropped the boss to 0 HP
+    pub killing_blow: bool,
+
+    /// Answered at least one problem and never got one wrong this raid
+    pub flawless_bonus: bool,
+
+    /// Fastest single correct answer among this raid's roster
+    pub speed_bonus: bool,
+
+    /// Subtracted for disconnecting mid-raid - full RAID_REWARD_FORFEIT_PENALTY if
+    /// they never resumed (raid_player stayed inactive with in_raid_id still
+    /// pointing at this raid when the raid ended), half that if they went
+    /// inactive at some point but did reconnect before the end
+    pub forfeit_penalty: u32,
+
+    /// This player's raid_reward_ledger pool share, folded into the total for
+    /// display even though it's actually paid out on open_loot_chest claim
+    pub chest_bonus: u32,
+
+    /// Scales with RaidPlayer.peak_streak (see RAID_REWARD_STREAK_BONUS_PER_STACK)
+    pub streak_bonus: u32,
+
+    /// contribution_points + bonuses + chest_bonus - forfeit_penalty, floored at 0
+    pub total_points: u32,
+
+    pub computed_at: Timestamp,
+}
+
+/// A player's claimable share of a victorious raid's AP pool (see
+/// settle_raid_rewards) - open_loot_chest looks this row up instead of the old
+/// RaidPlayer.pending_chest_bonus lottery, staking-payout style: share is
+/// proportional to damage contribution instead of a random roll. Rows outlive
+/// their claim (see claimed/claimed_at) so a student who reconnects after a
+/// Chromebook reboot can still open the chest; cleanup_abandoned_raids sweeps
+/// rows past RAID_REWARD_LEDGER_TTL_DAYS the same way it sweeps timeback_event_queue.
+#[table(name = raid_reward_ledger, public)]
+pub struct RaidRewardLedger {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+
     #[index(btree)]
-    pub fact_key: String,
-    
-    /// Total attempts on this fact (all time)
-    pub total_attempts: u32,
-    
-    /// Total correct attempts (all time)
-    pub total_correct: u32,
-    
-    /// Last time this fact was practiced
-    pub last_seen: Timestamp,
-    
-    /// Average response time for correct answers (milliseconds)
-    pub avg_response_ms: u32,
-    
-    /// Fastest correct response ever (milliseconds)
-    pub fastest_ms: u32,
-    
-    /// Recent attempt history (up to 100 attempts)
-    /// Used to calculate mastery_level based on current grade
-    pub recent_attempts: Vec<AttemptRecord>,
-    
-    /// Mastery level (0-5) - server-maintained cache
-    /// CACHE INVALIDATION:
-    /// - Every answer: recalculated immediately using current player grade
-    /// - Grade change: batch recalculation via set_grade reducer for all player facts
-    /// - Always consistent: SpacetimeDB transactions ensure atomicity
-    /// DERIVED FROM: Last 3 recent_attempts + player.grade + fast_threshold
-    pub mastery_level: u8,
+    pub raid_id: u64,
+
+    #[index(btree)]
+    pub player_id: String,
+
+    /// This player's share of the pool - RAID_REWARD_CONTRIBUTION_POOL scaled by
+    /// boss_level, times (damage_dealt / total_damage), with the daily
+    /// first-victory bonus and any active reward window's ap_multiplier applied
+    /// (the same value as RaidReward.chest_bonus)
+    pub amount: u32,
+
+    #[default(false)]
+    pub claimed: bool,
+
+    #[default(None::<Timestamp>)]
+    pub claimed_at: Option<Timestamp>,
+
+    pub created_at: Timestamp,
 }
 
-/// Schedule table for cleanup tasks
-#[table(name = cleanup_schedule, scheduled(cleanup_abandoned_raids))]
-pub struct CleanupSchedule {
+/// Data-driven boss encounter phases, keyed by (boss_level, phase_index). Like a
+/// skill/mob table in other games, new bosses/phases are added by inserting rows
+/// here, not by shipping new reducer code. See recompute_raid_phase for how a
+/// raid transitions between these as boss_hp drops.
+#[table(name = boss_phase, public)]
+pub struct BossPhase {
     #[primary_key]
     #[auto_inc]
     pub id: u64,
-    
-    /// When to run the cleanup
-    pub scheduled_at: ScheduleAt,
+
+    #[index(btree)]
+    pub boss_level: u8,
+
+    /// 0-based, ascending with hp_fraction_trigger descending (phase 0 = opening phase)
+    pub phase_index: u8,
+
+    /// Remaining-HP fraction of boss_max_hp at which this phase begins (1.0 = fight start)
+    pub hp_fraction_trigger: f32,
+
+    /// Signed shift applied to a fact's mastery bucket before weighting (see
+    /// calculate_fact_weight) - positive pushes toward harder facts, negative easier
+    pub difficulty_shift: i8,
+
+    /// If set, answers slower than this many ms deal no damage while this phase is active
+    pub time_pressure_ms: Option<u32>,
+
+    /// Enrage-only damage kicker: percent bonus added to a correct answer's damage
+    /// while this phase is active (see submit_answer's damage calc) - 0 outside enrage
+    pub fast_answer_bonus_pct: u8,
+
+    /// If set, overrides PROBLEM_TIMEOUT_SECS while this phase is active - see
+    /// problem_timeout_delay_secs. Enrage phases tighten the watchdog window.
+    pub problem_timeout_secs_override: Option<u64>,
 }
 
-/// Schedule table for raid timeouts (2:30 for adaptive, 2:00 for fixed boss levels, 3:00 safety net)
-#[table(name = raid_timeout_schedule, scheduled(check_raid_timeout))]
-pub struct RaidTimeoutSchedule {
+/// A debuff a resolved boss_cast applies to players - see BOSS_ABILITIES and
+/// status_effect_damage_multiplier (consulted by submit_answer's damage calc)
+#[derive(SpacetimeType, Debug, Clone, Copy, PartialEq)]
+pub enum StatusEffectKind {
+    SlowAnswers,      // Damage scored as if the response came in slower than it did
+    ScrambleChoices,  // Client-only: shuffle/obscure the answer layout, no server scoring change
+    DamageReduction,  // Flat percentage knock to damage dealt while active
+}
+
+/// Active debuff on a player in a raid, applied when a boss_cast resolves. No unique
+/// constraint on (raid_id, player_id) - multiple kinds can stack, same "no multi-column
+/// unique" workaround as RaidPlayer/RaidOutcome (auto_inc id + btree indexes).
+#[table(name = status_effect, public)]
+pub struct StatusEffect {
     #[primary_key]
     #[auto_inc]
     pub id: u64,
-    
-    /// Which raid this timeout is for
+
+    #[index(btree)]
     pub raid_id: u64,
-    
-    /// When to check for timeout
+
+    #[index(btree)]
+    pub player_id: String,
+
+    pub kind: StatusEffectKind,
+
+    /// When this debuff stops applying - status_effect_damage_multiplier ignores
+    /// expired rows rather than eagerly deleting them (cheap to filter, no extra write)
+    pub expires_at: Timestamp,
+}
+
+/// Telegraph marker for a boss's in-flight ability cast - clients subscribe to this
+/// to show the wind-up before it resolves. One active row per raid at a time; deleted
+/// when boss_skill_schedule's tick lands at cast_ends_at and applies the debuff.
+#[table(name = boss_cast, public)]
+pub struct BossCast {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+
+    #[index(btree)]
+    pub raid_id: u64,
+
+    /// Boss visual id (0-8, see boss_visual_id) that owns this ability - indexes BOSS_ABILITIES
+    pub ability_id: u8,
+
+    pub cast_ends_at: Timestamp,
+}
+
+/// One pending boss-ability event per in-progress raid - either "decide whether to
+/// start a new cast" (no active boss_cast) or "resolve the in-flight cast" (fired
+/// exactly at cast_ends_at). Re-inserted with the next fire time after each tick,
+/// same one-row-per-raid pattern as raid_timeout_schedule.
+#[table(name = boss_skill_schedule, scheduled(fire_boss_skill))]
+pub struct BossSkillSchedule {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+
+    #[index(btree)]
+    pub raid_id: u64,
+
     pub scheduled_at: ScheduleAt,
 }
 
-/// Schedule table for countdown completion (3-2-1-GO before raid starts)
-/// After countdown completes, raid transitions to InProgress and problems are issued
-#[table(name = countdown_schedule, public, scheduled(countdown_complete))]
-pub struct CountdownSchedule {
+/// One pending boss-tick event per in-progress raid - the enrage soft-deadline (see
+/// fire_boss_tick), independent of answer cadence and of boss_skill_schedule's
+/// ability-cast cycle. Same one-row-per-raid pattern as boss_skill_schedule.
+#[table(name = boss_tick_schedule, scheduled(fire_boss_tick))]
+pub struct BossTickSchedule {
     #[primary_key]
     #[auto_inc]
     pub id: u64,
-    
-    /// Which raid this countdown is for
+
+    #[index(btree)]
     pub raid_id: u64,
-    
-    /// When countdown finishes (4 seconds after start for 3-2-1-GO display)
+
     pub scheduled_at: ScheduleAt,
 }
 
-/// Leaderboard rankings for each grade
-#[table(name = leaderboard_entry, public)]
-pub struct LeaderboardEntry {
+/// A summoned add that rides on a raid's boss-ability ticks (see fire_boss_skill) -
+/// modeled on the slave-mob pattern of a minion that locks onto and chases its
+/// master's current target. At most one alive at a time per raid, same "one active
+/// row" convention as boss_cast. Correct-answer damage routes here instead of the
+/// boss while hp > 0 - see submit_answer.
+#[table(name = raid_minion, public)]
+pub struct RaidMinion {
     #[primary_key]
     #[auto_inc]
     pub id: u64,
-    
-    /// Grade level for this leaderboard (0=K, 1-5)
+
     #[index(btree)]
-    pub grade: u8,
+    pub raid_id: u64,
+
+    pub hp: u32,
+    pub max_hp: u32,
+
+    /// The threat leader (highest damage_dealt active raid_player) at summon time -
+    /// fixed for the minion's lifetime, same as a slave mob locking its target on spawn
+    pub target_player_id: String,
+
+    pub spawned_at: Timestamp,
+}
+
+/// Math problem presented to players
+#[table(
+    name = problem,
+    public,
+    index(name = idx_raid_player, btree(columns = [raid_id, player_id]))
+)]
+pub struct Problem {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
     
-    /// Position in grade (1-based ranking)
-    pub position: u32,
+    /// Which raid this problem belongs to
+    #[index(btree)]
+    pub raid_id: u64,
     
-    /// Player ID
+    /// Which player this problem is for
+    #[index(btree)]
     pub player_id: String,
     
-    /// Player name (denormalized for display)
-    pub player_name: String,
+    /// Left operand
+    pub left_operand: u8,
     
-    /// Current rank
-    pub rank: String,
+    /// Right operand  
+    pub right_operand: u8,
     
-    /// Division within rank (I-IV, or empty for legendary)
-    pub division: String,
+    /// Operation (for now just multiplication)
+    pub operation: Operation,
     
-    /// Mastery percentage (0-100)
-    pub mastery_percent: u32,
+    /// Correct answer
+    pub answer: u16,
     
-    /// Speed percentage based on recent fast answers (0-100)
-    pub speed_percent: u32,
+    /// When this problem was shown
+    pub issued_at: Timestamp,
+    
+    /// Problem sequence number in raid
+    pub sequence: u32,
 }
 
-/// Performance tracking for CQPM analytics
-#[table(name = performance_snapshot, public)]  // Client needs to subscribe
-pub struct PerformanceSnapshot {
+#[derive(SpacetimeType, Debug, Clone, PartialEq)]
+pub enum Operation {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+impl Operation {
+    /// Compute the result of applying this operation to two operands
+    pub fn compute(&self, left: u8, right: u8) -> i16 {
+        match self {
+            Operation::Add => (left as i16) + (right as i16),
+            Operation::Subtract => (left as i16) - (right as i16),
+            Operation::Multiply => (left as i16) * (right as i16),
+            Operation::Divide => {
+                // For division, we ensure no division by zero
+                // This should be handled by fact generation, but let's be safe
+                if right == 0 {
+                    0
+                } else {
+                    (left as i16) / (right as i16)
+                }
+            }
+        }
+    }
+    
+    /// Get the display symbol for this operation
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Operation::Add => "+",
+            Operation::Subtract => "-",
+            Operation::Multiply => "×",
+            Operation::Divide => "÷",
+        }
+    }
+}
+
+/// Single attempt record for fact mastery tracking
+#[derive(SpacetimeType, Clone, Debug)]
+pub struct AttemptRecord {
+    /// Response time in milliseconds
+    pub time_ms: u32,
+    /// Whether answer was correct
+    pub correct: bool,
+    /// When this attempt occurred
+    pub timestamp: Timestamp,
+}
+
+/// Player's answer to a problem
+#[table(name = player_answer, public)]  // Client needs to subscribe
+pub struct PlayerAnswer {
     #[primary_key]
     #[auto_inc]
     pub id: u64,
     
-    /// Player this snapshot belongs to
     #[index(btree)]
-    pub player_id: String,
+    pub problem_id: u64,
     
-    /// When this snapshot was recorded
-    pub timestamp: Timestamp,
+    #[index(btree)]
+    pub player_id: String,
     
-    /// Player's grade at time of snapshot (0=K, 1-5)
-    pub grade: u8,
+    /// Response time in milliseconds
+    pub response_ms: u32,
     
-    /// Track practiced during this session (None = all facts for grade, Some("ALL") = explicit all)
+    /// Whether answer was correct
+    pub is_correct: bool,
+    
+    /// Damage dealt (0 if incorrect)
+    pub damage: u32,
+}
+
+/// Kind of entry in a raid's replay stream - see RaidEvent.
+#[derive(SpacetimeType, Debug, Clone, PartialEq)]
+pub enum RaidEventKind {
+    /// Damage dealt to the boss (or soaked by a live minion) by a correct answer
+    Damage,
+    /// Raid ended in victory
+    Victory,
+    /// Raid ended in defeat (timeout or forfeit)
+    Defeat,
+}
+
+/// Append-only per-raid replay stream - written from submit_answer (every damage
+/// tick) and end_raid_with_killing_blow (the final Victory/Defeat entry). `offset`
+/// is a monotonically increasing per-raid sequence number (borrowed from the
+/// stream-offset idea in message brokers, see Raid::event_offset) so a spectator
+/// joining mid-fight, or a post-game analysis view, can reconstruct exact boss-HP
+/// progression and per-player damage timing without replaying the whole raid.
+/// Seek by either `offset` (numeric) or `timestamp` (wall-clock) - both indexed,
+/// see replay_raid.
+#[table(name = raid_event, public)]
+pub struct RaidEvent {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+
+    #[index(btree)]
+    pub raid_id: u64,
+
+    /// Per-raid sequence number - the real seek key, since `id` is shared across
+    /// every raid's stream
+    #[index(btree)]
+    pub offset: u64,
+
+    pub kind: RaidEventKind,
+
+    /// Player who caused this event (the answerer for Damage, the killing blow for
+    /// a Victory - None for Defeat, or a Victory with no recorded killing blow)
+    pub player_id: Option<String>,
+
+    /// Damage dealt by this event (0 for Victory/Defeat entries)
+    pub damage: u32,
+
+    /// Boss HP immediately after this event
+    pub boss_hp_after: u32,
+
+    #[index(btree)]
+    pub timestamp: Timestamp,
+}
+
+/// Kind of notable event surfaced on the Announcement feed
+#[derive(SpacetimeType, Debug, Clone, PartialEq)]
+pub enum AnnouncementKind {
+    /// A loot chest reward notably large relative to the base contribution
+    /// pool - see ANNOUNCEMENT_RARE_LOOT_THRESHOLD. This codebase has no
+    /// Epic/Legendary rarity roll (chest_bonus is pool-share-based, not a
+    /// weighted random tier - see settle_raid_rewards), so "large payout" is
+    /// the closest honest stand-in for "rare loot roll"
+    RareLoot,
+    /// A player's very first completed raid
+    FirstRaid,
+    /// A 7/14/30-day raid streak milestone
+    StreakMilestone,
+    /// Third solo victory on a grade's goal boss
+    TrackMaster,
+}
+
+/// Server-wide feed of notable events (rare loot, milestones) for a live
+/// "ticker" UI. Public so clients can subscribe directly. Pruned after
+/// ANNOUNCEMENT_TTL_SECS by prune_announcements so the subscription stays
+/// small - see announcement_prune_schedule.
+#[table(name = announcement, public)]
+pub struct Announcement {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+
+    pub kind: AnnouncementKind,
+    pub player_name: String,
+    pub message: String,
+    pub grade: u8,
     pub track: Option<String>,
+
+    #[index(btree)]
+    pub timestamp: Timestamp,
+}
+
+/// How long an announcement stays in the feed before prune_announcements
+/// removes it
+const ANNOUNCEMENT_TTL_SECS: u64 = 5 * 60;
+
+/// A chest_bonus at or above RAID_REWARD_CONTRIBUTION_POOL is "notable" -
+/// see settle_raid_rewards and AnnouncementKind::RareLoot
+const ANNOUNCEMENT_RARE_LOOT_THRESHOLD: u32 = RAID_REWARD_CONTRIBUTION_POOL;
+
+/// Scheduled pruning pass for the announcement feed - same recurring-interval
+/// pattern as cleanup_schedule
+#[table(name = announcement_prune_schedule, scheduled(prune_announcements))]
+pub struct AnnouncementPruneSchedule {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+
+    pub scheduled_at: ScheduleAt,
+}
+
+/// Deletes announcements older than ANNOUNCEMENT_TTL_SECS. Runs every
+/// ANNOUNCEMENT_TTL_SECS/5 (see init) - small table, full scan is cheap.
+#[reducer]
+pub fn prune_announcements(ctx: &ReducerContext, _schedule: AnnouncementPruneSchedule) {
+    if ctx.sender != ctx.identity() {
+        log::warn!("Client {} attempted to call prune_announcements", ctx.sender);
+        return;
+    }
+
+    let mut pruned = 0u32;
+    for row in ctx.db.announcement().iter() {
+        let age_secs = ctx.timestamp.duration_since(row.timestamp)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        if age_secs > ANNOUNCEMENT_TTL_SECS {
+            ctx.db.announcement().id().delete(&row.id);
+            pruned += 1;
+        }
+    }
+
+    if pruned > 0 {
+        log::info!("[ANNOUNCEMENT] pruned {} stale rows", pruned);
+    }
+}
+
+/// Track player performance on specific multiplication facts for automaticity training
+#[table(name = fact_mastery, public)]  // Needs public for client subscriptions
+pub struct FactMastery {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
     
-    /// Player's rank at time of snapshot
-    pub rank: Option<String>,
+    /// Player this record belongs to
+    #[index(btree)]
+    pub player_id: String,
     
-    /// Player's division within rank at time of snapshot (I, II, III, IV)
-    pub division: Option<String>,
+    /// Normalized fact key (e.g., "3x7" where first number is always smaller)
+    #[index(btree)]
+    pub fact_key: String,
     
-    /// Number of facts mastered (Level 5) at time of snapshot
-    pub facts_mastered_at_snapshot: u32,
+    /// Total attempts on this fact (all time)
+    pub total_attempts: u32,
     
-    /// Problems attempted in this session
-    pub problems_attempted: u32,
+    /// Total correct attempts (all time)
+    pub total_correct: u32,
     
-    /// Problems answered correctly
-    pub problems_correct: u32,
+    /// Last time this fact was practiced
+    pub last_seen: Timestamp,
     
-    /// Session duration in seconds
-    pub session_seconds: u32,
+    /// Average response time for correct answers (milliseconds)
+    pub avg_response_ms: u32,
     
-    /// Total damage dealt during this session
-    pub damage_dealt: u32,
+    /// Fastest correct response ever (milliseconds)
+    pub fastest_ms: u32,
     
-    /// Raid type: Some("solo") or Some("multiplayer"), None for pre-1.6 data
-    #[default(None::<String>)]
-    pub raid_type: Option<String>,
+    /// Recent attempt history (up to 100 attempts)
+    /// Used to calculate mastery_level based on current grade
+    pub recent_attempts: Vec<AttemptRecord>,
     
-    /// Commutative units for TimeBack (5×6 and 6×5 count as 2)
-    #[default(0u32)]
-    pub timeback_units_at_snapshot: u32,
+    /// Mastery level (0-5) - server-maintained cache
+    /// CACHE INVALIDATION:
+    /// - Every answer: recalculated immediately using current player grade
+    /// - Grade change: batch recalculation via set_grade reducer for all player facts
+    /// - Always consistent: SpacetimeDB transactions ensure atomicity
+    /// DERIVED FROM: stability bucketed against FSRS_MASTERY_STABILITY_DAYS
+    pub mastery_level: u8,
+
+    /// FSRS memory stability S, in days - roughly "how long until recall odds for
+    /// this fact drop to 90%". Maintained by update_fact_mastery via fsrs_next_stability_*
+    #[default(0.0f32)]
+    pub stability: f32,
+
+    /// FSRS memory difficulty D, in [1, 10] - how hard this fact is to retain,
+    /// independent of how overdue it is. Maintained alongside stability
+    #[default(0.0f32)]
+    pub difficulty: f32,
+}
+
+/// Schedule table for cleanup tasks
+#[table(name = cleanup_schedule, scheduled(cleanup_abandoned_raids))]
+pub struct CleanupSchedule {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
     
-    /// Boss difficulty level (0 = Adaptive, 1-7 = Fixed HP tiers)
-    #[default(0u8)]
-    pub boss_level: u8,
+    /// When to run the cleanup
+    pub scheduled_at: ScheduleAt,
+}
+
+/// Schedule table for raid timeouts (2:30 for adaptive, 2:00 for fixed boss levels, 3:00 safety net)
+#[table(name = raid_timeout_schedule, scheduled(check_raid_timeout))]
+pub struct RaidTimeoutSchedule {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
     
-    /// Whether this raid was won (None = pre-tracking data, unknown)
-    #[default(None::<bool>)]
-    pub victory: Option<bool>,
+    /// Which raid this timeout is for
+    pub raid_id: u64,
+    
+    /// When to check for timeout
+    pub scheduled_at: ScheduleAt,
 }
 
-// ==================== BOSS LEVEL SYSTEM ====================
+/// Schedule table for countdown completion (3-2-1-GO before raid starts)
+/// After countdown completes, raid transitions to InProgress and problems are issued
+#[table(name = countdown_schedule, public, scheduled(countdown_complete))]
+pub struct CountdownSchedule {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    
+    /// Which raid this countdown is for
+    pub raid_id: u64,
+    
+    /// When countdown finishes (4 seconds after start for 3-2-1-GO display)
+    pub scheduled_at: ScheduleAt,
+}
 
-/// Boss HP values - validated ladder based on CQPM (Correct Questions Per Minute)
-/// 
-/// ## The Model (Nov 2025, 352 pilot student raids)
-/// 
-/// Formula: HP = CQPM × 150 (validated against Seth, Renee, De'Marcus, Finn)
-/// 
-/// At these HP values:
-///   - Students AT target CQPM win ~33% of the time (good days only)
-///   - Students BELOW target win ~0% (properly gated out)
-///   - Clean separation confirmed at 20 CQPM and 30 CQPM benchmarks
-/// 
-/// ## What Beating The Boss Means
-/// 
-///   - Beat once = "First Clear" celebration, you hit grade level on a good day
-///   - Beat 3× = "Track Master" badge, proven fluency, go take the test
-/// 
-/// ## Grade Benchmarks (AlphaMath Fluency targets)
-/// 
-/// - K:    20 CQPM → Level 4 (3,000 HP) - validated with Renee/De'Marcus/Finn
-/// - G1-3: 30 CQPM → Level 6 (4,500 HP) - validated with Seth
-/// - G4:   35 CQPM → Level 7 (5,250 HP) - extrapolated
-/// - G5:   40 CQPM → Level 8 (6,000 HP) - extrapolated
-/// 
-/// ## Why 33% Win Rate?
-/// 
-/// Students beat the boss only on "good days" (above-average performance).
-/// This means when they beat it, they've demonstrated they CAN hit the target.
-/// The 3× requirement for Track Master filters out lucky peaks.
-/// 
-/// Timeout: Fixed levels use exactly 2:00 (120s) to match the HP model
-const BOSS_HP_VALUES: [u32; 9] = [
-    0,    // Level 0: Adaptive (uses player's recent performance)
-    900,  // Level 1:  5 CQPM - Gloop Jr. (Slime)
-    1750, // Level 2: 10 CQPM - Whisper (Ghost)
-    2600, // Level 3: 15 CQPM - Bonehead (Skull)
-    3500, // Level 4: 20 CQPM - Boomer (Bomb) - K goal ⭐
-    4200, // Level 5: 25 CQPM - Frosty (Snowman)
-    5000, // Level 6: 30 CQPM - Titan (Mech) - G1-3 goal ⭐
-    5500, // Level 7: 35 CQPM - Captain Nova - G4 goal ⭐
-    6000, // Level 8: 40 CQPM - Void Emperor - G5 goal ⭐
-];
+/// Schedule table for the per-player reconnect grace window (see RECONNECT_DEADLINE_SECS).
+/// One row per (raid_id, player_id) currently counting down - canceled on reconnect,
+/// fired forfeits the player from the raid if they're still inactive.
+#[table(name = reconnect_deadline, scheduled(check_reconnect_deadline))]
+pub struct ReconnectDeadline {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
 
-/// Calculate boss HP based on level and player count
-fn boss_hp_for_level(level: u8, player_count: u32, adaptive_hp: u32) -> u32 {
-    if is_adaptive_boss(level) {
-        // Adaptive: use personalized HP calculation
-        return adaptive_hp;
-    }
-    
-    // Fixed HP: base value × player count
-    let level_idx = level as usize;
-    if level_idx >= BOSS_HP_VALUES.len() {
-        return adaptive_hp;  // Invalid level, fallback to adaptive
-    }
-    BOSS_HP_VALUES[level_idx] * player_count
+    /// Which raid this deadline is for
+    #[index(btree)]
+    pub raid_id: u64,
+
+    /// Which player must reconnect before this fires
+    pub player_id: String,
+
+    /// When the grace window expires
+    pub scheduled_at: ScheduleAt,
 }
 
-/// Raid timeout duration based on boss level
-fn raid_timeout_seconds(boss_level: u8) -> u64 {
-    if is_adaptive_boss(boss_level) {
-        150  // Adaptive: 2:30 (personalized, more forgiving)
-    } else {
-        120  // Fixed tiers: 2:00 exactly (HP model assumes 2 min, variance provides cushion)
-    }
+/// Schedule table for the whole-raid pause watchdog (see PAUSE_TIMEOUT_GRACE_SECS).
+/// One row per raid while it sits Paused - canceled on resume, fired abandons the
+/// raid outright instead of leaving it parked forever.
+#[table(name = pause_timeout_schedule, scheduled(check_pause_timeout))]
+pub struct PauseTimeoutSchedule {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+
+    /// Which raid this watchdog is for
+    #[index(btree)]
+    pub raid_id: u64,
+
+    /// When the grace window expires
+    pub scheduled_at: ScheduleAt,
 }
 
-// ==================== ROW LEVEL SECURITY ====================
+/// Schedule table for the per-raid idle-answer watchdog (see PROBLEM_ANSWER_DEADLINE_SECS).
+/// One recurring row per in-progress raid, re-inserted on every tick by its own reducer -
+/// same self-rescheduling idiom as boss_skill_schedule.
+#[table(name = idle_check_schedule, scheduled(check_idle_players))]
+pub struct IdleCheckSchedule {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
 
-/// RLS Filter: Only authorized workers (module owner) can see timeback_event_queue rows
-/// This prevents students from seeing sensitive TimeBack event data while still
-/// allowing the worker to subscribe and process events
-#[client_visibility_filter]
-const TIMEBACK_QUEUE_VISIBILITY: Filter = Filter::Sql(
-    "SELECT tq.* FROM timeback_event_queue tq 
-     JOIN authorized_worker aw WHERE aw.identity = :sender"
-);
+    /// Which raid this idle sweep is for
+    #[index(btree)]
+    pub raid_id: u64,
 
-// ==================== REDUCERS ====================
+    pub scheduled_at: ScheduleAt,
+}
 
-/// Create a verified session for a client identity
-/// This is called by the Bun gateway AFTER verifying the Playcademy JWT
+/// Schedule table for the per-player, per-problem timeout (see PROBLEM_TIMEOUT_SECS).
+/// One row per (raid_id, player_id) currently live - canceled whenever that player
+/// answers, fired either opens the grace window (in_grace_period: false -> true,
+/// same row reused) or, if still unanswered after the grace window, auto-fails the
+/// problem and advances. This is tighter/faster than idle_check_schedule, which only
+/// catches a player who has gone fully idle across PROBLEM_ANSWER_DEADLINE_SECS.
+#[table(name = problem_timeout_schedule, scheduled(check_problem_timeout))]
+pub struct ProblemTimeoutSchedule {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+
+    /// Which raid this timeout is for
+    #[index(btree)]
+    pub raid_id: u64,
+
+    /// Which player must answer before this fires
+    pub player_id: String,
+
+    /// The problem being timed
+    pub problem_id: u64,
+
+    /// false on the first fire (deadline just hit - open the grace window), true on
+    /// the second (grace window elapsed - finalize the auto-fail)
+    pub in_grace_period: bool,
+
+    pub scheduled_at: ScheduleAt,
+}
+
+/// Recurring trigger for the public world-boss event - see spawn_world_boss.
+/// A single row with ScheduleAt::Interval, set up once in init.
+#[table(name = world_boss_schedule, scheduled(spawn_world_boss))]
+pub struct WorldBossSchedule {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+
+    pub scheduled_at: ScheduleAt,
+}
+
+/// One-shot timer that auto-starts a world-boss raid after its matchmaking window,
+/// so the event doesn't need a leader to press start - see auto_start_world_boss.
+#[table(name = world_boss_start_schedule, scheduled(auto_start_world_boss))]
+pub struct WorldBossStartSchedule {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+
+    /// Which world-boss raid this starts
+    pub raid_id: u64,
+
+    pub scheduled_at: ScheduleAt,
+}
+
+/// One grade band's world-boss event - spawn_world_boss inserts one of these per
+/// active grade instead of the single shared raid it used to spawn, so each grade
+/// gets its own roster. Created at the moment its raid spawns, with
+/// join_window_closes_at set so clients can show a countdown to auto_start_world_boss.
+#[table(name = world_boss_event, public)]
+pub struct WorldBossEvent {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+
+    #[index(btree)]
+    pub grade: u8,
+
+    pub boss_level: u8,
+
+    /// When this event's raid spawns (or did spawn, once live)
+    pub scheduled_spawn_at: Timestamp,
+
+    /// Set once spawned: when the join window (WORLD_BOSS_MATCHMAKING_WINDOW_SECS)
+    /// closes and auto_start_world_boss fires
+    #[default(None::<Timestamp>)]
+    pub join_window_closes_at: Option<Timestamp>,
+
+    /// Set once spawned - the live raid clients should join via join_world_boss.
+    /// Cleared (row deleted, see cleanup_raid_data) once that raid is cleaned up.
+    #[index(btree)]
+    #[default(None::<u64>)]
+    pub raid_id: Option<u64>,
+}
+
+/// Aggregated operational/pedagogical metric for external Prometheus scraping.
+/// Modeled on how a network scheduler surfaces Prometheus counters, fitted to
+/// SpacetimeDB's model: an external exporter subscribes to this table and
+/// translates each row into a Prometheus series. New metrics need no schema
+/// migration - just a new `name`.
+/// Note: No unique constraint on (name, labels_json) - `upsert_metric_snapshot`
+/// manually deletes the prior row for a (name, labels_json) pair before inserting.
+#[table(name = metric_snapshot, public)]
+pub struct MetricSnapshot {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+
+    /// Metric name, e.g. "raids_won_total", "timeback_queue_depth"
+    #[index(btree)]
+    pub name: String,
+
+    /// JSON object of label key/values, e.g. {"boss_level":"6"} or {"grade":"3"}
+    pub labels_json: String,
+
+    pub value: f64,
+
+    /// When this snapshot was computed
+    pub computed_at: Timestamp,
+}
+
+/// Schedule table for metric snapshot computation
+#[table(name = metric_snapshot_schedule, scheduled(compute_metric_snapshot))]
+pub struct MetricSnapshotSchedule {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+
+    pub scheduled_at: ScheduleAt,
+}
+
+/// SM-2 spaced-repetition schedule for a player's fact practice.
+/// Drives adaptive problem selection toward facts the learner is about to
+/// forget, instead of only avoiding recent repeats.
+/// Note: No unique constraint on (player_id, fact_key) - `update_practice_schedule`
+/// manually finds-or-inserts, same pattern as `RaidPlayer`/`FactMastery`.
+#[table(name = practice_schedule)]
+pub struct PracticeSchedule {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+
+    #[index(btree)]
+    pub player_id: String,
+
+    #[index(btree)]
+    pub fact_key: String,
+
+    /// SM-2 ease factor - floors at 1.3
+    pub ease_factor: f32,
+
+    /// Consecutive successful repetitions (reset to 0 whenever quality < 3)
+    pub repetitions: u32,
+
+    /// Current review interval, in days
+    pub interval_days: u32,
+
+    /// When this fact is next due for review
+    #[index(btree)]
+    pub due_at: Timestamp,
+}
+
+/// Schedule table firing once when the active season's ends_at passes
+/// (one-shot, same ScheduleAt::Time pattern as RaidTimeoutSchedule/CountdownSchedule
+/// rather than a recurring interval - there's only ever one close pending at a time)
+#[table(name = season_schedule, scheduled(close_season))]
+pub struct SeasonSchedule {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+
+    pub season_id: u64,
+    pub scheduled_at: ScheduleAt,
+}
+
+/// Schedule table for the integrity scrubber (resumable background repair pass)
+#[table(name = scrub_schedule, scheduled(run_integrity_scrub))]
+pub struct ScrubSchedule {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+
+    /// How often to run a scrub pass
+    pub scheduled_at: ScheduleAt,
+}
+
+/// Resume cursor for the integrity scrubber, one row per scanned table.
+/// Modeled on a storage-repair worker's cursor: each tick scans a bounded
+/// batch starting after `last_key` and saves where it left off, so a full
+/// pass over a large table is amortized across many scheduled ticks instead
+/// of blocking a single reducer call.
+#[table(name = scrub_cursor)]
+pub struct ScrubCursor {
+    #[primary_key]
+    pub target: String, // "problem" | "player" | "raid_player" | "fact_mastery"
+
+    /// Last-scanned primary key for this target ("" = start of a fresh pass)
+    pub last_key: String,
+}
+
+/// Running totals and last-run stats for the integrity scrubber, for monitoring
+#[table(name = scrub_stats, public)]
+pub struct ScrubStats {
+    #[primary_key]
+    pub id: u8, // singleton row (always 0)
+
+    pub last_run_at: Timestamp,
+    pub rows_scanned: u64,
+    pub orphaned_problems_deleted: u64,
+    pub stale_player_raids_cleared: u64,
+    pub stale_raid_players_deactivated: u64,
+    pub recent_attempts_trimmed: u64,
+    pub mastery_levels_fixed: u64,
+}
+
+/// Leaderboard rankings for each grade
+#[table(name = leaderboard_entry, public)]
+pub struct LeaderboardEntry {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    
+    /// Grade level for this leaderboard (0=K, 1-5)
+    #[index(btree)]
+    pub grade: u8,
+    
+    /// Position in grade (1-based ranking)
+    pub position: u32,
+    
+    /// Player ID
+    pub player_id: String,
+    
+    /// Player name (denormalized for display)
+    pub player_name: String,
+    
+    /// Current rank
+    pub rank: String,
+    
+    /// Division within rank (I-IV, or empty for legendary)
+    pub division: String,
+    
+    /// Mastery percentage (0-100)
+    pub mastery_percent: u32,
+    
+    /// Speed percentage based on recent fast answers (0-100)
+    pub speed_percent: u32,
+
+    /// Player's best_elo_rating, rounded - hidden skill rating exposed here so
+    /// clients can show it without querying player directly. Not a sort key:
+    /// the leaderboard still ranks by mastery/speed (see refresh_leaderboard).
+    pub best_rating: u32,
+}
+
+/// One row per player+track - a mastery-completion forecast, recomputed on
+/// demand by refresh_mastery_forecast rather than kept live (same
+/// refresh-then-query pattern as LeaderboardEntry). Lets a player or guide
+/// see "at this pace you'll finish this track on <done_on>".
+#[table(name = mastery_forecast, public)]
+pub struct MasteryForecast {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+
+    #[index(btree)]
+    pub player_id: String,
+
+    pub track: Option<String>,
+
+    /// total_facts - mastered_count for this player's current grade (see
+    /// get_player_mastery_stats). 0 = track complete.
+    pub facts_remaining: u32,
+
+    /// Facts mastered per day over the trailing window - None if the window
+    /// doesn't span enough history yet (see MASTERY_FORECAST_MIN_SNAPSHOTS/
+    /// MASTERY_FORECAST_MIN_SPAN_DAYS). Can be zero or negative (stalled or
+    /// regressing), in which case days_remaining/done_on are None too.
+    pub rate_per_day: Option<f32>,
+
+    /// None when facts_remaining is 0 (complete), rate_per_day is None (not
+    /// enough history), or rate_per_day <= 0.0 (not on pace)
+    pub days_remaining: Option<f32>,
+
+    /// Projected completion date - same None conditions as days_remaining
+    pub done_on: Option<Timestamp>,
+
+    pub computed_at: Timestamp,
+}
+
+/// A competitive ranking period. Grade-agnostic - every grade shares the same
+/// season window, each with its own leaderboard within it. Only one row should
+/// have active=true at a time (enforced by close_season/open_next_season).
+#[table(name = season, public)]
+pub struct Season {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+
+    pub starts_at: Timestamp,
+    pub ends_at: Timestamp,
+    pub active: bool,
+}
+
+/// Snapshot of a season's final top finishers per grade, taken when the season
+/// closes. Public so clients can show "last season's champions" even after the
+/// live leaderboard has moved on to the new season.
+#[table(name = season_archive, public)]
+pub struct SeasonArchive {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+
+    #[index(btree)]
+    pub season_id: u64,
+
+    #[index(btree)]
+    pub grade: u8,
+
+    pub final_position: u32,
+    pub player_id: String,
+    pub player_name: String,
+    pub rank: String,
+    pub mastery_percent: u32,
+}
+
+/// Performance tracking for CQPM analytics
+#[table(name = performance_snapshot, public)]  // Client needs to subscribe
+pub struct PerformanceSnapshot {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    
+    /// Player this snapshot belongs to
+    #[index(btree)]
+    pub player_id: String,
+    
+    /// When this snapshot was recorded
+    pub timestamp: Timestamp,
+    
+    /// Player's grade at time of snapshot (0=K, 1-5)
+    pub grade: u8,
+    
+    /// Track practiced during this session (None = all facts for grade, Some("ALL") = explicit all)
+    pub track: Option<String>,
+    
+    /// Player's rank at time of snapshot
+    pub rank: Option<String>,
+    
+    /// Player's division within rank at time of snapshot (I, II, III, IV)
+    pub division: Option<String>,
+    
+    /// Number of facts mastered (Level 5) at time of snapshot
+    pub facts_mastered_at_snapshot: u32,
+    
+    /// Problems attempted in this session
+    pub problems_attempted: u32,
+    
+    /// Problems answered correctly
+    pub problems_correct: u32,
+    
+    /// Session duration in seconds
+    pub session_seconds: u32,
+    
+    /// Total damage dealt during this session
+    pub damage_dealt: u32,
+    
+    /// Raid type: Some("solo") or Some("multiplayer"), None for pre-1.6 data
+    #[default(None::<String>)]
+    pub raid_type: Option<String>,
+    
+    /// Commutative units for TimeBack (5×6 and 6×5 count as 2)
+    #[default(0u32)]
+    pub timeback_units_at_snapshot: u32,
+    
+    /// Boss difficulty level (0 = Adaptive, 1-7 = Fixed HP tiers)
+    #[default(0u8)]
+    pub boss_level: u8,
+    
+    /// Whether this raid was won (None = pre-tracking data, unknown)
+    #[default(None::<bool>)]
+    pub victory: Option<bool>,
+
+    /// True if this session's CQPM fell suspiciously far below the player's cqpm_baseline
+    /// without an accompanying grade change - likely intentional adaptive-HP sandbagging
+    /// rather than a real skill dip. See SANDBAG_RATIO_THRESHOLD.
+    #[default(false)]
+    pub sandbag_suspected: bool,
+
+    /// Player's elo_rating immediately after this raid's update - see
+    /// update_elo_rating. Lets clients chart rating history without re-deriving it.
+    #[default(1200.0f32)]
+    pub elo_rating_at_snapshot: f32,
+}
+
+// ==================== BOSS LEVEL SYSTEM ====================
+
+/// Boss HP values - validated ladder based on CQPM (Correct Questions Per Minute)
+/// 
+/// ## The Model (Nov 2025, 352 pilot student raids)
+/// 
+/// Formula: HP = CQPM × 150 (validated against Seth, Renee, De'Marcus, Finn)
+/// 
+/// At these HP values:
+///   - Students AT target CQPM win ~33% of the time (good days only)
+///   - Students BELOW target win ~0% (properly gated out)
+///   - Clean separation confirmed at 20 CQPM and 30 CQPM benchmarks
+/// 
+/// ## What Beating The Boss Means
+/// 
+///   - Beat once = "First Clear" celebration, you hit grade level on a good day
+///   - Beat 3× = "Track Master" badge, proven fluency, go take the test
+/// 
+/// ## Grade Benchmarks (AlphaMath Fluency targets)
+/// 
+/// - K:    20 CQPM → Level 4 (3,000 HP) - validated with Renee/De'Marcus/Finn
+/// - G1-3: 30 CQPM → Level 6 (4,500 HP) - validated with Seth
+/// - G4:   35 CQPM → Level 7 (5,250 HP) - extrapolated
+/// - G5:   40 CQPM → Level 8 (6,000 HP) - extrapolated
+/// 
+/// ## Why 33% Win Rate?
+/// 
+/// Students beat the boss only on "good days" (above-average performance).
+/// This means when they beat it, they've demonstrated they CAN hit the target.
+/// The 3× requirement for Track Master filters out lucky peaks.
+/// 
+/// Timeout: Fixed levels use exactly 2:00 (120s) to match the HP model
+const BOSS_HP_VALUES: [u32; 9] = [
+    0,    // Level 0: Adaptive (uses player's recent performance)
+    900,  // Level 1:  5 CQPM - Gloop Jr. (Slime)
+    1750, // Level 2: 10 CQPM - Whisper (Ghost)
+    2600, // Level 3: 15 CQPM - Bonehead (Skull)
+    3500, // Level 4: 20 CQPM - Boomer (Bomb) - K goal ⭐
+    4200, // Level 5: 25 CQPM - Frosty (Snowman)
+    5000, // Level 6: 30 CQPM - Titan (Mech) - G1-3 goal ⭐
+    5500, // Level 7: 35 CQPM - Captain Nova - G4 goal ⭐
+    6000, // Level 8: 40 CQPM - Void Emperor - G5 goal ⭐
+];
+
+/// Calculate boss HP based on level and player count
+fn boss_hp_for_level(level: u8, player_count: u32, adaptive_hp: u32) -> u32 {
+    if is_adaptive_boss(level) {
+        // Adaptive: use personalized HP calculation
+        return adaptive_hp;
+    }
+    
+    // Fixed HP: base value × player count
+    let level_idx = level as usize;
+    if level_idx >= BOSS_HP_VALUES.len() {
+        return adaptive_hp;  // Invalid level, fallback to adaptive
+    }
+    BOSS_HP_VALUES[level_idx] * player_count
+}
+
+/// Raid timeout duration based on boss level
+fn raid_timeout_seconds(boss_level: u8) -> u64 {
+    if is_adaptive_boss(boss_level) {
+        150  // Adaptive: 2:30 (personalized, more forgiving)
+    } else {
+        120  // Fixed tiers: 2:00 exactly (HP model assumes 2 min, variance provides cushion)
+    }
+}
+
+/// All phases defined for a boss level, ascending by hp_fraction_trigger
+/// (phase 0 / trigger 1.0 last). Empty if the boss has no phase data -
+/// callers should treat that as "single flat phase" and no-op.
+fn get_boss_phases(ctx: &ReducerContext, boss_level: u8) -> Vec<BossPhase> {
+    let mut phases: Vec<BossPhase> = ctx.db.boss_phase()
+        .boss_level()
+        .filter(&boss_level)
+        .collect();
+    phases.sort_by(|a, b| a.hp_fraction_trigger.partial_cmp(&b.hp_fraction_trigger).unwrap());
+    phases
+}
+
+/// The phase that should be active for a raid right now, based on remaining HP
+/// fraction of boss_max_hp. Works unmodified for adaptive bosses (level 0)
+/// because boss_max_hp already holds the raid's personalized starting HP, not
+/// a BOSS_HP_VALUES lookup - there's nothing adaptive-specific to special-case.
+fn current_boss_phase(ctx: &ReducerContext, raid: &Raid) -> Option<BossPhase> {
+    if raid.boss_max_hp == 0 {
+        return None;
+    }
+    let remaining_fraction = raid.boss_hp as f32 / raid.boss_max_hp as f32;
+    let phases = get_boss_phases(ctx, raid.boss_level);
+    // Phases are ascending by trigger; the active one is the smallest trigger
+    // still >= remaining HP (e.g. triggers [1.0, 0.66, 0.33], remaining 0.5 -> 0.66)
+    phases.into_iter().find(|p| p.hp_fraction_trigger >= remaining_fraction)
+}
+
+/// Lazily seed the standard phase ladder for a boss_level the first time a raid
+/// starts against it - idempotent (no-op once any boss_phase rows exist for that
+/// level), same "insert if missing" convention as init()'s schedule seeding.
+/// Mechanics escalate as HP drops: phase 1/2 bias fact selection toward the
+/// player's weaker facts (see calculate_fact_weight), phase 3 is the "enrage"
+/// finisher below 15% HP - tighter time pressure, shorter problem timeout, and
+/// a damage bonus for fast correct answers.
+fn seed_boss_phases(ctx: &ReducerContext, boss_level: u8) {
+    if ctx.db.boss_phase().boss_level().filter(&boss_level).count() > 0 {
+        return;
+    }
+    let phases: [(u8, f32, i8, Option<u32>, u8, Option<u64>); 4] = [
+        (0, 1.00, 0, None, 0, None),
+        (1, 0.66, 1, None, 0, None),
+        (2, 0.33, 2, None, 0, None),
+        (3, 0.15, 2, Some(2000), 50, Some(10)),
+    ];
+    for (phase_index, hp_fraction_trigger, difficulty_shift, time_pressure_ms, fast_answer_bonus_pct, problem_timeout_secs_override) in phases {
+        ctx.db.boss_phase().insert(BossPhase {
+            id: 0,
+            boss_level,
+            phase_index,
+            hp_fraction_trigger,
+            difficulty_shift,
+            time_pressure_ms,
+            fast_answer_bonus_pct,
+            problem_timeout_secs_override,
+        });
+    }
+    log::info!("[RAID] seeded boss phases boss_level:{}", boss_level);
+}
+
+/// Per-problem watchdog delay for a raid right now - PROBLEM_TIMEOUT_SECS unless
+/// the active boss phase overrides it (enrage phases shorten the window)
+fn problem_timeout_delay_secs(ctx: &ReducerContext, raid_id: u64) -> u64 {
+    ctx.db.raid().id().find(&raid_id)
+        .and_then(|raid| current_boss_phase(ctx, &raid))
+        .and_then(|phase| phase.problem_timeout_secs_override)
+        .unwrap_or(PROBLEM_TIMEOUT_SECS)
+}
+
+/// Recompute and, if changed, apply the raid's current_phase_index for its boss's
+/// remaining HP. Idempotent: a no-op if the computed phase matches what's already
+/// stored, so calling this again on reconnect (when HP hasn't moved) never re-fires
+/// a phase transition. Returns the active phase, if any boss_phase data exists.
+fn recompute_raid_phase(ctx: &ReducerContext, raid_id: u64) -> Option<BossPhase> {
+    let mut raid = ctx.db.raid().id().find(&raid_id)?;
+    let phase = current_boss_phase(ctx, &raid)?;
+
+    if phase.phase_index != raid.current_phase_index {
+        log::info!("[RAID] phase transition raid:{} boss_level:{} phase:{}->{} hp_fraction:{:.2}",
+            raid_id, raid.boss_level, raid.current_phase_index, phase.phase_index, phase.hp_fraction_trigger);
+        raid.current_phase_index = phase.phase_index;
+        ctx.db.raid().id().update(raid);
+        regenerate_unanswered_problems_for_phase(ctx, raid_id);
+    }
+
+    Some(phase)
+}
+
+/// The prefetched problem queue (see generate_problem_batch) bakes in whatever
+/// phase's difficulty_shift was live at generation time, so a mid-raid phase
+/// transition wouldn't otherwise reach problems already sitting in the queue.
+/// Called on every phase change: regenerates each active player's still-unanswered,
+/// not-yet-reached problems (generate_problem reads the raid's now-current phase
+/// live, so the new difficulty_shift applies automatically). The one problem
+/// already in flight (current_unanswered_problem) is left alone so an answer
+/// already submitted against it doesn't get orphaned.
+fn regenerate_unanswered_problems_for_phase(ctx: &ReducerContext, raid_id: u64) {
+    let active_players: Vec<RaidPlayer> = ctx.db.raid_player()
+        .raid_id()
+        .filter(&raid_id)
+        .filter(|rp| rp.is_active)
+        .collect();
+
+    for mut raid_player in active_players {
+        let player_id = raid_player.player_id.clone();
+        let in_flight_id = current_unanswered_problem(ctx, raid_id, &player_id).map(|p| p.id);
+
+        let to_regenerate: Vec<Problem> = ctx.db.problem()
+            .iter()
+            .filter(|p| p.raid_id == raid_id && p.player_id == player_id)
+            .filter(|p| Some(p.id) != in_flight_id)
+            .filter(|p| ctx.db.player_answer().iter().find(|a| a.problem_id == p.id && a.player_id == player_id).is_none())
+            .collect();
+
+        for old in to_regenerate {
+            let (left, right, operation) = generate_problem(old.sequence, ctx, &mut raid_player);
+            let answer = operation.compute(left, right) as u16;
+            ctx.db.problem().id().delete(&old.id);
+            ctx.db.problem().insert(Problem {
+                id: 0,
+                raid_id,
+                player_id: player_id.clone(),
+                left_operand: left,
+                right_operand: right,
+                operation,
+                answer,
+                issued_at: ctx.timestamp,
+                sequence: old.sequence,
+            });
+        }
+
+        ctx.db.raid_player().id().update(raid_player);
+    }
+}
+
+// ==================== BOSS ABILITIES ====================
+// Telegraphed casts on a timer, modeled on how Hercules mobs wind up a skill then
+// unleash it - see boss_cast/boss_skill_schedule table docs for the full flow.
+
+/// How long a raid goes between ability casts while the boss isn't already winding
+/// one up (the "decide to cast" tick interval)
+const BOSS_CAST_DECISION_INTERVAL_SECS: u64 = 20;
+
+/// Telegraph wind-up window before a cast resolves - long enough for clients to
+/// show a warning and players to react
+const BOSS_CAST_WINDUP_SECS: u64 = 6;
+
+/// How long the debuff applied by a resolved cast lasts
+const BOSS_ABILITY_DEBUFF_SECS: u64 = 12;
+
+/// Sanity cap on any single boss_skill_schedule delay, mirroring Hercules'
+/// MOB_MAX_CASTTIME invariant - nothing server-driven should ever wait longer than this
+const BOSS_CAST_MAX_SECS: u64 = 600;
+
+/// Each boss visual (0-8, see boss_visual_id) has one signature ability
+const BOSS_ABILITIES: [StatusEffectKind; 9] = [
+    StatusEffectKind::SlowAnswers,      // 0: Clank
+    StatusEffectKind::DamageReduction,  // 1
+    StatusEffectKind::ScrambleChoices,  // 2
+    StatusEffectKind::SlowAnswers,      // 3
+    StatusEffectKind::DamageReduction,  // 4
+    StatusEffectKind::ScrambleChoices,  // 5
+    StatusEffectKind::SlowAnswers,      // 6
+    StatusEffectKind::DamageReduction,  // 7: Captain Nova
+    StatusEffectKind::ScrambleChoices,  // 8: Void Emperor
+];
+
+/// Derive a uniform 0-8 "monster identity" from a boss_level, regardless of whether
+/// it's encoded as a fixed tier (1-8), an adaptive boss with a specific visual
+/// (101-108), or plain random-visual adaptive (0/100, which has no fixed identity -
+/// defaults to Clank's kit)
+fn boss_visual_id(boss_level: u8) -> u8 {
+    if boss_level >= 101 {
+        (boss_level - 100).min(8)
+    } else if boss_level == 0 || boss_level == 100 {
+        0
+    } else {
+        boss_level.min(8)
+    }
+}
+
+/// Damage multiplier contributed by a player's active status effects. DamageReduction
+/// is the only kind that touches this - SlowAnswers instead widens the effective
+/// response time before this multiplier is even considered (see submit_answer),
+/// and ScrambleChoices is purely a client-side presentation effect.
+fn status_effect_damage_multiplier(ctx: &ReducerContext, raid_id: u64, player_id: &str) -> f32 {
+    let mut multiplier = 1.0f32;
+    for effect in ctx.db.status_effect().player_id().filter(&player_id.to_string())
+        .filter(|e| e.raid_id == raid_id && e.kind == StatusEffectKind::DamageReduction)
+    {
+        if ctx.timestamp.duration_since(effect.expires_at).is_none() {
+            multiplier *= 0.75;
+        }
+    }
+    multiplier
+}
+
+/// Extra response-time penalty (ms) from an active SlowAnswers debuff - folded into
+/// the damage calc the same way a slow reaction naturally would be
+fn slow_answers_penalty_ms(ctx: &ReducerContext, raid_id: u64, player_id: &str) -> u32 {
+    let has_slow = ctx.db.status_effect().player_id().filter(&player_id.to_string())
+        .any(|e| e.raid_id == raid_id
+            && e.kind == StatusEffectKind::SlowAnswers
+            && ctx.timestamp.duration_since(e.expires_at).is_none());
+    if has_slow { 400 } else { 0 }
+}
+
+/// (Re)schedule the next boss-ability tick for this raid. Idempotent - cancels any
+/// pending tick first so this can be called freely from countdown_complete and resume.
+/// Does NOT touch an in-flight boss_cast - fire_boss_skill reschedules itself this
+/// way to resolve a telegraph that's already been started.
+fn schedule_boss_skill(ctx: &ReducerContext, raid_id: u64, delay_secs: u64) {
+    for schedule in ctx.db.boss_skill_schedule().iter().filter(|s| s.raid_id == raid_id) {
+        ctx.db.boss_skill_schedule().id().delete(&schedule.id);
+    }
+    let delay_secs = delay_secs.min(BOSS_CAST_MAX_SECS);
+    let fires_at = ctx.timestamp + std::time::Duration::from_secs(delay_secs);
+    ctx.db.boss_skill_schedule().insert(BossSkillSchedule {
+        id: 0,
+        raid_id,
+        scheduled_at: ScheduleAt::Time(fires_at.into()),
+    });
+}
+
+/// Cancel a raid's pending boss-ability tick (idempotent). Leaves an in-flight
+/// boss_cast telegraph alone - on pause it survives untouched and resume_raid_from_pause
+/// shifts its cast_ends_at, same as started_at.
+fn cancel_boss_skill_schedule(ctx: &ReducerContext, raid_id: u64) {
+    for schedule in ctx.db.boss_skill_schedule().iter().filter(|s| s.raid_id == raid_id) {
+        ctx.db.boss_skill_schedule().id().delete(&schedule.id);
+    }
+}
+
+/// Full teardown of a raid's boss-ability state: pending tick, in-flight telegraph,
+/// and any still-active debuffs. Use this (not cancel_boss_skill_schedule) when the
+/// raid itself is ending, not just pausing.
+fn cleanup_boss_skill_data(ctx: &ReducerContext, raid_id: u64) {
+    cancel_boss_skill_schedule(ctx, raid_id);
+    for cast in ctx.db.boss_cast().iter().filter(|c| c.raid_id == raid_id) {
+        ctx.db.boss_cast().id().delete(&cast.id);
+    }
+    for effect in ctx.db.status_effect().iter().filter(|e| e.raid_id == raid_id) {
+        ctx.db.status_effect().id().delete(&effect.id);
+    }
+    for minion in ctx.db.raid_minion().iter().filter(|m| m.raid_id == raid_id) {
+        ctx.db.raid_minion().id().delete(&minion.id);
+    }
+}
+
+/// Grace period after a raid starts before the boss-tick enrage mechanic engages -
+/// gives the group a chance to get problems flowing before the soft deadline starts
+const BOSS_TICK_GRACE_PERIOD_SECS: u64 = 60;
+
+/// How often the boss-tick enrage mechanic fires once engaged
+const BOSS_TICK_INTERVAL_SECS: u64 = 20;
+
+/// Per-tick boss HP regen (percent of boss_max_hp) once enrage engages
+const BOSS_TICK_BASE_REGEN_PCT: f32 = 1.0;
+
+/// Each tick the group spends falling behind grows the regen rate by this many
+/// percentage points, capped at BOSS_TICK_MAX_REGEN_PCT - the longer the fight
+/// drags on without the group keeping pace, the harder the soft deadline bites
+const BOSS_TICK_REGEN_GROWTH_PCT: f32 = 0.5;
+const BOSS_TICK_MAX_REGEN_PCT: f32 = 8.0;
+
+/// A raid_player counts as "keeping up" this tick if they've answered within this
+/// many seconds - tighter than PROBLEM_ANSWER_DEADLINE_SECS since this feeds the
+/// enrage escalation read, not an idle/disconnect call
+const BOSS_TICK_KEEPING_UP_SECS: u64 = 20;
+
+/// If fewer than this fraction of active players are keeping up, the tick escalates
+/// its regen rate instead of holding steady - the soft deadline only tightens when
+/// the group's collective CQPM is actually dropping, not on a fixed timer regardless
+const BOSS_TICK_BEHIND_FRACTION_THRESHOLD: f32 = 0.5;
+
+/// (Re)schedule the next boss-tick enrage event for this raid. Idempotent - cancels
+/// any pending tick first, same convention as schedule_boss_skill.
+fn schedule_boss_tick(ctx: &ReducerContext, raid_id: u64, delay_secs: u64) {
+    cancel_boss_tick_schedule(ctx, raid_id);
+    let fires_at = ctx.timestamp + std::time::Duration::from_secs(delay_secs);
+    ctx.db.boss_tick_schedule().insert(BossTickSchedule {
+        id: 0,
+        raid_id,
+        scheduled_at: ScheduleAt::Time(fires_at.into()),
+    });
+}
+
+/// Cancel a raid's pending boss-tick event (idempotent)
+fn cancel_boss_tick_schedule(ctx: &ReducerContext, raid_id: u64) {
+    for schedule in ctx.db.boss_tick_schedule().iter().filter(|s| s.raid_id == raid_id) {
+        ctx.db.boss_tick_schedule().id().delete(&schedule.id);
+    }
+}
+
+/// Out of 100, the chance a decision tick summons an add instead of starting the
+/// boss's own telegraphed ability - only rolled when no add is currently alive
+const RAID_MINION_SUMMON_CHANCE_PCT: u32 = 25;
+
+/// Floor on a summoned add's HP so it's never a free one-shot, even against a
+/// low-difficulty boss
+const RAID_MINION_MIN_HP: u32 = 20;
+
+/// Pick the active raid_player with the most damage_dealt - the threat leader a
+/// newly summoned minion locks onto, Hercules slave-mob style
+fn pick_threat_leader(ctx: &ReducerContext, raid_id: u64) -> Option<String> {
+    ctx.db.raid_player()
+        .raid_id().filter(&raid_id)
+        .filter(|rp| rp.is_active)
+        .max_by_key(|rp| rp.damage_dealt)
+        .map(|rp| rp.player_id)
+}
+
+/// Summon a new add locked onto the current threat leader. No-op if the raid has
+/// nobody active to target.
+fn spawn_raid_minion(ctx: &ReducerContext, raid_id: u64, raid: &Raid) {
+    let target_player_id = match pick_threat_leader(ctx, raid_id) {
+        Some(id) => id,
+        None => return, // Nobody active to chase - skip this tick's summon
+    };
+
+    let hp = (raid.boss_max_hp / 10).max(RAID_MINION_MIN_HP);
+    ctx.db.raid_minion().insert(RaidMinion {
+        id: 0,
+        raid_id,
+        hp,
+        max_hp: hp,
+        target_player_id: target_player_id.clone(),
+        spawned_at: ctx.timestamp,
+    });
+    log::info!("[RAID] minion summoned raid:{} target:{} hp:{}",
+        raid_id, &target_player_id[..8.min(target_player_id.len())], hp);
+}
+
+/// Apply the minion's periodic attack - a SlowAnswers debuff on its target, same
+/// debuff kind and duration the boss's own telegraphed abilities use
+fn apply_minion_attack(ctx: &ReducerContext, raid_id: u64, minion: &RaidMinion) {
+    let expires_at = ctx.timestamp + std::time::Duration::from_secs(BOSS_ABILITY_DEBUFF_SECS);
+    ctx.db.status_effect().insert(StatusEffect {
+        id: 0,
+        raid_id,
+        player_id: minion.target_player_id.clone(),
+        kind: StatusEffectKind::SlowAnswers,
+        expires_at,
+    });
+}
+
+// ==================== ROW LEVEL SECURITY ====================
+
+/// RLS Filter: Only authorized workers (module owner) can see timeback_event_queue rows
+/// This prevents students from seeing sensitive TimeBack event data while still
+/// allowing the worker to subscribe and process events
+#[client_visibility_filter]
+const TIMEBACK_QUEUE_VISIBILITY: Filter = Filter::Sql(
+    "SELECT tq.* FROM timeback_event_queue tq
+     JOIN authorized_worker aw WHERE aw.identity = :sender"
+);
+
+/// RLS Filter: Only authorized workers (module owner) can see timeback_dead_letter rows
+/// Same rationale as TIMEBACK_QUEUE_VISIBILITY - this is operator-facing, not student-facing
+#[client_visibility_filter]
+const TIMEBACK_DEAD_LETTER_VISIBILITY: Filter = Filter::Sql(
+    "SELECT dl.* FROM timeback_dead_letter dl
+     JOIN authorized_worker aw WHERE aw.identity = :sender"
+);
+
+/// RLS Filter: Only authorized workers (e.g. the Prometheus exporter) can see
+/// metric_snapshot rows - operational data, not something students need
+#[client_visibility_filter]
+const METRIC_SNAPSHOT_VISIBILITY: Filter = Filter::Sql(
+    "SELECT m.* FROM metric_snapshot m
+     JOIN authorized_worker aw WHERE aw.identity = :sender"
+);
+
+// ==================== REDUCERS ====================
+
+/// Create a verified session for a client identity
+/// This is called by the Bun gateway AFTER verifying the Playcademy JWT
 /// Only authorized workers (gateway with owner token) can call this
 #[reducer]
-pub fn create_session(ctx: &ReducerContext, client_identity: String, player_id: String) {
-    // Authorization check: only authorized workers can create sessions
-    if ctx.db.authorized_worker().identity().find(&ctx.sender).is_none() {
-        panic!("Unauthorized: only gateway can create sessions");
+pub fn create_session(ctx: &ReducerContext, client_identity: String, player_id: String) {
+    // Authorization check: only authorized workers can create sessions
+    if ctx.db.authorized_worker().identity().find(&ctx.sender).is_none() {
+        panic!("Unauthorized: only gateway can create sessions");
+    }
+    
+    let identity = Identity::from_hex(&client_identity)
+        .expect("Invalid identity hex string");
+    
+    // Delete stale sessions: same player (unclean reconnect) OR same connection_id (prevents PK conflict)
+    let stale_sessions: Vec<_> = ctx.db.session()
+        .iter()
+        .filter(|s| s.player_id == player_id || s.connection_id == identity)
+        .map(|s| s.connection_id)
+        .collect();
+    for conn_id in stale_sessions {
+        ctx.db.session().connection_id().delete(&conn_id);
+    }
+    
+    // Create verified session
+    ctx.db.session().insert(Session {
+        connection_id: identity,
+        player_id: player_id.clone(),
+        connected_at: ctx.timestamp,
+    });
+    
+    log::info!("[SESSION] created player:{} ws:{}", &player_id[..8.min(player_id.len())], &client_identity[..8.min(client_identity.len())]);
+}
+
+/// Player connects to the game
+/// The player_id is read from the verified session created by the gateway
+/// PII (timeback_id, email) comes from client - can only affect their own record
+#[reducer]
+pub fn connect(ctx: &ReducerContext, name: String, grade: Option<u8>, timeback_id: Option<String>, email: Option<String>) {
+    // Get player_id from verified session (created by gateway)
+    // This is the ONLY thing we verify - client can't spoof playerId
+    let session = ctx.db.session()
+        .connection_id()
+        .find(&ctx.sender)
+        .expect("Session not found - verify with gateway first");
+    
+    let player_id = session.player_id.clone();
+    // timeback_id and email from client are fine - they can only affect their own record
+    
+    // Get or create player
+    let _player = if let Some(mut existing) = ctx.db.player().id().find(&player_id) {
+        // Existing player - update last played and handle resets
+        
+        // Update last played and reset daily if new day
+        if is_new_day(&existing.timezone, existing.last_played, ctx.timestamp) {
+            // Check RAID streak (not login streak) before resetting daily quests
+            // Streak requires raiding daily, not just logging in
+            let days_since_raid = calculate_days_between(&existing.timezone, existing.last_raid, ctx.timestamp);
+            if days_since_raid > 1 {
+                // Didn't raid yesterday - break streak
+                let mut quests = parse_quests(&existing.quests);
+                let old_streak = quests["daily_streak"].as_u64().unwrap_or(0);
+                quests["daily_streak"] = json!(0);
+                existing.quests = Some(quests.to_string());
+                log::info!("[QUEST] streak broken player:{} lost_streak:{} days_since_raid:{}", 
+                    existing.name, old_streak, days_since_raid - 1);
+            }
+            
+            reset_quests_by_prefix(&mut existing, "daily_");
+            log::info!("[CONNECT] daily reset for {}", existing.name);
+        }
+        
+        // Reset weekly if new week (Monday reset)
+        if is_new_week(&existing.timezone, existing.last_weekly_reset, ctx.timestamp) {
+            reset_quests_by_prefix(&mut existing, "weekly_");
+            existing.last_weekly_reset = ctx.timestamp;
+            log::info!("[CONNECT] weekly reset for {}", existing.name);
+        }
+        
+        existing.last_played = ctx.timestamp;
+        
+        // Only update grade if provided (Some = from API, None = API failed, keep existing)
+        let old_grade = existing.grade;
+        match grade {
+            Some(new_grade) if existing.grade != new_grade => {
+                log::info!("[CONNECT] grade {} → {} for {}", existing.grade, new_grade, existing.name);
+                existing.grade = new_grade.min(5);
+            }
+            None => {
+                // API failed - keeping existing. Worth noting for debugging grade issues.
+                log::debug!("[CONNECT] grade API failed, keeping {} for {}", existing.grade, existing.name);
+            }
+            _ => {} // Grade unchanged, no log needed
+        }
+        
+        // Update identity fields from verified token (track changes for canonical log)
+        let old_email = existing.email.clone();
+        let old_timeback = existing.timeback_id.clone();
+        existing.name = name;
+        if timeback_id.is_some() {
+            existing.timeback_id = timeback_id.clone();
+        }
+        if email.is_some() {
+            existing.email = email.clone();
+        }
+        let email_changed = old_email != existing.email;
+        let timeback_changed = old_timeback != existing.timeback_id;
+        
+        // Recalculate grade-dependent data if grade changed
+        recalculate_for_grade_change(ctx, &mut existing, old_grade);
+        
+        // Save updated player (includes grade change effects if any)
+        ctx.db.player().id().update(existing.clone());
+        
+        // Resume paused raid if player was in one
+        if let Some(raid_id) = existing.in_raid_id {
+            if let Some(raid) = ctx.db.raid().id().find(&raid_id) {
+                let state_name = match raid.state {
+                    RaidState::Paused => "paused",
+                    RaidState::InProgress => "running",
+                    RaidState::Victory => "victory",
+                    RaidState::Failed => "defeat",
+                    RaidState::Rematch => "rematch",
+                    RaidState::Matchmaking => "matchmaking",
+                    RaidState::Countdown => "countdown",
+                };
+                
+                match raid.state {
+                    RaidState::Paused => {
+                        if let Some(mut rp) = find_raid_player(ctx, &player_id, raid_id) {
+                            rp.is_active = true;
+                            rp.inactive_since = None; // Reconnected - abandon grace window resets
+                            update_raid_player(ctx, rp);
+                        }
+                        cancel_reconnect_deadline(ctx, raid_id, &player_id);
+                        if let Err(e) = resume_raid_from_pause(ctx, raid_id) {
+                            log::error!("[RAID] resume failed raid:{} error:{}", raid_id, e);
+                        }
+                    }
+                    RaidState::InProgress | RaidState::Victory | RaidState::Failed | RaidState::Rematch => {
+                        if let Some(mut rp) = find_raid_player(ctx, &player_id, raid_id) {
+                            if !rp.is_active {
+                                rp.is_active = true;
+                                rp.inactive_since = None; // Reconnected - abandon grace window resets
+                                if raid.state == RaidState::Rematch {
+                                    rp.is_ready = false;
+                                }
+                                update_raid_player(ctx, rp);
+                            }
+                        }
+                        cancel_reconnect_deadline(ctx, raid_id, &player_id);
+                        // Resync boss phase in case teammates dealt damage while this
+                        // player was gone - idempotent, so a reconnect never re-fires
+                        // a phase that's already current
+                        if raid.state == RaidState::InProgress {
+                            recompute_raid_phase(ctx, raid_id);
+                        }
+                    }
+                    _ => {}
+                }
+
+                // One canonical log for reconnect-to-raid
+                log::info!("[CONNECT] {} rejoining raid:{} state:{}", existing.name, raid_id, state_name);
+            } else {
+                log::warn!("[CONNECT] {} had stale raid:{} - cleared", existing.name, raid_id);
+                existing.in_raid_id = None;
+                ctx.db.player().id().update(existing.clone());
+            }
+        }
+        
+        // Wide event: one canonical log with full player context
+        let pid = &player_id[..8.min(player_id.len())];
+        let has_timeback = existing.timeback_id.is_some();
+        let quests = parse_quests(&existing.quests);
+        let streak = quests["daily_streak"].as_u64().unwrap_or(0);
+        let rank_str = existing.rank.as_deref().unwrap_or("unranked");
+        
+        if email_changed || timeback_changed {
+            log::warn!("[CONNECT] player=\"{}\" player_id={} type=returning grade={} rank={} raids={} streak={} timeback={} identity_change=true", 
+                existing.name, pid, existing.grade, rank_str, existing.total_raids, streak, has_timeback);
+        } else {
+            log::info!("[CONNECT] player=\"{}\" player_id={} type=returning grade={} rank={} raids={} streak={} timeback={}", 
+                existing.name, pid, existing.grade, rank_str, existing.total_raids, streak, has_timeback);
+        }
+        
+        existing
+    } else {
+        // Create new player
+        let resolved_grade = grade.unwrap_or(3).min(5);
+        let grade_source = if grade.is_some() { "api" } else { "default" };
+        let new_player = Player {
+            id: player_id.clone(),
+            name,
+            grade: resolved_grade,
+            rank: None, // New players start unranked
+            total_problems: 0,
+            total_correct: 0,
+            avg_response_ms: 0,
+            best_response_ms: u32::MAX,
+            total_raids: 0,
+            quests: Some(json!({
+                "daily_raid_count": 0,
+                "daily_streak": 0
+            }).to_string()),
+            last_played: ctx.timestamp,
+            last_raid: ctx.timestamp,  // Initialize to now (no existing streak)
+            last_weekly_reset: ctx.timestamp,
+            total_ap: 0,
+            in_raid_id: None,
+            timeback_id,  // From client (can only affect their own record)
+            email,        // From client (can only affect their own record)
+            abandon_score: 0,
+            matchmaking_cooldown_until: None,
+            matchmaking_cooldown_strikes: 0,
+            cqpm_baseline: 0.0,
+            skill_rating: SKILL_RATING_ANCHOR,
+            last_bonus_date: None,
+            daily_bonus_streak: 0,
+            bonus_score: 0,
+            target_retention: FSRS_DEFAULT_TARGET_RETENTION,
+            elo_rating: ELO_RATING_DEFAULT,
+            best_elo_rating: ELO_RATING_DEFAULT,
+            mastered_fact_count: 0,
+            facts_seen_count: 0,
+            stability_sum: 0.0,
+            division: None,
+            timezone: tz::DEFAULT_ZONE.to_string(),
+            difficulty_level: DIFFICULTY_LEVEL_DEFAULT,
+        };
+        ctx.db.player().insert(new_player.clone());
+        
+        // Wide event: one canonical log for new player
+        let pid = &player_id[..8.min(player_id.len())];
+        let has_timeback = new_player.timeback_id.is_some();
+        log::info!("[CONNECT] player=\"{}\" player_id={} type=new grade={} rank=unranked raids=0 streak=0 timeback={} grade_source={}", 
+            new_player.name, pid, new_player.grade, has_timeback, grade_source);
+        
+        new_player
+    };
+}
+
+/// Recalculate grade-dependent data when player's grade changes
+/// Called by both set_grade (admin) and connect (Timeback API)
+fn recalculate_for_grade_change(ctx: &ReducerContext, player: &mut Player, old_grade: u8) {
+    // Early return if grade didn't actually change
+    if player.grade == old_grade {
+        return;
+    }
+    
+    let player_id = player.id.clone();
+    
+    // Recalculate ALL fact mastery levels for new grade thresholds
+    let mut recalc_count = 0;
+    for mut fact in ctx.db.fact_mastery().player_id().filter(&player_id) {
+        // mastery_level change tracking removed (was used for verbose logging)
+        fact.mastery_level = calculate_mastery_level(&fact, player.grade);
+        
+        ctx.db.fact_mastery().id().update(fact);
+        recalc_count += 1;
+    }
+    
+    // The grade change invalidates the cached mastery aggregate wholesale (it's
+    // keyed to a fact pool that just changed), so it needs a full rebuild here -
+    // the one place update_fact_mastery's per-answer delta doesn't apply
+    rebuild_mastery_aggregate(ctx, player);
+
+    // Recalculate rank for new grade's fact pool
+    let (mastered_count, total_facts) = get_player_mastery_stats(ctx, &player);
+    let new_rank = calculate_player_rank(mastered_count, total_facts);
+    player.rank = new_rank.clone();
+    player.division = Some(calculate_division(&player.rank, mastered_count, total_facts));
+
+    // A grade change is a genuine reason for CQPM to drop - reset the sandbagging
+    // baseline instead of letting the new grade's slower pace trip the guard
+    player.cqpm_baseline = 0.0;
+    
+    // Refresh leaderboards for both old and new grades
+    refresh_leaderboard(ctx, old_grade);
+    if old_grade != player.grade {
+        refresh_leaderboard(ctx, player.grade);
+    }
+    
+    // One canonical log for grade change
+    log::info!("[GRADE] changed player:{} grade:{}→{} recalc:{} rank:{:?}", 
+        &player_id[..8.min(player_id.len())], old_grade, player.grade, recalc_count, new_rank);
+}
+
+/// Update player's grade level
+/// - Admins can change any player's grade
+/// - Non-TimeBack students can self-service their own grade (it's just a difficulty setting)
+/// - TimeBack students' grades are locked (synced from AlphaMath enrollment)
+/// Set player's TimeBack ID (admin function for fixing Playcademy sync failures)
+#[reducer]
+pub fn set_timeback_id(ctx: &ReducerContext, player_id: String, timeback_id: String) {
+    // Capability check: full admins pass unconditionally; scoped workers need
+    // a worker_capability grant for "set_timeback_id" that satisfies its restrictions
+    if let Err(e) = check_capability(ctx, "set_timeback_id", 2) {
+        log::warn!("Unauthorized set_timeback_id attempt by {}: {}", ctx.sender, e);
+        return;
+    }
+    
+    // Admin-only: Manually fix TimeBack ID when Playcademy integration fails
+    if let Some(mut player) = ctx.db.player().id().find(&player_id) {
+        player.timeback_id = Some(timeback_id.clone());
+        ctx.db.player().id().update(player);
+        log::info!("[ADMIN] set_timeback_id player:{} timeback:{}", &player_id[..8.min(player_id.len())], &timeback_id[..8.min(timeback_id.len())]);
+    } else {
+        log::error!("set_timeback_id: Player {} not found", player_id);
+    }
+}
+
+/// Update player's grade level
+/// - Admins: Can change any player's grade
+/// - Non-TimeBack students: Can self-service their own grade (difficulty setting)
+/// - TimeBack students: Grade locked (synced from AlphaMath enrollment)
+#[reducer]
+pub fn set_grade(ctx: &ReducerContext, grade: u8, player_id: Option<String>) {
+    let is_admin = ctx.db.authorized_worker().identity().find(&ctx.sender).is_some();
+    
+    if !is_admin {
+        // Self-service mode: verify student can change own grade
+        
+        // Can't change someone else's grade
+        if player_id.is_some() {
+            log::warn!("Non-admin attempted to change another player's grade");
+        return;
+        }
+        
+        // Get own player
+        let player = match get_player(ctx) {
+            Ok(p) => p,
+            Err(e) => {
+                log::error!("set_grade self-service failed: {}", e);
+                return;
+            }
+        };
+        
+        // TimeBack students can't self-service (grade syncs from AlphaMath enrollment)
+        // Treat empty string as no TimeBack (admin may have cleared it)
+        let has_timeback = player.timeback_id.as_ref().map_or(false, |id| !id.is_empty());
+        if has_timeback {
+            log::warn!("TimeBack student {} attempted to self-change grade (rejected)", player.name);
+            return;
+        }
+        
+        // OK - non-TimeBack student changing own grade
+    }
+    
+    let mut player = if let Some(id) = player_id {
+        // Admin mode: change specified player's grade
+        match ctx.db.player().id().find(&id) {
+            Some(p) => p,
+            None => {
+                log::error!("set_grade: player {} not found", id);
+                return;
+            }
+        }
+    } else {
+        // Self-service mode: change own grade
+        match get_player(ctx) {
+            Ok(p) => p,
+            Err(e) => {
+                log::error!("set_grade failed: {}", e);
+                return;
+            }
+        }
+    };
+    
+    let old_grade = player.grade;
+    player.grade = grade.min(5); // Cap at grade 5
+    
+    // Save grade FIRST so leaderboard refresh sees correct grade in DB
+    ctx.db.player().id().update(player.clone());
+    
+    // Recalculate all grade-dependent data (updates rank in memory)
+    recalculate_for_grade_change(ctx, &mut player, old_grade);
+    
+    // Save final player state (with updated rank)
+    ctx.db.player().id().update(player);
+}
+
+/// Self-service: set the player's own timezone (see tz module). Only affects
+/// how is_new_day/is_new_week/calculate_days_between compute that player's
+/// reset boundaries - unrecognized zone names are accepted here (tz module
+/// falls back to the old fixed PST behavior) rather than rejected, since a
+/// typo shouldn't brick the player's streak tracking.
+#[reducer]
+pub fn set_timezone(ctx: &ReducerContext, timezone: String) {
+    let mut player = match get_player(ctx) {
+        Ok(p) => p,
+        Err(e) => {
+            log::error!("set_timezone failed: {}", e);
+            return;
+        }
+    };
+    player.timezone = timezone;
+    ctx.db.player().id().update(player);
+}
+
+/// Admin: Reset a player's progress (keep identity, wipe stats)
+/// Used when sibling plays on wrong account, demo resets, etc.
+#[reducer]
+pub fn admin_reset_player(ctx: &ReducerContext, player_id: String) {
+    // Capability check: full admins pass unconditionally; scoped workers need
+    // a worker_capability grant for "admin_reset_player" that satisfies its restrictions
+    if let Err(e) = check_capability(ctx, "admin_reset_player", 1) {
+        panic!("Unauthorized: {}", e);
+    }
+    
+    let mut player = match ctx.db.player().id().find(&player_id) {
+        Some(p) => p,
+        None => {
+            log::error!("admin_reset_player: Player {} not found", player_id);
+            return;
+        }
+    };
+    
+    let player_name = player.name.clone();
+    
+    // Delete all performance snapshots
+    let snapshots: Vec<_> = ctx.db.performance_snapshot()
+        .player_id().filter(&player_id)
+        .collect();
+    let snapshot_count = snapshots.len();
+    for ps in snapshots {
+        ctx.db.performance_snapshot().id().delete(&ps.id);
+    }
+    
+    // Delete all fact mastery (will repopulate on play)
+    let masteries: Vec<_> = ctx.db.fact_mastery()
+        .player_id().filter(&player_id)
+        .collect();
+    let mastery_count = masteries.len();
+    for fm in masteries {
+        ctx.db.fact_mastery().id().delete(&fm.id);
+    }
+    
+    // Reset player to defaults (keep id, name, grade, timeback_id, email)
+    player.rank = None;
+    player.total_problems = 0;
+    player.total_correct = 0;
+    player.avg_response_ms = 0;
+    player.best_response_ms = u32::MAX;
+    player.total_raids = 0;
+    player.total_ap = 0;
+    player.quests = Some(json!({
+        "daily_raid_count": 0,
+        "daily_streak": 0
+    }).to_string());
+    player.last_played = ctx.timestamp;
+    player.last_raid = ctx.timestamp;
+    player.last_weekly_reset = ctx.timestamp;
+    player.in_raid_id = None;
+    
+    ctx.db.player().id().update(player);
+    
+    log::info!("[ADMIN] reset player:{} snapshots:{} masteries:{}", player_name, snapshot_count, mastery_count);
+}
+
+// ==================== PAUSE/RESUME HELPERS ====================
+
+/// Count active players in a raid
+fn count_active_raid_players(ctx: &ReducerContext, raid_id: u64) -> usize {
+    ctx.db.raid_player()
+        .raid_id().filter(&raid_id)
+        .filter(|rp| rp.is_active)
+        .count()
+}
+
+/// How long a multiplayer straggler has to reconnect before being forfeited from
+/// the raid - see schedule_reconnect_deadline / check_reconnect_deadline
+const RECONNECT_DEADLINE_SECS: u64 = 45;
+
+/// Mark player as inactive WITHOUT clearing player.in_raid_id
+/// This allows resume on reconnect - only cleanup_player_raid_data clears in_raid_id
+fn mark_player_inactive_in_raid(ctx: &ReducerContext, player_id: &String, raid_id: u64) {
+    if let Some(mut rp) = find_raid_player(ctx, player_id, raid_id) {
+        rp.is_active = false;
+        if rp.inactive_since.is_none() {
+            rp.inactive_since = Some(ctx.timestamp);
+        }
+        rp.disconnected_mid_raid = true;
+        update_raid_player(ctx, rp);
+    }
+
+    // Multiplayer raids get a bounded grace window before the straggler is forfeited
+    // so the team isn't stuck waiting indefinitely - solo raids just pause, there's no
+    // teammate to keep moving without them
+    let is_multiplayer = ctx.db.raid().id().find(&raid_id)
+        .map(|r| r.room_code.is_some())
+        .unwrap_or(false);
+    if is_multiplayer {
+        schedule_reconnect_deadline(ctx, raid_id, player_id.clone());
+    }
+}
+
+/// (Re)schedule a reconnect-deadline firing for this (raid, player) pair. Idempotent -
+/// cancels any existing deadline first so a flaky connection bouncing before the first
+/// deadline fires doesn't stack schedules.
+fn schedule_reconnect_deadline(ctx: &ReducerContext, raid_id: u64, player_id: String) {
+    cancel_reconnect_deadline(ctx, raid_id, &player_id);
+    let fires_at = ctx.timestamp + std::time::Duration::from_secs(RECONNECT_DEADLINE_SECS);
+    ctx.db.reconnect_deadline().insert(ReconnectDeadline {
+        id: 0,
+        raid_id,
+        player_id,
+        scheduled_at: ScheduleAt::Time(fires_at.into()),
+    });
+}
+
+/// Cancel a player's pending reconnect deadline for this raid (idempotent)
+fn cancel_reconnect_deadline(ctx: &ReducerContext, raid_id: u64, player_id: &str) {
+    for schedule in ctx.db.reconnect_deadline().iter().filter(|s| s.raid_id == raid_id && s.player_id == player_id) {
+        ctx.db.reconnect_deadline().id().delete(&schedule.id);
+    }
+}
+
+/// Cancel raid timeout (idempotent)
+fn cancel_raid_timeout(ctx: &ReducerContext, raid_id: u64) {
+    for schedule in ctx.db.raid_timeout_schedule().iter().filter(|s| s.raid_id == raid_id) {
+        ctx.db.raid_timeout_schedule().id().delete(&schedule.id);
+    }
+}
+
+fn cancel_countdown_schedule(ctx: &ReducerContext, raid_id: u64) {
+    for schedule in ctx.db.countdown_schedule().iter().filter(|s| s.raid_id == raid_id) {
+        ctx.db.countdown_schedule().id().delete(&schedule.id);
+    }
+}
+
+/// How long a fully-empty raid sits Paused before the watchdog gives up on it and
+/// abandons it outright - see schedule_pause_timeout / check_pause_timeout
+const PAUSE_TIMEOUT_GRACE_SECS: u64 = 300;
+
+/// (Re)schedule the whole-raid pause watchdog. Idempotent - cancels any existing
+/// watchdog first, same convention as schedule_reconnect_deadline.
+fn schedule_pause_timeout(ctx: &ReducerContext, raid_id: u64) {
+    cancel_pause_timeout(ctx, raid_id);
+    let fires_at = ctx.timestamp + std::time::Duration::from_secs(PAUSE_TIMEOUT_GRACE_SECS);
+    ctx.db.pause_timeout_schedule().insert(PauseTimeoutSchedule {
+        id: 0,
+        raid_id,
+        scheduled_at: ScheduleAt::Time(fires_at.into()),
+    });
+}
+
+/// Cancel a raid's pending pause watchdog (idempotent)
+fn cancel_pause_timeout(ctx: &ReducerContext, raid_id: u64) {
+    for schedule in ctx.db.pause_timeout_schedule().iter().filter(|s| s.raid_id == raid_id) {
+        ctx.db.pause_timeout_schedule().id().delete(&schedule.id);
+    }
+}
+
+/// How often the idle-answer watchdog sweeps an in-progress raid for stalled
+/// players - see schedule_idle_check / check_idle_players
+const IDLE_CHECK_INTERVAL_SECS: u64 = 15;
+
+/// A connected player who hasn't submitted an answer in this long is treated as
+/// idle and marked inactive, same as if they'd disconnected - see check_idle_players.
+/// Comfortably above PROBLEMS_PER_RAID pacing so it never trips a player who's just
+/// thinking hard about one problem, only one who's truly stalled.
+const PROBLEM_ANSWER_DEADLINE_SECS: u64 = 90;
+
+/// (Re)schedule the next idle-answer sweep for this raid. Idempotent - cancels any
+/// pending sweep first so pause/resume can't stack schedules.
+fn schedule_idle_check(ctx: &ReducerContext, raid_id: u64, delay_secs: u64) {
+    cancel_idle_check(ctx, raid_id);
+    let fires_at = ctx.timestamp + std::time::Duration::from_secs(delay_secs);
+    ctx.db.idle_check_schedule().insert(IdleCheckSchedule {
+        id: 0,
+        raid_id,
+        scheduled_at: ScheduleAt::Time(fires_at.into()),
+    });
+}
+
+/// Cancel a raid's pending idle-answer sweep (idempotent)
+fn cancel_idle_check(ctx: &ReducerContext, raid_id: u64) {
+    for schedule in ctx.db.idle_check_schedule().iter().filter(|s| s.raid_id == raid_id) {
+        ctx.db.idle_check_schedule().id().delete(&schedule.id);
+    }
+}
+
+/// How long a player has to answer the problem currently in front of them before
+/// check_problem_timeout opens the grace window - see generate_problem_batch /
+/// submit_answer, which (re)arm this every time a problem becomes "current".
+const PROBLEM_TIMEOUT_SECS: u64 = 20;
+
+/// Extra window after PROBLEM_TIMEOUT_SECS elapses during which a late-arriving
+/// submit_answer is still accepted normally instead of being raced by the auto-fail -
+/// same "accept a move that lands right at the deadline" reconciliation a turn-based
+/// server uses to tolerate network jitter.
+const PROBLEM_TIMEOUT_GRACE_SECS: u64 = 3;
+
+/// Damage bonus per stack of RaidPlayer.current_streak (consecutive first-attempt
+/// correct answers) - see submit_answer's damage calc
+const STREAK_DAMAGE_BONUS_PCT_PER_STACK: u32 = 5;
+
+/// Cap on the streak damage bonus, so a long streak doesn't eventually dwarf the
+/// base roll
+const STREAK_DAMAGE_BONUS_PCT_MAX: u32 = 50;
+
+/// Consecutive fast-correct (first-attempt) answers needed to trigger a bonus
+/// target burst - see RaidPlayer.fast_streak
+const BONUS_TARGET_FAST_STREAK_N: u32 = 5;
+
+/// Extra damage pct on the answer that completes a bonus target streak
+const BONUS_TARGET_DAMAGE_BURST_PCT: u32 = 40;
+
+/// Raid bonus points awarded per triggered bonus target burst
+const BONUS_TARGET_POINTS_PER_BURST: u32 = 10;
+
+/// Raid bonus points subtracted per wrong first-attempt answer
+const BONUS_TARGET_WRONG_PENALTY: u32 = 3;
+
+/// Combo streak length (consecutive first-attempt correct answers) per stack of
+/// the Combo buff - see BuffKind::Combo
+const COMBO_BUFF_STREAK_PER_STACK: u32 = 5;
+
+/// Damage bonus per Combo stack
+const COMBO_BUFF_DAMAGE_BONUS_PCT: f32 = 10.0;
+
+/// Cap on the Combo buff's damage bonus
+const COMBO_BUFF_DAMAGE_BONUS_PCT_MAX: f32 = 50.0;
+
+/// How long a Focus buff lasts once earned (see BONUS_TARGET_FAST_STREAK_N, which
+/// triggers it)
+const FOCUS_BUFF_DURATION_SECS: u64 = 30;
+
+/// Focus buff doubles crit chance - stored as the flat percentage-point add on
+/// top of the base 15% crit roll in calculate_damage
+const FOCUS_BUFF_CRIT_CHANCE_BONUS_PCT: u32 = 15;
+
+/// Stagger debuff halves damage until the next correct answer clears it
+const STAGGER_DEBUFF_DAMAGE_MULT_PCT: f32 = 50.0;
+
+/// The player's current problem: lowest-sequence problem in this raid that doesn't
+/// have a PlayerAnswer yet. None once they've worked through the whole prefetched batch.
+fn current_unanswered_problem(ctx: &ReducerContext, raid_id: u64, player_id: &str) -> Option<Problem> {
+    ctx.db.problem()
+        .iter()
+        .filter(|p| p.raid_id == raid_id && p.player_id == player_id)
+        .filter(|p| ctx.db.player_answer().iter().find(|a| a.problem_id == p.id && a.player_id == player_id).is_none())
+        .min_by_key(|p| p.sequence)
+}
+
+/// (Re)arm the per-problem timeout for this player's current problem. Idempotent -
+/// cancels any existing timeout first so a stale schedule never outlives the problem
+/// it was watching.
+fn schedule_problem_timeout(ctx: &ReducerContext, raid_id: u64, player_id: &str, problem_id: u64, delay_secs: u64) {
+    cancel_problem_timeout(ctx, raid_id, player_id);
+    let fires_at = ctx.timestamp + std::time::Duration::from_secs(delay_secs);
+    ctx.db.problem_timeout_schedule().insert(ProblemTimeoutSchedule {
+        id: 0,
+        raid_id,
+        player_id: player_id.to_string(),
+        problem_id,
+        in_grace_period: false,
+        scheduled_at: ScheduleAt::Time(fires_at.into()),
+    });
+}
+
+/// Cancel a player's pending problem timeout for this raid (idempotent)
+fn cancel_problem_timeout(ctx: &ReducerContext, raid_id: u64, player_id: &str) {
+    for schedule in ctx.db.problem_timeout_schedule().iter().filter(|s| s.raid_id == raid_id && s.player_id == player_id) {
+        ctx.db.problem_timeout_schedule().id().delete(&schedule.id);
+    }
+}
+
+/// Cancel every pending problem timeout for this raid, any player - called when the
+/// raid itself ends/cleans up, same spot cancel_idle_check is called from.
+fn cancel_all_problem_timeouts(ctx: &ReducerContext, raid_id: u64) {
+    for schedule in ctx.db.problem_timeout_schedule().iter().filter(|s| s.raid_id == raid_id) {
+        ctx.db.problem_timeout_schedule().id().delete(&schedule.id);
+    }
+}
+
+/// Pause raid if all players disconnected
+/// Only pauses when active_player_count == 0 (solo DC or all multi players DC'd)
+fn pause_raid_if_empty(ctx: &ReducerContext, raid_id: u64) -> Result<(), String> {
+    let mut raid = ctx.db.raid().id().find(&raid_id)
+        .ok_or("Raid not found")?;
+
+    if raid.state != RaidState::InProgress {
+        return Ok(());  // Only pause active raids
+    }
+    if count_active_raid_players(ctx, raid_id) > 0 {
+        return Ok(());  // Still has active players - DON'T PAUSE (squad continues)
+    }
+
+    // Transition: InProgress -> Paused
+    raid.state = RaidState::Paused;
+    raid.pause_started_at = Some(ctx.timestamp);
+    cancel_raid_timeout(ctx, raid_id);
+    cancel_boss_skill_schedule(ctx, raid_id);
+    cancel_boss_tick_schedule(ctx, raid_id);
+    cancel_idle_check(ctx, raid_id);
+    cancel_all_problem_timeouts(ctx, raid_id);
+    ctx.db.raid().id().update(raid);
+    schedule_pause_timeout(ctx, raid_id);
+    Ok(())
+}
+
+/// abandon_score threshold that trips a matchmaking cooldown (see record_raid_outcome)
+const ABANDON_SCORE_THRESHOLD: u32 = 4;
+
+/// An inactive player reconnecting within this window counts as a transient network
+/// drop (Good), not an Abandon
+const RECONNECT_GRACE_SECONDS: u64 = 90;
+
+/// Escalating matchmaking cooldown durations (5min, 15min, then holds at 60min)
+/// indexed by matchmaking_cooldown_strikes (capped at the last entry)
+const ABANDON_COOLDOWN_LADDER_SECS: [u64; 3] = [300, 900, 3600];
+
+/// Classify each participant's outcome for a just-ended multiplayer raid.
+/// `raid_players` must be the FULL (unfiltered) roster for the raid, not just
+/// those who dealt damage or answered problems - NoShow/pure-abandon rows matter here.
+fn classify_raid_outcomes(ctx: &ReducerContext, raid_players: &[RaidPlayer]) -> Vec<(String, RaidOutcomeKind)> {
+    raid_players.iter().map(|rp| {
+        let outcome = if !rp.is_ready && rp.problems_answered == 0 && rp.damage_dealt == 0 {
+            RaidOutcomeKind::NoShow
+        } else if !rp.is_active {
+            let reconnect_grace_elapsed = rp.inactive_since
+                .and_then(|since| ctx.timestamp.duration_since(since))
+                .map(|d| d.as_secs() >= RECONNECT_GRACE_SECONDS)
+                .unwrap_or(false);
+            if reconnect_grace_elapsed {
+                RaidOutcomeKind::Abandon
+            } else {
+                RaidOutcomeKind::Good
+            }
+        } else {
+            RaidOutcomeKind::Good
+        };
+        (rp.player_id.clone(), outcome)
+    }).collect()
+}
+
+/// Record a raid outcome and update the player's abandon_score / matchmaking cooldown.
+/// Lives server-side (called only from end_raid) so it can't be spoofed by the client.
+fn record_raid_outcome(ctx: &ReducerContext, player_id: &str, raid_id: u64, outcome: RaidOutcomeKind) {
+    ctx.db.raid_outcome().insert(RaidOutcome {
+        id: 0,
+        player_id: player_id.to_string(),
+        raid_id,
+        outcome,
+        recorded_at: ctx.timestamp,
+    });
+
+    let mut player = match ctx.db.player().id().find(&player_id.to_string()) {
+        Some(p) => p,
+        None => return,
+    };
+
+    match outcome {
+        RaidOutcomeKind::Abandon => player.abandon_score = player.abandon_score.saturating_add(1),
+        RaidOutcomeKind::Good => player.abandon_score = player.abandon_score.saturating_sub(1),
+        RaidOutcomeKind::NoShow => {}
+    }
+
+    if player.abandon_score >= ABANDON_SCORE_THRESHOLD {
+        let ladder_idx = (player.matchmaking_cooldown_strikes as usize).min(ABANDON_COOLDOWN_LADDER_SECS.len() - 1);
+        let cooldown_secs = ABANDON_COOLDOWN_LADDER_SECS[ladder_idx];
+        player.matchmaking_cooldown_strikes = player.matchmaking_cooldown_strikes.saturating_add(1);
+        player.matchmaking_cooldown_until = Some(ctx.timestamp + std::time::Duration::from_secs(cooldown_secs));
+        player.abandon_score = 0; // Reset so the next trip requires a fresh run of abandons
+        log::info!("[MATCHMAKING] cooldown player:{} strikes:{} cooldown_secs:{}",
+            &player.id[..8.min(player.id.len())], player.matchmaking_cooldown_strikes, cooldown_secs);
+    }
+
+    ctx.db.player().id().update(player);
+}
+
+/// Append an entry to this raid's replay stream, bumping `raid.event_offset`.
+/// Takes an already-loaded `&mut Raid` so a caller that's about to persist the raid
+/// anyway (submit_answer's boss-damage block, end_raid_with_killing_blow) doesn't
+/// pay for a second fetch/update round trip - see emit_raid_event for the
+/// self-contained variant.
+fn record_raid_event(ctx: &ReducerContext, raid: &mut Raid, kind: RaidEventKind, player_id: Option<String>, damage: u32) {
+    let offset = raid.event_offset;
+    raid.event_offset = raid.event_offset.saturating_add(1);
+    ctx.db.raid_event().insert(RaidEvent {
+        id: 0,
+        raid_id: raid.id,
+        offset,
+        kind,
+        player_id,
+        damage,
+        boss_hp_after: raid.boss_hp,
+        timestamp: ctx.timestamp,
+    });
+}
+
+/// Self-contained variant of record_raid_event for call sites with no raid
+/// mutation already in flight - fetches, bumps event_offset, persists, and inserts
+/// the event in one shot.
+fn emit_raid_event(ctx: &ReducerContext, raid_id: u64, kind: RaidEventKind, player_id: Option<String>, damage: u32) {
+    if let Some(mut raid) = ctx.db.raid().id().find(&raid_id) {
+        record_raid_event(ctx, &mut raid, kind, player_id, damage);
+        ctx.db.raid().id().update(raid);
+    }
+}
+
+/// "Replay" a raid's event stream from a given point - see RaidEvent.
+///
+/// A SpacetimeDB reducer can't hand data back to its caller the way an RPC
+/// response would - every read in this module goes through a client subscribing to
+/// a public table and filtering with SQL (see MetricSnapshot / compute_metric_snapshot
+/// for the same "public table is the query result" idiom). `raid_event` is public
+/// and indexed on both `offset` and `timestamp`, so the actual seek - "everything at
+/// or after this offset" or "everything from this timestamp on" - happens
+/// client-side against those columns. This reducer validates the request and logs
+/// it for server-side replay-usage telemetry.
+#[reducer]
+pub fn replay_raid(ctx: &ReducerContext, raid_id: u64, from_offset: Option<u64>, from_timestamp: Option<Timestamp>) {
+    if ctx.db.raid().id().find(&raid_id).is_none() {
+        log::warn!("replay_raid: raid {} not found", raid_id);
+        return;
+    }
+
+    match (from_offset, from_timestamp) {
+        (Some(offset), _) => log::info!("[REPLAY] raid:{} from_offset:{}", raid_id, offset),
+        (None, Some(ts)) => log::info!("[REPLAY] raid:{} from_timestamp:{:?}", raid_id, ts),
+        (None, None) => log::info!("[REPLAY] raid:{} from_offset:0 (full replay)", raid_id),
+    }
+}
+
+// ==================== REWARD MULTIPLIER WINDOWS ====================
+// Admin-scheduled "double raid" events - see active_reward_window.
+
+/// A time window where raid AP/XP rewards are boosted, e.g. a daily/weekend
+/// "double raid" event. Created/cleared by admin_create_reward_window /
+/// admin_clear_reward_window. Optionally scoped to one grade and/or track;
+/// None means "applies to every grade"/"applies to every track".
+#[table(name = reward_multiplier_window, public)]
+pub struct RewardMultiplierWindow {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+
+    pub start_ts: Timestamp,
+    pub end_ts: Timestamp,
+
+    pub ap_multiplier: f32,
+    pub xp_multiplier: f32,
+
+    pub grade: Option<u8>,
+    pub track: Option<String>,
+
+    /// Breaks ties when more than one window is active at once - see
+    /// active_reward_window. A more specific event should outrank a standing
+    /// global one, so give it a higher priority rather than letting them stack.
+    #[default(0i32)]
+    pub priority: i32,
+
+    /// Freeform label for admin tooling and the [RAID]/[XP] log lines
+    pub label: String,
+}
+
+/// Returns the single active window for this grade/track, if any - the
+/// highest-priority window whose half-open start/end range contains
+/// ctx.timestamp and whose grade/track scoping matches, if scoped at all.
+/// Ties broken by id, latest created wins, so behavior is deterministic.
+fn active_reward_window(ctx: &ReducerContext, grade: u8, track: Option<&str>) -> Option<RewardMultiplierWindow> {
+    ctx.db.reward_multiplier_window()
+        .iter()
+        .filter(|w| ctx.timestamp.duration_since(w.start_ts).is_some()
+            && ctx.timestamp.duration_since(w.end_ts).is_none())
+        .filter(|w| w.grade.is_none() || w.grade == Some(grade))
+        .filter(|w| w.track.is_none() || w.track.as_deref() == track)
+        .max_by(|a, b| a.priority.cmp(&b.priority).then(a.id.cmp(&b.id)))
+}
+
+/// Admin: open a reward multiplier window (e.g. "double raid weekend").
+#[reducer]
+pub fn admin_create_reward_window(
+    ctx: &ReducerContext,
+    start_ts: Timestamp,
+    end_ts: Timestamp,
+    ap_multiplier: f32,
+    xp_multiplier: f32,
+    grade: Option<u8>,
+    track: Option<String>,
+    priority: i32,
+    label: String,
+) -> Result<(), String> {
+    if let Err(e) = check_capability(ctx, "admin_create_reward_window", 8) {
+        return Err(e);
+    }
+
+    let window = ctx.db.reward_multiplier_window().insert(RewardMultiplierWindow {
+        id: 0,
+        start_ts,
+        end_ts,
+        ap_multiplier,
+        xp_multiplier,
+        grade,
+        track,
+        priority,
+        label: label.clone(),
+    });
+
+    log::info!("[REWARD_WINDOW] created id:{} label:\"{}\" start:{:?} end:{:?} ap_mult:{:.2} xp_mult:{:.2} grade:{:?} track:{:?} priority:{}",
+        window.id, label, start_ts, end_ts, ap_multiplier, xp_multiplier, grade, track, priority);
+    Ok(())
+}
+
+/// Admin: clear (delete) a reward multiplier window before it naturally expires.
+#[reducer]
+pub fn admin_clear_reward_window(ctx: &ReducerContext, window_id: u64) -> Result<(), String> {
+    if let Err(e) = check_capability(ctx, "admin_clear_reward_window", 1) {
+        return Err(e);
+    }
+
+    if ctx.db.reward_multiplier_window().id().delete(&window_id) {
+        log::info!("[REWARD_WINDOW] cleared id:{}", window_id);
+        Ok(())
+    } else {
+        Err(format!("No reward window with id {}", window_id))
+    }
+}
+
+/// Base pool of contribution points split across the roster by damage fraction,
+/// scaled by accuracy - see settle_raid_rewards
+const RAID_REWARD_CONTRIBUTION_POOL: u32 = 200;
+
+/// Bonus awarded for landing the blow that dropped the boss to 0 HP
+const RAID_REWARD_KILLING_BLOW_BONUS: u32 = 50;
+
+/// Bonus for answering at least one problem and never missing one this raid
+const RAID_REWARD_FLAWLESS_BONUS: u32 = 30;
+
+/// Bonus for the single fastest correct answer among the roster
+const RAID_REWARD_SPEED_BONUS: u32 = 20;
+
+/// Penalty for disconnecting and never resuming before the raid ended
+const RAID_REWARD_FORFEIT_PENALTY: u32 = 40;
+
+/// Bonus for every member of the team with the higher cumulative damage in a PvP room
+const RAID_REWARD_PVP_WIN_BONUS: u32 = 40;
+
+/// Points per peak_streak stack folded into the streak_bonus reward
+const RAID_REWARD_STREAK_BONUS_PER_STACK: u32 = 2;
+
+/// Cap on the streak_bonus reward, so one very long streak can't dwarf contribution
+const RAID_REWARD_STREAK_BONUS_MAX: u32 = 40;
+
+/// Extra AP added to the claimable pool (see raid_reward_ledger) per boss_level,
+/// on top of RAID_REWARD_CONTRIBUTION_POOL - tougher bosses pay out more
+const RAID_REWARD_POOL_PER_BOSS_LEVEL: u32 = 3;
+
+/// How long a raid_reward_ledger row survives after the raid ends, claimed or
+/// not - reuses the 7-day grace window cleanup_abandoned_raids already applies
+/// to timeback_event_queue
+const RAID_REWARD_LEDGER_TTL_DAYS: i64 = 7;
+
+/// Extra pct added to a player's pool share on their first raid victory each day
+const DAILY_BONUS_MULTIPLIER_PCT: u32 = 100;
+
+/// Extra pct per consecutive daily-bonus day, on top of DAILY_BONUS_MULTIPLIER_PCT
+const DAILY_BONUS_STREAK_BONUS_PCT_PER_DAY: u32 = 10;
+
+/// Cap on the streak add-on, so a long streak can't run away
+const DAILY_BONUS_STREAK_BONUS_PCT_MAX: u32 = 50;
+
+/// Doubles (plus an escalating streak bonus) a player's raid_reward_ledger pool
+/// share on their first raid victory of the day - see Player.last_bonus_date.
+/// Returns pool_share unchanged if they've already claimed today or this raid
+/// wasn't a victory.
+fn apply_daily_victory_bonus(ctx: &ReducerContext, player_id: &str, pool_share: u32, victory: bool) -> u32 {
+    if !victory || pool_share == 0 {
+        return pool_share;
+    }
+    let mut player = match ctx.db.player().id().find(player_id) {
+        Some(p) => p,
+        None => return pool_share,
+    };
+
+    let already_claimed_today = player.last_bonus_date
+        .map(|last| !is_new_day(&player.timezone, last, ctx.timestamp))
+        .unwrap_or(false);
+    if already_claimed_today {
+        return pool_share;
+    }
+
+    let new_streak = match player.last_bonus_date {
+        Some(last) if calculate_days_between(&player.timezone, last, ctx.timestamp) == 1 => player.daily_bonus_streak.saturating_add(1),
+        _ => 1,
+    };
+    let streak_bonus_pct = (new_streak.saturating_sub(1) as u32 * DAILY_BONUS_STREAK_BONUS_PCT_PER_DAY)
+        .min(DAILY_BONUS_STREAK_BONUS_PCT_MAX);
+    let multiplier_pct = DAILY_BONUS_MULTIPLIER_PCT + streak_bonus_pct;
+
+    player.last_bonus_date = Some(ctx.timestamp);
+    player.daily_bonus_streak = new_streak;
+    ctx.db.player().id().update(player);
+
+    pool_share + (pool_share * multiplier_pct / 100)
+}
+
+/// Adapts the MUD raid-reward model (contribution share + objective bonuses +
+/// bail penalty) into a post-battle summary, called once from end_raid. Inserts
+/// one RaidReward row per roster member - including no-shows/forfeits, so the
+/// results screen has a complete picture, not just active contributors. Also
+/// stakes out each player's claimable share of the victory AP pool into
+/// raid_reward_ledger - see open_loot_chest for the claim side.
+fn settle_raid_rewards(ctx: &ReducerContext, raid_id: u64, victory: bool, killing_blow_player_id: Option<&str>) {
+    let roster: Vec<_> = ctx.db.raid_player().raid_id().filter(&raid_id).collect();
+    if roster.is_empty() {
+        return;
+    }
+
+    let boss_level = ctx.db.raid().id().find(&raid_id).map(|r| r.boss_level).unwrap_or(0);
+    let reward_pool = RAID_REWARD_CONTRIBUTION_POOL + boss_level as u32 * RAID_REWARD_POOL_PER_BOSS_LEVEL;
+
+    let total_damage: u64 = roster.iter().map(|rp| rp.damage_dealt as u64).sum();
+    let fastest_ms = roster.iter()
+        .filter(|rp| rp.correct_answers > 0)
+        .map(|rp| rp.fastest_answer_ms)
+        .min();
+
+    // PvP rooms also award the team that out-damaged the other, on top of each
+    // player's individual contribution share
+    let winning_team = ctx.db.raid().id().find(&raid_id).and_then(|raid| {
+        if !raid.is_pvp || raid.team_a_damage == raid.team_b_damage {
+            None
+        } else if raid.team_a_damage > raid.team_b_damage {
+            Some(0u8)
+        } else {
+            Some(1u8)
+        }
+    });
+
+    for rp in &roster {
+        let accuracy_pct = if rp.problems_answered > 0 {
+            (rp.correct_answers * 100) / rp.problems_answered
+        } else {
+            0
+        };
+        let damage_share = if total_damage > 0 {
+            (rp.damage_dealt as u64 * RAID_REWARD_CONTRIBUTION_POOL as u64 / total_damage) as u32
+        } else {
+            0
+        };
+        // Accuracy scales the share down rather than adding on top - a low-accuracy
+        // high-damage player (lots of retries) shouldn't out-earn a clean one
+        let contribution_points = (damage_share as u64 * accuracy_pct.max(50) as u64 / 100) as u32;
+
+        let killing_blow = victory && killing_blow_player_id == Some(rp.player_id.as_str());
+        let flawless_bonus = rp.problems_answered > 0 && rp.correct_answers == rp.problems_answered;
+        let speed_bonus = rp.correct_answers > 0 && fastest_ms == Some(rp.fastest_answer_ms);
+
+        // Forfeit: disconnected and the raid ended before they ever came back
+        let still_claims_raid = ctx.db.player().id().find(&rp.player_id)
+            .map(|p| p.in_raid_id == Some(raid_id))
+            .unwrap_or(false);
+        let forfeited = !rp.is_active && still_claims_raid;
+        // A forfeit already carries its own (larger) penalty below - don't also
+        // charge the lighter reconnect penalty on top of it
+        let forfeit_penalty = if forfeited {
+            RAID_REWARD_FORFEIT_PENALTY
+        } else if rp.disconnected_mid_raid {
+            RAID_REWARD_FORFEIT_PENALTY / 2
+        } else {
+            0
+        };
+
+        let streak_bonus = (rp.peak_streak * RAID_REWARD_STREAK_BONUS_PER_STACK).min(RAID_REWARD_STREAK_BONUS_MAX);
+
+        // Victory-only claimable pool share, staked into raid_reward_ledger below -
+        // straight damage proportion, unscaled by accuracy (unlike contribution_points)
+        let base_pool_share = if victory && total_damage > 0 {
+            (rp.damage_dealt as u64 * reward_pool as u64 / total_damage) as u32
+        } else {
+            0
+        };
+        // Doubled (plus streak) if this is the player's first victory today
+        let pool_share = apply_daily_victory_bonus(ctx, &rp.player_id, base_pool_share, victory);
+        let player_grade = ctx.db.player().id().find(&rp.player_id).map(|p| p.grade).unwrap_or(0);
+        let ap_window = active_reward_window(ctx, player_grade, rp.track.as_deref());
+        let ap_multiplier = ap_window.as_ref().map(|w| w.ap_multiplier).unwrap_or(1.0);
+        let chest_bonus = (pool_share as f32 * ap_multiplier) as u32;
+        if chest_bonus >= ANNOUNCEMENT_RARE_LOOT_THRESHOLD {
+            ctx.db.announcement().insert(Announcement {
+                id: 0,
+                kind: AnnouncementKind::RareLoot,
+                player_name: rp.player_name.clone(),
+                message: format!("{} pulled a huge loot haul ({} AP)!", rp.player_name, chest_bonus),
+                grade: player_grade,
+                track: rp.track.clone(),
+                timestamp: ctx.timestamp,
+            });
+        }
+        let pvp_win_bonus = rp.team.is_some() && rp.team == winning_team;
+
+        let mut total_points = contribution_points;
+        if killing_blow { total_points += RAID_REWARD_KILLING_BLOW_BONUS; }
+        if flawless_bonus { total_points += RAID_REWARD_FLAWLESS_BONUS; }
+        if speed_bonus { total_points += RAID_REWARD_SPEED_BONUS; }
+        if pvp_win_bonus { total_points += RAID_REWARD_PVP_WIN_BONUS; }
+        total_points += chest_bonus;
+        total_points += streak_bonus;
+        total_points = total_points.saturating_sub(forfeit_penalty);
+
+        ctx.db.raid_reward().insert(RaidReward {
+            id: 0,
+            raid_id,
+            player_id: rp.player_id.clone(),
+            contribution_points,
+            killing_blow,
+            flawless_bonus,
+            speed_bonus,
+            forfeit_penalty,
+            chest_bonus,
+            streak_bonus,
+            total_points,
+            computed_at: ctx.timestamp,
+        });
+
+        if pool_share > 0 {
+            ctx.db.raid_reward_ledger().insert(RaidRewardLedger {
+                id: 0,
+                raid_id,
+                player_id: rp.player_id.clone(),
+                amount: chest_bonus,
+                claimed: false,
+                claimed_at: None,
+                created_at: ctx.timestamp,
+            });
+        }
     }
+}
+
+/// Resume raid from pause (transitions to InProgress, shifts started_at, reschedules timeout)
+fn resume_raid_from_pause(ctx: &ReducerContext, raid_id: u64) -> Result<(), String> {
+    let mut raid = ctx.db.raid().id().find(&raid_id)
+        .ok_or("Raid not found")?;
     
-    let identity = Identity::from_hex(&client_identity)
-        .expect("Invalid identity hex string");
+    if raid.state != RaidState::Paused {
+        return Ok(());
+    }
     
-    // Delete stale sessions: same player (unclean reconnect) OR same connection_id (prevents PK conflict)
-    let stale_sessions: Vec<_> = ctx.db.session()
+    let pause_started_at = raid.pause_started_at
+        .ok_or("Invalid state: Paused but no pause_started_at")?;
+    
+    let pause_duration = ctx.timestamp.duration_since(pause_started_at)
+        .ok_or("Invalid pause timestamp")?;
+    
+    // Shift started_at forward by pause duration so existing timer logic still works
+    let pause_secs = pause_duration.as_secs();
+    let new_started_at = raid.started_at + std::time::Duration::from_secs(pause_secs);
+    
+    // Validate time remaining (calculate from shifted start time)
+    let elapsed = match ctx.timestamp.duration_since(new_started_at) {
+        Some(d) => d,
+        None => {
+            log::error!("Invalid time: raid {} started_at ({:?}) > now ({:?})", 
+                raid_id, new_started_at, ctx.timestamp);
+            return Err("Invalid timestamp: start time is in the future".to_string());
+        }
+    };
+    // Use correct timeout duration based on boss level
+    let total_duration = raid_timeout_seconds(raid.boss_level);
+    let time_remaining_secs = total_duration.saturating_sub(elapsed.as_secs());
+    
+    if time_remaining_secs == 0 {
+        end_raid(ctx, raid_id, false);
+        return Ok(());
+    }
+
+    // Transition: Paused -> InProgress
+    raid.state = RaidState::InProgress;
+    raid.started_at = new_started_at;
+    raid.pause_started_at = None;
+    // Shift the enrage clock forward by the pause duration too, same as started_at -
+    // time spent with nobody connected shouldn't count against the soft deadline
+    if let Some(enrage_started_at) = raid.enrage_started_at {
+        raid.enrage_started_at = Some(enrage_started_at + std::time::Duration::from_secs(pause_secs));
+    }
+    ctx.db.raid().id().update(raid);
+    cancel_pause_timeout(ctx, raid_id);
+
+    // Resync boss phase (idempotent - HP hasn't moved during the pause, so this
+    // only matters if phase data changed, never re-fires an unchanged transition)
+    recompute_raid_phase(ctx, raid_id);
+
+    // Shift last_answered_at forward by the pause duration too - a player wasn't
+    // idle by choice while everyone was disconnected, so don't hold that against them
+    let paused_players: Vec<_> = ctx.db.raid_player().raid_id().filter(&raid_id).collect();
+    for mut rp in paused_players {
+        rp.last_answered_at = rp.last_answered_at + std::time::Duration::from_secs(pause_secs);
+        let player_id = rp.player_id.clone();
+        let is_active = rp.is_active;
+        update_raid_player(ctx, rp);
+        // Rearm the per-problem watchdog fresh, same "don't bother shifting, just
+        // restart the interval" treatment as the idle-check sweep below
+        if is_active {
+            if let Some(current) = current_unanswered_problem(ctx, raid_id, &player_id) {
+                schedule_problem_timeout(ctx, raid_id, &player_id, current.id, problem_timeout_delay_secs(ctx, raid_id));
+            }
+        }
+    }
+    schedule_idle_check(ctx, raid_id, IDLE_CHECK_INTERVAL_SECS);
+
+    // Reschedule timeout
+    let new_timeout = ctx.timestamp + std::time::Duration::from_secs(time_remaining_secs);
+    ctx.db.raid_timeout_schedule().insert(RaidTimeoutSchedule {
+        id: 0,
+        raid_id,
+        scheduled_at: ScheduleAt::Time(new_timeout.into()),
+    });
+
+    // Shift any in-flight boss cast by the same pause duration, exactly like started_at,
+    // then reschedule the resolution tick; no pending cast just means a fresh decision tick
+    if let Some(mut cast) = ctx.db.boss_cast().iter().find(|c| c.raid_id == raid_id) {
+        cast.cast_ends_at = cast.cast_ends_at + std::time::Duration::from_secs(pause_secs);
+        let resume_delay = match cast.cast_ends_at.duration_since(ctx.timestamp) {
+            Some(d) => d.as_secs(),
+            None => 0, // cast_ends_at already passed somehow - resolve immediately
+        };
+        ctx.db.boss_cast().id().update(cast);
+        schedule_boss_skill(ctx, raid_id, resume_delay);
+    } else {
+        schedule_boss_skill(ctx, raid_id, BOSS_CAST_DECISION_INTERVAL_SECS);
+    }
+
+    // Resume the boss-tick watchdog at its normal interval - no telegraph to shift,
+    // unlike boss_cast, so there's no in-flight state to reconcile
+    schedule_boss_tick(ctx, raid_id, BOSS_TICK_INTERVAL_SECS);
+
+    Ok(())
+}
+
+/// Clean up session when player disconnects
+#[reducer(client_disconnected)]
+pub fn on_disconnect(ctx: &ReducerContext) {
+    if let Some(session) = ctx.db.session().connection_id().find(&ctx.sender) {
+        if let Some(player) = ctx.db.player().id().find(&session.player_id) {
+            // Calculate session duration
+            let session_duration_secs = ctx.timestamp.duration_since(session.connected_at)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            let in_raid = player.in_raid_id.is_some();
+            // Canonical log for disconnect with session context
+            log::info!("[DISCONNECT] {} player:{} session_min:{:.1} in_raid:{}",
+                player.name, &player.id[..8.min(player.id.len())],
+                session_duration_secs as f32 / 60.0, in_raid);
+
+            // Spectating is independent of in_raid_id - clean it up separately from
+            // the active-player inactive/resume path below
+            cleanup_spectator_rows(ctx, &player.id, None);
+
+            if let Some(raid_id) = player.in_raid_id {
+                
+                // DC from matchmaking leaves queue (prevents limbo state on reconnect)
+                if let Some(raid) = ctx.db.raid().id().find(&raid_id) {
+                    if raid.state == RaidState::Matchmaking {
+                        log::info!("[DISCONNECT] {} left matchmaking raid:{}", player.name, raid_id);
+                        cleanup_player_raid_data(ctx, &player.id, raid_id);
+                        ctx.db.session().connection_id().delete(&ctx.sender);
+                        return;
+                    }
+                }
+                
+                // Active raid: mark inactive, preserve in_raid_id for resume
+                let was_last_active = count_active_raid_players(ctx, raid_id) == 1;
+                mark_player_inactive_in_raid(ctx, &player.id, raid_id);
+                
+                // Pause if last player left (solo always pauses, multi only if last)
+                if was_last_active {
+                    if let Err(e) = pause_raid_if_empty(ctx, raid_id) {
+                        log::warn!("Failed to pause raid {}: {}", raid_id, e);
+                    }
+                }
+        }
+    }
+    
+        // Delete session (ephemeral connection mapping)
+    ctx.db.session().connection_id().delete(&ctx.sender);
+    }
+}
+
+/// Generate a unique 4-letter room code
+fn generate_room_code(ctx: &ReducerContext) -> String {
+    use spacetimedb::rand::Rng;
+    // Avoid confusing letters (no I, O, 0, 1)
+    const CHARS: &str = "ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+    let mut rng = ctx.rng();
+    (0..4)
+        .map(|_| CHARS.chars().nth(rng.gen_range(0..CHARS.len())).unwrap())
+        .collect()
+}
+
+/// Create a private room with a shareable code
+#[reducer]
+pub fn create_private_room(ctx: &ReducerContext, track: Option<String>, boss_level: Option<u8>) {
+    let level = boss_level.unwrap_or(0); // Default to adaptive
+    // Entry log removed - canonical log at end
+    
+    // Check player exists and not in a raid
+    let mut player = match get_player(ctx) {
+        Ok(p) if p.in_raid_id.is_none() => p,
+        Ok(p) => {
+            log::warn!("Player {} already in raid: {:?}", p.id, p.in_raid_id);
+            return;
+        },
+        Err(e) => {
+            log::error!("Player lookup failed: {}", e);
+            return;
+        }
+    };
+
+    // Repeat-abandon players are restricted to solo-only raids until their cooldown expires
+    if let Some(until) = player.matchmaking_cooldown_until {
+        if ctx.timestamp.to_micros_since_unix_epoch() < until.to_micros_since_unix_epoch() {
+            log::warn!("[MATCHMAKING] create_private_room blocked player:{} cooldown_until:{:?}", player.id, until);
+            return;
+        }
+    }
+
+    // Generate unique code
+    let mut code = generate_room_code(ctx);
+    
+    // Ensure uniqueness (unlikely collision but safe)
+    while ctx.db.raid()
         .iter()
-        .filter(|s| s.player_id == player_id || s.connection_id == identity)
-        .map(|s| s.connection_id)
-        .collect();
-    for conn_id in stale_sessions {
-        ctx.db.session().connection_id().delete(&conn_id);
+        .any(|r| r.room_code == Some(code.clone()) && 
+                 matches!(r.state, RaidState::Matchmaking)) {
+        code = generate_room_code(ctx);
+    }
+    
+    // Create raid with room code
+    let raid = ctx.db.raid().insert(Raid {
+        id: 0, // Auto-increment
+        boss_hp: 1000,  // Placeholder, updated when raid starts
+        boss_max_hp: 1000,
+        state: RaidState::Matchmaking,
+        room_code: Some(code.clone()),
+        started_at: ctx.timestamp,
+        pause_started_at: None,
+        duration_seconds: None,
+        problems_issued: 0,
+        max_problems: 999,
+        boss_level: level,
+        countdown_started_at: None, // Not in countdown yet
+        current_phase_index: 0,
+        is_public: false,
+        is_pvp: false,
+        team_a_damage: 0,
+        team_b_damage: 0,
+        event_offset: 0,
+        enrage_started_at: None,
+        boss_tick_rate_pct: 0.0,
+    });
+
+    // Add creator as leader
+    // Calculate division for matchmaking display
+    let (mastered_count, total_facts) = get_player_mastery_stats(ctx, &player);
+    let division = calculate_division(&player.rank, mastered_count, total_facts);
+    
+    // Check for duplicate (player already in this raid)
+    // SpacetimeDB doesn't support multi-column unique constraints, so we check manually
+    let already_in_raid = ctx.db.raid_player()
+        .iter()
+        .any(|rp| rp.player_id == player.id && rp.raid_id == raid.id);
+    
+    if already_in_raid {
+        log::warn!("Player {} already has raid_player row for raid {}", player.id, raid.id);
+        return; // Don't insert duplicate
     }
     
-    // Create verified session
-    ctx.db.session().insert(Session {
-        connection_id: identity,
-        player_id: player_id.clone(),
-        connected_at: ctx.timestamp,
-    });
+    let raid_player = RaidPlayer {
+        id: 0, // Auto-inc
+        player_id: player.id.clone(),
+        raid_id: raid.id,
+        player_name: player.name.clone(),
+        grade: player.grade,
+        rank: player.rank.clone(),
+        division: Some(division),
+        is_active: true,  // Player is actively in raid
+        damage_dealt: 0,
+        problems_answered: 0,
+        correct_answers: 0,
+        fastest_answer_ms: u32::MAX,
+        is_ready: false,
+        is_leader: true, // Creator is always leader
+        recent_problems: String::new(),
+        track: track.clone(), // Store track selection
+        inactive_since: None,
+        last_answered_at: ctx.timestamp,
+        team: None, // Room isn't PvP yet - leader enables it via set_pvp_mode
+        current_streak: 0,
+        peak_streak: 0,
+        disconnected_mid_raid: false,
+        fast_streak: 0,
+        bonus_points: 0,
+        wrong_count: 0,
+        active_buffs: Vec::new(),
+    };
     
-    log::info!("[SESSION] created player:{} ws:{}", &player_id[..8.min(player_id.len())], &client_identity[..8.min(client_identity.len())]);
+    ctx.db.raid_player().insert(raid_player);
+    
+    // Update player
+    player.in_raid_id = Some(raid.id);
+    let player_name = player.name.clone();
+    ctx.db.player().id().update(player);
+    
+    log::info!("[ROOM] created code:{} player:{}", code, player_name);
+    // Room code is now accessible through the raid's room_code field
 }
 
-/// Player connects to the game
-/// The player_id is read from the verified session created by the gateway
-/// PII (timeback_id, email) comes from client - can only affect their own record
+/// Set boss visual for adaptive raids (Quick Play)
+/// Leaders can pick which boss to fight while keeping adaptive HP
+/// visual: 0 = random, 1-8 = specific boss visual
 #[reducer]
-pub fn connect(ctx: &ReducerContext, name: String, grade: Option<u8>, timeback_id: Option<String>, email: Option<String>) {
-    // Get player_id from verified session (created by gateway)
-    // This is the ONLY thing we verify - client can't spoof playerId
-    let session = ctx.db.session()
-        .connection_id()
-        .find(&ctx.sender)
-        .expect("Session not found - verify with gateway first");
-    
-    let player_id = session.player_id.clone();
-    // timeback_id and email from client are fine - they can only affect their own record
+pub fn set_boss_visual(ctx: &ReducerContext, visual: u8) {
+    // Entry log removed - canonical log at end
     
-    // Get or create player
-    let _player = if let Some(mut existing) = ctx.db.player().id().find(&player_id) {
-        // Existing player - update last played and handle resets
-        
-        // Update last played and reset daily if new day
-        if is_new_day(existing.last_played, ctx.timestamp) {
-            // Check RAID streak (not login streak) before resetting daily quests
-            // Streak requires raiding daily, not just logging in
-            let days_since_raid = calculate_days_between(existing.last_raid, ctx.timestamp);
-            if days_since_raid > 1 {
-                // Didn't raid yesterday - break streak
-                let mut quests = parse_quests(&existing.quests);
-                let old_streak = quests["daily_streak"].as_u64().unwrap_or(0);
-                quests["daily_streak"] = json!(0);
-                existing.quests = Some(quests.to_string());
-                log::info!("[QUEST] streak broken player:{} lost_streak:{} days_since_raid:{}", 
-                    existing.name, old_streak, days_since_raid - 1);
-            }
-            
-            reset_quests_by_prefix(&mut existing, "daily_");
-            log::info!("[CONNECT] daily reset for {}", existing.name);
-        }
-        
-        // Reset weekly if new week (Monday reset)
-        if is_new_week(existing.last_weekly_reset, ctx.timestamp) {
-            reset_quests_by_prefix(&mut existing, "weekly_");
-            existing.last_weekly_reset = ctx.timestamp;
-            log::info!("[CONNECT] weekly reset for {}", existing.name);
-        }
-        
-        existing.last_played = ctx.timestamp;
-        
-        // Only update grade if provided (Some = from API, None = API failed, keep existing)
-        let old_grade = existing.grade;
-        match grade {
-            Some(new_grade) if existing.grade != new_grade => {
-                log::info!("[CONNECT] grade {} → {} for {}", existing.grade, new_grade, existing.name);
-                existing.grade = new_grade.min(5);
-            }
-            None => {
-                // API failed - keeping existing. Worth noting for debugging grade issues.
-                log::debug!("[CONNECT] grade API failed, keeping {} for {}", existing.grade, existing.name);
-            }
-            _ => {} // Grade unchanged, no log needed
-        }
-        
-        // Update identity fields from verified token (track changes for canonical log)
-        let old_email = existing.email.clone();
-        let old_timeback = existing.timeback_id.clone();
-        existing.name = name;
-        if timeback_id.is_some() {
-            existing.timeback_id = timeback_id.clone();
-        }
-        if email.is_some() {
-            existing.email = email.clone();
-        }
-        let email_changed = old_email != existing.email;
-        let timeback_changed = old_timeback != existing.timeback_id;
-        
-        // Recalculate grade-dependent data if grade changed
-        recalculate_for_grade_change(ctx, &mut existing, old_grade);
-        
-        // Save updated player (includes grade change effects if any)
-        ctx.db.player().id().update(existing.clone());
-        
-        // Resume paused raid if player was in one
-        if let Some(raid_id) = existing.in_raid_id {
-            if let Some(raid) = ctx.db.raid().id().find(&raid_id) {
-                let state_name = match raid.state {
-                    RaidState::Paused => "paused",
-                    RaidState::InProgress => "running",
-                    RaidState::Victory => "victory",
-                    RaidState::Failed => "defeat",
-                    RaidState::Rematch => "rematch",
-                    RaidState::Matchmaking => "matchmaking",
-                    RaidState::Countdown => "countdown",
-                };
-                
-                match raid.state {
-                    RaidState::Paused => {
-                        if let Some(mut rp) = find_raid_player(ctx, &player_id, raid_id) {
-                            rp.is_active = true;
-                            update_raid_player(ctx, rp);
-                        }
-                        if let Err(e) = resume_raid_from_pause(ctx, raid_id) {
-                            log::error!("[RAID] resume failed raid:{} error:{}", raid_id, e);
-                        }
-                    }
-                    RaidState::InProgress | RaidState::Victory | RaidState::Failed | RaidState::Rematch => {
-                        if let Some(mut rp) = find_raid_player(ctx, &player_id, raid_id) {
-                            if !rp.is_active {
-                                rp.is_active = true;
-                                if raid.state == RaidState::Rematch {
-                                    rp.is_ready = false;
-                                }
-                                update_raid_player(ctx, rp);
-                            }
-                        }
-                    }
-                    _ => {}
-                }
-                
-                // One canonical log for reconnect-to-raid
-                log::info!("[CONNECT] {} rejoining raid:{} state:{}", existing.name, raid_id, state_name);
-            } else {
-                log::warn!("[CONNECT] {} had stale raid:{} - cleared", existing.name, raid_id);
-                existing.in_raid_id = None;
-                ctx.db.player().id().update(existing.clone());
-            }
+    // Get player and their raid
+    let player = match get_player(ctx) {
+        Ok(p) => p,
+        Err(e) => {
+            log::warn!("set_boss_visual: Player lookup failed: {}", e);
+            return;
         }
-        
-        // Wide event: one canonical log with full player context
-        let pid = &player_id[..8.min(player_id.len())];
-        let has_timeback = existing.timeback_id.is_some();
-        let quests = parse_quests(&existing.quests);
-        let streak = quests["daily_streak"].as_u64().unwrap_or(0);
-        let rank_str = existing.rank.as_deref().unwrap_or("unranked");
-        
-        if email_changed || timeback_changed {
-            log::warn!("[CONNECT] player=\"{}\" player_id={} type=returning grade={} rank={} raids={} streak={} timeback={} identity_change=true", 
-                existing.name, pid, existing.grade, rank_str, existing.total_raids, streak, has_timeback);
-        } else {
-            log::info!("[CONNECT] player=\"{}\" player_id={} type=returning grade={} rank={} raids={} streak={} timeback={}", 
-                existing.name, pid, existing.grade, rank_str, existing.total_raids, streak, has_timeback);
+    };
+    
+    let raid_id = match player.in_raid_id {
+        Some(id) => id,
+        None => {
+            log::warn!("set_boss_visual: Player {} not in a raid", player.id);
+            return;
         }
-        
-        existing
-    } else {
-        // Create new player
-        let resolved_grade = grade.unwrap_or(3).min(5);
-        let grade_source = if grade.is_some() { "api" } else { "default" };
-        let new_player = Player {
-            id: player_id.clone(),
-            name,
-            grade: resolved_grade,
-            rank: None, // New players start unranked
-            total_problems: 0,
-            total_correct: 0,
-            avg_response_ms: 0,
-            best_response_ms: u32::MAX,
-            total_raids: 0,
-            quests: Some(json!({
-                "daily_raid_count": 0,
-                "daily_streak": 0
-            }).to_string()),
-            last_played: ctx.timestamp,
-            last_raid: ctx.timestamp,  // Initialize to now (no existing streak)
-            last_weekly_reset: ctx.timestamp,
-            total_ap: 0,
-            in_raid_id: None,
-            timeback_id,  // From client (can only affect their own record)
-            email         // From client (can only affect their own record)
-        };
-        ctx.db.player().insert(new_player.clone());
-        
-        // Wide event: one canonical log for new player
-        let pid = &player_id[..8.min(player_id.len())];
-        let has_timeback = new_player.timeback_id.is_some();
-        log::info!("[CONNECT] player=\"{}\" player_id={} type=new grade={} rank=unranked raids=0 streak=0 timeback={} grade_source={}", 
-            new_player.name, pid, new_player.grade, has_timeback, grade_source);
-        
-        new_player
     };
-}
-
-/// Recalculate grade-dependent data when player's grade changes
-/// Called by both set_grade (admin) and connect (Timeback API)
-fn recalculate_for_grade_change(ctx: &ReducerContext, player: &mut Player, old_grade: u8) {
-    // Early return if grade didn't actually change
-    if player.grade == old_grade {
-        return;
-    }
     
-    let player_id = player.id.clone();
+    // Get the raid
+    let mut raid = match ctx.db.raid().id().find(&raid_id) {
+        Some(r) => r,
+        None => {
+            log::warn!("set_boss_visual: Raid {} not found", raid_id);
+            return;
+        }
+    };
     
-    // Recalculate ALL fact mastery levels for new grade thresholds
-    let mut recalc_count = 0;
-    for mut fact in ctx.db.fact_mastery().player_id().filter(&player_id) {
-        // mastery_level change tracking removed (was used for verbose logging)
-        fact.mastery_level = calculate_mastery_level(&fact, player.grade);
-        
-        ctx.db.fact_mastery().id().update(fact);
-        recalc_count += 1;
+    // Must be in Matchmaking or Rematch state (both are "waiting to start" states)
+    if !matches!(raid.state, RaidState::Matchmaking | RaidState::Rematch) {
+        log::warn!("set_boss_visual: Raid {} not in Matchmaking/Rematch state", raid_id);
+        return;
     }
     
-    // Recalculate rank for new grade's fact pool
-    let (mastered_count, total_facts) = get_player_mastery_stats(ctx, &player);
-    let new_rank = calculate_player_rank(mastered_count, total_facts);
-    player.rank = new_rank.clone();
+    // Must be the leader
+    let raid_player = ctx.db.raid_player()
+        .raid_id()
+        .filter(&raid_id)
+        .find(|rp| rp.player_id == player.id);
     
-    // Refresh leaderboards for both old and new grades
-    refresh_leaderboard(ctx, old_grade);
-    if old_grade != player.grade {
-        refresh_leaderboard(ctx, player.grade);
+    match raid_player {
+        Some(rp) if rp.is_leader => {}
+        _ => {
+            log::warn!("set_boss_visual: Player {} is not the leader of raid {}", player.id, raid_id);
+            return;
+        }
     }
     
-    // One canonical log for grade change
-    log::info!("[GRADE] changed player:{} grade:{}→{} recalc:{} rank:{:?}", 
-        &player_id[..8.min(player_id.len())], old_grade, player.grade, recalc_count, new_rank);
-}
-
-/// Update player's grade level
-/// - Admins can change any player's grade
-/// - Non-TimeBack students can self-service their own grade (it's just a difficulty setting)
-/// - TimeBack students' grades are locked (synced from AlphaMath enrollment)
-/// Set player's TimeBack ID (admin function for fixing Playcademy sync failures)
-#[reducer]
-pub fn set_timeback_id(ctx: &ReducerContext, player_id: String, timeback_id: String) {
-    // Authorization check: only authorized workers can manually set TimeBack IDs
-    if ctx.db.authorized_worker().identity().find(&ctx.sender).is_none() {
-        log::warn!("Unauthorized set_timeback_id attempt by {}", ctx.sender);
+    // Only allow visual selection for adaptive raids (0 or >= 100)
+    if !is_adaptive_boss(raid.boss_level) {
+        log::warn!("set_boss_visual: Raid {} is not adaptive (boss_level={})", raid_id, raid.boss_level);
         return;
     }
     
-    // Admin-only: Manually fix TimeBack ID when Playcademy integration fails
-    if let Some(mut player) = ctx.db.player().id().find(&player_id) {
-        player.timeback_id = Some(timeback_id.clone());
-        ctx.db.player().id().update(player);
-        log::info!("[ADMIN] set_timeback_id player:{} timeback:{}", &player_id[..8.min(player_id.len())], &timeback_id[..8.min(timeback_id.len())]);
-    } else {
-        log::error!("set_timeback_id: Player {} not found", player_id);
+    // Validate visual is in valid range (0-8, includes Captain Nova at 7, Void Emperor at 8)
+    if visual > 8 {
+        log::warn!("set_boss_visual: Invalid visual {} (must be 0-8)", visual);
+        return;
     }
+    
+    // Encode the visual selection
+    let new_boss_level = encode_adaptive_boss(visual);
+    raid.boss_level = new_boss_level;
+    ctx.db.raid().id().update(raid);
+    
+    log::info!("[RAID] boss visual set raid:{} visual:{}", raid_id, visual);
 }
 
-/// Update player's grade level
-/// - Admins: Can change any player's grade
-/// - Non-TimeBack students: Can self-service their own grade (difficulty setting)
-/// - TimeBack students: Grade locked (synced from AlphaMath enrollment)
+/// Leaders can pick which Mastery Trial boss to fight (fixed HP tiers 1-8)
+/// boss_level: 1-8 = specific boss tier with fixed HP
 #[reducer]
-pub fn set_grade(ctx: &ReducerContext, grade: u8, player_id: Option<String>) {
-    let is_admin = ctx.db.authorized_worker().identity().find(&ctx.sender).is_some();
+pub fn set_mastery_boss(ctx: &ReducerContext, boss_level: u8) {
+    // Entry log removed - canonical log at end
     
-    if !is_admin {
-        // Self-service mode: verify student can change own grade
-        
-        // Can't change someone else's grade
-        if player_id.is_some() {
-            log::warn!("Non-admin attempted to change another player's grade");
-        return;
-        }
-        
-        // Get own player
-        let player = match get_player(ctx) {
-            Ok(p) => p,
-            Err(e) => {
-                log::error!("set_grade self-service failed: {}", e);
-                return;
-            }
-        };
-        
-        // TimeBack students can't self-service (grade syncs from AlphaMath enrollment)
-        // Treat empty string as no TimeBack (admin may have cleared it)
-        let has_timeback = player.timeback_id.as_ref().map_or(false, |id| !id.is_empty());
-        if has_timeback {
-            log::warn!("TimeBack student {} attempted to self-change grade (rejected)", player.name);
+    // Get player and their raid
+    let player = match get_player(ctx) {
+        Ok(p) => p,
+        Err(e) => {
+            log::warn!("set_mastery_boss: Player lookup failed: {}", e);
             return;
         }
-        
-        // OK - non-TimeBack student changing own grade
-    }
+    };
     
-    let mut player = if let Some(id) = player_id {
-        // Admin mode: change specified player's grade
-        match ctx.db.player().id().find(&id) {
-            Some(p) => p,
-            None => {
-                log::error!("set_grade: player {} not found", id);
-                return;
-            }
-        }
-    } else {
-        // Self-service mode: change own grade
-        match get_player(ctx) {
-            Ok(p) => p,
-            Err(e) => {
-                log::error!("set_grade failed: {}", e);
-                return;
-            }
+    let raid_id = match player.in_raid_id {
+        Some(id) => id,
+        None => {
+            log::warn!("set_mastery_boss: Player {} not in a raid", player.id);
+            return;
         }
     };
     
-    let old_grade = player.grade;
-    player.grade = grade.min(5); // Cap at grade 5
-    
-    // Save grade FIRST so leaderboard refresh sees correct grade in DB
-    ctx.db.player().id().update(player.clone());
-    
-    // Recalculate all grade-dependent data (updates rank in memory)
-    recalculate_for_grade_change(ctx, &mut player, old_grade);
-    
-    // Save final player state (with updated rank)
-    ctx.db.player().id().update(player);
-}
-
-/// Admin: Reset a player's progress (keep identity, wipe stats)
-/// Used when sibling plays on wrong account, demo resets, etc.
-#[reducer]
-pub fn admin_reset_player(ctx: &ReducerContext, player_id: String) {
-    // Authorization check: only authorized workers (admin panel with owner token)
-    if ctx.db.authorized_worker().identity().find(&ctx.sender).is_none() {
-        panic!("Unauthorized: only admin can reset players");
-    }
-    
-    let mut player = match ctx.db.player().id().find(&player_id) {
-        Some(p) => p,
+    // Get the raid
+    let mut raid = match ctx.db.raid().id().find(&raid_id) {
+        Some(r) => r,
         None => {
-            log::error!("admin_reset_player: Player {} not found", player_id);
+            log::warn!("set_mastery_boss: Raid {} not found", raid_id);
             return;
         }
     };
     
-    let player_name = player.name.clone();
-    
-    // Delete all performance snapshots
-    let snapshots: Vec<_> = ctx.db.performance_snapshot()
-        .player_id().filter(&player_id)
-        .collect();
-    let snapshot_count = snapshots.len();
-    for ps in snapshots {
-        ctx.db.performance_snapshot().id().delete(&ps.id);
+    // Must be in Matchmaking or Rematch state (both are "waiting to start" states)
+    if !matches!(raid.state, RaidState::Matchmaking | RaidState::Rematch) {
+        log::warn!("set_mastery_boss: Raid {} not in Matchmaking/Rematch state", raid_id);
+        return;
     }
     
-    // Delete all fact mastery (will repopulate on play)
-    let masteries: Vec<_> = ctx.db.fact_mastery()
-        .player_id().filter(&player_id)
-        .collect();
-    let mastery_count = masteries.len();
-    for fm in masteries {
-        ctx.db.fact_mastery().id().delete(&fm.id);
+    // Must be the leader
+    let raid_player = ctx.db.raid_player()
+        .raid_id()
+        .filter(&raid_id)
+        .find(|rp| rp.player_id == player.id);
+    
+    match raid_player {
+        Some(rp) if rp.is_leader => {}
+        _ => {
+            log::warn!("set_mastery_boss: Player {} is not the leader of raid {}", player.id, raid_id);
+            return;
+        }
     }
     
-    // Reset player to defaults (keep id, name, grade, timeback_id, email)
-    player.rank = None;
-    player.total_problems = 0;
-    player.total_correct = 0;
-    player.avg_response_ms = 0;
-    player.best_response_ms = u32::MAX;
-    player.total_raids = 0;
-    player.total_ap = 0;
-    player.quests = Some(json!({
-        "daily_raid_count": 0,
-        "daily_streak": 0
-    }).to_string());
-    player.last_played = ctx.timestamp;
-    player.last_raid = ctx.timestamp;
-    player.last_weekly_reset = ctx.timestamp;
-    player.in_raid_id = None;
+    // Only allow for fixed HP raids (1-8), not adaptive (0 or >= 100)
+    if is_adaptive_boss(raid.boss_level) {
+        log::warn!("set_mastery_boss: Raid {} is adaptive (boss_level={}), use set_boss_visual instead", raid_id, raid.boss_level);
+        return;
+    }
     
-    ctx.db.player().id().update(player);
+    // Validate boss_level is in valid range (1-8)
+    if boss_level < 1 || boss_level > 8 {
+        log::warn!("set_mastery_boss: Invalid boss_level {} (must be 1-8)", boss_level);
+        return;
+    }
     
-    log::info!("[ADMIN] reset player:{} snapshots:{} masteries:{}", player_name, snapshot_count, mastery_count);
+    // Set the boss level directly (no encoding needed for fixed HP)
+    raid.boss_level = boss_level;
+    ctx.db.raid().id().update(raid);
+
+    log::info!("[RAID] boss level set raid:{} level:{}", raid_id, boss_level);
 }
 
-// ==================== PAUSE/RESUME HELPERS ====================
+/// Toggle a private room into/out of PvP mode (two teams racing to out-damage each
+/// other against the same shared boss). Leader-only, same gating as set_mastery_boss.
+/// Enabling re-splits the whole current roster with assign_team; disabling clears
+/// every team assignment back to None.
+#[reducer]
+pub fn set_pvp_mode(ctx: &ReducerContext, enabled: bool) {
+    let player = match get_player(ctx) {
+        Ok(p) => p,
+        Err(e) => {
+            log::warn!("set_pvp_mode: Player lookup failed: {}", e);
+            return;
+        }
+    };
 
-/// Count active players in a raid
-fn count_active_raid_players(ctx: &ReducerContext, raid_id: u64) -> usize {
-    ctx.db.raid_player()
-        .raid_id().filter(&raid_id)
-        .filter(|rp| rp.is_active)
-        .count()
-}
+    let raid_id = match player.in_raid_id {
+        Some(id) => id,
+        None => {
+            log::warn!("set_pvp_mode: Player {} not in a raid", player.id);
+            return;
+        }
+    };
+
+    let mut raid = match ctx.db.raid().id().find(&raid_id) {
+        Some(r) => r,
+        None => {
+            log::warn!("set_pvp_mode: Raid {} not found", raid_id);
+            return;
+        }
+    };
+
+    if !matches!(raid.state, RaidState::Matchmaking | RaidState::Rematch) {
+        log::warn!("set_pvp_mode: Raid {} not in Matchmaking/Rematch state", raid_id);
+        return;
+    }
+
+    let raid_player = ctx.db.raid_player()
+        .raid_id()
+        .filter(&raid_id)
+        .find(|rp| rp.player_id == player.id);
+
+    match raid_player {
+        Some(rp) if rp.is_leader => {}
+        _ => {
+            log::warn!("set_pvp_mode: Player {} is not the leader of raid {}", player.id, raid_id);
+            return;
+        }
+    }
+
+    raid.is_pvp = enabled;
+    raid.team_a_damage = 0;
+    raid.team_b_damage = 0;
+    ctx.db.raid().id().update(raid);
 
-/// Mark player as inactive WITHOUT clearing player.in_raid_id
-/// This allows resume on reconnect - only cleanup_player_raid_data clears in_raid_id
-fn mark_player_inactive_in_raid(ctx: &ReducerContext, player_id: &String, raid_id: u64) {
-    if let Some(mut rp) = find_raid_player(ctx, player_id, raid_id) {
-        rp.is_active = false;
+    let roster: Vec<_> = ctx.db.raid_player().raid_id().filter(&raid_id).collect();
+    for mut rp in roster {
+        rp.team = if enabled { Some(assign_team(ctx, raid_id)) } else { None };
         update_raid_player(ctx, rp);
     }
+
+    log::info!("[RAID] pvp mode raid:{} enabled:{}", raid_id, enabled);
 }
 
-/// Cancel raid timeout (idempotent)
-fn cancel_raid_timeout(ctx: &ReducerContext, raid_id: u64) {
-    for schedule in ctx.db.raid_timeout_schedule().iter().filter(|s| s.raid_id == raid_id) {
-        ctx.db.raid_timeout_schedule().id().delete(&schedule.id);
+/// PickTeam/TeamCount balancing rule: whichever team has fewer active members gets
+/// the new player; a tie goes to the team with the lower cumulative damage_dealt;
+/// a further tie (both empty, fresh room) is a coin flip.
+fn assign_team(ctx: &ReducerContext, raid_id: u64) -> u8 {
+    let roster: Vec<_> = ctx.db.raid_player()
+        .raid_id().filter(&raid_id)
+        .filter(|rp| rp.is_active && rp.team.is_some())
+        .collect();
+
+    let team_a: Vec<_> = roster.iter().filter(|rp| rp.team == Some(0)).collect();
+    let team_b: Vec<_> = roster.iter().filter(|rp| rp.team == Some(1)).collect();
+
+    if team_a.len() != team_b.len() {
+        return if team_a.len() < team_b.len() { 0 } else { 1 };
     }
-}
 
-fn cancel_countdown_schedule(ctx: &ReducerContext, raid_id: u64) {
-    for schedule in ctx.db.countdown_schedule().iter().filter(|s| s.raid_id == raid_id) {
-        ctx.db.countdown_schedule().id().delete(&schedule.id);
+    let damage_a: u32 = team_a.iter().map(|rp| rp.damage_dealt).sum();
+    let damage_b: u32 = team_b.iter().map(|rp| rp.damage_dealt).sum();
+
+    if damage_a != damage_b {
+        return if damage_a < damage_b { 0 } else { 1 };
     }
+
+    ctx.rng().gen_range(0..2) as u8
 }
 
-/// Pause raid if all players disconnected
-/// Only pauses when active_player_count == 0 (solo DC or all multi players DC'd)
-fn pause_raid_if_empty(ctx: &ReducerContext, raid_id: u64) -> Result<(), String> {
-    let mut raid = ctx.db.raid().id().find(&raid_id)
-        .ok_or("Raid not found")?;
+/// Join a private room using a code
+#[reducer]
+pub fn join_private_room(ctx: &ReducerContext, code: String, track: Option<String>) {
+    // Entry log removed - canonical log at end
     
-    if raid.state != RaidState::InProgress {
-        return Ok(());  // Only pause active raids
-    }
-    if count_active_raid_players(ctx, raid_id) > 0 {
-        return Ok(());  // Still has active players - DON'T PAUSE (squad continues)
+    // Validate room code format - must be 4 alphanumeric characters
+    const VALID_CHARS: &str = "ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+    if code.len() != 4 || !code.chars().all(|c| VALID_CHARS.contains(c)) {
+        log::warn!("Invalid room code format: '{}' from {}", code, ctx.sender);
+        return;
     }
     
-    // Transition: InProgress -> Paused
-    raid.state = RaidState::Paused;
-    raid.pause_started_at = Some(ctx.timestamp);
-    cancel_raid_timeout(ctx, raid_id);
-    ctx.db.raid().id().update(raid);
-    Ok(())
-}
+    // Validate player
+    let mut player = match get_player(ctx) {
+        Ok(p) if p.in_raid_id.is_none() => p,
+        Ok(p) => {
+            log::warn!("Player {} already in raid: {:?}", p.id, p.in_raid_id);
+            return;
+        },
+        Err(e) => {
+            log::error!("Player lookup failed: {}", e);
+            return;
+        }
+    };
 
-/// Resume raid from pause (transitions to InProgress, shifts started_at, reschedules timeout)
-fn resume_raid_from_pause(ctx: &ReducerContext, raid_id: u64) -> Result<(), String> {
-    let mut raid = ctx.db.raid().id().find(&raid_id)
-        .ok_or("Raid not found")?;
+    // Repeat-abandon players are restricted to solo-only raids until their cooldown expires
+    if let Some(until) = player.matchmaking_cooldown_until {
+        if ctx.timestamp.to_micros_since_unix_epoch() < until.to_micros_since_unix_epoch() {
+            log::warn!("[MATCHMAKING] join_private_room blocked player:{} cooldown_until:{:?}", player.id, until);
+            return;
+        }
+    }
+
+    // Find room with code (case insensitive)
+    let raid = match ctx.db.raid()
+        .iter()
+        .find(|r| r.room_code == Some(code.to_uppercase()) && 
+                  matches!(r.state, RaidState::Matchmaking)) {
+        Some(r) => r,
+        None => {
+            // Distinguish between different failure reasons for better debugging
+            let exists_but_wrong_state = ctx.db.raid()
+                .iter()
+                .any(|r| r.room_code == Some(code.to_uppercase()));
+            
+            if exists_but_wrong_state {
+                log::warn!("Room {} exists but is not in Matchmaking state", code);
+            } else {
+                log::warn!("Room code {} does not exist", code);
+            }
+            return;
+        }
+    };
     
-    if raid.state != RaidState::Paused {
-        return Ok(());
+    // Check room not full (count only active players)
+    let active_player_count = ctx.db.raid_player()
+        .raid_id()
+        .filter(&raid.id)
+        .filter(|rp| rp.is_active)
+        .count();
+        
+    if active_player_count >= MAX_PLAYERS_PER_RAID {
+        log::warn!("Room {} is full ({}/{} active players)", code, active_player_count, MAX_PLAYERS_PER_RAID);
+        return;
     }
     
-    let pause_started_at = raid.pause_started_at
-        .ok_or("Invalid state: Paused but no pause_started_at")?;
+    // Add player (not leader since joining)
+    // Calculate division for matchmaking display
+    let (mastered_count, total_facts) = get_player_mastery_stats(ctx, &player);
+    let division = calculate_division(&player.rank, mastered_count, total_facts);
     
-    let pause_duration = ctx.timestamp.duration_since(pause_started_at)
-        .ok_or("Invalid pause timestamp")?;
+    // Check if player was previously in this raid (inactive row from refresh/disconnect)
+    if let Some(mut existing_rp) = ctx.db.raid_player()
+        .iter()
+        .find(|rp| rp.player_id == player.id && rp.raid_id == raid.id)
+    {
+        if !existing_rp.is_active {
+            // Reactivate existing row instead of creating new one
+            existing_rp.is_active = true;
+            existing_rp.is_ready = false;  // Reset ready state
+            existing_rp.is_leader = false; // Reset leadership (in case they were leader before)
+            existing_rp.inactive_since = None; // Reconnected - abandon grace window resets
+            update_raid_player(ctx, existing_rp);
+            cancel_reconnect_deadline(ctx, raid.id, &player.id);
+
+            // Update player's in_raid_id
+            player.in_raid_id = Some(raid.id);
+            ctx.db.player().id().update(player);
+
+            return;
+        } else {
+            // Truly duplicate - already active
+            log::warn!("Player {} already active in raid {}", player.id, raid.id);
+            return;
+        }
+    }
     
-    // Shift started_at forward by pause duration so existing timer logic still works
-    let pause_secs = pause_duration.as_secs();
-    let new_started_at = raid.started_at + std::time::Duration::from_secs(pause_secs);
+    // No existing row - create new one
+    let team = if raid.is_pvp { Some(assign_team(ctx, raid.id)) } else { None };
+    let raid_player = RaidPlayer {
+        id: 0,
+        player_id: player.id.clone(),
+        raid_id: raid.id,
+        player_name: player.name.clone(),
+        grade: player.grade,
+        rank: player.rank.clone(),
+        division: Some(division),
+        damage_dealt: 0,
+        problems_answered: 0,
+        correct_answers: 0,
+        fastest_answer_ms: u32::MAX,
+        is_active: true,
+        is_ready: false,
+        is_leader: false, // Joiners are not leaders
+        recent_problems: String::new(),
+        track: track.clone(), // Store track selection
+        inactive_since: None,
+        last_answered_at: ctx.timestamp,
+        team,
+        current_streak: 0,
+        peak_streak: 0,
+        disconnected_mid_raid: false,
+        fast_streak: 0,
+        bonus_points: 0,
+        wrong_count: 0,
+        active_buffs: Vec::new(),
+    };
+
+    ctx.db.raid_player().insert(raid_player);
     
-    // Validate time remaining (calculate from shifted start time)
-    let elapsed = match ctx.timestamp.duration_since(new_started_at) {
-        Some(d) => d,
+    // Update player
+    player.in_raid_id = Some(raid.id);
+    let player_name = player.name.clone();
+    ctx.db.player().id().update(player);
+    
+    log::info!("[ROOM] joined code:{} player:{}", code, player_name);
+}
+
+/// Join a raid as a spectator - watch a live raid board (e.g. a teacher or teammate)
+/// without affecting play. Unlike join_private_room, this never touches in_raid_id,
+/// raid_player, or any readiness/pause logic - a spectator row is purely observational.
+#[reducer]
+pub fn join_as_spectator(ctx: &ReducerContext, code: String) {
+    const VALID_CHARS: &str = "ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+    if code.len() != 4 || !code.chars().all(|c| VALID_CHARS.contains(c)) {
+        log::warn!("Invalid room code format: '{}' from {}", code, ctx.sender);
+        return;
+    }
+
+    let player = match get_player(ctx) {
+        Ok(p) => p,
+        Err(e) => {
+            log::error!("join_as_spectator: player lookup failed: {}", e);
+            return;
+        }
+    };
+
+    let raid = match ctx.db.raid()
+        .iter()
+        .find(|r| r.room_code == Some(code.to_uppercase()) &&
+                  matches!(r.state, RaidState::Matchmaking | RaidState::Countdown | RaidState::InProgress)) {
+        Some(r) => r,
         None => {
-            log::error!("Invalid time: raid {} started_at ({:?}) > now ({:?})", 
-                raid_id, new_started_at, ctx.timestamp);
-            return Err("Invalid timestamp: start time is in the future".to_string());
+            log::warn!("join_as_spectator: no joinable raid for code {}", code);
+            return;
         }
     };
-    // Use correct timeout duration based on boss level
-    let total_duration = raid_timeout_seconds(raid.boss_level);
-    let time_remaining_secs = total_duration.saturating_sub(elapsed.as_secs());
-    
-    if time_remaining_secs == 0 {
-        end_raid(ctx, raid_id, false);
-        return Ok(());
+
+    // Idempotent - a spectator reconnecting (or re-clicking the link) shouldn't stack rows
+    if ctx.db.spectator().iter().any(|s| s.player_id == player.id && s.raid_id == raid.id) {
+        return;
     }
-    
-    // Transition: Paused -> InProgress
-    raid.state = RaidState::InProgress;
-    raid.started_at = new_started_at;
-    raid.pause_started_at = None;
-    ctx.db.raid().id().update(raid);
-    
-    // Reschedule timeout
-    let new_timeout = ctx.timestamp + std::time::Duration::from_secs(time_remaining_secs);
-    ctx.db.raid_timeout_schedule().insert(RaidTimeoutSchedule {
+
+    ctx.db.spectator().insert(Spectator {
         id: 0,
-        raid_id,
-        scheduled_at: ScheduleAt::Time(new_timeout.into()),
+        player_id: player.id.clone(),
+        raid_id: raid.id,
+        joined_at: ctx.timestamp,
     });
-    
-    Ok(())
+
+    log::info!("[SPECTATE] joined code:{} player:{}", code, player.name);
 }
 
-/// Clean up session when player disconnects
-#[reducer(client_disconnected)]
-pub fn on_disconnect(ctx: &ReducerContext) {
-    if let Some(session) = ctx.db.session().connection_id().find(&ctx.sender) {
-        if let Some(player) = ctx.db.player().id().find(&session.player_id) {
-            // Calculate session duration
-            let session_duration_secs = ctx.timestamp.duration_since(session.connected_at)
-                .map(|d| d.as_secs())
-                .unwrap_or(0);
-            let in_raid = player.in_raid_id.is_some();
-            // Canonical log for disconnect with session context
-            log::info!("[DISCONNECT] {} player:{} session_min:{:.1} in_raid:{}", 
-                player.name, &player.id[..8.min(player.id.len())], 
-                session_duration_secs as f32 / 60.0, in_raid);
-            if let Some(raid_id) = player.in_raid_id {
-                
-                // DC from matchmaking leaves queue (prevents limbo state on reconnect)
-                if let Some(raid) = ctx.db.raid().id().find(&raid_id) {
-                    if raid.state == RaidState::Matchmaking {
-                        log::info!("[DISCONNECT] {} left matchmaking raid:{}", player.name, raid_id);
-                        cleanup_player_raid_data(ctx, &player.id, raid_id);
-                        ctx.db.session().connection_id().delete(&ctx.sender);
-                        return;
-                    }
-                }
-                
-                // Active raid: mark inactive, preserve in_raid_id for resume
-                let was_last_active = count_active_raid_players(ctx, raid_id) == 1;
-                mark_player_inactive_in_raid(ctx, &player.id, raid_id);
-                
-                // Pause if last player left (solo always pauses, multi only if last)
-                if was_last_active {
-                    if let Err(e) = pause_raid_if_empty(ctx, raid_id) {
-                        log::warn!("Failed to pause raid {}: {}", raid_id, e);
-                    }
-                }
+/// Stop spectating a raid
+#[reducer]
+pub fn leave_spectator(ctx: &ReducerContext, raid_id: u64) {
+    let player = match get_player(ctx) {
+        Ok(p) => p,
+        Err(e) => {
+            log::error!("leave_spectator: player lookup failed: {}", e);
+            return;
+        }
+    };
+    cleanup_spectator_rows(ctx, &player.id, Some(raid_id));
+}
+
+/// Spectate a raid by id instead of room code - the entry point for raids a
+/// viewer can't join_as_spectator via a shared code, like the world-boss event
+/// (room_code is always None for those) or a squadmate's solo raid.
+#[reducer]
+pub fn spectate_raid(ctx: &ReducerContext, raid_id: u64) {
+    let player = match get_player(ctx) {
+        Ok(p) => p,
+        Err(e) => {
+            log::error!("spectate_raid: player lookup failed: {}", e);
+            return;
+        }
+    };
+
+    let raid = match ctx.db.raid().id().find(&raid_id) {
+        Some(r) if matches!(r.state, RaidState::Countdown | RaidState::InProgress) => r,
+        Some(r) => {
+            log::warn!("spectate_raid: raid {} not joinable (state: {:?})", raid_id, r.state);
+            return;
+        }
+        None => {
+            log::warn!("spectate_raid: raid {} not found", raid_id);
+            return;
         }
+    };
+
+    // Idempotent, same as join_as_spectator
+    if ctx.db.spectator().iter().any(|s| s.player_id == player.id && s.raid_id == raid.id) {
+        return;
     }
-    
-        // Delete session (ephemeral connection mapping)
-    ctx.db.session().connection_id().delete(&ctx.sender);
+
+    ctx.db.spectator().insert(Spectator {
+        id: 0,
+        player_id: player.id.clone(),
+        raid_id: raid.id,
+        joined_at: ctx.timestamp,
+    });
+
+    log::info!("[SPECTATE] joined raid:{} player:{}", raid.id, player.name);
+}
+
+/// Stop spectating every raid the caller is currently watching - the bulk
+/// counterpart to leave_spectator, for a viewer who just wants to stop watching
+/// without tracking which raid_id(s) they joined
+#[reducer]
+pub fn stop_spectating(ctx: &ReducerContext) {
+    let player = match get_player(ctx) {
+        Ok(p) => p,
+        Err(e) => {
+            log::error!("stop_spectating: player lookup failed: {}", e);
+            return;
+        }
+    };
+    cleanup_spectator_rows(ctx, &player.id, None);
+}
+
+/// Delete a player's spectator row(s) - `raid_id` narrows to one raid, None clears all
+/// (used on disconnect, since a spectator's session isn't scoped to a single raid)
+fn cleanup_spectator_rows(ctx: &ReducerContext, player_id: &str, raid_id: Option<u64>) {
+    let rows: Vec<_> = ctx.db.spectator()
+        .player_id().filter(&player_id.to_string())
+        .filter(|s| raid_id.map(|rid| s.raid_id == rid).unwrap_or(true))
+        .collect();
+    for row in rows {
+        ctx.db.spectator().id().delete(&row.id);
     }
 }
 
-/// Generate a unique 4-letter room code
-fn generate_room_code(ctx: &ReducerContext) -> String {
-    use spacetimedb::rand::Rng;
-    // Avoid confusing letters (no I, O, 0, 1)
-    const CHARS: &str = "ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
-    let mut rng = ctx.rng();
-    (0..4)
-        .map(|_| CHARS.chars().nth(rng.gen_range(0..CHARS.len())).unwrap())
-        .collect()
+/// Drop every spectator watching a raid - called once the raid leaves
+/// Matchmaking/Countdown/InProgress (the states join_as_spectator allows joining in)
+fn cleanup_raid_spectators(ctx: &ReducerContext, raid_id: u64) {
+    for row in ctx.db.spectator().iter().filter(|s| s.raid_id == raid_id) {
+        ctx.db.spectator().id().delete(&row.id);
+    }
 }
 
-/// Create a private room with a shareable code
+/// Start a solo practice raid (single player)
 #[reducer]
-pub fn create_private_room(ctx: &ReducerContext, track: Option<String>, boss_level: Option<u8>) {
+pub fn start_solo_raid(ctx: &ReducerContext, track: Option<String>, boss_level: Option<u8>) {
     let level = boss_level.unwrap_or(0); // Default to adaptive
     // Entry log removed - canonical log at end
     
-    // Check player exists and not in a raid
+    // Player must exist and not be in a raid
     let mut player = match get_player(ctx) {
         Ok(p) if p.in_raid_id.is_none() => p,
         Ok(p) => {
@@ -1482,59 +4445,69 @@ pub fn create_private_room(ctx: &ReducerContext, track: Option<String>, boss_lev
             return;
         },
         Err(e) => {
-            log::error!("Player lookup failed: {}", e);
+            log::warn!("Player lookup failed: {}", e);
             return;
         }
     };
     
-    // Generate unique code
-    let mut code = generate_room_code(ctx);
+    // Calculate HP based on boss level or adaptive
+    let adaptive_hp = calculate_player_contribution_with_context(&player, Some(ctx), track.as_deref());
+    let hp = boss_hp_for_level(level, 1, adaptive_hp);
     
-    // Ensure uniqueness (unlikely collision but safe)
-    while ctx.db.raid()
-        .iter()
-        .any(|r| r.room_code == Some(code.clone()) && 
-                 matches!(r.state, RaidState::Matchmaking)) {
-        code = generate_room_code(ctx);
-    }
+    // Verbose debug log removed - canonical [RAID] solo created log at end
     
-    // Create raid with room code
+    // Create raid in Countdown state (3-2-1-GO before starting)
+    // Timeout and problems are scheduled in countdown_complete, not here
     let raid = ctx.db.raid().insert(Raid {
         id: 0, // Auto-increment
-        boss_hp: 1000,  // Placeholder, updated when raid starts
-        boss_max_hp: 1000,
-        state: RaidState::Matchmaking,
-        room_code: Some(code.clone()),
-        started_at: ctx.timestamp,
+        boss_hp: hp,
+        boss_max_hp: hp,
+        state: RaidState::Countdown, // Start with countdown
+        room_code: None, // Solo raids don't have room codes
+        started_at: ctx.timestamp, // Will be overwritten in countdown_complete
         pause_started_at: None,
         duration_seconds: None,
         problems_issued: 0,
         max_problems: 999,
         boss_level: level,
-        countdown_started_at: None, // Not in countdown yet
+        countdown_started_at: Some(ctx.timestamp), // For client sync
+        current_phase_index: 0,
+        is_public: false,
+        is_pvp: false,
+        team_a_damage: 0,
+        team_b_damage: 0,
+        event_offset: 0,
+        enrage_started_at: None,
+        boss_tick_rate_pct: 0.0,
     });
-    
-    // Add creator as leader
-    // Calculate division for matchmaking display
+
+    // Schedule countdown completion (3-2-1-GO display)
+    let countdown_time = ctx.timestamp + std::time::Duration::from_secs(COUNTDOWN_DURATION_SECS);
+    ctx.db.countdown_schedule().insert(CountdownSchedule {
+        id: 0,
+        raid_id: raid.id,
+        scheduled_at: ScheduleAt::Time(countdown_time.into()),
+    });
+    // Add player as the only participant
+    // Calculate division for matchmaking display (even though solo, keeps data consistent)
     let (mastered_count, total_facts) = get_player_mastery_stats(ctx, &player);
     let division = calculate_division(&player.rank, mastered_count, total_facts);
     
-    // Check for duplicate (player already in this raid)
-    // SpacetimeDB doesn't support multi-column unique constraints, so we check manually
+    // Check for duplicate (shouldn't happen in solo, but defensive programming)
     let already_in_raid = ctx.db.raid_player()
         .iter()
         .any(|rp| rp.player_id == player.id && rp.raid_id == raid.id);
     
     if already_in_raid {
-        log::warn!("Player {} already has raid_player row for raid {}", player.id, raid.id);
-        return; // Don't insert duplicate
+        log::warn!("Player {} already in raid {}", player.id, raid.id);
+        return;
     }
     
-    let raid_player = RaidPlayer {
-        id: 0, // Auto-inc
+    ctx.db.raid_player().insert(RaidPlayer {
+        id: 0,
         player_id: player.id.clone(),
         raid_id: raid.id,
-        player_name: player.name.clone(),
+        player_name: player.name.clone(), // Denormalized for efficient queries
         grade: player.grade,
         rank: player.rank.clone(),
         division: Some(division),
@@ -1543,36 +4516,201 @@ pub fn create_private_room(ctx: &ReducerContext, track: Option<String>, boss_lev
         problems_answered: 0,
         correct_answers: 0,
         fastest_answer_ms: u32::MAX,
-        is_ready: false,
-        is_leader: true, // Creator is always leader
+        is_ready: true, // Auto-ready for solo
+        is_leader: true, // Solo player is always leader
         recent_problems: String::new(),
-        pending_chest_bonus: None,
         track: track.clone(), // Store track selection
+        inactive_since: None,
+        last_answered_at: ctx.timestamp,
+        team: None, // Solo raids have no PvP teams
+        current_streak: 0,
+        peak_streak: 0,
+        disconnected_mid_raid: false,
+        fast_streak: 0,
+        bonus_points: 0,
+        wrong_count: 0,
+        active_buffs: Vec::new(),
+    });
+    
+    // Update player
+    player.in_raid_id = Some(raid.id);
+    let player_id = player.id.clone();
+    ctx.db.player().id().update(player);
+    
+    log::info!("[RAID] solo created raid:{} player:{}", raid.id, &player_id[..8.min(player_id.len())]);
+    // Note: Problems are generated in countdown_complete, not here
+}
+
+/// Toggle ready state for a player
+#[reducer]
+pub fn toggle_ready(ctx: &ReducerContext) {
+    // Entry log removed - not needed for toggle_ready
+    
+    // Get raid player entry
+    let player = get_player(ctx).ok();
+    if let Some(p) = player {
+        // Find raid_player for this player's current raid
+        if let Some(raid_id) = p.in_raid_id {
+            let raid_player = ctx.db.raid_player()
+                .iter()
+                .find(|rp| rp.player_id == p.id && rp.raid_id == raid_id);
+            
+            if let Some(mut rp) = raid_player {
+                rp.is_ready = !rp.is_ready;
+                ctx.db.raid_player().id().update(rp);
+                // No log needed - client sees state change via subscription
+            }
+        }
+    }
+    // Error case: no log needed - player just doesn't see toggle effect
+}
+
+/// Leader starts the raid manually
+#[reducer]
+pub fn start_raid_manual(ctx: &ReducerContext) {
+    // Entry log removed - [RAID] starting log at end
+    
+    // Find player's raid and verify they're the leader
+    let player = match get_player(ctx) {
+        Ok(p) if p.in_raid_id.is_some() => p,
+        _ => {
+            log::warn!("start_raid_manual: Player not in a raid");
+            return;
+        }
+    };
+    
+    // Safe to unwrap because we checked is_some() above, but let's be explicit
+    let raid_id = match player.in_raid_id {
+        Some(id) => id,
+        None => {
+            log::error!("start_raid_manual: Unexpected None in_raid_id");
+            return;
+        }
+    };
+    // Check if sender is the leader (only active players)
+    let raid_players: Vec<_> = ctx.db.raid_player()
+        .raid_id()
+        .filter(&raid_id)
+        .filter(|rp| rp.is_active)
+        .collect();
+        
+    let is_leader = raid_players.iter()
+        .any(|rp| rp.player_id == player.id && rp.is_leader);
+    
+    if !is_leader {
+        log::warn!("Player {} is not the leader of raid {}", player.id, raid_id);
+        return; // Only leader can start
+    }
+    
+    // Multiplayer raids require at least 2 players
+    // (Use start_solo_raid for single player practice)
+    if raid_players.len() < 2 {
+        log::warn!("Raid {} only has {} players, need at least 2 for multiplayer", 
+            raid_id, raid_players.len());
+        return;
+    }
+    
+    // Check if all players are ready
+    let all_ready = raid_players.iter().all(|rp| rp.is_ready);
+    
+    if !all_ready {
+        log::warn!("Not all players are ready in raid {}", raid_id);
+        return; // All players must be ready
+    }
+    
+    // All checks passed - start_raid will log [RAID] starting
+    start_raid(ctx, raid_id);
+}
+
+/// Start a raid that has enough players
+pub fn start_raid(ctx: &ReducerContext, raid_id: u64) {
+    // Double-check that all players are actually ready (safety)
+    let raid_players: Vec<_> = ctx.db.raid_player()
+        .raid_id()
+        .filter(&raid_id)
+        .collect();
+        
+    let active_players: Vec<_> = raid_players.iter()
+        .filter(|rp| rp.is_active)
+        .cloned()
+        .collect();
+    
+    if !active_players.iter().all(|rp| rp.is_ready) {
+        log::error!("[RAID] ✗ start failed raid:{} reason:not_all_ready", raid_id);
+        return;
+    }
+    
+    if active_players.len() < 2 {
+        log::error!("[RAID] ✗ start failed raid:{} reason:not_enough_players count:{}", raid_id, active_players.len());
+        return;
+    }
+    let mut raid = match ctx.db.raid().id().find(&raid_id) {
+        Some(r) if matches!(r.state, RaidState::Matchmaking | RaidState::Rematch) => r,
+        _ => {
+            log::warn!("start_raid called but raid {} is not in Matchmaking/Rematch state", raid_id);
+            return;
+        }
+    };
+    // Calculate HP based on boss level or adaptive
+    let total_hp = if is_adaptive_boss(raid.boss_level) {
+        // Adaptive: sum all players' contributions
+        let mut hp = 0u32;
+    for rp in &active_players {
+        if let Some(player) = ctx.db.player().id().find(&rp.player_id) {
+            let contribution = calculate_player_contribution_with_context(&player, Some(ctx), rp.track.as_deref());
+                hp = hp.saturating_add(contribution);
+        }
+    }
+        hp.max(300) // Ensure minimum HP for safety
+    } else if raid.is_pvp {
+        // PvP: scale off the larger team, not the combined squad - otherwise a
+        // lopsided split makes the boss melt faster than either team could alone
+        let team_a_count = active_players.iter().filter(|rp| rp.team == Some(0)).count();
+        let team_b_count = active_players.iter().filter(|rp| rp.team == Some(1)).count();
+        boss_hp_for_level(raid.boss_level, team_a_count.max(team_b_count).max(1) as u32, 0)
+    } else {
+        // Fixed tier: HP from lookup table
+        boss_hp_for_level(raid.boss_level, active_players.len() as u32, 0)
     };
     
-    ctx.db.raid_player().insert(raid_player);
+    // Data-driven phase ladder (see BossPhase) - seeded once per boss_level
+    seed_boss_phases(ctx, raid.boss_level);
+
+    // Update raid state and HP - start with countdown
+    raid.boss_hp = total_hp;
+    raid.boss_max_hp = total_hp;
+    raid.state = RaidState::Countdown;
+    raid.started_at = ctx.timestamp; // Will be overwritten in countdown_complete
+    raid.countdown_started_at = Some(ctx.timestamp); // For client sync
+    raid.pause_started_at = None;
+    raid.current_phase_index = 0; // Fresh HP pool - back to the opening phase
+    
+    // Canonical log: one line for raid start with squad info for multiplayer
+    let squad_names: Vec<&str> = active_players.iter().map(|rp| rp.player_name.as_str()).collect();
+    log::info!("[RAID] starting raid:{} players:{} squad={:?} hp:{} level:{}", 
+        raid_id, active_players.len(), squad_names, total_hp, raid.boss_level);
     
-    // Update player
-    player.in_raid_id = Some(raid.id);
-    let player_name = player.name.clone();
-    ctx.db.player().id().update(player);
+    ctx.db.raid().id().update(raid);
     
-    log::info!("[ROOM] created code:{} player:{}", code, player_name);
-    // Room code is now accessible through the raid's room_code field
+    // Schedule countdown completion (3-2-1-GO display)
+    let countdown_time = ctx.timestamp + std::time::Duration::from_secs(COUNTDOWN_DURATION_SECS);
+    ctx.db.countdown_schedule().insert(CountdownSchedule {
+        id: 0,
+        raid_id,
+        scheduled_at: ScheduleAt::Time(countdown_time.into()),
+    });
+    // Note: Problems are generated in countdown_complete, not here
 }
 
-/// Set boss visual for adaptive raids (Quick Play)
-/// Leaders can pick which boss to fight while keeping adaptive HP
-/// visual: 0 = random, 1-8 = specific boss visual
+/// Submit an answer to the current problem
 #[reducer]
-pub fn set_boss_visual(ctx: &ReducerContext, visual: u8) {
-    // Entry log removed - canonical log at end
-    
-    // Get player and their raid
+pub fn submit_answer(ctx: &ReducerContext, problem_id: u64, answer_value: u16, response_ms: u32) {
+
+    // Get player - in_raid_id gates out spectators too, since join_as_spectator never sets it
     let player = match get_player(ctx) {
-        Ok(p) => p,
-        Err(e) => {
-            log::warn!("set_boss_visual: Player lookup failed: {}", e);
+        Ok(p) if p.in_raid_id.is_some() => p,
+        _ => {
+            log::warn!("submit_answer: Player not in a raid");
             return;
         }
     };
@@ -1580,1280 +4718,1557 @@ pub fn set_boss_visual(ctx: &ReducerContext, visual: u8) {
     let raid_id = match player.in_raid_id {
         Some(id) => id,
         None => {
-            log::warn!("set_boss_visual: Player {} not in a raid", player.id);
+            log::error!("submit_answer: Unexpected None in_raid_id");
             return;
         }
     };
     
-    // Get the raid
-    let mut raid = match ctx.db.raid().id().find(&raid_id) {
-        Some(r) => r,
-        None => {
-            log::warn!("set_boss_visual: Raid {} not found", raid_id);
+    // Edge case: Check if raid is still in progress
+    let raid = match ctx.db.raid().id().find(&raid_id) {
+        Some(r) if matches!(r.state, RaidState::InProgress) => r,
+        _ => {
+            log::warn!("submit_answer: Raid {} not in progress", raid_id);
             return;
         }
     };
     
-    // Must be in Matchmaking or Rematch state (both are "waiting to start" states)
-    if !matches!(raid.state, RaidState::Matchmaking | RaidState::Rematch) {
-        log::warn!("set_boss_visual: Raid {} not in Matchmaking/Rematch state", raid_id);
+    // Auto-reconnect: If player was marked inactive but is submitting answers, they are back!
+    // This fixes the "ghost player" bug where disconnected players could play but were hidden in UI
+    if let Some(mut rp) = find_raid_player(ctx, &player.id, raid_id) {
+        if !rp.is_active {
+            rp.is_active = true;
+            rp.inactive_since = None; // Reconnected - abandon grace window resets
+            update_raid_player(ctx, rp);
+            cancel_reconnect_deadline(ctx, raid_id, &player.id);
+        }
+    }
+
+    // Safety net: 3-minute hard timeout (scheduler should fire at 2:30 for adaptive or 2:00 for fixed levels)
+    let duration_secs = ctx.timestamp.duration_since(raid.started_at).unwrap_or_default().as_secs();
+    if duration_secs >= 180 {
+        log::warn!("Raid {} exceeded 3-minute safety timeout (scheduler may have failed)", raid_id);
+        end_raid(ctx, raid_id, false);
         return;
     }
     
-    // Must be the leader
-    let raid_player = ctx.db.raid_player()
-        .raid_id()
-        .filter(&raid_id)
-        .find(|rp| rp.player_id == player.id);
-    
-    match raid_player {
-        Some(rp) if rp.is_leader => {}
+    // Get problem
+    let problem = match ctx.db.problem().id().find(&problem_id) {
+        Some(p) if p.raid_id == raid_id && p.player_id == player.id => p,
         _ => {
-            log::warn!("set_boss_visual: Player {} is not the leader of raid {}", player.id, raid_id);
+            log::warn!("submit_answer: Problem {} not found or not for this player", problem_id);
             return;
         }
-    }
-    
-    // Only allow visual selection for adaptive raids (0 or >= 100)
-    if !is_adaptive_boss(raid.boss_level) {
-        log::warn!("set_boss_visual: Raid {} is not adaptive (boss_level={})", raid_id, raid.boss_level);
-        return;
-    }
-    
-    // Validate visual is in valid range (0-8, includes Captain Nova at 7, Void Emperor at 8)
-    if visual > 8 {
-        log::warn!("set_boss_visual: Invalid visual {} (must be 0-8)", visual);
-        return;
-    }
+    };
+
+    // Use client timing since problems are batch-prefetched at raid start
+    // Client tracks when each problem is displayed, server validates correctness
+    // Clamp to reasonable bounds (min 200ms to prevent cheating, max 60s)
+    let response_ms = response_ms.clamp(200, 60_000);
     
-    // Encode the visual selection
-    let new_boss_level = encode_adaptive_boss(visual);
-    raid.boss_level = new_boss_level;
-    ctx.db.raid().id().update(raid);
+    // Check if already answered - allow retry ONLY if previous was wrong AND new is correct
+    let previous_answer = ctx.db.player_answer()
+        .iter()
+        .find(|a| a.problem_id == problem.id && a.player_id == player.id);
     
-    log::info!("[RAID] boss visual set raid:{} visual:{}", raid_id, visual);
-}
-
-/// Leaders can pick which Mastery Trial boss to fight (fixed HP tiers 1-8)
-/// boss_level: 1-8 = specific boss tier with fixed HP
-#[reducer]
-pub fn set_mastery_boss(ctx: &ReducerContext, boss_level: u8) {
-    // Entry log removed - canonical log at end
+    let is_correct = answer_value == problem.answer;
+    let is_retry;  // Track for mastery update decision
     
-    // Get player and their raid
-    let player = match get_player(ctx) {
-        Ok(p) => p,
-        Err(e) => {
-            log::warn!("set_mastery_boss: Player lookup failed: {}", e);
+    if let Some(prev) = previous_answer {
+        if prev.is_correct {
+            // Already answered correctly - reject duplicate
+            log::warn!("Player {} already answered problem {} correctly", player.id, problem.id);
             return;
         }
-    };
-    
-    let raid_id = match player.in_raid_id {
-        Some(id) => id,
-        None => {
-            log::warn!("set_mastery_boss: Player {} not in a raid", player.id);
+        // Previous was wrong
+        if !is_correct {
+            // Still wrong - no point updating, keep original wrong answer
             return;
         }
-    };
+        // Previous wrong, new correct = successful retry, delete old
+        ctx.db.player_answer().id().delete(prev.id);
+        is_retry = true;
+    } else {
+        is_retry = false;
+    }
     
-    // Get the raid
-    let mut raid = match ctx.db.raid().id().find(&raid_id) {
-        Some(r) => r,
-        None => {
-            log::warn!("set_mastery_boss: Raid {} not found", raid_id);
-            return;
-        }
+    // Boss phase in effect when this problem was answered - may tighten the
+    // response window (time_pressure_ms) beyond the normal 60s clamp
+    let time_pressure_ms = current_boss_phase(ctx, &raid).and_then(|p| p.time_pressure_ms);
+    // A SlowAnswers debuff widens the effective response time before anything else
+    // sees it, same as if the player had actually reacted slower
+    let effective_response_ms = response_ms + slow_answers_penalty_ms(ctx, raid_id, &player.id);
+    let beat_time_pressure = time_pressure_ms.map(|limit| effective_response_ms <= limit).unwrap_or(true);
+
+    // Fetched once: streak/fast-streak going into this answer (a retry doesn't
+    // cash in on either - they were already broken by the wrong first attempt
+    // it's correcting), and the live buff set calculate_damage resolves
+    let raid_player_snapshot = find_raid_player(ctx, &player.id, raid_id);
+    let current_streak = raid_player_snapshot.as_ref().map(|rp| rp.current_streak).unwrap_or(0);
+    let fast_streak = raid_player_snapshot.as_ref().map(|rp| rp.fast_streak).unwrap_or(0);
+    let is_fast = response_ms <= get_fast_threshold_ms(player.grade);
+    let triggers_bonus_burst = is_correct && !is_retry && is_fast
+        && (fast_streak + 1) % BONUS_TARGET_FAST_STREAK_N == 0;
+
+    // Calculate damage based on speed and correctness
+    // Retries deal 2/3 damage - reward for recovery, but first attempt is always better
+    let damage = if is_correct && beat_time_pressure {
+        let base = calculate_damage(effective_response_ms, player.grade, ctx, raid_player_snapshot.as_ref());
+        let base = if is_retry { base * 2 / 3 } else { base };
+        // DamageReduction is the only status effect that scales damage directly
+        let base = (base as f32 * status_effect_damage_multiplier(ctx, raid_id, &player.id)) as u32;
+        // Enrage phases reward beating time_pressure_ms with extra damage on top
+        let fast_answer_bonus_pct = current_boss_phase(ctx, &raid).map(|p| p.fast_answer_bonus_pct).unwrap_or(0);
+        let base = base + (base * fast_answer_bonus_pct as u32 / 100);
+        // Escalating bonus for consecutive first-attempt correct answers
+        let streak_bonus_pct = if is_retry { 0 } else {
+            (current_streak * STREAK_DAMAGE_BONUS_PCT_PER_STACK).min(STREAK_DAMAGE_BONUS_PCT_MAX)
+        };
+        let base = base + (base * streak_bonus_pct / 100);
+        // Bonus target burst: a damage spike every BONUS_TARGET_FAST_STREAK_N'th
+        // consecutive fast-correct answer
+        let burst_bonus_pct = if triggers_bonus_burst { BONUS_TARGET_DAMAGE_BURST_PCT } else { 0 };
+        let base = base + (base * burst_bonus_pct / 100);
+        base.min(raid.boss_hp)
+    } else { 0 };
+    
+    // Record answer
+    let answer = PlayerAnswer { 
+        id: 0, // auto-increment will handle this
+        problem_id: problem.id, 
+        player_id: player.id.clone(), 
+        response_ms, 
+        is_correct, 
+        damage 
     };
+    ctx.db.player_answer().insert(answer);
     
-    // Must be in Matchmaking or Rematch state (both are "waiting to start" states)
-    if !matches!(raid.state, RaidState::Matchmaking | RaidState::Rematch) {
-        log::warn!("set_mastery_boss: Raid {} not in Matchmaking/Rematch state", raid_id);
-        return;
+    // Track fact mastery for automaticity training
+    // Skip mastery update on retry - the wrong answer already recorded the struggle
+    // Retry just gives them damage, doesn't count toward learning
+    if !is_retry {
+        update_fact_mastery(
+            ctx,
+            player.id.clone(),
+            problem.left_operand,
+            problem.right_operand,
+            &problem.operation,
+            is_correct,
+            response_ms,
+        );
     }
     
-    // Must be the leader
-    let raid_player = ctx.db.raid_player()
-        .raid_id()
-        .filter(&raid_id)
-        .find(|rp| rp.player_id == player.id);
+    // Update player stats BEFORE boss death check (so final blow counts)
+    update_player_stats(ctx, &player.id, is_correct, response_ms);
     
-    match raid_player {
-        Some(rp) if rp.is_leader => {}
-        _ => {
-            log::warn!("set_mastery_boss: Player {} is not the leader of raid {}", player.id, raid_id);
+    // Update raid player stats
+    let player_again = get_player(ctx).ok();
+    if let Some(p) = player_again {
+        if let Some(current_raid_id) = p.in_raid_id {
+            let raid_player = ctx.db.raid_player()
+                .iter()
+                .find(|rp| rp.player_id == p.id && rp.raid_id == current_raid_id);
+            
+            if let Some(mut rp) = raid_player {
+                rp.damage_dealt = rp.damage_dealt.saturating_add(damage);
+                // Only count stats on first attempt (retry = helper, not real correct)
+                // This ensures Timeback gets honest accuracy
+                if !is_retry {
+                    rp.problems_answered = rp.problems_answered.saturating_add(1);
+                    if is_correct {
+                        rp.correct_answers = rp.correct_answers.saturating_add(1);
+                    }
+                }
+                // Fastest answer tracked regardless (could be retry)
+                if is_correct && response_ms < rp.fastest_answer_ms {
+                    rp.fastest_answer_ms = response_ms;
+                }
+                // Streak only advances on a clean first-attempt correct answer -
+                // a retry already broke it, a miss breaks it now
+                if !is_retry {
+                    if is_correct {
+                        rp.current_streak = rp.current_streak.saturating_add(1);
+                        rp.peak_streak = rp.peak_streak.max(rp.current_streak);
+                        if is_fast {
+                            rp.fast_streak = rp.fast_streak.saturating_add(1);
+                        } else {
+                            rp.fast_streak = 0;
+                        }
+                        if triggers_bonus_burst {
+                            rp.bonus_points = rp.bonus_points.saturating_add(BONUS_TARGET_POINTS_PER_BURST);
+                        }
+                    } else {
+                        rp.current_streak = 0;
+                        rp.fast_streak = 0;
+                        rp.wrong_count = rp.wrong_count.saturating_add(1);
+                    }
+
+                    // Sweep expired buffs before adding/refreshing this answer's
+                    let now_micros = ctx.timestamp.to_micros_since_unix_epoch();
+                    rp.active_buffs.retain(|b| b.expires_at.to_micros_since_unix_epoch() > now_micros);
+
+                    if is_correct {
+                        // Stagger only ever clears on a correct answer, not on expiry
+                        rp.active_buffs.retain(|b| b.kind != BuffKind::Stagger);
+
+                        // Combo magnitude tracks current_streak directly, so it
+                        // just needs refreshing (not re-added) each stack
+                        let combo_stacks = rp.current_streak / COMBO_BUFF_STREAK_PER_STACK;
+                        rp.active_buffs.retain(|b| b.kind != BuffKind::Combo);
+                        if combo_stacks > 0 {
+                            let magnitude = (combo_stacks as f32 * COMBO_BUFF_DAMAGE_BONUS_PCT)
+                                .min(COMBO_BUFF_DAMAGE_BONUS_PCT_MAX);
+                            rp.active_buffs.push(ActiveBuff {
+                                kind: BuffKind::Combo,
+                                // Refreshed every correct answer - this expiry is just a
+                                // safety bound well past the problem timeout, not the
+                                // real clear condition (current_streak breaking is)
+                                magnitude,
+                                expires_at: Timestamp::from_micros_since_unix_epoch(now_micros + 300 * 1_000_000),
+                            });
+                        }
+
+                        if triggers_bonus_burst {
+                            rp.active_buffs.retain(|b| b.kind != BuffKind::Focus);
+                            rp.active_buffs.push(ActiveBuff {
+                                kind: BuffKind::Focus,
+                                magnitude: FOCUS_BUFF_CRIT_CHANCE_BONUS_PCT as f32,
+                                expires_at: Timestamp::from_micros_since_unix_epoch(
+                                    now_micros + (FOCUS_BUFF_DURATION_SECS as i64) * 1_000_000,
+                                ),
+                            });
+                        }
+                    } else {
+                        rp.active_buffs.retain(|b| b.kind != BuffKind::Combo);
+                        rp.active_buffs.push(ActiveBuff {
+                            kind: BuffKind::Stagger,
+                            magnitude: STAGGER_DEBUFF_DAMAGE_MULT_PCT,
+                            // Cleared explicitly on the next correct answer above, but
+                            // still needs a concrete expiry for the expired-buff sweep
+                            expires_at: Timestamp::from_micros_since_unix_epoch(now_micros + 300 * 1_000_000),
+                        });
+                    }
+                }
+                // Any submission, right or wrong, proves the player isn't stalled
+                rp.last_answered_at = ctx.timestamp;
+                ctx.db.raid_player().id().update(rp);
+            }
+        }
+    }
+    
+    // This problem is resolved (first attempt or successful retry) - cancel its
+    // watchdog and arm the next unanswered one, if any remain
+    cancel_problem_timeout(ctx, raid_id, &player.id);
+    if let Some(next) = current_unanswered_problem(ctx, raid_id, &player.id) {
+        schedule_problem_timeout(ctx, raid_id, &player.id, next.id, problem_timeout_delay_secs(ctx, raid_id));
+    }
+
+    // A live minion soaks damage before the boss does - once it dies, threat
+    // re-evaluates to whoever's on top next time one gets summoned
+    if damage > 0 {
+        if let Some(mut minion) = ctx.db.raid_minion().iter().find(|m| m.raid_id == raid_id && m.hp > 0) {
+            minion.hp = minion.hp.saturating_sub(damage);
+            let minion_hp = minion.hp;
+            ctx.db.raid_minion().id().update(minion);
+            if minion_hp == 0 {
+                log::info!("[RAID] minion slain raid:{}", raid_id);
+            }
+            emit_raid_event(ctx, raid_id, RaidEventKind::Damage, Some(player.id.clone()), damage);
             return;
         }
     }
+
+    // Apply damage to boss
+    if damage > 0 {
+        if let Some(mut raid) = ctx.db.raid().id().find(&raid_id) {
+            // Edge case: Prevent multiple players from "winning" simultaneously
+            if raid.boss_hp == 0 {
+                return;
+            }
+            
+            raid.boss_hp = raid.boss_hp.saturating_sub(damage);
+            let new_hp = raid.boss_hp;
+            // PvP rooms track each team's cumulative damage for the "winning team" award
+            // in settle_raid_rewards, separately from the shared boss_hp pool above
+            if raid.is_pvp {
+                if let Some(team) = find_raid_player(ctx, &player.id, raid_id).and_then(|rp| rp.team) {
+                    match team {
+                        0 => raid.team_a_damage = raid.team_a_damage.saturating_add(damage),
+                        _ => raid.team_b_damage = raid.team_b_damage.saturating_add(damage),
+                    }
+                }
+            }
+            record_raid_event(ctx, &mut raid, RaidEventKind::Damage, Some(player.id.clone()), damage);
+            ctx.db.raid().id().update(raid);
+
+            // Check for victory immediately after damage
+            if new_hp == 0 {
+                log::info!("Boss defeated! Player {} dealt the final blow", player.id);
+                end_raid_with_killing_blow(ctx, raid_id, true, Some(&player.id));
+                return;
+            }
+
+            // HP dropped - may have crossed into the next boss phase
+            recompute_raid_phase(ctx, raid_id);
+        }
+    }
     
-    // Only allow for fixed HP raids (1-8), not adaptive (0 or >= 100)
-    if is_adaptive_boss(raid.boss_level) {
-        log::warn!("set_mastery_boss: Raid {} is adaptive (boss_level={}), use set_boss_visual instead", raid_id, raid.boss_level);
-        return;
-    }
-    
-    // Validate boss_level is in valid range (1-8)
-    if boss_level < 1 || boss_level > 8 {
-        log::warn!("set_mastery_boss: Invalid boss_level {} (must be 1-8)", boss_level);
-        return;
-    }
-    
-    // Set the boss level directly (no encoding needed for fixed HP)
-    raid.boss_level = boss_level;
-    ctx.db.raid().id().update(raid);
-    
-    log::info!("[RAID] boss level set raid:{} level:{}", raid_id, boss_level);
+    // NOTE: No need to issue next problem - all problems pre-generated at raid start
+    // Client displays from local queue instantly
 }
 
-/// Join a private room using a code
+/// Request a new problem if player doesn't have one
 #[reducer]
-pub fn join_private_room(ctx: &ReducerContext, code: String, track: Option<String>) {
-    // Entry log removed - canonical log at end
-    
-    // Validate room code format - must be 4 alphanumeric characters
-    const VALID_CHARS: &str = "ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
-    if code.len() != 4 || !code.chars().all(|c| VALID_CHARS.contains(c)) {
-        log::warn!("Invalid room code format: '{}' from {}", code, ctx.sender);
-        return;
-    }
-    
-    // Validate player
-    let mut player = match get_player(ctx) {
-        Ok(p) if p.in_raid_id.is_none() => p,
-        Ok(p) => {
-            log::warn!("Player {} already in raid: {:?}", p.id, p.in_raid_id);
-            return;
-        },
-        Err(e) => {
-            log::error!("Player lookup failed: {}", e);
+pub fn request_problem(ctx: &ReducerContext) {
+    // Player must be in an active raid - also gates out spectators, who never get in_raid_id set
+    let player = match get_player(ctx) {
+        Ok(p) if p.in_raid_id.is_some() => p,
+        _ => {
+            log::warn!("request_problem: Player {} not found or not in raid", ctx.sender);
             return;
         }
     };
     
-    // Find room with code (case insensitive)
-    let raid = match ctx.db.raid()
-        .iter()
-        .find(|r| r.room_code == Some(code.to_uppercase()) && 
-                  matches!(r.state, RaidState::Matchmaking)) {
-        Some(r) => r,
+    let raid_id = match player.in_raid_id {
+        Some(id) => id,
         None => {
-            // Distinguish between different failure reasons for better debugging
-            let exists_but_wrong_state = ctx.db.raid()
-                .iter()
-                .any(|r| r.room_code == Some(code.to_uppercase()));
-            
-            if exists_but_wrong_state {
-                log::warn!("Room {} exists but is not in Matchmaking state", code);
-            } else {
-                log::warn!("Room code {} does not exist", code);
-            }
+            log::error!("request_problem: Unexpected None in_raid_id for player {}", player.id);
             return;
         }
     };
     
-    // Check room not full (count only active players)
-    let active_player_count = ctx.db.raid_player()
-        .raid_id()
-        .filter(&raid.id)
-        .filter(|rp| rp.is_active)
-        .count();
-        
-    if active_player_count >= MAX_PLAYERS_PER_RAID {
-        log::warn!("Room {} is full ({}/{} active players)", code, active_player_count, MAX_PLAYERS_PER_RAID);
-        return;
-    }
-    
-    // Add player (not leader since joining)
-    // Calculate division for matchmaking display
-    let (mastered_count, total_facts) = get_player_mastery_stats(ctx, &player);
-    let division = calculate_division(&player.rank, mastered_count, total_facts);
-    
-    // Check if player was previously in this raid (inactive row from refresh/disconnect)
-    if let Some(mut existing_rp) = ctx.db.raid_player()
-        .iter()
-        .find(|rp| rp.player_id == player.id && rp.raid_id == raid.id)
-    {
-        if !existing_rp.is_active {
-            // Reactivate existing row instead of creating new one
-            existing_rp.is_active = true;
-            existing_rp.is_ready = false;  // Reset ready state
-            existing_rp.is_leader = false; // Reset leadership (in case they were leader before)
-            update_raid_player(ctx, existing_rp);
-            
-            // Update player's in_raid_id
-            player.in_raid_id = Some(raid.id);
-            ctx.db.player().id().update(player);
-            
+    // Raid must be in progress
+    let _raid = match ctx.db.raid().id().find(&raid_id) {
+        Some(r) if matches!(r.state, RaidState::InProgress) => r,
+        Some(r) => {
+            log::warn!("request_problem: Raid {} not in progress (state: {:?})", raid_id, r.state);
             return;
-        } else {
-            // Truly duplicate - already active
-            log::warn!("Player {} already active in raid {}", player.id, raid.id);
+        }
+        None => {
+            log::warn!("request_problem: Raid {} not found", raid_id);
             return;
         }
-    }
-    
-    // No existing row - create new one
-    let raid_player = RaidPlayer {
-        id: 0,
-        player_id: player.id.clone(),
-        raid_id: raid.id,
-        player_name: player.name.clone(),
-        grade: player.grade,
-        rank: player.rank.clone(),
-        division: Some(division),
-        damage_dealt: 0,
-        problems_answered: 0,
-        correct_answers: 0,
-        fastest_answer_ms: u32::MAX,
-        is_active: true,
-        is_ready: false,
-        is_leader: false, // Joiners are not leaders
-        recent_problems: String::new(),
-        pending_chest_bonus: None,
-        track: track.clone(), // Store track selection
     };
     
-    ctx.db.raid_player().insert(raid_player);
+    // Check if player already has an unanswered problem
+    let unanswered_problem = ctx.db.problem()
+        .iter()
+        .filter(|p| p.raid_id == raid_id && p.player_id == player.id)
+        .find(|p| {
+            // Problem is unanswered if no answer exists for it
+            // With composite primary key, we need to check for this specific player's answer
+            ctx.db.player_answer()
+                .iter()
+                .find(|a| a.problem_id == p.id && a.player_id == player.id)
+                .is_none()
+        });
     
-    // Update player
-    player.in_raid_id = Some(raid.id);
-    let player_name = player.name.clone();
-    ctx.db.player().id().update(player);
+    if unanswered_problem.is_some() {
+        return;
+    }
     
-    log::info!("[ROOM] joined code:{} player:{}", code, player_name);
+    // Check if player is active in raid (issue_problem_to_player also checks this, but log here too)
+    let player_in_raid = ctx.db.raid_player()
+        .iter()
+        .any(|rp| rp.raid_id == raid_id && rp.player_id == player.id && rp.is_active);
+    
+    if !player_in_raid {
+        log::warn!("request_problem: Player {} not actively in raid {} (is_active = false)", player.id, raid_id);
+        return;
+    }
+    
+    // Only issue new problem if player doesn't have one
+    issue_problem_to_player(ctx, raid_id, player.id);
 }
 
+/// Number of problems to pre-generate per raid (enough for any raid duration)
+/// At 60 problems/min max, 150 covers 2.5 min raid with buffer
+const PROBLEMS_PER_RAID: u32 = 150;
 
-
-/// Start a solo practice raid (single player)
-#[reducer]
-pub fn start_solo_raid(ctx: &ReducerContext, track: Option<String>, boss_level: Option<u8>) {
-    let level = boss_level.unwrap_or(0); // Default to adaptive
-    // Entry log removed - canonical log at end
-    
-    // Player must exist and not be in a raid
-    let mut player = match get_player(ctx) {
-        Ok(p) if p.in_raid_id.is_none() => p,
-        Ok(p) => {
-            log::warn!("Player {} already in raid: {:?}", p.id, p.in_raid_id);
-            return;
-        },
-        Err(e) => {
-            log::warn!("Player lookup failed: {}", e);
+/// Generate all problems for a raid at once (batch prefetch)
+/// This eliminates per-problem network latency - client displays from local queue
+fn generate_problem_batch(ctx: &ReducerContext, raid_id: u64, player_id: &str) {
+    let mut raid_player = match ctx.db.raid_player()
+        .iter()
+        .find(|rp| rp.player_id == player_id && rp.raid_id == raid_id)
+    {
+        Some(rp) => rp,
+        None => {
+            log::error!("generate_problem_batch: Player {} not in raid {}", player_id, raid_id);
             return;
         }
     };
     
-    // Calculate HP based on boss level or adaptive
-    let adaptive_hp = calculate_player_contribution_with_context(&player, Some(ctx), track.as_deref());
-    let hp = boss_hp_for_level(level, 1, adaptive_hp);
-    
-    // Verbose debug log removed - canonical [RAID] solo created log at end
-    
-    // Create raid in Countdown state (3-2-1-GO before starting)
-    // Timeout and problems are scheduled in countdown_complete, not here
-    let raid = ctx.db.raid().insert(Raid {
-        id: 0, // Auto-increment
-        boss_hp: hp,
-        boss_max_hp: hp,
-        state: RaidState::Countdown, // Start with countdown
-        room_code: None, // Solo raids don't have room codes
-        started_at: ctx.timestamp, // Will be overwritten in countdown_complete
-        pause_started_at: None,
-        duration_seconds: None,
-        problems_issued: 0,
-        max_problems: 999,
-        boss_level: level,
-        countdown_started_at: Some(ctx.timestamp), // For client sync
-    });
-    
-    // Schedule countdown completion (3-2-1-GO display)
-    let countdown_time = ctx.timestamp + std::time::Duration::from_secs(COUNTDOWN_DURATION_SECS);
-    ctx.db.countdown_schedule().insert(CountdownSchedule {
-        id: 0,
-        raid_id: raid.id,
-        scheduled_at: ScheduleAt::Time(countdown_time.into()),
-    });
-    // Add player as the only participant
-    // Calculate division for matchmaking display (even though solo, keeps data consistent)
-    let (mastered_count, total_facts) = get_player_mastery_stats(ctx, &player);
-    let division = calculate_division(&player.rank, mastered_count, total_facts);
+    for sequence in 0..PROBLEMS_PER_RAID {
+        let (left, right, operation) = generate_problem(sequence, ctx, &mut raid_player);
+        let answer = operation.compute(left, right) as u16;
+        
+        let problem = Problem {
+            id: 0, // Auto-increment
+            raid_id,
+            player_id: player_id.to_string(),
+            left_operand: left,
+            right_operand: right,
+            operation,
+            answer,
+            issued_at: ctx.timestamp,
+            sequence,
+        };
+        ctx.db.problem().insert(problem);
+    }
     
-    // Check for duplicate (shouldn't happen in solo, but defensive programming)
-    let already_in_raid = ctx.db.raid_player()
-        .iter()
-        .any(|rp| rp.player_id == player.id && rp.raid_id == raid.id);
+    // Update the raid_player with final recent_problems list
+    ctx.db.raid_player().id().update(raid_player);
+
+    // Arm the per-problem timeout on the first problem in the batch - see
+    // PROBLEM_TIMEOUT_SECS / check_problem_timeout
+    if let Some(first) = current_unanswered_problem(ctx, raid_id, player_id) {
+        schedule_problem_timeout(ctx, raid_id, player_id, first.id, problem_timeout_delay_secs(ctx, raid_id));
+    }
+}
+
+/// Issue a problem to a specific player
+/// DEPRECATED: Use generate_problem_batch for new raids
+pub fn issue_problem_to_player(ctx: &ReducerContext, raid_id: u64, player_id: String) {
+    let raid = match ctx.db.raid().id().find(&raid_id) {
+        Some(r) if matches!(r.state, RaidState::InProgress) => r,
+        _ => return,
+    };
     
-    if already_in_raid {
-        log::warn!("Player {} already in raid {}", player.id, raid.id);
+    // Edge case: Double-check boss isn't already dead
+    if raid.boss_hp == 0 {
+        // Don't call end_raid here - it should have been called by submit_answer
+        // This prevents duplicate performance snapshots
         return;
     }
     
-    ctx.db.raid_player().insert(RaidPlayer {
-        id: 0,
-        player_id: player.id.clone(),
-        raid_id: raid.id,
-        player_name: player.name.clone(), // Denormalized for efficient queries
-        grade: player.grade,
-        rank: player.rank.clone(),
-        division: Some(division),
-        is_active: true,  // Player is actively in raid
-        damage_dealt: 0,
-        problems_answered: 0,
-        correct_answers: 0,
-        fastest_answer_ms: u32::MAX,
-        is_ready: true, // Auto-ready for solo
-        is_leader: true, // Solo player is always leader
-        recent_problems: String::new(),
-        pending_chest_bonus: None,
-        track: track.clone(), // Store track selection
-    });
+    // Edge case: Verify player is still actively in the raid
+    let player_in_raid = ctx.db.raid_player()
+        .iter()
+        .any(|rp| rp.raid_id == raid_id && rp.player_id == player_id && rp.is_active);
+        
+    if !player_in_raid {
+        log::warn!("Not issuing problem - player {} not actively in raid {}", player_id, raid_id);
+        return;
+    }
     
-    // Update player
-    player.in_raid_id = Some(raid.id);
-    let player_id = player.id.clone();
-    ctx.db.player().id().update(player);
+    // IDIOMATIC: Always check for existing unanswered problem to prevent duplicates
+    // This prevents race conditions between submit_answer and request_problem
+    let unanswered_problem = ctx.db.problem()
+        .iter()
+        .filter(|p| p.raid_id == raid_id && p.player_id == player_id)
+        .find(|p| {
+            // Problem is unanswered if no answer exists for it
+            // With composite primary key, we need to check for this specific player's answer
+            ctx.db.player_answer()
+                .iter()
+                .find(|a| a.problem_id == p.id && a.player_id == player_id)
+                .is_none()
+        });
     
-    log::info!("[RAID] solo created raid:{} player:{}", raid.id, &player_id[..8.min(player_id.len())]);
-    // Note: Problems are generated in countdown_complete, not here
-}
-
-/// Toggle ready state for a player
-#[reducer]
-pub fn toggle_ready(ctx: &ReducerContext) {
-    // Entry log removed - not needed for toggle_ready
+    if unanswered_problem.is_some() {
+        return;
+    }
     
-    // Get raid player entry
-    let player = get_player(ctx).ok();
-    if let Some(p) = player {
-        // Find raid_player for this player's current raid
-        if let Some(raid_id) = p.in_raid_id {
-            let raid_player = ctx.db.raid_player()
-                .iter()
-                .find(|rp| rp.player_id == p.id && rp.raid_id == raid_id);
-            
-            if let Some(mut rp) = raid_player {
-                rp.is_ready = !rp.is_ready;
-                ctx.db.raid_player().id().update(rp);
-                // No log needed - client sees state change via subscription
+    // Count problems answered by this player
+    let player_problem_count = ctx.db.player_answer()
+        .iter()
+        .filter(|a| {
+            if let Some(problem) = ctx.db.problem().id().find(&a.problem_id) {
+                problem.raid_id == raid_id && a.player_id == player_id
+            } else {
+                false
             }
+        })
+        .count() as u32;
+    
+    // Generate problem based on this player's progression
+    let raid_player = ctx.db.raid_player()
+        .iter()
+        .find(|rp| rp.player_id == player_id && rp.raid_id == raid_id);
+    
+    let mut raid_player = match raid_player {
+        Some(rp) => rp,
+        None => {
+            log::error!("issue_problem_to_player: Player {} not in raid {}", player_id, raid_id);
+            return;
         }
-    }
-    // Error case: no log needed - player just doesn't see toggle effect
+    };
+    
+    let (left, right, operation) = generate_problem(player_problem_count, ctx, &mut raid_player);
+    
+    // Update the raid_player with new recent_problems list
+    ctx.db.raid_player().id().update(raid_player);
+    
+    // Compute answer using the operation's compute method
+    let answer = operation.compute(left, right) as u16;
+    
+    let problem = Problem {
+        id: 0, // Auto-increment
+        raid_id,
+        player_id: player_id.clone(),
+        left_operand: left,
+        right_operand: right,
+        operation,
+        answer,
+        issued_at: ctx.timestamp,
+        sequence: player_problem_count,
+    };
+    let inserted = ctx.db.problem().insert(problem);
+    schedule_problem_timeout(ctx, raid_id, &player_id, inserted.id, problem_timeout_delay_secs(ctx, raid_id));
 }
 
-/// Leader starts the raid manually
+/// Leave current raid and return to lobby
+/// Note: Players must create/join a new room - auto-matchmaking removed for safety
+/// Transition completed raid to Rematch state (shows ready-check modal)
+/// Doesn't create new raid yet - just marks intent to rematch
 #[reducer]
-pub fn start_raid_manual(ctx: &ReducerContext) {
-    // Entry log removed - [RAID] starting log at end
-    
-    // Find player's raid and verify they're the leader
+pub fn raid_again(ctx: &ReducerContext) {
     let player = match get_player(ctx) {
-        Ok(p) if p.in_raid_id.is_some() => p,
-        _ => {
-            log::warn!("start_raid_manual: Player not in a raid");
+        Ok(p) => p,
+        Err(e) => {
+            log::error!("raid_again: Could not get player: {}", e);
             return;
         }
     };
     
-    // Safe to unwrap because we checked is_some() above, but let's be explicit
+    // Must be in a completed raid
     let raid_id = match player.in_raid_id {
         Some(id) => id,
         None => {
-            log::error!("start_raid_manual: Unexpected None in_raid_id");
+            log::warn!("raid_again: Player {} not in a raid", player.id);
             return;
         }
     };
-    // Check if sender is the leader (only active players)
-    let raid_players: Vec<_> = ctx.db.raid_player()
-        .raid_id()
-        .filter(&raid_id)
-        .filter(|rp| rp.is_active)
-        .collect();
-        
-    let is_leader = raid_players.iter()
-        .any(|rp| rp.player_id == player.id && rp.is_leader);
-    
-    if !is_leader {
-        log::warn!("Player {} is not the leader of raid {}", player.id, raid_id);
-        return; // Only leader can start
-    }
-    
-    // Multiplayer raids require at least 2 players
-    // (Use start_solo_raid for single player practice)
-    if raid_players.len() < 2 {
-        log::warn!("Raid {} only has {} players, need at least 2 for multiplayer", 
-            raid_id, raid_players.len());
-        return;
-    }
-    
-    // Check if all players are ready
-    let all_ready = raid_players.iter().all(|rp| rp.is_ready);
-    
-    if !all_ready {
-        log::warn!("Not all players are ready in raid {}", raid_id);
-        return; // All players must be ready
-    }
-    
-    // All checks passed - start_raid will log [RAID] starting
-    start_raid(ctx, raid_id);
-}
-
-/// Start a raid that has enough players
-pub fn start_raid(ctx: &ReducerContext, raid_id: u64) {
-    // Double-check that all players are actually ready (safety)
-    let raid_players: Vec<_> = ctx.db.raid_player()
-        .raid_id()
-        .filter(&raid_id)
-        .collect();
-        
-    let active_players: Vec<_> = raid_players.iter()
-        .filter(|rp| rp.is_active)
-        .cloned()
-        .collect();
     
-    if !active_players.iter().all(|rp| rp.is_ready) {
-        log::error!("[RAID] ✗ start failed raid:{} reason:not_all_ready", raid_id);
-        return;
-    }
-    
-    if active_players.len() < 2 {
-        log::error!("[RAID] ✗ start failed raid:{} reason:not_enough_players count:{}", raid_id, active_players.len());
-        return;
-    }
     let mut raid = match ctx.db.raid().id().find(&raid_id) {
-        Some(r) if matches!(r.state, RaidState::Matchmaking | RaidState::Rematch) => r,
-        _ => {
-            log::warn!("start_raid called but raid {} is not in Matchmaking/Rematch state", raid_id);
+        Some(r) => r,
+        None => {
+            log::error!("raid_again: Raid {} not found", raid_id);
             return;
         }
     };
-    // Calculate HP based on boss level or adaptive
-    let total_hp = if is_adaptive_boss(raid.boss_level) {
-        // Adaptive: sum all players' contributions
-        let mut hp = 0u32;
-    for rp in &active_players {
-        if let Some(player) = ctx.db.player().id().find(&rp.player_id) {
-            let contribution = calculate_player_contribution_with_context(&player, Some(ctx), rp.track.as_deref());
-                hp = hp.saturating_add(contribution);
-        }
-    }
-        hp.max(300) // Ensure minimum HP for safety
-    } else {
-        // Fixed tier: HP from lookup table
-        boss_hp_for_level(raid.boss_level, active_players.len() as u32, 0)
-    };
-    
-    // Update raid state and HP - start with countdown
-    raid.boss_hp = total_hp;
-    raid.boss_max_hp = total_hp;
-    raid.state = RaidState::Countdown;
-    raid.started_at = ctx.timestamp; // Will be overwritten in countdown_complete
-    raid.countdown_started_at = Some(ctx.timestamp); // For client sync
-    raid.pause_started_at = None;
     
-    // Canonical log: one line for raid start with squad info for multiplayer
-    let squad_names: Vec<&str> = active_players.iter().map(|rp| rp.player_name.as_str()).collect();
-    log::info!("[RAID] starting raid:{} players:{} squad={:?} hp:{} level:{}", 
-        raid_id, active_players.len(), squad_names, total_hp, raid.boss_level);
+    // Can only raid_again from completed raids
+    if !matches!(raid.state, RaidState::Victory | RaidState::Failed) {
+        log::warn!("raid_again: Raid {} not completed (state: {:?})", raid_id, raid.state);
+        return;
+    }
     
+    // Transition to Rematch state (triggers modal on client)
+    raid.state = RaidState::Rematch;
     ctx.db.raid().id().update(raid);
     
-    // Schedule countdown completion (3-2-1-GO display)
-    let countdown_time = ctx.timestamp + std::time::Duration::from_secs(COUNTDOWN_DURATION_SECS);
-    ctx.db.countdown_schedule().insert(CountdownSchedule {
-        id: 0,
-        raid_id,
-        scheduled_at: ScheduleAt::Time(countdown_time.into()),
-    });
-    // Note: Problems are generated in countdown_complete, not here
+    // Reset all active players' ready states for new ready-check
+    for mut rp in ctx.db.raid_player().raid_id().filter(&raid_id) {
+        if rp.is_active {
+            rp.is_ready = false;
+            update_raid_player(ctx, rp);
+        }
+    }
+    
 }
 
-/// Submit an answer to the current problem
+/// Start a new raid from Rematch state (creates new raid with same group)
 #[reducer]
-pub fn submit_answer(ctx: &ReducerContext, problem_id: u64, answer_value: u16, response_ms: u32) {
-    
-    // Get player
+pub fn start_rematch(ctx: &ReducerContext) {
     let player = match get_player(ctx) {
-        Ok(p) if p.in_raid_id.is_some() => p,
-        _ => {
-            log::warn!("submit_answer: Player not in a raid");
+        Ok(p) => p,
+        Err(e) => {
+            log::error!("start_rematch: {}", e);
             return;
         }
     };
     
-    let raid_id = match player.in_raid_id {
+    let old_raid_id = match player.in_raid_id {
         Some(id) => id,
-        None => {
-            log::error!("submit_answer: Unexpected None in_raid_id");
-            return;
-        }
+        None => return,
     };
     
-    // Edge case: Check if raid is still in progress
-    let raid = match ctx.db.raid().id().find(&raid_id) {
-        Some(r) if matches!(r.state, RaidState::InProgress) => r,
+    let old_raid = match ctx.db.raid().id().find(&old_raid_id) {
+        Some(r) if r.state == RaidState::Rematch => r,
         _ => {
-            log::warn!("submit_answer: Raid {} not in progress", raid_id);
+            log::warn!("start_rematch: Raid not in Rematch state");
             return;
         }
     };
     
-    // Auto-reconnect: If player was marked inactive but is submitting answers, they are back!
-    // This fixes the "ghost player" bug where disconnected players could play but were hidden in UI
-    if let Some(mut rp) = find_raid_player(ctx, &player.id, raid_id) {
-        if !rp.is_active {
-            rp.is_active = true;
-            update_raid_player(ctx, rp);
-        }
-    }
-
-    // Safety net: 3-minute hard timeout (scheduler should fire at 2:30 for adaptive or 2:00 for fixed levels)
-    let duration_secs = ctx.timestamp.duration_since(raid.started_at).unwrap_or_default().as_secs();
-    if duration_secs >= 180 {
-        log::warn!("Raid {} exceeded 3-minute safety timeout (scheduler may have failed)", raid_id);
-        end_raid(ctx, raid_id, false);
+    // Get active players only
+    let old_players: Vec<_> = ctx.db.raid_player()
+        .raid_id()
+        .filter(&old_raid_id)
+        .filter(|rp| rp.is_active)
+        .collect();
+    
+    // Check all active players ready
+    if !old_players.iter().all(|rp| rp.is_ready) || old_players.len() < 2 {
+        log::warn!("start_rematch: Not all active players ready or not enough players");
         return;
     }
     
-    // Get problem
-    let problem = match ctx.db.problem().id().find(&problem_id) {
-        Some(p) if p.raid_id == raid_id && p.player_id == player.id => p,
-        _ => {
-            log::warn!("submit_answer: Problem {} not found or not for this player", problem_id);
-            return;
+    // Preserve boss level from previous raid
+    let boss_level = old_raid.boss_level;
+    
+    // Calculate HP based on boss level or adaptive
+    let total_hp = if is_adaptive_boss(boss_level) {
+        // Adaptive: sum players' contributions
+        let mut hp = 0u32;
+    for rp in &old_players {
+        if let Some(p) = ctx.db.player().id().find(&rp.player_id) {
+                hp = hp.saturating_add(calculate_player_contribution_with_context(&p, Some(ctx), rp.track.as_deref()));
         }
+    }
+        hp
+    } else {
+        // Fixed tier: HP from lookup table
+        boss_hp_for_level(boss_level, old_players.len() as u32, 0)
     };
-
-    // Use client timing since problems are batch-prefetched at raid start
-    // Client tracks when each problem is displayed, server validates correctness
-    // Clamp to reasonable bounds (min 200ms to prevent cheating, max 60s)
-    let response_ms = response_ms.clamp(200, 60_000);
-    
-    // Check if already answered - allow retry ONLY if previous was wrong AND new is correct
-    let previous_answer = ctx.db.player_answer()
-        .iter()
-        .find(|a| a.problem_id == problem.id && a.player_id == player.id);
-    
-    let is_correct = answer_value == problem.answer;
-    let is_retry;  // Track for mastery update decision
     
-    if let Some(prev) = previous_answer {
-        if prev.is_correct {
-            // Already answered correctly - reject duplicate
-            log::warn!("Player {} already answered problem {} correctly", player.id, problem.id);
-            return;
-        }
-        // Previous was wrong
-        if !is_correct {
-            // Still wrong - no point updating, keep original wrong answer
-            return;
+    // Create new raid in Countdown state
+    let new_raid = ctx.db.raid().insert(Raid {
+        id: 0,
+        boss_hp: total_hp,
+        boss_max_hp: total_hp,
+        state: RaidState::Countdown,  // Start with countdown
+        room_code: old_raid.room_code.clone(),
+        started_at: ctx.timestamp, // Will be overwritten in countdown_complete
+        pause_started_at: None,
+        duration_seconds: None,
+        problems_issued: 0,
+        max_problems: 999,
+        boss_level,
+        countdown_started_at: Some(ctx.timestamp), // For client sync
+        current_phase_index: 0,
+        is_public: old_raid.is_public,
+        is_pvp: old_raid.is_pvp,
+        team_a_damage: 0,
+        team_b_damage: 0,
+        event_offset: 0,
+        enrage_started_at: None,
+        boss_tick_rate_pct: 0.0,
+    });
+
+    // Schedule countdown completion (3-2-1-GO display)
+    let countdown_time = ctx.timestamp + std::time::Duration::from_secs(COUNTDOWN_DURATION_SECS);
+    ctx.db.countdown_schedule().insert(CountdownSchedule {
+        id: 0,
+        raid_id: new_raid.id,
+        scheduled_at: ScheduleAt::Time(countdown_time.into()),
+    });
+    // Mark old raid_players as inactive (preserves stats, logically removes from old raid)
+    for old_rp in &old_players {
+        if let Some(mut rp) = ctx.db.raid_player().id().find(&old_rp.id) {
+            rp.is_active = false;
+            update_raid_player(ctx, rp);
         }
-        // Previous wrong, new correct = successful retry, delete old
-        ctx.db.player_answer().id().delete(prev.id);
-        is_retry = true;
-    } else {
-        is_retry = false;
     }
     
-    // Calculate damage based on speed and correctness
-    // Retries deal 2/3 damage - reward for recovery, but first attempt is always better
-    let damage = if is_correct {
-        let base = calculate_damage(response_ms, player.grade, ctx);
-        if is_retry { base * 2 / 3 } else { base }.min(raid.boss_hp)
-    } else { 0 };
+    // Migrate players to new raid
+    for old_rp in &old_players {
+        if let Some(mut p) = ctx.db.player().id().find(&old_rp.player_id) {
+            p.in_raid_id = Some(new_raid.id);
+            ctx.db.player().id().update(p);
+        }
+        
+        ctx.db.raid_player().insert(RaidPlayer {
+            id: 0,
+            player_id: old_rp.player_id.clone(),
+            raid_id: new_raid.id,
+            player_name: old_rp.player_name.clone(),
+            grade: old_rp.grade,
+            rank: old_rp.rank.clone(),
+            division: old_rp.division.clone(),
+            damage_dealt: 0,
+            problems_answered: 0,
+            correct_answers: 0,
+            fastest_answer_ms: u32::MAX,
+            is_active: true,
+            is_ready: false,
+            is_leader: old_rp.is_leader,
+            recent_problems: String::new(),
+            track: old_rp.track.clone(),
+            inactive_since: None,
+            last_answered_at: ctx.timestamp,
+            team: old_rp.team, // Keep the same squads across a rematch
+            current_streak: 0,
+            peak_streak: 0,
+            disconnected_mid_raid: false,
+            fast_streak: 0,
+            bonus_points: 0,
+            wrong_count: 0,
+            active_buffs: Vec::new(),
+        });
+        // Note: Problems are generated in countdown_complete, not here
+    }
     
-    // Record answer
-    let answer = PlayerAnswer { 
-        id: 0, // auto-increment will handle this
-        problem_id: problem.id, 
-        player_id: player.id.clone(), 
-        response_ms, 
-        is_correct, 
-        damage 
+}
+
+/// Atomically leave current raid and start a new solo raid
+/// Preserves track selection; optionally override boss level for boss picker
+#[reducer]
+pub fn solo_again(ctx: &ReducerContext, boss_level: Option<u8>) {
+    // Get player
+    let player = match get_player(ctx) {
+        Ok(p) => p,
+        Err(_) => {
+            log::warn!("solo_again: Could not get player");
+            return;
+        }
     };
-    ctx.db.player_answer().insert(answer);
     
-    // Track fact mastery for automaticity training
-    // Skip mastery update on retry - the wrong answer already recorded the struggle
-    // Retry just gives them damage, doesn't count toward learning
-    if !is_retry {
-        update_fact_mastery(
-            ctx,
-            player.id.clone(),
-            problem.left_operand,
-            problem.right_operand,
-            &problem.operation,
-            is_correct,
-            response_ms,
-        );
-    }
+    // Get track and current boss_level from current raid (before leaving)
+    let (track, current_boss) = player.in_raid_id.map_or((None, 0), |raid_id| {
+        let track = find_raid_player(ctx, &player.id, raid_id).and_then(|rp| rp.track);
+        let level = ctx.db.raid().id().find(&raid_id).map(|r| r.boss_level).unwrap_or(0);
+        (track, level)
+    });
     
-    // Update player stats BEFORE boss death check (so final blow counts)
-    update_player_stats(ctx, &player.id, is_correct, response_ms);
+    // Use provided boss_level or fall back to current
+    let level = boss_level.unwrap_or(current_boss);
     
-    // Update raid player stats
-    let player_again = get_player(ctx).ok();
-    if let Some(p) = player_again {
-        if let Some(current_raid_id) = p.in_raid_id {
-            let raid_player = ctx.db.raid_player()
-                .iter()
-                .find(|rp| rp.player_id == p.id && rp.raid_id == current_raid_id);
-            
-            if let Some(mut rp) = raid_player {
-                rp.damage_dealt = rp.damage_dealt.saturating_add(damage);
-                // Only count stats on first attempt (retry = helper, not real correct)
-                // This ensures Timeback gets honest accuracy
-                if !is_retry {
-                    rp.problems_answered = rp.problems_answered.saturating_add(1);
-                    if is_correct {
-                        rp.correct_answers = rp.correct_answers.saturating_add(1);
-                    }
-                }
-                // Fastest answer tracked regardless (could be retry)
-                if is_correct && response_ms < rp.fastest_answer_ms {
-                    rp.fastest_answer_ms = response_ms;
-                }
-                ctx.db.raid_player().id().update(rp);
-            }
-        }
-    }
+    // Leave current raid
+    leave_raid_internal(ctx);
     
-    // Apply damage to boss
-    if damage > 0 {
-        if let Some(mut raid) = ctx.db.raid().id().find(&raid_id) {
-            // Edge case: Prevent multiple players from "winning" simultaneously
-            if raid.boss_hp == 0 {
-                return;
-            }
-            
-            raid.boss_hp = raid.boss_hp.saturating_sub(damage);
-            let new_hp = raid.boss_hp;
-            ctx.db.raid().id().update(raid);
+    // Start new raid with same track and selected boss level
+    start_solo_raid(ctx, track, Some(level));
+}
+
+/// Leave current raid
+#[reducer]
+pub fn leave_raid(ctx: &ReducerContext) {
+    leave_raid_internal(ctx);
+}
+
+/// Internal helper to leave raid (used by multiple reducers)
+fn leave_raid_internal(ctx: &ReducerContext) {
+    if let Ok(player) = get_player(ctx) {
+        if let Some(raid_id) = player.in_raid_id {
+            // Mark player inactive and clear their in_raid_id
+            cleanup_player_raid_data(ctx, &player.id, raid_id);
             
-            // Check for victory immediately after damage
-            if new_hp == 0 {
-                log::info!("Boss defeated! Player {} dealt the final blow", player.id);
-                end_raid(ctx, raid_id, true);
-                return;
+            // If raid is now empty (all players left), delete it immediately
+            // This prevents abandoned raids from timing out and creating fake performance snapshots
+            if count_active_raid_players(ctx, raid_id) == 0 {
+                // Log closure before cleanup deletes data
+                if let Some(raid) = ctx.db.raid().id().find(&raid_id) {
+                    let state_name = match raid.state {
+                        RaidState::Matchmaking => "matchmaking",
+                        RaidState::Countdown => "countdown",
+                        RaidState::InProgress => "in_progress",
+                        RaidState::Paused => "paused",
+                        RaidState::Rematch => "rematch",
+                        RaidState::Victory => "victory",
+                        RaidState::Failed => "failed",
+                    };
+                    let age_micros = ctx.timestamp.to_micros_since_unix_epoch() - raid.started_at.to_micros_since_unix_epoch();
+                    let age_seconds = age_micros / 1_000_000;
+                    let player_count = ctx.db.raid_player().raid_id().filter(&raid_id).count();
+                    let total_damage: u32 = ctx.db.raid_player()
+                        .raid_id().filter(&raid_id)
+                        .map(|rp| rp.damage_dealt)
+                        .sum();
+                    
+                    log::info!("[RAID] closed raid_id={} reason=left state={} age_sec={} players={} damage={}",
+                        raid_id, state_name, age_seconds, player_count, total_damage);
+                }
+                cleanup_raid_data(ctx, raid_id);
             }
         }
     }
-    
-    // NOTE: No need to issue next problem - all problems pre-generated at raid start
-    // Client displays from local queue instantly
 }
 
-/// Request a new problem if player doesn't have one
+/// Open loot chest and claim pre-calculated bonus
 #[reducer]
-pub fn request_problem(ctx: &ReducerContext) {
-    // Player must be in an active raid
-    let player = match get_player(ctx) {
-        Ok(p) if p.in_raid_id.is_some() => p,
-        _ => {
-            log::warn!("request_problem: Player {} not found or not in raid", ctx.sender);
+pub fn open_loot_chest(ctx: &ReducerContext) {
+    let mut player = match get_player(ctx) {
+        Ok(p) => p,
+        Err(e) => {
+            log::warn!("open_loot_chest: {}", e);
             return;
         }
     };
     
+    // Must be in a completed raid
     let raid_id = match player.in_raid_id {
         Some(id) => id,
         None => {
-            log::error!("request_problem: Unexpected None in_raid_id for player {}", player.id);
+            log::warn!("open_loot_chest: Player {} not in a raid", player.id);
             return;
         }
     };
     
-    // Raid must be in progress
-    let _raid = match ctx.db.raid().id().find(&raid_id) {
-        Some(r) if matches!(r.state, RaidState::InProgress) => r,
-        Some(r) => {
-            log::warn!("request_problem: Raid {} not in progress (state: {:?})", raid_id, r.state);
-            return;
-        }
-        None => {
-            log::warn!("request_problem: Raid {} not found", raid_id);
+    // Verify raid is complete
+    match ctx.db.raid().id().find(&raid_id) {
+        Some(r) if matches!(r.state, RaidState::Victory | RaidState::Failed) => {},
+        _ => {
+            log::warn!("open_loot_chest: Raid {} not complete", raid_id);
             return;
         }
     };
     
-    // Check if player already has an unanswered problem
-    let unanswered_problem = ctx.db.problem()
-        .iter()
-        .filter(|p| p.raid_id == raid_id && p.player_id == player.id)
-        .find(|p| {
-            // Problem is unanswered if no answer exists for it
-            // With composite primary key, we need to check for this specific player's answer
-            ctx.db.player_answer()
-                .iter()
-                .find(|a| a.problem_id == p.id && a.player_id == player.id)
-                .is_none()
-        });
-    
-    if unanswered_problem.is_some() {
-        return;
-    }
-    
-    // Check if player is active in raid (issue_problem_to_player also checks this, but log here too)
-    let player_in_raid = ctx.db.raid_player()
-        .iter()
-        .any(|rp| rp.raid_id == raid_id && rp.player_id == player.id && rp.is_active);
-    
-    if !player_in_raid {
-        log::warn!("request_problem: Player {} not actively in raid {} (is_active = false)", player.id, raid_id);
-        return;
-    }
-    
-    // Only issue new problem if player doesn't have one
-    issue_problem_to_player(ctx, raid_id, player.id);
-}
+    // Find this player's unclaimed pool share (see settle_raid_rewards)
+    let ledger_row = ctx.db.raid_reward_ledger()
+        .raid_id()
+        .filter(&raid_id)
+        .find(|l| l.player_id == player.id && !l.claimed);
 
-/// Number of problems to pre-generate per raid (enough for any raid duration)
-/// At 60 problems/min max, 150 covers 2.5 min raid with buffer
-const PROBLEMS_PER_RAID: u32 = 150;
+    match ledger_row {
+        Some(mut ledger_row) => {
+            // Award AP
+            player.total_ap = player.total_ap.saturating_add(ledger_row.amount);
+            ctx.db.player().id().update(player);
 
-/// Generate all problems for a raid at once (batch prefetch)
-/// This eliminates per-problem network latency - client displays from local queue
-fn generate_problem_batch(ctx: &ReducerContext, raid_id: u64, player_id: &str) {
-    let mut raid_player = match ctx.db.raid_player()
-        .iter()
-        .find(|rp| rp.player_id == player_id && rp.raid_id == raid_id)
-    {
-        Some(rp) => rp,
+            // Flip claimed (row stays around past claim, see RAID_REWARD_LEDGER_TTL_DAYS)
+            ledger_row.claimed = true;
+            ledger_row.claimed_at = Some(ctx.timestamp);
+            ctx.db.raid_reward_ledger().id().update(ledger_row);
+        },
         None => {
-            log::error!("generate_problem_batch: Player {} not in raid {}", player_id, raid_id);
-            return;
+            log::warn!("open_loot_chest: Player {} already claimed chest or no bonus available",
+                player.id);
         }
-    };
-    
-    for sequence in 0..PROBLEMS_PER_RAID {
-        let (left, right, operation) = generate_problem(sequence, ctx, &mut raid_player);
-        let answer = operation.compute(left, right) as u16;
-        
-        let problem = Problem {
-            id: 0, // Auto-increment
-            raid_id,
-            player_id: player_id.to_string(),
-            left_operand: left,
-            right_operand: right,
-            operation,
-            answer,
-            issued_at: ctx.timestamp,
-            sequence,
-        };
-        ctx.db.problem().insert(problem);
-    }
-    
-    // Update the raid_player with final recent_problems list
-    ctx.db.raid_player().id().update(raid_player);
-    
-}
-
-/// Issue a problem to a specific player
-/// DEPRECATED: Use generate_problem_batch for new raids
-pub fn issue_problem_to_player(ctx: &ReducerContext, raid_id: u64, player_id: String) {
-    let raid = match ctx.db.raid().id().find(&raid_id) {
-        Some(r) if matches!(r.state, RaidState::InProgress) => r,
-        _ => return,
-    };
-    
-    // Edge case: Double-check boss isn't already dead
-    if raid.boss_hp == 0 {
-        // Don't call end_raid here - it should have been called by submit_answer
-        // This prevents duplicate performance snapshots
-        return;
-    }
-    
-    // Edge case: Verify player is still actively in the raid
-    let player_in_raid = ctx.db.raid_player()
-        .iter()
-        .any(|rp| rp.raid_id == raid_id && rp.player_id == player_id && rp.is_active);
-        
-    if !player_in_raid {
-        log::warn!("Not issuing problem - player {} not actively in raid {}", player_id, raid_id);
-        return;
-    }
-    
-    // IDIOMATIC: Always check for existing unanswered problem to prevent duplicates
-    // This prevents race conditions between submit_answer and request_problem
-    let unanswered_problem = ctx.db.problem()
-        .iter()
-        .filter(|p| p.raid_id == raid_id && p.player_id == player_id)
-        .find(|p| {
-            // Problem is unanswered if no answer exists for it
-            // With composite primary key, we need to check for this specific player's answer
-            ctx.db.player_answer()
-                .iter()
-                .find(|a| a.problem_id == p.id && a.player_id == player_id)
-                .is_none()
-        });
-    
-    if unanswered_problem.is_some() {
-        return;
     }
-    
-    // Count problems answered by this player
-    let player_problem_count = ctx.db.player_answer()
-        .iter()
-        .filter(|a| {
-            if let Some(problem) = ctx.db.problem().id().find(&a.problem_id) {
-                problem.raid_id == raid_id && a.player_id == player_id
-            } else {
-                false
-            }
-        })
-        .count() as u32;
-    
-    // Generate problem based on this player's progression
-    let raid_player = ctx.db.raid_player()
-        .iter()
-        .find(|rp| rp.player_id == player_id && rp.raid_id == raid_id);
-    
-    let mut raid_player = match raid_player {
-        Some(rp) => rp,
-        None => {
-            log::error!("issue_problem_to_player: Player {} not in raid {}", player_id, raid_id);
-            return;
-        }
-    };
-    
-    let (left, right, operation) = generate_problem(player_problem_count, ctx, &mut raid_player);
-    
-    // Update the raid_player with new recent_problems list
-    ctx.db.raid_player().id().update(raid_player);
-    
-    // Compute answer using the operation's compute method
-    let answer = operation.compute(left, right) as u16;
-    
-    let problem = Problem {
-        id: 0, // Auto-increment
-        raid_id,
-        player_id, // This will be updated by issue_problem_to_player
-        left_operand: left,
-        right_operand: right,
-        operation,
-        answer,
-        issued_at: ctx.timestamp,
-        sequence: player_problem_count,
-    };
-    ctx.db.problem().insert(problem);
 }
 
-/// Leave current raid and return to lobby
-/// Note: Players must create/join a new room - auto-matchmaking removed for safety
-/// Transition completed raid to Rematch state (shows ready-check modal)
-/// Doesn't create new raid yet - just marks intent to rematch
+/// Manually leave a completed raid (when done viewing results)
 #[reducer]
-pub fn raid_again(ctx: &ReducerContext) {
-    let player = match get_player(ctx) {
-        Ok(p) => p,
-        Err(e) => {
-            log::error!("raid_again: Could not get player: {}", e);
-            return;
+pub fn leave_completed_raid(ctx: &ReducerContext) {
+    if let Ok(mut player) = get_player(ctx) {
+        if let Some(raid_id) = player.in_raid_id {
+            // Only allow leaving completed raids
+            if let Some(raid) = ctx.db.raid().id().find(&raid_id) {
+                if matches!(raid.state, RaidState::Victory | RaidState::Failed | RaidState::Rematch) {
+                    // Mark inactive instead of delete (preserves stats, consistent with other cleanup)
+                    if let Some(mut rp) = find_raid_player(ctx, &player.id, raid_id) {
+                        rp.is_active = false;
+                        update_raid_player(ctx, rp);
+                    }
+                    
+                    // Clear player's raid association
+                    player.in_raid_id = None;
+                    ctx.db.player().id().update(player);
+                    
+                    // Scheduler will clean up when no active players remain
+                } else {
+                    log::warn!("Player {} tried to leave non-completed raid {}", 
+                        ctx.sender, raid_id);
+                }
+            }
         }
-    };
+    }
+}
+
+
+/// Countdown finished - transition raid to InProgress and issue problems
+/// This is the "GO!" moment after 3-2-1 countdown
+#[reducer]
+pub fn countdown_complete(ctx: &ReducerContext, schedule: CountdownSchedule) {
+    // Only allow scheduler to call this, not clients
+    if ctx.sender != ctx.identity() {
+        log::warn!("Client {} attempted to call countdown_complete", ctx.sender);
+        return;
+    }
     
-    // Must be in a completed raid
-    let raid_id = match player.in_raid_id {
-        Some(id) => id,
-        None => {
-            log::warn!("raid_again: Player {} not in a raid", player.id);
+    // Find raid and verify it's in Countdown state
+    let mut raid = match ctx.db.raid().id().find(&schedule.raid_id) {
+        Some(r) if r.state == RaidState::Countdown => r,
+        Some(r) => {
+            log::warn!("Countdown fired but raid {} is in {:?}, ignoring", schedule.raid_id, r.state);
             return;
         }
-    };
-    
-    let mut raid = match ctx.db.raid().id().find(&raid_id) {
-        Some(r) => r,
         None => {
-            log::error!("raid_again: Raid {} not found", raid_id);
+            log::warn!("Countdown fired but raid {} doesn't exist", schedule.raid_id);
             return;
         }
     };
     
-    // Can only raid_again from completed raids
-    if !matches!(raid.state, RaidState::Victory | RaidState::Failed) {
-        log::warn!("raid_again: Raid {} not completed (state: {:?})", raid_id, raid.state);
+    // Transition to InProgress - this is the real "start time"
+    raid.state = RaidState::InProgress;
+    raid.started_at = ctx.timestamp;
+    raid.countdown_started_at = None; // Clear countdown timestamp
+    let boss_level = raid.boss_level;
+    let raid_id = raid.id;
+    ctx.db.raid().id().update(raid);
+    
+    // NOW schedule timeout (timer starts after countdown)
+    let timeout_duration = raid_timeout_seconds(boss_level);
+    let timeout_time = ctx.timestamp + std::time::Duration::from_secs(timeout_duration);
+    ctx.db.raid_timeout_schedule().insert(RaidTimeoutSchedule {
+        id: 0,
+        raid_id,
+        scheduled_at: ScheduleAt::Time(timeout_time.into()),
+    });
+    // Boss starts passive - first ability decision tick fires after the usual interval
+    schedule_boss_skill(ctx, raid_id, BOSS_CAST_DECISION_INTERVAL_SECS);
+    // Start the idle-answer watchdog now that the clock is actually running
+    schedule_idle_check(ctx, raid_id, IDLE_CHECK_INTERVAL_SECS);
+    // Enrage soft deadline engages after a grace period, not immediately
+    schedule_boss_tick(ctx, raid_id, BOSS_TICK_GRACE_PERIOD_SECS);
+    // NOW issue first problem batch to each active player
+    let active_players: Vec<_> = ctx.db.raid_player()
+        .raid_id()
+        .filter(&raid_id)
+        .filter(|rp| rp.is_active)
+        .collect();
+
+    for mut player in active_players {
+        generate_problem_batch(ctx, raid_id, &player.player_id);
+        // Reset the idle clock to the real raid start - rows were created back at
+        // join/matchmaking time, long before the countdown finished
+        player.last_answered_at = ctx.timestamp;
+        update_raid_player(ctx, player);
+    }
+
+}
+
+/// Check if raid has timed out (scheduled reducer)
+#[reducer]
+pub fn check_raid_timeout(ctx: &ReducerContext, schedule: RaidTimeoutSchedule) {
+    // Only allow scheduler to call this, not clients
+    if ctx.sender != ctx.identity() {
+        log::warn!("Client {} attempted to call check_raid_timeout", ctx.sender);
+        ctx.db.raid_timeout_schedule().id().delete(&schedule.id);
         return;
     }
     
-    // Transition to Rematch state (triggers modal on client)
-    raid.state = RaidState::Rematch;
-    ctx.db.raid().id().update(raid);
-    
-    // Reset all active players' ready states for new ready-check
-    for mut rp in ctx.db.raid_player().raid_id().filter(&raid_id) {
-        if rp.is_active {
-            rp.is_ready = false;
-            update_raid_player(ctx, rp);
+    // Check raid state explicitly
+    if let Some(raid) = ctx.db.raid().id().find(&schedule.raid_id) {
+        match raid.state {
+            RaidState::InProgress => {
+                // Running raid - timeout is valid, end as defeat
+                end_raid(ctx, schedule.raid_id, false);
+            }
+            RaidState::Paused => {
+                // Paused raid - don't timeout (timeout was canceled when paused)
+                ctx.db.raid_timeout_schedule().id().delete(&schedule.id);
+            }
+            _ => {
+                // Already ended or other state - cleanup schedule only
+            }
         }
     }
     
+    // Clean up schedule row after handling event (idiomatic pattern)
+    ctx.db.raid_timeout_schedule().id().delete(&schedule.id);
 }
 
-/// Start a new raid from Rematch state (creates new raid with same group)
+/// Fires RECONNECT_DEADLINE_SECS after a multiplayer player goes inactive. If they're
+/// still inactive, forfeit them from the raid instead of leaving the team waiting forever.
 #[reducer]
-pub fn start_rematch(ctx: &ReducerContext) {
-    let player = match get_player(ctx) {
-        Ok(p) => p,
-        Err(e) => {
-            log::error!("start_rematch: {}", e);
-            return;
-        }
-    };
-    
-    let old_raid_id = match player.in_raid_id {
-        Some(id) => id,
-        None => return,
+pub fn check_reconnect_deadline(ctx: &ReducerContext, schedule: ReconnectDeadline) {
+    // Only allow scheduler to call this, not clients
+    if ctx.sender != ctx.identity() {
+        log::warn!("Client {} attempted to call check_reconnect_deadline", ctx.sender);
+        ctx.db.reconnect_deadline().id().delete(&schedule.id);
+        return;
+    }
+
+    ctx.db.reconnect_deadline().id().delete(&schedule.id);
+
+    let raid_id = schedule.raid_id;
+    let player_id = schedule.player_id;
+
+    // They may have reconnected between scheduling and firing - cancellation should
+    // normally prevent this, but re-check defensively rather than trust the race
+    let still_inactive = matches!(find_raid_player(ctx, &player_id, raid_id), Some(rp) if !rp.is_active);
+    if !still_inactive {
+        return;
+    }
+
+    let mut raid = match ctx.db.raid().id().find(&raid_id) {
+        Some(r) if matches!(r.state, RaidState::InProgress | RaidState::Paused) => r,
+        _ => return, // Raid already ended - nothing left to forfeit from
     };
-    
-    let old_raid = match ctx.db.raid().id().find(&old_raid_id) {
-        Some(r) if r.state == RaidState::Rematch => r,
-        _ => {
-            log::warn!("start_rematch: Raid not in Rematch state");
-            return;
+
+    // Permanently remove: clear in_raid_id so reconnecting no longer resumes them into
+    // this raid. The raid_player row itself stays (same "mark inactive instead of delete"
+    // convention as cleanup_player_raid_data - preserves stats for the results screen).
+    if let Some(mut player) = ctx.db.player().id().find(&player_id) {
+        if player.in_raid_id == Some(raid_id) {
+            player.in_raid_id = None;
+            ctx.db.player().id().update(player);
         }
+    }
+
+    // Rescale boss HP for the smaller remaining team so it isn't an un-winnable pool
+    // (no-op for adaptive bosses - boss_hp_for_level ignores player_count for those)
+    let remaining_players = ctx.db.raid_player()
+        .raid_id().filter(&raid_id)
+        .count()
+        .saturating_sub(1)
+        .max(1) as u32;
+    let new_max_hp = boss_hp_for_level(raid.boss_level, remaining_players, raid.boss_max_hp);
+    raid.boss_hp = if raid.boss_max_hp > 0 {
+        ((raid.boss_hp as u64 * new_max_hp as u64) / raid.boss_max_hp as u64) as u32
+    } else {
+        new_max_hp
     };
-    
-    // Get active players only
-    let old_players: Vec<_> = ctx.db.raid_player()
-        .raid_id()
-        .filter(&old_raid_id)
+    raid.boss_max_hp = new_max_hp;
+    ctx.db.raid().id().update(raid);
+    recompute_raid_phase(ctx, raid_id);
+
+    record_raid_outcome(ctx, &player_id, raid_id, RaidOutcomeKind::Abandon);
+
+    log::info!("[RAID] forfeited raid:{} player:{} reason:reconnect_deadline remaining:{}",
+        raid_id, &player_id[..8.min(player_id.len())], remaining_players);
+}
+
+/// Fires PAUSE_TIMEOUT_GRACE_SECS after a raid pauses with nobody left in it. If
+/// everyone is still gone, the raid is abandoned outright instead of leaking rows
+/// in Paused forever - see pause_raid_if_empty.
+#[reducer]
+pub fn check_pause_timeout(ctx: &ReducerContext, schedule: PauseTimeoutSchedule) {
+    // Only allow scheduler to call this, not clients
+    if ctx.sender != ctx.identity() {
+        log::warn!("Client {} attempted to call check_pause_timeout", ctx.sender);
+        ctx.db.pause_timeout_schedule().id().delete(&schedule.id);
+        return;
+    }
+
+    ctx.db.pause_timeout_schedule().id().delete(&schedule.id);
+
+    let raid_id = schedule.raid_id;
+
+    // Someone may have reconnected between scheduling and firing - cancellation should
+    // normally prevent this, but re-check defensively rather than trust the race
+    match ctx.db.raid().id().find(&raid_id) {
+        Some(r) if r.state == RaidState::Paused => {}
+        _ => return, // Already resumed, ended, or gone some other way
+    }
+    if count_active_raid_players(ctx, raid_id) > 0 {
+        return;
+    }
+
+    log::info!("[RAID] abandoned raid:{} reason:pause_timeout grace_secs:{}", raid_id, PAUSE_TIMEOUT_GRACE_SECS);
+    end_raid(ctx, raid_id, false);
+    cleanup_raid_data(ctx, raid_id);
+}
+
+/// Sweeps an in-progress raid every IDLE_CHECK_INTERVAL_SECS and marks anyone who
+/// hasn't answered within PROBLEM_ANSWER_DEADLINE_SECS inactive, same treatment as a
+/// disconnect, so a connected-but-idle player can't stall the clock for the squad.
+#[reducer]
+pub fn check_idle_players(ctx: &ReducerContext, schedule: IdleCheckSchedule) {
+    // Only allow scheduler to call this, not clients
+    if ctx.sender != ctx.identity() {
+        log::warn!("Client {} attempted to call check_idle_players", ctx.sender);
+        ctx.db.idle_check_schedule().id().delete(&schedule.id);
+        return;
+    }
+
+    ctx.db.idle_check_schedule().id().delete(&schedule.id);
+
+    let raid_id = schedule.raid_id;
+    if !matches!(ctx.db.raid().id().find(&raid_id), Some(r) if r.state == RaidState::InProgress) {
+        return; // Paused/ended - pause_raid_if_empty/end_raid already canceled this sweep
+    }
+
+    let idle_players: Vec<_> = ctx.db.raid_player()
+        .raid_id().filter(&raid_id)
         .filter(|rp| rp.is_active)
+        .filter(|rp| ctx.timestamp.duration_since(rp.last_answered_at)
+            .map(|d| d.as_secs() >= PROBLEM_ANSWER_DEADLINE_SECS)
+            .unwrap_or(false))
         .collect();
-    
-    // Check all active players ready
-    if !old_players.iter().all(|rp| rp.is_ready) || old_players.len() < 2 {
-        log::warn!("start_rematch: Not all active players ready or not enough players");
-        return;
+
+    for rp in &idle_players {
+        log::info!("[RAID] idle raid:{} player:{} reason:no_answer_{}s",
+            raid_id, &rp.player_id[..8.min(rp.player_id.len())], PROBLEM_ANSWER_DEADLINE_SECS);
+        mark_player_inactive_in_raid(ctx, &rp.player_id, raid_id);
     }
-    
-    // Preserve boss level from previous raid
-    let boss_level = old_raid.boss_level;
-    
-    // Calculate HP based on boss level or adaptive
-    let total_hp = if is_adaptive_boss(boss_level) {
-        // Adaptive: sum players' contributions
-        let mut hp = 0u32;
-    for rp in &old_players {
-        if let Some(p) = ctx.db.player().id().find(&rp.player_id) {
-                hp = hp.saturating_add(calculate_player_contribution_with_context(&p, Some(ctx), rp.track.as_deref()));
+
+    if !idle_players.is_empty() {
+        if let Err(e) = pause_raid_if_empty(ctx, raid_id) {
+            log::warn!("Failed to pause raid {} after idle sweep: {}", raid_id, e);
         }
     }
-        hp
-    } else {
-        // Fixed tier: HP from lookup table
-        boss_hp_for_level(boss_level, old_players.len() as u32, 0)
-    };
-    
-    // Create new raid in Countdown state
-    let new_raid = ctx.db.raid().insert(Raid {
-        id: 0,
-        boss_hp: total_hp,
-        boss_max_hp: total_hp,
-        state: RaidState::Countdown,  // Start with countdown
-        room_code: old_raid.room_code.clone(),
-        started_at: ctx.timestamp, // Will be overwritten in countdown_complete
-        pause_started_at: None,
-        duration_seconds: None,
-        problems_issued: 0,
-        max_problems: 999,
-        boss_level,
-        countdown_started_at: Some(ctx.timestamp), // For client sync
-    });
-    
-    // Schedule countdown completion (3-2-1-GO display)
-    let countdown_time = ctx.timestamp + std::time::Duration::from_secs(COUNTDOWN_DURATION_SECS);
-    ctx.db.countdown_schedule().insert(CountdownSchedule {
-        id: 0,
-        raid_id: new_raid.id,
-        scheduled_at: ScheduleAt::Time(countdown_time.into()),
-    });
-    // Mark old raid_players as inactive (preserves stats, logically removes from old raid)
-    for old_rp in &old_players {
-        if let Some(mut rp) = ctx.db.raid_player().id().find(&old_rp.id) {
-            rp.is_active = false;
-            update_raid_player(ctx, rp);
-        }
+
+    // Still in progress (pause_raid_if_empty only pauses once count hits zero) -
+    // keep the watchdog running
+    if matches!(ctx.db.raid().id().find(&raid_id), Some(r) if r.state == RaidState::InProgress) {
+        schedule_idle_check(ctx, raid_id, IDLE_CHECK_INTERVAL_SECS);
     }
-    
-    // Migrate players to new raid
-    for old_rp in &old_players {
-        if let Some(mut p) = ctx.db.player().id().find(&old_rp.player_id) {
-            p.in_raid_id = Some(new_raid.id);
-            ctx.db.player().id().update(p);
-        }
-        
-        ctx.db.raid_player().insert(RaidPlayer {
-            id: 0,
-            player_id: old_rp.player_id.clone(),
-            raid_id: new_raid.id,
-            player_name: old_rp.player_name.clone(),
-            grade: old_rp.grade,
-            rank: old_rp.rank.clone(),
-            division: old_rp.division.clone(),
-            damage_dealt: 0,
-            problems_answered: 0,
-            correct_answers: 0,
-            fastest_answer_ms: u32::MAX,
-            is_active: true,
-            is_ready: false,
-            is_leader: old_rp.is_leader,
-            recent_problems: String::new(),
-            pending_chest_bonus: None,
-            track: old_rp.track.clone(),
+}
+
+/// Per-problem timeout - much tighter than check_idle_players, which only catches a
+/// player who's gone fully silent. Dual purpose, same row serves both halves:
+/// - First fire (in_grace_period: false): deadline just hit. Don't fail yet - open a
+///   short grace window in case a submit_answer is already in flight.
+/// - Second fire (in_grace_period: true): grace window elapsed and still unanswered.
+///   Auto-record a wrong/no-answer PlayerAnswer, run the mastery-as-struggle update,
+///   and arm the watchdog on whatever problem comes next.
+#[reducer]
+pub fn check_problem_timeout(ctx: &ReducerContext, schedule: ProblemTimeoutSchedule) {
+    // Only allow scheduler to call this, not clients
+    if ctx.sender != ctx.identity() {
+        log::warn!("Client {} attempted to call check_problem_timeout", ctx.sender);
+        ctx.db.problem_timeout_schedule().id().delete(&schedule.id);
+        return;
+    }
+
+    ctx.db.problem_timeout_schedule().id().delete(&schedule.id);
+
+    let raid_id = schedule.raid_id;
+    let player_id = schedule.player_id;
+    let problem_id = schedule.problem_id;
+
+    // Answered in the meantime - either the normal path beat us here, or a late
+    // submission landed during the grace window. Either way, nothing left to do.
+    if ctx.db.player_answer().iter().any(|a| a.problem_id == problem_id && a.player_id == player_id) {
+        return;
+    }
+
+    if !matches!(ctx.db.raid().id().find(&raid_id), Some(r) if r.state == RaidState::InProgress) {
+        return; // Paused/ended - resume/end_raid already reconciled any pending timeout
+    }
+
+    if !schedule.in_grace_period {
+        let fires_at = ctx.timestamp + std::time::Duration::from_secs(PROBLEM_TIMEOUT_GRACE_SECS);
+        ctx.db.problem_timeout_schedule().insert(ProblemTimeoutSchedule {
+            id: 0,
+            raid_id,
+            player_id,
+            problem_id,
+            in_grace_period: true,
+            scheduled_at: ScheduleAt::Time(fires_at.into()),
         });
-        // Note: Problems are generated in countdown_complete, not here
+        return;
     }
-    
-}
 
-/// Atomically leave current raid and start a new solo raid
-/// Preserves track selection; optionally override boss level for boss picker
-#[reducer]
-pub fn solo_again(ctx: &ReducerContext, boss_level: Option<u8>) {
-    // Get player
-    let player = match get_player(ctx) {
-        Ok(p) => p,
-        Err(_) => {
-            log::warn!("solo_again: Could not get player");
-            return;
-        }
+    // Grace window elapsed with still no answer - auto-fail this problem and advance
+    let problem = match ctx.db.problem().id().find(&problem_id) {
+        Some(p) => p,
+        None => return,
     };
-    
-    // Get track and current boss_level from current raid (before leaving)
-    let (track, current_boss) = player.in_raid_id.map_or((None, 0), |raid_id| {
-        let track = find_raid_player(ctx, &player.id, raid_id).and_then(|rp| rp.track);
-        let level = ctx.db.raid().id().find(&raid_id).map(|r| r.boss_level).unwrap_or(0);
-        (track, level)
+    let failed_response_ms = ((PROBLEM_TIMEOUT_SECS + PROBLEM_TIMEOUT_GRACE_SECS) * 1000) as u32;
+
+    ctx.db.player_answer().insert(PlayerAnswer {
+        id: 0,
+        problem_id,
+        player_id: player_id.clone(),
+        response_ms: failed_response_ms,
+        is_correct: false,
+        damage: 0,
     });
-    
-    // Use provided boss_level or fall back to current
-    let level = boss_level.unwrap_or(current_boss);
-    
-    // Leave current raid
-    leave_raid_internal(ctx);
-    
-    // Start new raid with same track and selected boss level
-    start_solo_raid(ctx, track, Some(level));
+
+    update_fact_mastery(
+        ctx,
+        player_id.clone(),
+        problem.left_operand,
+        problem.right_operand,
+        &problem.operation,
+        false,
+        failed_response_ms,
+    );
+    update_player_stats(ctx, &player_id, false, failed_response_ms);
+
+    if let Some(mut rp) = find_raid_player(ctx, &player_id, raid_id) {
+        rp.problems_answered = rp.problems_answered.saturating_add(1);
+        rp.current_streak = 0; // Auto-fail breaks the streak same as a wrong answer
+        rp.fast_streak = 0;
+        rp.wrong_count = rp.wrong_count.saturating_add(1);
+        // Same buff fallout as a wrong answer - Combo drops with the streak, Stagger applies
+        rp.active_buffs.retain(|b| b.kind != BuffKind::Combo);
+        rp.active_buffs.push(ActiveBuff {
+            kind: BuffKind::Stagger,
+            magnitude: STAGGER_DEBUFF_DAMAGE_MULT_PCT,
+            expires_at: Timestamp::from_micros_since_unix_epoch(
+                ctx.timestamp.to_micros_since_unix_epoch() + 300 * 1_000_000,
+            ),
+        });
+        update_raid_player(ctx, rp);
+    }
+
+    log::info!("[RAID] problem timeout raid:{} player:{} problem:{}",
+        raid_id, &player_id[..8.min(player_id.len())], problem_id);
+
+    if let Some(next) = current_unanswered_problem(ctx, raid_id, &player_id) {
+        schedule_problem_timeout(ctx, raid_id, &player_id, next.id, problem_timeout_delay_secs(ctx, raid_id));
+    }
 }
 
-/// Leave current raid
+/// Periodic boss-ability tick (scheduled reducer) - dual purpose, same row serves
+/// both halves of a cast's lifecycle:
+/// - No boss_cast pending: pick an ability and start the telegraph, schedule this
+///   same reducer again at cast_ends_at to resolve it.
+/// - A boss_cast is pending and due: apply the debuff to active players, clear the
+///   telegraph, and schedule the next decision tick.
 #[reducer]
-pub fn leave_raid(ctx: &ReducerContext) {
-    leave_raid_internal(ctx);
-}
+pub fn fire_boss_skill(ctx: &ReducerContext, schedule: BossSkillSchedule) {
+    // Only allow scheduler to call this, not clients
+    if ctx.sender != ctx.identity() {
+        log::warn!("Client {} attempted to call fire_boss_skill", ctx.sender);
+        ctx.db.boss_skill_schedule().id().delete(&schedule.id);
+        return;
+    }
 
-/// Internal helper to leave raid (used by multiple reducers)
-fn leave_raid_internal(ctx: &ReducerContext) {
-    if let Ok(player) = get_player(ctx) {
-        if let Some(raid_id) = player.in_raid_id {
-            // Mark player inactive and clear their in_raid_id
-            cleanup_player_raid_data(ctx, &player.id, raid_id);
-            
-            // If raid is now empty (all players left), delete it immediately
-            // This prevents abandoned raids from timing out and creating fake performance snapshots
-            if count_active_raid_players(ctx, raid_id) == 0 {
-                // Log closure before cleanup deletes data
-                if let Some(raid) = ctx.db.raid().id().find(&raid_id) {
-                    let state_name = match raid.state {
-                        RaidState::Matchmaking => "matchmaking",
-                        RaidState::Countdown => "countdown",
-                        RaidState::InProgress => "in_progress",
-                        RaidState::Paused => "paused",
-                        RaidState::Rematch => "rematch",
-                        RaidState::Victory => "victory",
-                        RaidState::Failed => "failed",
-                    };
-                    let age_micros = ctx.timestamp.to_micros_since_unix_epoch() - raid.started_at.to_micros_since_unix_epoch();
-                    let age_seconds = age_micros / 1_000_000;
-                    let player_count = ctx.db.raid_player().raid_id().filter(&raid_id).count();
-                    let total_damage: u32 = ctx.db.raid_player()
-                        .raid_id().filter(&raid_id)
-                        .map(|rp| rp.damage_dealt)
-                        .sum();
-                    
-                    log::info!("[RAID] closed raid_id={} reason=left state={} age_sec={} players={} damage={}",
-                        raid_id, state_name, age_seconds, player_count, total_damage);
+    ctx.db.boss_skill_schedule().id().delete(&schedule.id);
+
+    let raid_id = schedule.raid_id;
+    let raid = match ctx.db.raid().id().find(&raid_id) {
+        Some(r) if r.state == RaidState::InProgress => r,
+        _ => return, // Raid paused/ended - resume_raid_from_pause or end_raid own rescheduling
+    };
+
+    let pending_cast = ctx.db.boss_cast().iter().find(|c| c.raid_id == raid_id);
+
+    match pending_cast {
+        Some(cast) => {
+            // Resolve: apply the debuff to every active player, clear the telegraph
+            let expires_at = ctx.timestamp + std::time::Duration::from_secs(BOSS_ABILITY_DEBUFF_SECS);
+            let ability = BOSS_ABILITIES[cast.ability_id.min(8) as usize];
+            let active_players: Vec<_> = ctx.db.raid_player()
+                .raid_id().filter(&raid_id)
+                .filter(|rp| rp.is_active)
+                .collect();
+            for rp in &active_players {
+                ctx.db.status_effect().insert(StatusEffect {
+                    id: 0,
+                    raid_id,
+                    player_id: rp.player_id.clone(),
+                    kind: ability,
+                    expires_at,
+                });
+            }
+            ctx.db.boss_cast().id().delete(&cast.id);
+            log::info!("[RAID] boss_cast resolved raid:{} ability:{:?} affected:{}",
+                raid_id, ability, active_players.len());
+
+            schedule_boss_skill(ctx, raid_id, BOSS_CAST_DECISION_INTERVAL_SECS);
+        }
+        None => {
+            // Decide tick also drives any live minion - attack its target if one's
+            // still alive, otherwise roll a chance to summon a fresh one
+            match ctx.db.raid_minion().iter().find(|m| m.raid_id == raid_id && m.hp > 0) {
+                Some(minion) => apply_minion_attack(ctx, raid_id, &minion),
+                None => {
+                    if (ctx.rng().gen_range(0..100) as u32) < RAID_MINION_SUMMON_CHANCE_PCT {
+                        spawn_raid_minion(ctx, raid_id, &raid);
+                    }
                 }
-                cleanup_raid_data(ctx, raid_id);
             }
+
+            // Start a new telegraph for this boss's signature ability
+            let ability_id = boss_visual_id(raid.boss_level);
+            let cast_ends_at = ctx.timestamp + std::time::Duration::from_secs(BOSS_CAST_WINDUP_SECS);
+            ctx.db.boss_cast().insert(BossCast {
+                id: 0,
+                raid_id,
+                ability_id,
+                cast_ends_at,
+            });
+            log::info!("[RAID] boss_cast started raid:{} ability_id:{} ends_at:{:?}",
+                raid_id, ability_id, cast_ends_at);
+
+            schedule_boss_skill(ctx, raid_id, BOSS_CAST_WINDUP_SECS);
         }
     }
 }
 
-/// Open loot chest and claim pre-calculated bonus
+/// Periodic boss-tick (scheduled reducer) - the enrage soft deadline, independent of
+/// answer cadence. Engages BOSS_TICK_GRACE_PERIOD_SECS after the raid starts, then
+/// every BOSS_TICK_INTERVAL_SECS reads each active RaidPlayer's last_answered_at to
+/// gauge whether the group is "keeping up"; if not, the boss regenerates HP at an
+/// escalating rate, turning generate_problem's open-ended pacing into a real
+/// difficulty target (see estimate_average_damage) instead of a purely reactive fight.
 #[reducer]
-pub fn open_loot_chest(ctx: &ReducerContext) {
-    let mut player = match get_player(ctx) {
-        Ok(p) => p,
-        Err(e) => {
-            log::warn!("open_loot_chest: {}", e);
-            return;
-        }
+pub fn fire_boss_tick(ctx: &ReducerContext, schedule: BossTickSchedule) {
+    // Only allow scheduler to call this, not clients
+    if ctx.sender != ctx.identity() {
+        log::warn!("Client {} attempted to call fire_boss_tick", ctx.sender);
+        ctx.db.boss_tick_schedule().id().delete(&schedule.id);
+        return;
+    }
+
+    ctx.db.boss_tick_schedule().id().delete(&schedule.id);
+
+    let raid_id = schedule.raid_id;
+    let mut raid = match ctx.db.raid().id().find(&raid_id) {
+        Some(r) if r.state == RaidState::InProgress => r,
+        _ => return, // Raid paused/ended - resume_raid_from_pause or end_raid own rescheduling
     };
-    
-    // Must be in a completed raid
-    let raid_id = match player.in_raid_id {
-        Some(id) => id,
-        None => {
-            log::warn!("open_loot_chest: Player {} not in a raid", player.id);
-            return;
+
+    let active_players: Vec<_> = ctx.db.raid_player()
+        .raid_id().filter(&raid_id)
+        .filter(|rp| rp.is_active)
+        .collect();
+    if active_players.is_empty() {
+        return; // pause_raid_if_empty already handles this - nothing to tick against
+    }
+
+    if raid.enrage_started_at.is_none() {
+        raid.enrage_started_at = Some(ctx.timestamp);
+        raid.boss_tick_rate_pct = BOSS_TICK_BASE_REGEN_PCT;
+    }
+
+    let keeping_up = active_players.iter()
+        .filter(|rp| ctx.timestamp.duration_since(rp.last_answered_at)
+            .map(|d| d.as_secs() <= BOSS_TICK_KEEPING_UP_SECS)
+            .unwrap_or(false))
+        .count();
+    let keeping_up_fraction = keeping_up as f32 / active_players.len() as f32;
+
+    if keeping_up_fraction < BOSS_TICK_BEHIND_FRACTION_THRESHOLD {
+        raid.boss_tick_rate_pct = (raid.boss_tick_rate_pct + BOSS_TICK_REGEN_GROWTH_PCT)
+            .min(BOSS_TICK_MAX_REGEN_PCT);
+    }
+
+    let regen = ((raid.boss_max_hp as f32) * raid.boss_tick_rate_pct / 100.0).round() as u32;
+    raid.boss_hp = (raid.boss_hp + regen).min(raid.boss_max_hp);
+
+    log::info!("[RAID] boss_tick raid:{} regen:{} rate_pct:{:.1} keeping_up:{}/{} boss_hp:{}/{}",
+        raid_id, regen, raid.boss_tick_rate_pct, keeping_up, active_players.len(),
+        raid.boss_hp, raid.boss_max_hp);
+
+    // Fully out-healed at max escalation = the group's pace can't beat the boss
+    // anymore - same failure outcome as a raid_timeout_schedule expiry
+    let wiped = raid.boss_hp >= raid.boss_max_hp && raid.boss_tick_rate_pct >= BOSS_TICK_MAX_REGEN_PCT;
+    ctx.db.raid().id().update(raid);
+
+    if wiped {
+        log::info!("[RAID] enrage wipe raid:{}", raid_id);
+        end_raid(ctx, raid_id, false);
+        return;
+    }
+
+    schedule_boss_tick(ctx, raid_id, BOSS_TICK_INTERVAL_SECS);
+}
+
+/// Distinct grades among players with a live Session right now - the "active
+/// grade bands" spawn_world_boss opens one event per, instead of one global event
+/// for the whole server.
+fn active_grade_bands(ctx: &ReducerContext) -> std::collections::HashSet<u8> {
+    ctx.db.session()
+        .iter()
+        .filter_map(|s| ctx.db.player().id().find(&s.player_id))
+        .map(|p| p.grade)
+        .collect()
+}
+
+/// True if grade already has a world_boss_event whose raid is still forming or running
+fn world_boss_event_active_for_grade(ctx: &ReducerContext, grade: u8) -> bool {
+    ctx.db.world_boss_event()
+        .grade()
+        .filter(&grade)
+        .filter_map(|e| e.raid_id)
+        .filter_map(|raid_id| ctx.db.raid().id().find(&raid_id))
+        .any(|r| matches!(r.state, RaidState::Matchmaking | RaidState::Countdown | RaidState::InProgress))
+}
+
+/// Recurring world-boss spawn (fires every WORLD_BOSS_INTERVAL_SECS). Opens one
+/// public raid per active grade band in Matchmaking for anyone in that grade to
+/// drop into via join_world_boss, then schedules auto_start_world_boss to kick
+/// each one off after its matchmaking window - no leader required, unlike private
+/// rooms. A world_boss_event row tracks each one so clients can show a
+/// join-window countdown before the event auto-starts.
+#[reducer]
+pub fn spawn_world_boss(ctx: &ReducerContext, _schedule: WorldBossSchedule) {
+    // Only allow scheduler to call this, not clients
+    if ctx.sender != ctx.identity() {
+        log::warn!("Client {} attempted to call spawn_world_boss", ctx.sender);
+        return;
+    }
+
+    let grades = active_grade_bands(ctx);
+    if grades.is_empty() {
+        log::info!("[WORLDBOSS] skip spawn, no connected players");
+        return;
+    }
+
+    for grade in grades {
+        // Don't stack events - skip this grade's slot if one is still forming or running
+        if world_boss_event_active_for_grade(ctx, grade) {
+            continue;
         }
-    };
-    
-    // Verify raid is complete
-    match ctx.db.raid().id().find(&raid_id) {
-        Some(r) if matches!(r.state, RaidState::Victory | RaidState::Failed) => {},
-        _ => {
-            log::warn!("open_loot_chest: Raid {} not complete", raid_id);
+
+        let connected_sessions = ctx.db.session().iter()
+            .filter(|s| ctx.db.player().id().find(&s.player_id).map(|p| p.grade == grade).unwrap_or(false))
+            .count() as u32;
+        let hp = (connected_sessions * WORLD_BOSS_HP_PER_SESSION).max(300);
+
+        let raid = ctx.db.raid().insert(Raid {
+            id: 0,
+            boss_hp: hp,
+            boss_max_hp: hp,
+            state: RaidState::Matchmaking,
+            room_code: None,
+            started_at: ctx.timestamp,
+            pause_started_at: None,
+            duration_seconds: None,
+            problems_issued: 0,
+            max_problems: 999,
+            boss_level: WORLD_BOSS_LEVEL,
+            countdown_started_at: None,
+            current_phase_index: 0,
+            is_public: true,
+            is_pvp: false,
+            team_a_damage: 0,
+            team_b_damage: 0,
+            event_offset: 0,
+            enrage_started_at: None,
+            boss_tick_rate_pct: 0.0,
+        });
+
+        let start_time = ctx.timestamp + std::time::Duration::from_secs(WORLD_BOSS_MATCHMAKING_WINDOW_SECS);
+        ctx.db.world_boss_event().insert(WorldBossEvent {
+            id: 0,
+            grade,
+            boss_level: WORLD_BOSS_LEVEL,
+            scheduled_spawn_at: ctx.timestamp,
+            join_window_closes_at: Some(start_time),
+            raid_id: Some(raid.id),
+        });
+        ctx.db.world_boss_start_schedule().insert(WorldBossStartSchedule {
+            id: 0,
+            raid_id: raid.id,
+            scheduled_at: ScheduleAt::Time(start_time.into()),
+        });
+
+        log::info!("[WORLDBOSS] spawned raid:{} grade:{} sessions:{} hp:{}", raid.id, grade, connected_sessions, hp);
+    }
+}
+
+/// Join the currently-open world-boss event (see spawn_world_boss / join_world_boss)
+#[reducer]
+pub fn join_world_boss(ctx: &ReducerContext, track: Option<String>) {
+    let mut player = match get_player(ctx) {
+        Ok(p) if p.in_raid_id.is_none() => p,
+        Ok(p) => {
+            log::warn!("Player {} already in raid: {:?}", p.id, p.in_raid_id);
+            return;
+        },
+        Err(e) => {
+            log::error!("join_world_boss: player lookup failed: {}", e);
             return;
         }
     };
-    
-    // Find raid_player record with pending bonus
-    let mut raid_player = match find_raid_player(ctx, &player.id, raid_id) {
-        Some(rp) => rp,
+
+    let raid = match ctx.db.world_boss_event()
+        .grade()
+        .filter(&player.grade)
+        .filter_map(|e| e.raid_id)
+        .filter_map(|raid_id| ctx.db.raid().id().find(&raid_id))
+        .find(|r| r.is_public && r.state == RaidState::Matchmaking)
+    {
+        Some(r) => r,
         None => {
-            log::warn!("open_loot_chest: No raid_player record found");
+            log::warn!("join_world_boss: no open world-boss event for grade {} right now", player.grade);
             return;
         }
     };
-    
-    // Claim the pre-calculated bonus
-    match raid_player.pending_chest_bonus {
-        Some(bonus) => {
-            // Award AP
-            player.total_ap = player.total_ap.saturating_add(bonus);
-            
-            // Clear the pending bonus (can only claim once)
-            raid_player.pending_chest_bonus = None;
-            update_raid_player(ctx, raid_player);
-            
-            // Update player
-            ctx.db.player().id().update(player);
-            
-        },
-        None => {
-            log::warn!("open_loot_chest: Player {} already claimed chest or no bonus available", 
-                player.id);
-        }
-    }
-}
 
-/// Manually leave a completed raid (when done viewing results)
-#[reducer]
-pub fn leave_completed_raid(ctx: &ReducerContext) {
-    if let Ok(mut player) = get_player(ctx) {
-        if let Some(raid_id) = player.in_raid_id {
-            // Only allow leaving completed raids
-            if let Some(raid) = ctx.db.raid().id().find(&raid_id) {
-                if matches!(raid.state, RaidState::Victory | RaidState::Failed | RaidState::Rematch) {
-                    // Mark inactive instead of delete (preserves stats, consistent with other cleanup)
-                    if let Some(mut rp) = find_raid_player(ctx, &player.id, raid_id) {
-                        rp.is_active = false;
-                        update_raid_player(ctx, rp);
-                    }
-                    
-                    // Clear player's raid association
-                    player.in_raid_id = None;
-                    ctx.db.player().id().update(player);
-                    
-                    // Scheduler will clean up when no active players remain
-                } else {
-                    log::warn!("Player {} tried to leave non-completed raid {}", 
-                        ctx.sender, raid_id);
-                }
-            }
-        }
+    let active_player_count = ctx.db.raid_player()
+        .raid_id().filter(&raid.id)
+        .filter(|rp| rp.is_active)
+        .count();
+    if active_player_count >= MAX_PLAYERS_PER_WORLD_BOSS {
+        log::warn!("World boss raid {} is full ({}/{})", raid.id, active_player_count, MAX_PLAYERS_PER_WORLD_BOSS);
+        return;
     }
-}
 
+    let (mastered_count, total_facts) = get_player_mastery_stats(ctx, &player);
+    let division = calculate_division(&player.rank, mastered_count, total_facts);
+
+    ctx.db.raid_player().insert(RaidPlayer {
+        id: 0,
+        player_id: player.id.clone(),
+        raid_id: raid.id,
+        player_name: player.name.clone(),
+        grade: player.grade,
+        rank: player.rank.clone(),
+        division: Some(division),
+        damage_dealt: 0,
+        problems_answered: 0,
+        correct_answers: 0,
+        fastest_answer_ms: u32::MAX,
+        is_active: true,
+        is_ready: true, // No leader to gate on - everyone auto-readies for the event
+        is_leader: false,
+        recent_problems: String::new(),
+        track: track.clone(),
+        inactive_since: None,
+        last_answered_at: ctx.timestamp,
+        team: None, // World-boss events aren't PvP
+        current_streak: 0,
+        peak_streak: 0,
+        disconnected_mid_raid: false,
+        fast_streak: 0,
+        bonus_points: 0,
+        wrong_count: 0,
+        active_buffs: Vec::new(),
+    });
+
+    player.in_raid_id = Some(raid.id);
+    let player_name = player.name.clone();
+    ctx.db.player().id().update(player);
 
-/// Countdown finished - transition raid to InProgress and issue problems
-/// This is the "GO!" moment after 3-2-1 countdown
+    log::info!("[WORLDBOSS] joined raid:{} player:{}", raid.id, player_name);
+}
+
+/// Auto-starts a world-boss event WORLD_BOSS_MATCHMAKING_WINDOW_SECS after it spawned -
+/// no leader press required, unlike start_raid_manual. A dead event (nobody joined)
+/// just gets cleaned up instead of starting.
 #[reducer]
-pub fn countdown_complete(ctx: &ReducerContext, schedule: CountdownSchedule) {
+pub fn auto_start_world_boss(ctx: &ReducerContext, schedule: WorldBossStartSchedule) {
     // Only allow scheduler to call this, not clients
     if ctx.sender != ctx.identity() {
-        log::warn!("Client {} attempted to call countdown_complete", ctx.sender);
+        log::warn!("Client {} attempted to call auto_start_world_boss", ctx.sender);
+        ctx.db.world_boss_start_schedule().id().delete(&schedule.id);
         return;
     }
-    
-    // Find raid and verify it's in Countdown state
-    let mut raid = match ctx.db.raid().id().find(&schedule.raid_id) {
-        Some(r) if r.state == RaidState::Countdown => r,
-        Some(r) => {
-            log::warn!("Countdown fired but raid {} is in {:?}, ignoring", schedule.raid_id, r.state);
-            return;
-        }
-        None => {
-            log::warn!("Countdown fired but raid {} doesn't exist", schedule.raid_id);
-            return;
-        }
+
+    ctx.db.world_boss_start_schedule().id().delete(&schedule.id);
+
+    let raid_id = schedule.raid_id;
+    let raid = match ctx.db.raid().id().find(&raid_id) {
+        Some(r) if r.state == RaidState::Matchmaking => r,
+        _ => return, // Already started/canceled some other way
     };
-    
-    // Transition to InProgress - this is the real "start time"
-    raid.state = RaidState::InProgress;
-    raid.started_at = ctx.timestamp;
-    raid.countdown_started_at = None; // Clear countdown timestamp
-    let boss_level = raid.boss_level;
-    let raid_id = raid.id;
-    ctx.db.raid().id().update(raid);
-    
-    // NOW schedule timeout (timer starts after countdown)
-    let timeout_duration = raid_timeout_seconds(boss_level);
-    let timeout_time = ctx.timestamp + std::time::Duration::from_secs(timeout_duration);
-    ctx.db.raid_timeout_schedule().insert(RaidTimeoutSchedule {
-        id: 0,
-        raid_id,
-        scheduled_at: ScheduleAt::Time(timeout_time.into()),
-    });
-    // NOW issue first problem batch to each active player
+
     let active_players: Vec<_> = ctx.db.raid_player()
-        .raid_id()
-        .filter(&raid_id)
+        .raid_id().filter(&raid_id)
         .filter(|rp| rp.is_active)
         .collect();
-    
-    for player in active_players {
-        generate_problem_batch(ctx, raid_id, &player.player_id);
-    }
-    
-}
 
-/// Check if raid has timed out (scheduled reducer)
-#[reducer]
-pub fn check_raid_timeout(ctx: &ReducerContext, schedule: RaidTimeoutSchedule) {
-    // Only allow scheduler to call this, not clients
-    if ctx.sender != ctx.identity() {
-        log::warn!("Client {} attempted to call check_raid_timeout", ctx.sender);
-        ctx.db.raid_timeout_schedule().id().delete(&schedule.id);
+    if active_players.is_empty() {
+        log::info!("[WORLDBOSS] no one joined raid:{}, closing event", raid_id);
+        cleanup_raid_data(ctx, raid_id);
         return;
     }
-    
-    // Check raid state explicitly
-    if let Some(raid) = ctx.db.raid().id().find(&schedule.raid_id) {
-        match raid.state {
-            RaidState::InProgress => {
-                // Running raid - timeout is valid, end as defeat
-                end_raid(ctx, schedule.raid_id, false);
-            }
-            RaidState::Paused => {
-                // Paused raid - don't timeout (timeout was canceled when paused)
-                ctx.db.raid_timeout_schedule().id().delete(&schedule.id);
-            }
-            _ => {
-                // Already ended or other state - cleanup schedule only
-            }
+
+    // World-boss events auto-start regardless of headcount (even a single early
+    // joiner gets their fight) - unlike start_raid, there's no leader-readiness
+    // gate or 2-player minimum to satisfy.
+    let mut hp = 0u32;
+    for rp in &active_players {
+        if let Some(p) = ctx.db.player().id().find(&rp.player_id) {
+            hp = hp.saturating_add(calculate_player_contribution_with_context(&p, Some(ctx), rp.track.as_deref()));
         }
     }
-    
-    // Clean up schedule row after handling event (idiomatic pattern)
-    ctx.db.raid_timeout_schedule().id().delete(&schedule.id);
+    let total_hp = hp.max(300);
+
+    let mut raid = raid;
+    raid.boss_hp = total_hp;
+    raid.boss_max_hp = total_hp;
+    raid.state = RaidState::Countdown;
+    raid.started_at = ctx.timestamp;
+    raid.countdown_started_at = Some(ctx.timestamp);
+    raid.current_phase_index = 0;
+    ctx.db.raid().id().update(raid);
+
+    let squad_names: Vec<&str> = active_players.iter().map(|rp| rp.player_name.as_str()).collect();
+    log::info!("[WORLDBOSS] starting raid:{} players:{} squad={:?} hp:{}",
+        raid_id, active_players.len(), squad_names, total_hp);
+
+    let countdown_time = ctx.timestamp + std::time::Duration::from_secs(COUNTDOWN_DURATION_SECS);
+    ctx.db.countdown_schedule().insert(CountdownSchedule {
+        id: 0,
+        raid_id,
+        scheduled_at: ScheduleAt::Time(countdown_time.into()),
+    });
 }
 
 /// Scheduled cleanup task (runs every 30 seconds).
-/// 
+///
 /// Despite the name, this handles TWO things:
 /// 1. Abandoned raids - delete raids stuck >1hr 
 /// 2. TimeBack events - delete sent events after 7d, log+delete dead letters after 7d
@@ -2922,17 +6337,18 @@ pub fn cleanup_abandoned_raids(ctx: &ReducerContext, _schedule: CleanupSchedule)
     
     // -------------------- TimeBack Event Queue Cleanup --------------------
     // Delete sent events 7 days after sent_at (audit window closed)
-    // Log + delete dead letters 7 days after created_at (Axiom preserves for replay)
-    
+    // Stale unsent events (stuck without ever hitting mark_event_sent's
+    // dead-lettering path) still get a safety-net log + delete here
+
     let seven_days_micros: i128 = 7 * 24 * 60 * 60 * 1_000_000;
     let now_micros = now.to_micros_since_unix_epoch() as i128;
-    
+
     for event in ctx.db.timeback_event_queue().iter() {
         if event.sent {
             // Use sent_at for TTL (fallback to created_at for legacy rows)
             let reference_time = event.sent_at.unwrap_or(event.created_at);
             let age_micros = now_micros - reference_time.to_micros_since_unix_epoch() as i128;
-            
+
             if age_micros > seven_days_micros {
                 // Sent successfully, past audit window - delete silently
                 ctx.db.timeback_event_queue().id().delete(&event.id);
@@ -2940,12 +6356,13 @@ pub fn cleanup_abandoned_raids(ctx: &ReducerContext, _schedule: CleanupSchedule)
         } else {
             // Unsent events: use created_at for age
             let age_micros = now_micros - event.created_at.to_micros_since_unix_epoch() as i128;
-            
+
             if age_micros > seven_days_micros {
-                // Dead letter - log with full payload for Axiom replay, then delete
+                // Never made it through mark_event_sent's dead-letter path (e.g. worker
+                // stopped polling it) - log with full payload for Axiom replay, then delete
                 let player_prefix = &event.player_id[..8.min(event.player_id.len())];
                 log::error!(
-                    "[TIMEBACK] ✗ dead_letter event:{} player:{} attempts:{} age:{}d error:{} payload:{}",
+                    "[TIMEBACK] ✗ stale_unsent event:{} player:{} attempts:{} age:{}d error:{} payload:{}",
                     event.id,
                     player_prefix,
                     event.attempts,
@@ -2957,6 +6374,354 @@ pub fn cleanup_abandoned_raids(ctx: &ReducerContext, _schedule: CleanupSchedule)
             }
         }
     }
+
+    // Dead letters are already logged in full at dead-letter time - just
+    // expire them silently once the 7-day replay window has passed
+    for dead in ctx.db.timeback_dead_letter().iter() {
+        let age_micros = now_micros - dead.dead_lettered_at.to_micros_since_unix_epoch() as i128;
+        if age_micros > seven_days_micros {
+            ctx.db.timeback_dead_letter().id().delete(&dead.id);
+        }
+    }
+
+    // -------------------- Raid Reward Ledger Cleanup --------------------
+    // Sweep rows past RAID_REWARD_LEDGER_TTL_DAYS whether or not they were
+    // ever claimed - unclaimed rows are just missed loot at that point
+    let ledger_ttl_micros: i128 = RAID_REWARD_LEDGER_TTL_DAYS as i128 * 24 * 60 * 60 * 1_000_000;
+    for ledger_row in ctx.db.raid_reward_ledger().iter() {
+        let age_micros = now_micros - ledger_row.created_at.to_micros_since_unix_epoch() as i128;
+        if age_micros > ledger_ttl_micros {
+            ctx.db.raid_reward_ledger().id().delete(&ledger_row.id);
+        }
+    }
+}
+
+// ==================== INTEGRITY SCRUBBER ====================
+
+/// Rows examined per target, per scrub tick. Keeps each pass cheap and
+/// bounded regardless of table size - the cursor carries state between ticks.
+const SCRUB_BATCH_SIZE: usize = 200;
+
+fn get_scrub_cursor(ctx: &ReducerContext, target: &str) -> String {
+    ctx.db.scrub_cursor().target().find(&target.to_string())
+        .map(|c| c.last_key)
+        .unwrap_or_default()
+}
+
+fn set_scrub_cursor(ctx: &ReducerContext, target: &str, last_key: String) {
+    let row = ScrubCursor { target: target.to_string(), last_key };
+    if ctx.db.scrub_cursor().target().find(&target.to_string()).is_some() {
+        ctx.db.scrub_cursor().target().update(row);
+    } else {
+        ctx.db.scrub_cursor().insert(row);
+    }
+}
+
+/// Scrub a bounded batch of `Problem` rows: delete ones whose raid no longer
+/// exists (or has ended in Victory/Failed) and that have no PlayerAnswer -
+/// orphaned leftovers no client will ever read again.
+/// Returns (rows_scanned, rows_repaired).
+fn scrub_orphaned_problems(ctx: &ReducerContext) -> (u64, u64) {
+    let cursor: u64 = get_scrub_cursor(ctx, "problem").parse().unwrap_or(0);
+    let mut ids: Vec<u64> = ctx.db.problem().iter().map(|p| p.id).filter(|id| *id > cursor).collect();
+    ids.sort();
+    let reached_end = ids.len() <= SCRUB_BATCH_SIZE;
+    ids.truncate(SCRUB_BATCH_SIZE);
+    let scanned = ids.len() as u64;
+
+    let mut repaired = 0u64;
+    let mut last_id = cursor;
+    for id in &ids {
+        last_id = *id;
+        let Some(problem) = ctx.db.problem().id().find(id) else { continue };
+        let raid_ended_or_missing = match ctx.db.raid().id().find(&problem.raid_id) {
+            Some(raid) => matches!(raid.state, RaidState::Victory | RaidState::Failed),
+            None => true,
+        };
+        if !raid_ended_or_missing {
+            continue;
+        }
+        let has_answer = ctx.db.player_answer().problem_id().filter(&problem.id).next().is_some();
+        if !has_answer {
+            ctx.db.problem().id().delete(&problem.id);
+            repaired += 1;
+        }
+    }
+
+    set_scrub_cursor(ctx, "problem", if reached_end { "0".to_string() } else { last_id.to_string() });
+    (scanned, repaired)
+}
+
+/// Scrub a bounded batch of `Player` rows: clear `in_raid_id` when it points
+/// at a raid that no longer exists (e.g. the raid was cleaned up by
+/// `cleanup_abandoned_raids` without the player's pointer getting reset).
+/// Returns (rows_scanned, rows_repaired).
+fn scrub_stale_player_raids(ctx: &ReducerContext) -> (u64, u64) {
+    let cursor = get_scrub_cursor(ctx, "player");
+    let mut ids: Vec<String> = ctx.db.player().iter().map(|p| p.id).filter(|id| *id > cursor).collect();
+    ids.sort();
+    let reached_end = ids.len() <= SCRUB_BATCH_SIZE;
+    ids.truncate(SCRUB_BATCH_SIZE);
+    let scanned = ids.len() as u64;
+
+    let mut repaired = 0u64;
+    let mut last_id = cursor.clone();
+    for id in &ids {
+        last_id = id.clone();
+        let Some(mut player) = ctx.db.player().id().find(id) else { continue };
+        if let Some(raid_id) = player.in_raid_id {
+            if ctx.db.raid().id().find(&raid_id).is_none() {
+                player.in_raid_id = None;
+                ctx.db.player().id().update(player);
+                repaired += 1;
+            }
+        }
+    }
+
+    set_scrub_cursor(ctx, "player", if reached_end { String::new() } else { last_id });
+    (scanned, repaired)
+}
+
+/// Scrub a bounded batch of `RaidPlayer` rows: flip `is_active` off when the
+/// raid they're marked active for has vanished (cleaned up or lost in a crash).
+/// Returns (rows_scanned, rows_repaired).
+fn scrub_stale_raid_players(ctx: &ReducerContext) -> (u64, u64) {
+    let cursor: u64 = get_scrub_cursor(ctx, "raid_player").parse().unwrap_or(0);
+    let mut ids: Vec<u64> = ctx.db.raid_player().iter().map(|rp| rp.id).filter(|id| *id > cursor).collect();
+    ids.sort();
+    let reached_end = ids.len() <= SCRUB_BATCH_SIZE;
+    ids.truncate(SCRUB_BATCH_SIZE);
+    let scanned = ids.len() as u64;
+
+    let mut repaired = 0u64;
+    let mut last_id = cursor;
+    for id in &ids {
+        last_id = *id;
+        let Some(mut rp) = ctx.db.raid_player().id().find(id) else { continue };
+        if rp.is_active && ctx.db.raid().id().find(&rp.raid_id).is_none() {
+            rp.is_active = false;
+            ctx.db.raid_player().id().update(rp);
+            repaired += 1;
+        }
+    }
+
+    set_scrub_cursor(ctx, "raid_player", if reached_end { "0".to_string() } else { last_id.to_string() });
+    (scanned, repaired)
+}
+
+/// Scrub a bounded batch of `FactMastery` rows: trim `recent_attempts` back
+/// down to the documented 100-entry cap, and recompute `mastery_level` from
+/// scratch to heal any drift from the cached value (e.g. a grade change that
+/// somehow skipped the `set_grade` batch recalculation).
+/// Returns (rows_scanned, attempts_trimmed, mastery_levels_fixed).
+fn scrub_fact_mastery(ctx: &ReducerContext) -> (u64, u64, u64) {
+    const MAX_RECENT_ATTEMPTS: usize = 100;
+
+    let cursor: u64 = get_scrub_cursor(ctx, "fact_mastery").parse().unwrap_or(0);
+    let mut ids: Vec<u64> = ctx.db.fact_mastery().iter().map(|f| f.id).filter(|id| *id > cursor).collect();
+    ids.sort();
+    let reached_end = ids.len() <= SCRUB_BATCH_SIZE;
+    ids.truncate(SCRUB_BATCH_SIZE);
+    let scanned = ids.len() as u64;
+
+    let mut trimmed = 0u64;
+    let mut fixed = 0u64;
+    let mut last_id = cursor;
+    for id in &ids {
+        last_id = *id;
+        let Some(mut fact) = ctx.db.fact_mastery().id().find(id) else { continue };
+        let Some(player) = ctx.db.player().id().find(&fact.player_id) else { continue };
+
+        let mut changed = false;
+        if fact.recent_attempts.len() > MAX_RECENT_ATTEMPTS {
+            let drop = fact.recent_attempts.len() - MAX_RECENT_ATTEMPTS;
+            fact.recent_attempts.drain(0..drop);
+            trimmed += 1;
+            changed = true;
+        }
+
+        let recomputed = calculate_mastery_level(&fact, player.grade);
+        if recomputed != fact.mastery_level {
+            fact.mastery_level = recomputed;
+            fixed += 1;
+            changed = true;
+        }
+
+        if changed {
+            ctx.db.fact_mastery().id().update(fact);
+        }
+    }
+
+    set_scrub_cursor(ctx, "fact_mastery", if reached_end { "0".to_string() } else { last_id.to_string() });
+    (scanned, trimmed, fixed)
+}
+
+/// Resumable integrity scrub pass (runs every 10 seconds).
+///
+/// Walks `problem`, `player`, `raid_player`, and `fact_mastery` in small,
+/// cursor-tracked batches to detect and heal the inconsistencies this schema
+/// can accumulate from crashes, races, or migrations - the same cursor-plus-
+/// rate-limit pattern a storage-repair worker uses so a full pass over a
+/// large table never blocks a single tick.
+#[reducer]
+pub fn run_integrity_scrub(ctx: &ReducerContext, _schedule: ScrubSchedule) {
+    // Only allow scheduler to call this, not clients
+    if ctx.sender != ctx.identity() {
+        log::warn!("Client {} attempted to call run_integrity_scrub", ctx.sender);
+        return;
+    }
+
+    let (problems_scanned, orphaned_problems) = scrub_orphaned_problems(ctx);
+    let (players_scanned, stale_player_raids) = scrub_stale_player_raids(ctx);
+    let (raid_players_scanned, stale_raid_players) = scrub_stale_raid_players(ctx);
+    let (facts_scanned, attempts_trimmed, mastery_fixed) = scrub_fact_mastery(ctx);
+    let rows_scanned = problems_scanned + players_scanned + raid_players_scanned + facts_scanned;
+
+    let mut stats = ctx.db.scrub_stats().id().find(&0).unwrap_or(ScrubStats {
+        id: 0,
+        last_run_at: ctx.timestamp,
+        rows_scanned: 0,
+        orphaned_problems_deleted: 0,
+        stale_player_raids_cleared: 0,
+        stale_raid_players_deactivated: 0,
+        recent_attempts_trimmed: 0,
+        mastery_levels_fixed: 0,
+    });
+    stats.last_run_at = ctx.timestamp;
+    stats.rows_scanned += rows_scanned;
+    stats.orphaned_problems_deleted += orphaned_problems;
+    stats.stale_player_raids_cleared += stale_player_raids;
+    stats.stale_raid_players_deactivated += stale_raid_players;
+    stats.recent_attempts_trimmed += attempts_trimmed;
+    stats.mastery_levels_fixed += mastery_fixed;
+
+    if ctx.db.scrub_stats().id().find(&0).is_some() {
+        ctx.db.scrub_stats().id().update(stats);
+    } else {
+        ctx.db.scrub_stats().insert(stats);
+    }
+
+    let total_repairs = orphaned_problems + stale_player_raids + stale_raid_players + attempts_trimmed + mastery_fixed;
+    if total_repairs > 0 {
+        log::info!(
+            "[SCRUB] scanned={} orphaned_problems={} stale_player_raids={} stale_raid_players={} attempts_trimmed={} mastery_fixed={}",
+            rows_scanned, orphaned_problems, stale_player_raids, stale_raid_players, attempts_trimmed, mastery_fixed
+        );
+    }
+}
+
+// ==================== METRIC SNAPSHOTS ====================
+
+/// Median of a sorted slice (assumes `values` is already sorted)
+fn median_of_sorted(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let mid = values.len() / 2;
+    if values.len() % 2 == 0 {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+/// Write one metric row, deleting any prior snapshot for the same
+/// (name, labels_json) so the table only ever holds the latest value
+fn upsert_metric_snapshot(ctx: &ReducerContext, name: &str, labels_json: &str, value: f64, computed_at: Timestamp) {
+    if let Some(existing) = ctx.db.metric_snapshot()
+        .name()
+        .filter(&name.to_string())
+        .find(|m| m.labels_json == labels_json)
+    {
+        ctx.db.metric_snapshot().id().delete(&existing.id);
+    }
+    ctx.db.metric_snapshot().insert(MetricSnapshot {
+        id: 0, // auto_inc
+        name: name.to_string(),
+        labels_json: labels_json.to_string(),
+        value,
+        computed_at,
+    });
+}
+
+/// Recompute operational/pedagogical metrics into `metric_snapshot` (runs
+/// every 60 seconds). Each row is a (name, labels_json, value, computed_at)
+/// tuple so an external exporter can subscribe and translate it into a
+/// Prometheus series without any module-side HTTP server.
+#[reducer]
+pub fn compute_metric_snapshot(ctx: &ReducerContext, _schedule: MetricSnapshotSchedule) {
+    // Only allow scheduler to call this, not clients
+    if ctx.sender != ctx.identity() {
+        log::warn!("Client {} attempted to call compute_metric_snapshot", ctx.sender);
+        return;
+    }
+
+    let now = ctx.timestamp;
+
+    // -------------------- Per-boss-level win/loss + median raid duration --------------------
+    let mut by_boss: std::collections::HashMap<u8, (u32, u32, Vec<f64>)> = std::collections::HashMap::new();
+    for snap in ctx.db.performance_snapshot().iter() {
+        let entry = by_boss.entry(snap.boss_level).or_insert((0, 0, Vec::new()));
+        match snap.victory {
+            Some(true) => entry.0 += 1,
+            Some(false) => entry.1 += 1,
+            None => {}
+        }
+        entry.2.push(snap.session_seconds as f64);
+    }
+    for (boss_level, (wins, losses, mut durations)) in by_boss {
+        let labels = format!("{{\"boss_level\":\"{}\"}}", boss_level);
+        upsert_metric_snapshot(ctx, "raids_won_total", &labels, wins as f64, now);
+        upsert_metric_snapshot(ctx, "raids_lost_total", &labels, losses as f64, now);
+        durations.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        upsert_metric_snapshot(ctx, "raid_duration_seconds_median", &labels, median_of_sorted(&durations), now);
+    }
+
+    // -------------------- TimeBack queue depth + oldest-unsent age --------------------
+    let unsent_events: Vec<TimebackEventQueue> = ctx.db.timeback_event_queue().iter().filter(|e| !e.sent).collect();
+    upsert_metric_snapshot(ctx, "timeback_queue_depth", "{}", unsent_events.len() as f64, now);
+    let oldest_age_seconds = unsent_events.iter()
+        .map(|e| (now.to_micros_since_unix_epoch() - e.created_at.to_micros_since_unix_epoch()) / 1_000_000)
+        .max()
+        .unwrap_or(0);
+    upsert_metric_snapshot(ctx, "timeback_queue_oldest_unsent_age_seconds", "{}", oldest_age_seconds as f64, now);
+
+    // -------------------- Answer correctness rate --------------------
+    let total_answers = ctx.db.player_answer().iter().count();
+    let correct_answers = ctx.db.player_answer().iter().filter(|a| a.is_correct).count();
+    let correctness_rate = if total_answers > 0 {
+        correct_answers as f64 / total_answers as f64
+    } else {
+        0.0
+    };
+    upsert_metric_snapshot(ctx, "answer_correctness_rate", "{}", correctness_rate, now);
+
+    // -------------------- CQPM distribution bucketed by grade --------------------
+    let mut cqpm_by_grade: std::collections::HashMap<u8, Vec<f64>> = std::collections::HashMap::new();
+    for snap in ctx.db.performance_snapshot().iter() {
+        if snap.session_seconds == 0 {
+            continue;
+        }
+        let cqpm = snap.problems_correct as f64 / (snap.session_seconds as f64 / 60.0);
+        cqpm_by_grade.entry(snap.grade).or_default().push(cqpm);
+    }
+    for (grade, mut values) in cqpm_by_grade {
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let labels = format!("{{\"grade\":\"{}\"}}", grade);
+        upsert_metric_snapshot(ctx, "cqpm_median", &labels, median_of_sorted(&values), now);
+    }
+
+    // -------------------- Active raid / active player gauges --------------------
+    let active_raids = ctx.db.raid().iter()
+        .filter(|r| matches!(r.state, RaidState::InProgress | RaidState::Countdown))
+        .count();
+    upsert_metric_snapshot(ctx, "active_raids", "{}", active_raids as f64, now);
+
+    let active_players = ctx.db.raid_player().iter().filter(|rp| rp.is_active).count();
+    upsert_metric_snapshot(ctx, "active_players", "{}", active_players as f64, now);
+
+    log::info!("[METRICS] snapshot computed: queue_depth={} active_raids={} active_players={} correctness_rate={:.2}",
+        unsent_events.len(), active_raids, active_players, correctness_rate);
 }
 
 /// Refresh leaderboard for a specific grade (private helper function)
@@ -2978,7 +6743,7 @@ fn refresh_leaderboard(ctx: &ReducerContext, grade: u8) {
     // Calculate speed and mastery for each player
     // Note: We only collect mastery/speed here. Rank and division are derived
     // fresh from mastery_percent in the insert loop (single source of truth).
-    let mut leaderboard_data: Vec<(Player, u32, u32, u32, u32)> = players.iter()
+    let mut leaderboard_data: Vec<(Player, u32, u32, u32, u32, i32)> = players.iter()
         .map(|player| {
             // Get mastery stats using existing helper
             let (mastered_count, total_facts) = get_player_mastery_stats(ctx, player);
@@ -3021,27 +6786,29 @@ fn refresh_leaderboard(ctx: &ReducerContext, grade: u8) {
             };
             
             // Return raw data - rank/division calculated fresh in insert loop
-            (player.clone(), mastery_percent, speed_percent, mastered_count, total_facts)
+            (player.clone(), mastery_percent, speed_percent, mastered_count, total_facts, player.bonus_score)
         })
         .collect();
-    
-    // Sort by mastery %, then speed %, then player ID
-    // Note: Rank and division are calculated FROM mastery %, so sorting by mastery
-    // automatically groups by rank and orders by division correctly
+
+    // Sort by mastery %, then speed %, then bonus_score (clean streaks beat misses
+    // among equally-fast-and-accurate players - see RaidPlayer.bonus_points), then
+    // player ID. Rank and division are calculated FROM mastery %, so sorting by
+    // mastery automatically groups by rank and orders by division correctly
     leaderboard_data.sort_by(|a, b| {
         b.1.cmp(&a.1)  // Higher mastery first
             .then(b.2.cmp(&a.2))  // Higher speed as tiebreaker
+            .then(b.5.cmp(&a.5))  // Higher bonus_score as second tiebreaker
             .then(a.0.id.cmp(&b.0.id))  // Player ID for stable ordering
     });
-    
+
     // Insert sorted entries with tie-aware positions
     let mut display_position = 1;
-    
-    for (i, (player, mastery_percent, speed_percent, mastered_count, total_facts)) in leaderboard_data.iter().enumerate() {
-        // Check if tied with previous entry (same mastery AND speed)
+
+    for (i, (player, mastery_percent, speed_percent, mastered_count, total_facts, bonus_score)) in leaderboard_data.iter().enumerate() {
+        // Check if tied with previous entry (same mastery, speed, AND bonus_score)
         if i > 0 {
             let prev = &leaderboard_data[i - 1];
-            if prev.1 != *mastery_percent || prev.2 != *speed_percent {
+            if prev.1 != *mastery_percent || prev.2 != *speed_percent || prev.5 != *bonus_score {
                 // Not tied - advance to actual index position
                 display_position = (i + 1) as u32;
             }
@@ -3078,9 +6845,151 @@ fn refresh_leaderboard(ctx: &ReducerContext, grade: u8) {
             division: calculated_division,
             mastery_percent: *mastery_percent,
             speed_percent: *speed_percent,
+            best_rating: player.best_elo_rating.round() as u32,
+        });
+    }
+
+}
+
+/// How long a season runs before it auto-closes and the next one opens
+const SEASON_DURATION_SECS: u64 = 14 * 24 * 60 * 60; // 2 weeks
+
+/// How many top finishers per grade get archived (and badged) at season close
+const SEASON_ARCHIVE_TOP_N: usize = 10;
+
+/// Facts mastered *during the season window* for one player/grade, used to rank
+/// seasons on gains rather than lifetime totals. Derived the same way end_raid
+/// derives mastery_delta for TimeBack: the last snapshot before the season
+/// started is the baseline, current mastery is the endpoint.
+fn season_mastery_gain(ctx: &ReducerContext, player: &Player, season_starts_at: Timestamp) -> u32 {
+    let (mastered_now, _) = get_player_mastery_stats(ctx, player);
+
+    let mastered_before_season = ctx.db.performance_snapshot()
+        .player_id()
+        .filter(&player.id)
+        .filter(|s| s.grade == player.grade && s.timestamp < season_starts_at)
+        .max_by_key(|s| s.timestamp)
+        .map(|s| s.facts_mastered_at_snapshot)
+        .unwrap_or(0);
+
+    mastered_now.saturating_sub(mastered_before_season)
+}
+
+/// Total raid damage dealt since the season started, used as a tiebreaker
+/// (same derivation as season_mastery_gain - sum of PerformanceSnapshot rows
+/// rather than a live counter, since seasons rank on gains within the window).
+fn season_damage_gain(ctx: &ReducerContext, player_id: &str, grade: u8, season_starts_at: Timestamp) -> u32 {
+    ctx.db.performance_snapshot()
+        .player_id()
+        .filter(&player_id.to_string())
+        .filter(|s| s.grade == grade && s.timestamp >= season_starts_at)
+        .map(|s| s.damage_dealt)
+        .sum()
+}
+
+/// Award a persistent seasonal badge by appending to the player's quests JSON blob
+/// (same storage mechanism as quest/streak counters - no dedicated table needed
+/// for something this infrequent and read-mostly).
+fn award_season_badge(player: &mut Player, season_id: u64, grade: u8, position: u32) {
+    let mut quests = parse_quests(&player.quests);
+    let mut badges = quests["season_badges"].as_array().cloned().unwrap_or_default();
+    badges.push(json!({
+        "season_id": season_id,
+        "grade": grade,
+        "position": position,
+    }));
+    quests["season_badges"] = json!(badges);
+    player.quests = Some(quests.to_string());
+}
+
+/// Snapshot the top SEASON_ARCHIVE_TOP_N players per grade (ranked on in-season
+/// mastery gain, damage dealt as tiebreaker) into season_archive, then badge the
+/// top finishers.
+fn archive_season(ctx: &ReducerContext, season: &Season) {
+    let grades: std::collections::HashSet<u8> = ctx.db.player().iter().map(|p| p.grade).collect();
+
+    for grade in grades {
+        let mut standings: Vec<(Player, u32, u32)> = ctx.db.player()
+            .iter()
+            .filter(|p| p.grade == grade)
+            .map(|p| {
+                let gain = season_mastery_gain(ctx, &p, season.starts_at);
+                let damage = season_damage_gain(ctx, &p.id, grade, season.starts_at);
+                (p, gain, damage)
+            })
+            .filter(|(_, gain, damage)| *gain > 0 || *damage > 0)
+            .collect();
+
+        standings.sort_by(|a, b| {
+            b.1.cmp(&a.1) // Higher mastery gain first
+                .then(b.2.cmp(&a.2)) // Higher damage as tiebreaker
+                .then(a.0.id.cmp(&b.0.id))
         });
+
+        for (position, (player, _gain, _damage)) in standings.iter_mut().enumerate().take(SEASON_ARCHIVE_TOP_N) {
+            let final_position = (position + 1) as u32;
+            let (mastered_count, total_facts) = get_player_mastery_stats(ctx, player);
+            let mastery_percent = if total_facts > 0 { (mastered_count * 100) / total_facts } else { 0 };
+
+            ctx.db.season_archive().insert(SeasonArchive {
+                id: 0,
+                season_id: season.id,
+                grade,
+                final_position,
+                player_id: player.id.clone(),
+                player_name: player.name.clone(),
+                rank: player.rank.clone().unwrap_or_else(|| "bronze".to_string()),
+                mastery_percent,
+            });
+
+            award_season_badge(player, season.id, grade, final_position);
+            ctx.db.player().id().update(player.clone());
+        }
+    }
+
+    log::info!("[SEASON] archived season:{} grades_processed", season.id);
+}
+
+/// Open the next season immediately after the previous one closes
+fn open_next_season(ctx: &ReducerContext) {
+    let starts_at = ctx.timestamp;
+    let ends_at = starts_at + std::time::Duration::from_secs(SEASON_DURATION_SECS);
+
+    let new_season = ctx.db.season().insert(Season {
+        id: 0,
+        starts_at,
+        ends_at,
+        active: true,
+    });
+
+    ctx.db.season_schedule().insert(SeasonSchedule {
+        id: 0,
+        season_id: new_season.id,
+        scheduled_at: ScheduleAt::Time(ends_at.into()),
+    });
+
+    log::info!("[SEASON] opened season:{} ends_at:{:?}", new_season.id, ends_at);
+}
+
+/// Scheduled close of the active season: archive its top finishers, award badges,
+/// then open the next season. Only the scheduler may call this.
+#[reducer]
+pub fn close_season(ctx: &ReducerContext, schedule: SeasonSchedule) {
+    if ctx.sender != ctx.identity() {
+        log::warn!("Client {} attempted to call close_season", ctx.sender);
+        return;
     }
-    
+
+    if let Some(mut season) = ctx.db.season().id().find(&schedule.season_id) {
+        if season.active {
+            archive_season(ctx, &season);
+            season.active = false;
+            ctx.db.season().id().update(season);
+            open_next_season(ctx);
+        }
+    }
+
+    ctx.db.season_schedule().id().delete(&schedule.id);
 }
 
 /// Initialize module - set up scheduled tasks
@@ -3105,7 +7014,62 @@ pub fn init(ctx: &ReducerContext) {
             scheduled_at: ScheduleAt::Interval(std::time::Duration::from_secs(30).into()),
         });
     }
-    
+
+    // Schedule the integrity scrubber to run every 10 seconds
+    // (bounded per-tick batches, so this stays cheap even on a large DB)
+    if ctx.db.scrub_schedule().iter().count() == 0 {
+        ctx.db.scrub_schedule().insert(ScrubSchedule {
+            id: 0, // auto_inc will handle this
+            scheduled_at: ScheduleAt::Interval(std::time::Duration::from_secs(10).into()),
+        });
+    }
+
+    // Prune the announcement feed every minute (rows live ANNOUNCEMENT_TTL_SECS)
+    if ctx.db.announcement_prune_schedule().iter().count() == 0 {
+        ctx.db.announcement_prune_schedule().insert(AnnouncementPruneSchedule {
+            id: 0, // auto_inc will handle this
+            scheduled_at: ScheduleAt::Interval(std::time::Duration::from_secs(60).into()),
+        });
+    }
+
+    // Schedule metric snapshot computation to run every 60 seconds
+    if ctx.db.metric_snapshot_schedule().iter().count() == 0 {
+        ctx.db.metric_snapshot_schedule().insert(MetricSnapshotSchedule {
+            id: 0, // auto_inc will handle this
+            scheduled_at: ScheduleAt::Interval(std::time::Duration::from_secs(60).into()),
+        });
+    }
+
+    // Sweep for due TimeBack events once per retry bucket - see
+    // TimebackEventQueue.due_epoch/claimed and dispatch_due_timeback_events
+    if ctx.db.timeback_dispatch_schedule().iter().count() == 0 {
+        ctx.db.timeback_dispatch_schedule().insert(TimebackDispatchSchedule {
+            id: 0, // auto_inc will handle this
+            scheduled_at: ScheduleAt::Interval(std::time::Duration::from_secs(TIMEBACK_RETRY_BUCKET_SECS as u64).into()),
+        });
+    }
+
+    // Open the first season if none exists yet
+    if ctx.db.season().iter().count() == 0 {
+        open_next_season(ctx);
+    }
+
+    // Sweep for stale, incomplete restore_session rows every 10 minutes
+    if ctx.db.restore_session_gc_schedule().iter().count() == 0 {
+        ctx.db.restore_session_gc_schedule().insert(RestoreSessionGcSchedule {
+            id: 0, // auto_inc will handle this
+            scheduled_at: ScheduleAt::Interval(std::time::Duration::from_secs(10 * 60).into()),
+        });
+    }
+
+    // Schedule the recurring world-boss event
+    if ctx.db.world_boss_schedule().iter().count() == 0 {
+        ctx.db.world_boss_schedule().insert(WorldBossSchedule {
+            id: 0, // auto_inc will handle this
+            scheduled_at: ScheduleAt::Interval(std::time::Duration::from_secs(WORLD_BOSS_INTERVAL_SECS).into()),
+        });
+    }
+
     // Log module initialization for debugging
     log::info!("Math Raiders module initialized successfully");
     
@@ -3118,6 +7082,428 @@ pub fn init(ctx: &ReducerContext) {
 
 // ==================== HELPER FUNCTIONS ====================
 
+/// How many recent same-grade performance_snapshot rows establish a player's CQPM
+/// baseline (see cqpm_baseline / sandbagging_hp_floor)
+const SANDBAG_BASELINE_SAMPLES: usize = 10;
+
+/// Recent CQPM below this fraction of the established baseline, without a grade
+/// change, is treated as intentional underperformance rather than a real skill dip
+const SANDBAG_RATIO_THRESHOLD: f32 = 0.5;
+
+/// How much of the gap to a higher candidate baseline closes per raid - the baseline
+/// only ratchets up slowly so one standout session can't be gamed into a floor
+const SANDBAG_BASELINE_RATCHET: f32 = 0.2;
+
+/// CQPM (Correct Questions Per Minute) implied by one performance_snapshot row
+fn snapshot_cqpm(snapshot: &PerformanceSnapshot) -> f32 {
+    if snapshot.session_seconds == 0 {
+        return 0.0;
+    }
+    snapshot.problems_correct as f32 / (snapshot.session_seconds as f32 / 60.0)
+}
+
+/// Nearest-rank percentile of an already-sorted slice (p in [0.0, 1.0])
+fn percentile_of_sorted(sorted: &[f32], p: f32) -> f32 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let idx = ((sorted.len() as f32 - 1.0) * p).round() as usize;
+    sorted[idx.min(sorted.len() - 1)]
+}
+
+/// Recompute a player's cqpm_baseline from their recent same-grade sessions, folding
+/// in `latest_cqpm` (the session that just ended). Only moves the baseline upward,
+/// and ratchets gradually rather than jumping straight to the new candidate - see
+/// SANDBAG_BASELINE_RATCHET.
+fn update_cqpm_baseline(ctx: &ReducerContext, player: &mut Player, latest_cqpm: f32) {
+    let mut cqpms: Vec<f32> = ctx.db.performance_snapshot()
+        .player_id()
+        .filter(&player.id)
+        .filter(|s| s.grade == player.grade)
+        .rev()
+        .take(SANDBAG_BASELINE_SAMPLES)
+        .map(|s| snapshot_cqpm(&s))
+        .collect();
+    cqpms.push(latest_cqpm);
+    cqpms.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let candidate_baseline = percentile_of_sorted(&cqpms, 0.75);
+
+    if player.cqpm_baseline <= 0.0 {
+        player.cqpm_baseline = candidate_baseline; // Cold start - establish immediately
+    } else if candidate_baseline > player.cqpm_baseline {
+        player.cqpm_baseline += (candidate_baseline - player.cqpm_baseline) * SANDBAG_BASELINE_RATCHET;
+    }
+}
+
+// -------------------- Adaptive Difficulty (block-difficulty-style retarget) --------------------
+
+/// Damping divisor (D) - each raid moves difficulty_level by at most
+/// level/D * K, mirroring block-difficulty retargeting
+const DIFFICULTY_DAMPING: i32 = 16;
+
+/// Floor is pinned at exactly DIFFICULTY_DAMPING (not 0) so level/D never
+/// truncates to zero at the bottom of the range - a naive floor of 0 would
+/// let a struggling player's difficulty get stuck there forever, since
+/// (0 / D) * k is 0 no matter how well they play afterward
+const DIFFICULTY_LEVEL_FLOOR: i32 = DIFFICULTY_DAMPING;
+
+/// Ceiling is 10x the floor - gives a wide, symmetric-enough range around the
+/// default midpoint for the proportional step to operate in
+const DIFFICULTY_LEVEL_CEILING: i32 = DIFFICULTY_LEVEL_FLOOR * 10;
+
+/// Player.difficulty_level starts here - midpoint between floor and ceiling,
+/// so a brand new player's first few raids can move it in either direction
+const DIFFICULTY_LEVEL_DEFAULT: i32 = (DIFFICULTY_LEVEL_FLOOR + DIFFICULTY_LEVEL_CEILING) / 2;
+
+/// Caps the signed step multiplier (K) fed into the level/D proportional term
+const DIFFICULTY_MAX_STEP: i32 = 4;
+
+/// Scales the session/target CQPM ratio into the signed step (S) - e.g. 20%
+/// over target rounds to roughly +2 before the +/-K clamp
+const DIFFICULTY_STEP_SCALE: f32 = 10.0;
+
+/// Target CQPM for a grade - same "correct answers per minute" standard
+/// get_fast_threshold_ms encodes as a per-problem time, just expressed as a rate
+fn target_cqpm(grade: u8) -> f32 {
+    60_000.0 / get_fast_threshold_ms(grade) as f32
+}
+
+/// Nudge a player's difficulty_level after a raid using a bounded proportional
+/// feedback step (modeled on block-difficulty retargeting): overshoot the
+/// target CQPM and the level climbs a fraction of itself; undershoot and it
+/// falls the same way. Clamped to DIFFICULTY_LEVEL_FLOOR/_CEILING so a player
+/// can never be pushed outside the valid in-grade range.
+fn update_difficulty_level(player: &mut Player, session_cqpm: f32) {
+    let target = target_cqpm(player.grade);
+    let k = ((session_cqpm / target - 1.0) * DIFFICULTY_STEP_SCALE).round() as i32;
+    let k = k.clamp(-DIFFICULTY_MAX_STEP, DIFFICULTY_MAX_STEP);
+    let step = (player.difficulty_level / DIFFICULTY_DAMPING) * k;
+    player.difficulty_level = (player.difficulty_level + step)
+        .clamp(DIFFICULTY_LEVEL_FLOOR, DIFFICULTY_LEVEL_CEILING);
+}
+
+/// Map a player's difficulty_level onto the same signed bucket-shift scale
+/// boss phases use (see BossPhase.difficulty_shift) - the midpoint shifts
+/// nothing, the floor/ceiling shift by roughly +/-2 buckets
+fn difficulty_level_shift(difficulty_level: i32) -> i8 {
+    let span = (DIFFICULTY_LEVEL_CEILING - DIFFICULTY_LEVEL_DEFAULT) as f32;
+    (((difficulty_level - DIFFICULTY_LEVEL_DEFAULT) as f32 / span) * 2.0)
+        .round()
+        .clamp(-2.0, 2.0) as i8
+}
+
+#[cfg(test)]
+mod difficulty_level_tests {
+    use super::*;
+
+    fn player_with(grade: u8, difficulty_level: i32) -> Player {
+        Player {
+            id: "test".to_string(),
+            name: "Test".to_string(),
+            grade,
+            rank: None,
+            total_problems: 0,
+            total_correct: 0,
+            avg_response_ms: 0,
+            best_response_ms: 0,
+            total_raids: 0,
+            quests: None,
+            last_played: Timestamp::from_micros_since_unix_epoch(0),
+            last_raid: Timestamp::from_micros_since_unix_epoch(0),
+            last_weekly_reset: Timestamp::from_micros_since_unix_epoch(0),
+            total_ap: 0,
+            in_raid_id: None,
+            timeback_id: None,
+            email: None,
+            abandon_score: 0,
+            matchmaking_cooldown_until: None,
+            matchmaking_cooldown_strikes: 0,
+            cqpm_baseline: 0.0,
+            skill_rating: SKILL_RATING_ANCHOR,
+            last_bonus_date: None,
+            daily_bonus_streak: 0,
+            bonus_score: 0,
+            target_retention: FSRS_DEFAULT_TARGET_RETENTION,
+            elo_rating: ELO_RATING_DEFAULT,
+            best_elo_rating: ELO_RATING_DEFAULT,
+            mastered_fact_count: 0,
+            facts_seen_count: 0,
+            stability_sum: 0.0,
+            division: None,
+            timezone: tz::DEFAULT_ZONE.to_string(),
+            difficulty_level,
+        }
+    }
+
+    #[test]
+    fn test_fast_play_increases_difficulty() {
+        let mut player = player_with(3, DIFFICULTY_LEVEL_DEFAULT);
+        let target = target_cqpm(3);
+        update_difficulty_level(&mut player, target * 2.0); // way over target
+        assert!(player.difficulty_level > DIFFICULTY_LEVEL_DEFAULT);
+    }
+
+    #[test]
+    fn test_slow_play_decreases_difficulty() {
+        let mut player = player_with(3, DIFFICULTY_LEVEL_DEFAULT);
+        let target = target_cqpm(3);
+        update_difficulty_level(&mut player, target * 0.5); // way under target
+        assert!(player.difficulty_level < DIFFICULTY_LEVEL_DEFAULT);
+    }
+
+    #[test]
+    fn test_on_target_play_holds_steady() {
+        let mut player = player_with(3, DIFFICULTY_LEVEL_DEFAULT);
+        let target = target_cqpm(3);
+        update_difficulty_level(&mut player, target);
+        assert_eq!(player.difficulty_level, DIFFICULTY_LEVEL_DEFAULT);
+    }
+
+    #[test]
+    fn test_clamps_at_ceiling() {
+        let mut player = player_with(3, DIFFICULTY_LEVEL_CEILING);
+        let target = target_cqpm(3);
+        for _ in 0..50 {
+            update_difficulty_level(&mut player, target * 3.0);
+        }
+        assert_eq!(player.difficulty_level, DIFFICULTY_LEVEL_CEILING);
+    }
+
+    #[test]
+    fn test_clamps_at_floor() {
+        let mut player = player_with(3, DIFFICULTY_LEVEL_FLOOR);
+        let target = target_cqpm(3);
+        for _ in 0..50 {
+            update_difficulty_level(&mut player, target * 0.1);
+        }
+        assert_eq!(player.difficulty_level, DIFFICULTY_LEVEL_FLOOR);
+    }
+
+    #[test]
+    fn test_shift_is_zero_at_default() {
+        assert_eq!(difficulty_level_shift(DIFFICULTY_LEVEL_DEFAULT), 0);
+    }
+
+    #[test]
+    fn test_shift_at_ceiling_is_positive() {
+        assert!(difficulty_level_shift(DIFFICULTY_LEVEL_CEILING) > 0);
+    }
+
+    #[test]
+    fn test_shift_at_floor_is_negative() {
+        assert!(difficulty_level_shift(DIFFICULTY_LEVEL_FLOOR) < 0);
+    }
+}
+
+/// If recent performance implies CQPM well below the player's established baseline
+/// (and they haven't just changed grade, which resets the baseline to 0), return an
+/// HP floor derived from the baseline instead of trusting the depressed recent number.
+/// Prevents sandbagging a stretch of slow/wrong answers to farm a trivially weak boss.
+fn sandbagging_hp_floor(player: &Player, recent_snapshots: &[PerformanceSnapshot]) -> Option<u32> {
+    if player.cqpm_baseline <= 0.0 || recent_snapshots.is_empty() {
+        return None;
+    }
+    let recent_cqpm = recent_snapshots.iter()
+        .map(snapshot_cqpm)
+        .sum::<f32>() / recent_snapshots.len() as f32;
+    if recent_cqpm >= player.cqpm_baseline * SANDBAG_RATIO_THRESHOLD {
+        return None;
+    }
+    let damage_per_answer = estimate_average_damage(player.avg_response_ms, player.grade) as f32;
+    let floor_dpm = player.cqpm_baseline * damage_per_answer;
+    Some((floor_dpm * ADAPTIVE_HP_MULTIPLIER) as u32)
+}
+
+// ==================== SKILL RATING ====================
+// Least-squares skill rating: after each raid, each co-raider's relative
+// performance this raid implies a rating gap versus their squad-mates. We solve
+// the overdetermined pairwise system for those gaps and nudge each player's
+// stored rating toward the solved value. See update_skill_ratings.
+
+/// Neutral starting point and regression anchor - a player who's never raided
+/// sits exactly here, and one roster member is pinned here each raid to resolve
+/// the system (pairwise differences alone can't separate an overall rating level)
+const SKILL_RATING_ANCHOR: f32 = 1000.0;
+
+/// Ridge regularization added to the normal-equations diagonal. Without it AᵀA is
+/// singular whenever a raid's pairwise comparisons don't fully connect every
+/// player (e.g. a raid of exactly 2, or players who tie on performance)
+const SKILL_RATING_RIDGE_LAMBDA: f32 = 0.5;
+
+/// How much of the solved per-raid delta actually moves a player's stored rating -
+/// keeps ratings stable across many raids instead of chasing one noisy sample,
+/// same idea as cqpm_baseline's ratchet
+const SKILL_RATING_LEARNING_RATE: f32 = 0.15;
+
+/// A single player's relative-performance score for one raid: accuracy scaled by
+/// answer speed. Only the instrument used to compare co-raiders against each
+/// other, not a value meaningful on its own.
+fn raid_performance_score(rp: &RaidPlayer) -> f32 {
+    if rp.problems_answered == 0 {
+        return 0.0;
+    }
+    let accuracy = rp.correct_answers as f32 / rp.problems_answered as f32;
+    let speed = 1000.0 / rp.fastest_answer_ms.max(1) as f32;
+    accuracy * speed
+}
+
+/// Solve (AᵀA + λI)·x = Aᵀb via Gaussian elimination with partial pivoting. `a` is
+/// row-major with `cols` columns per row. Small dense systems only - a raid roster
+/// tops out at MAX_PLAYERS_PER_RAID, so this never has to scale further.
+fn solve_ridge_normal_equations(a: &[Vec<f32>], b: &[f32], cols: usize, lambda: f32) -> Vec<f32> {
+    let mut ata = vec![vec![0.0f32; cols]; cols];
+    let mut atb = vec![0.0f32; cols];
+
+    for (row, &b_val) in a.iter().zip(b.iter()) {
+        for i in 0..cols {
+            atb[i] += row[i] * b_val;
+            for j in 0..cols {
+                ata[i][j] += row[i] * row[j];
+            }
+        }
+    }
+    for i in 0..cols {
+        ata[i][i] += lambda;
+    }
+
+    // Augmented-matrix Gaussian elimination with partial pivoting
+    let mut aug: Vec<Vec<f32>> = (0..cols)
+        .map(|i| {
+            let mut r = ata[i].clone();
+            r.push(atb[i]);
+            r
+        })
+        .collect();
+
+    for col in 0..cols {
+        let pivot_row = (col..cols)
+            .max_by(|&r1, &r2| aug[r1][col].abs().partial_cmp(&aug[r2][col].abs()).unwrap())
+            .unwrap();
+        aug.swap(col, pivot_row);
+
+        let pivot = aug[col][col];
+        if pivot.abs() < 1e-8 {
+            continue; // Degenerate column (ridge term should prevent this in practice)
+        }
+        for k in col..=cols {
+            aug[col][k] /= pivot;
+        }
+        for r in 0..cols {
+            if r == col {
+                continue;
+            }
+            let factor = aug[r][col];
+            for k in col..=cols {
+                aug[r][k] -= factor * aug[col][k];
+            }
+        }
+    }
+
+    (0..cols).map(|i| aug[i][cols]).collect()
+}
+
+/// Recompute skill ratings for a raid's roster from this raid's relative
+/// performance, called once from end_raid. Pins the first roster member (by
+/// player_id, for a deterministic anchor) at delta 0 and solves for the rest
+/// relative to them - the standard fix for the otherwise-singular normal
+/// equations when pairwise comparisons alone can't anchor an absolute level.
+fn update_skill_ratings(ctx: &ReducerContext, raid_id: u64) {
+    let mut roster: Vec<_> = ctx.db.raid_player()
+        .raid_id().filter(&raid_id)
+        .filter(|rp| rp.problems_answered > 0)
+        .collect();
+    if roster.len() < 2 {
+        return; // Nothing to compare against
+    }
+    roster.sort_by(|a, b| a.player_id.cmp(&b.player_id));
+
+    let n = roster.len();
+    let scores: Vec<f32> = roster.iter().map(raid_performance_score).collect();
+
+    // Anchor is roster[0] - solve only for the remaining n-1 deltas (columns),
+    // with the anchor's column implicitly fixed at 0 in every row
+    let cols = n - 1;
+    let mut a: Vec<Vec<f32>> = Vec::new();
+    let mut b: Vec<f32> = Vec::new();
+    for i in 0..n {
+        for j in (i + 1)..n {
+            let mut row = vec![0.0f32; cols];
+            if i > 0 {
+                row[i - 1] = 1.0;
+            }
+            if j > 0 {
+                row[j - 1] -= 1.0;
+            }
+            a.push(row);
+            b.push(scores[i] - scores[j]);
+        }
+    }
+
+    let deltas = solve_ridge_normal_equations(&a, &b, cols, SKILL_RATING_RIDGE_LAMBDA);
+
+    for (idx, rp) in roster.iter().enumerate() {
+        let delta = if idx == 0 { 0.0 } else { deltas[idx - 1] };
+        if let Some(mut player) = ctx.db.player().id().find(&rp.player_id) {
+            player.skill_rating += delta * SKILL_RATING_LEARNING_RATE;
+            ctx.db.player().id().update(player);
+        }
+    }
+}
+
+// ==================== BOSS ELO RATING ====================
+// A separate, absolute Elo rating of the player against an opponent derived
+// from boss difficulty. Unlike skill_rating (relative to squad-mates, so it
+// needs 2+ players and only moves on a shared raid), this treats every raid
+// - solo or multiplayer - as a match against "the boss" and updates
+// unconditionally each time end_raid processes a participant.
+
+/// Starting rating for a player who's never raided
+const ELO_RATING_DEFAULT: f32 = 1200.0;
+
+/// Floor under elo_rating - keeps a long losing streak from driving the
+/// expected-score formula into a degenerate near-zero rating
+const ELO_RATING_FLOOR: f32 = 400.0;
+
+/// K-factor while a player is still new (see player.total_raids) - converges
+/// fast to a reasonable starting rating
+const ELO_K_PROVISIONAL: f32 = 40.0;
+
+/// K-factor once a player has enough raids to trust their rating - smaller
+/// swings so an established rating doesn't whipsaw on one noisy raid
+const ELO_K_ESTABLISHED: f32 = 20.0;
+
+/// Raid count at which a player graduates from ELO_K_PROVISIONAL to
+/// ELO_K_ESTABLISHED
+const ELO_PROVISIONAL_RAID_COUNT: u32 = 10;
+
+/// Weight applied to raid_boss_max_hp when deriving the boss's opponent
+/// rating - a small nudge so two bosses at the same level but very different
+/// HP pools (e.g. an event boss) aren't treated as identical opponents
+const ELO_BOSS_HP_WEIGHT: f32 = 0.05;
+
+/// The boss's opponent rating for Elo purposes, derived from its level (and a
+/// small term from its HP pool) rather than stored anywhere - a level 0 boss
+/// sits at 1000, each level above that adds 100
+fn boss_elo_rating(raid_boss_level: u8, raid_boss_max_hp: u32) -> f32 {
+    1000.0 + (raid_boss_level as f32) * 100.0 + (raid_boss_max_hp as f32) * ELO_BOSS_HP_WEIGHT
+}
+
+/// Updates a player's elo_rating (and best_elo_rating, which only ratchets
+/// upward) for one completed raid. accuracy_pct is 0-100. Called for every
+/// participant of every raid - solo included - unlike update_skill_ratings.
+fn update_elo_rating(player: &mut Player, raid_boss_level: u8, raid_boss_max_hp: u32, victory: bool, accuracy_pct: f32) {
+    let r_boss = boss_elo_rating(raid_boss_level, raid_boss_max_hp);
+    let expected = 1.0 / (1.0 + 10f32.powf((r_boss - player.elo_rating) / 400.0));
+    let outcome = if victory { 1.0 } else { 0.0 };
+    let actual = outcome * (0.5 + 0.5 * (accuracy_pct / 100.0));
+    let k = if player.total_raids < ELO_PROVISIONAL_RAID_COUNT { ELO_K_PROVISIONAL } else { ELO_K_ESTABLISHED };
+
+    player.elo_rating = (player.elo_rating + k * (actual - expected)).max(ELO_RATING_FLOOR);
+    if player.elo_rating > player.best_elo_rating {
+        player.best_elo_rating = player.elo_rating;
+    }
+}
+
 /// Default HP for each grade when no performance data is available
 /// Tuned for ~30 second raids at expected CQPM:
 /// K~10, G1~13, G2~17, G3~18, G4~20, G5~25 CQPM
@@ -3136,12 +7522,25 @@ fn get_grade_default_hp(grade: u8) -> u32 {
 /// Calculate a player's HP contribution based on recent performance snapshots
 /// Uses average DPM (Damage Per Minute) from recent raids to calculate HP for 2-minute target
 /// Tiered fallback: Track-specific → Grade-wide → Lifetime stats → Grade defaults
+/// Wraps calculate_player_contribution_base with the skill_rating multiplier - see
+/// update_skill_ratings. Kept as a thin wrapper so every tiered-fallback return path
+/// in the base calculation gets the adjustment without duplicating it at each return.
 fn calculate_player_contribution_with_context(
-    player: &Player, 
+    player: &Player,
+    ctx: Option<&ReducerContext>,
+    track: Option<&str>,
+) -> u32 {
+    let base_hp = calculate_player_contribution_base(player, ctx, track);
+    let rating_multiplier = (player.skill_rating / SKILL_RATING_ANCHOR).clamp(0.7, 1.4);
+    ((base_hp as f32) * rating_multiplier) as u32
+}
+
+fn calculate_player_contribution_base(
+    player: &Player,
     ctx: Option<&ReducerContext>,
     track: Option<&str>,  // NEW: Track for track-specific HP calculation
 ) -> u32 {
-    
+
     // First raid only: Use grade-based default (cold start)
     // After raid 1, system adapts immediately based on actual performance
     // 
@@ -3195,7 +7594,15 @@ fn calculate_player_contribution_with_context(
                     // 5+ samples: trust the average fully
                     calculated_hp
                 };
-                
+
+                // Sandbagging guard: trust the baseline over a suspiciously depressed
+                // recent average (see sandbagging_hp_floor)
+                if let Some(floor) = sandbagging_hp_floor(player, &track_snapshots) {
+                    log::warn!("[MASTERY] sandbag_guard_tripped player:{} tier:track calculated_hp:{} floor_hp:{}",
+                        &player.id[..8.min(player.id.len())], final_hp, floor);
+                    return final_hp.max(floor);
+                }
+
                 return final_hp;
             }
         }
@@ -3236,11 +7643,19 @@ fn calculate_player_contribution_with_context(
                 // 5+ samples: trust the average fully
                 calculated_hp
             };
-            
+
+            // Sandbagging guard: trust the baseline over a suspiciously depressed
+            // recent average (see sandbagging_hp_floor)
+            if let Some(floor) = sandbagging_hp_floor(player, &snapshots) {
+                log::warn!("[MASTERY] sandbag_guard_tripped player:{} tier:grade calculated_hp:{} floor_hp:{}",
+                    &player.id[..8.min(player.id.len())], final_hp, floor);
+                return final_hp.max(floor);
+            }
+
             return final_hp;
         }
     }
-    
+
     // Tier 3: Use lifetime stats with DPM approach (rare - only for players without recent history)
     let accuracy = player.total_correct as f32 / player.total_problems.max(1) as f32;
     let problems_per_minute = 60000.0 / (player.avg_response_ms + 1000) as f32;  // +1s pause between problems
@@ -3277,7 +7692,18 @@ fn calculate_player_contribution_with_context(
 fn generate_adaptive_problem(sequence: u32, ctx: &ReducerContext, raid_player: &mut RaidPlayer) -> Option<(u8, u8, Operation)> {
     // Get player's grade for filtering
     let player = ctx.db.player().id().find(&raid_player.player_id)?;
-    
+
+    // Boss phase's difficulty_shift biases fact selection toward easier/harder
+    // buckets (0 if the boss has no phase data, or the raid can't be found),
+    // combined with the player's own adaptive difficulty_level (see
+    // update_difficulty_level) so strong/struggling players land on harder/
+    // easier facts within their grade independent of which phase the boss is in
+    let difficulty_shift = ctx.db.raid().id().find(&raid_player.raid_id)
+        .and_then(|raid| current_boss_phase(ctx, &raid))
+        .map(|phase| phase.difficulty_shift)
+        .unwrap_or(0)
+        .saturating_add(difficulty_level_shift(player.difficulty_level));
+
     // Filter facts by grade AND track (if specified)
     let grade_facts = if let Some(ref track) = raid_player.track {
         if track == "ALL" {
@@ -3313,9 +7739,18 @@ fn generate_adaptive_problem(sequence: u32, ctx: &ReducerContext, raid_player: &
     };
     
     let last_problem = recent_problems.last().cloned();
-    
+
     // Use timestamp + sequence for randomness
     let seed = ctx.timestamp.to_micros_since_unix_epoch() as u64 + sequence as u64;
+
+    // SM-2: facts due for review (most overdue first), restricted to this tier
+    let now_micros = ctx.timestamp.to_micros_since_unix_epoch();
+    let mut due_facts: Vec<PracticeSchedule> = ctx.db.practice_schedule()
+        .player_id()
+        .filter(&raid_player.player_id)
+        .filter(|p| p.due_at.to_micros_since_unix_epoch() <= now_micros && allowed_facts.contains(&p.fact_key))
+        .collect();
+    due_facts.sort_by_key(|p| p.due_at.to_micros_since_unix_epoch());
     
     // Build weighted list of facts
     let mut weighted_facts: Vec<(String, f32)> = Vec::new();
@@ -3327,7 +7762,7 @@ fn generate_adaptive_problem(sequence: u32, ctx: &ReducerContext, raid_player: &
             continue;
         }
         
-        let mut weight = calculate_fact_weight(&fact, ctx.timestamp);
+        let mut weight = calculate_fact_weight(&fact, ctx.timestamp, difficulty_shift, player.target_retention);
         
         // Phase 4: Prevent consecutive repeats
         if let Some(ref last) = last_problem {
@@ -3395,8 +7830,25 @@ fn generate_adaptive_problem(sequence: u32, ctx: &ReducerContext, raid_player: &
     let mut sorted_facts = weighted_facts.clone();
     sorted_facts.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
     
-    // Select a fact using weighted random
-    let selected_fact = weighted_random_selection(weighted_facts, seed)?;
+    // Prefer the most-overdue SM-2 fact (respecting repeat-prevention rules);
+    // fall back to the weighted ZPD/mastery pool when nothing is due
+    let selected_fact = due_facts.iter()
+        .find(|due| {
+            if let Some(ref last) = last_problem {
+                if &due.fact_key == last {
+                    return false;
+                }
+                if let (Some((last_l, last_r, _)), Some((fact_l, fact_r, _))) =
+                    (parse_fact_key(last), parse_fact_key(&due.fact_key)) {
+                    if last_l == fact_l || last_l == fact_r || last_r == fact_l || last_r == fact_r {
+                        return false;
+                    }
+                }
+            }
+            true
+        })
+        .map(|due| due.fact_key.clone())
+        .or_else(|| weighted_random_selection(weighted_facts, seed))?;
     
     // Parse the fact key (e.g., "7×8" -> (7, 8, Multiply))
     let (left, right, operation) = match parse_fact_key(&selected_fact) {
@@ -3548,18 +8000,54 @@ fn estimate_average_damage(response_ms: u32, grade: u8) -> u32 {
     }
 }
 
+/// Resolve RaidPlayer.active_buffs into a damage multiplier - Combo stacks add
+/// damage, Stagger halves it. Expired buffs (expires_at in the past) are
+/// ignored here; submit_answer sweeps them out of the row separately.
+fn apply_buffs_to_damage(base: u32, raid_player: &RaidPlayer, ctx: &ReducerContext) -> u32 {
+    let now = ctx.timestamp.to_micros_since_unix_epoch();
+    let mut damage = base as f32;
+
+    for buff in &raid_player.active_buffs {
+        if buff.expires_at.to_micros_since_unix_epoch() <= now {
+            continue;
+        }
+        match buff.kind {
+            BuffKind::Combo => damage *= 1.0 + buff.magnitude / 100.0,
+            BuffKind::Stagger => damage *= 1.0 - buff.magnitude / 100.0,
+            BuffKind::Focus => {} // Crit chance only - resolved in calculate_damage's roll
+        }
+    }
+
+    damage.round() as u32
+}
+
+/// Focus buff (see BuffKind::Focus) doubles crit chance while active
+fn focus_crit_bonus_pct(raid_player: Option<&RaidPlayer>, ctx: &ReducerContext) -> u32 {
+    let now = ctx.timestamp.to_micros_since_unix_epoch();
+    raid_player
+        .map(|rp| rp.active_buffs.iter()
+            .filter(|b| b.kind == BuffKind::Focus && b.expires_at.to_micros_since_unix_epoch() > now)
+            .map(|b| b.magnitude as u32)
+            .sum())
+        .unwrap_or(0)
+}
+
 /// Calculate damage for a correct answer based on response time.
 /// Grade affects the speed threshold (what counts as "fast"), not the damage output.
 /// This allows clean CQPM → DPS mapping: same CQPM = same DPS regardless of grade.
-fn calculate_damage(response_ms: u32, grade: u8, ctx: &ReducerContext) -> u32 {
+/// `raid_player` (if known) feeds apply_buffs_to_damage's combo/stagger layer and
+/// the Focus buff's crit-chance boost - see BuffKind.
+fn calculate_damage(response_ms: u32, grade: u8, ctx: &ReducerContext, raid_player: Option<&RaidPlayer>) -> u32 {
     let fast_threshold = get_fast_threshold_ms(grade);
-    
+
     // Smooth damage curve (scaled 1.5x to differentiate DPS from CQPM)
     // No grade multiplier - K at 3.0s deals same damage as G5 at 1.5s
-    if response_ms <= fast_threshold {
-        // Fast answers can crit (WoW-style: 15% chance for 2x damage)
+    let base = if response_ms <= fast_threshold {
+        // Fast answers can crit (WoW-style: 15% base chance for 2x damage),
+        // boosted by an active Focus buff
+        let crit_chance = 15 + focus_crit_bonus_pct(raid_player, ctx);
         let crit_roll = ctx.rng().gen_range(0..100);
-        if crit_roll < 15 {
+        if crit_roll < crit_chance {
             150  // CRIT (100 × 1.5)
         } else {
             75   // Normal (50 × 1.5)
@@ -3574,6 +8062,11 @@ fn calculate_damage(response_ms: u32, grade: u8, ctx: &ReducerContext) -> u32 {
         23   // +5s (15 × 1.5, rounded)
     } else {
         15   // Beyond (10 × 1.5)
+    };
+
+    match raid_player {
+        Some(rp) => apply_buffs_to_damage(base, rp, ctx),
+        None => base,
     }
 }
 
@@ -3596,54 +8089,191 @@ fn normalize_fact(left: u8, right: u8, operation: &Operation) -> String {
     }
 }
 
-/// Calculate mastery level from recent attempts and current grade
-/// Uses last 3 attempts with grade-appropriate fast threshold
-fn calculate_mastery_level(fact: &FactMastery, grade: u8) -> u8 {
+// ==================== FSRS MEMORY MODEL ====================
+// Free Spaced Repetition Scheduler per-fact memory state (stability S in days,
+// difficulty D in [1,10]), replacing the old last-3-attempts mastery heuristic
+// and the hand-tuned hour-bucket forgetting-curve weight boost.
+
+/// Power-forgetting-curve decay exponent: R(t) = (1 + FSRS_FACTOR*t/S)^FSRS_DECAY
+const FSRS_DECAY: f32 = -0.5;
+
+/// Chosen so R(t) = 0.9 exactly when t == S (19/81)
+const FSRS_FACTOR: f32 = 19.0 / 81.0;
+
+/// Fallback target retention - what simulate_review_load returns when a player
+/// has no facts with FSRS state yet, or when no candidate clears
+/// RETENTION_SIM_MIN_RETAINED_FRACTION
+const FSRS_DEFAULT_TARGET_RETENTION: f32 = 0.9;
+
+/// FSRS-4.5 reference weight vector. w[0..4] are initial stability per review
+/// grade (1=again..4=easy); the rest parameterize the difficulty/stability
+/// update formulas below
+const FSRS_WEIGHTS: [f32; 17] = [
+    0.4072, 1.1829, 3.1262, 15.4722, 7.2102, 0.5316, 1.0651, 0.0234, 1.616,
+    0.1544, 1.0824, 1.9813, 0.0953, 0.2975, 2.2042, 0.2407, 2.9466,
+];
+
+/// Stability thresholds (days) bucketing FSRS stability into mastery levels -
+/// L5 needs stability measured in weeks, not a couple of lucky fast answers
+const FSRS_MASTERY_STABILITY_DAYS: [f32; 5] = [1.0, 3.0, 7.0, 14.0, 21.0];
+
+/// Map one attempt to an FSRS review grade: 1=again, 2=hard, 3=good, 4=easy
+fn fsrs_review_grade(is_correct: bool, response_ms: u32, fast_threshold_ms: u32) -> u8 {
+    if !is_correct {
+        1
+    } else if response_ms <= fast_threshold_ms {
+        4
+    } else if response_ms <= fast_threshold_ms * 2 {
+        3
+    } else {
+        2
+    }
+}
+
+/// Retrievability at `t_days` since last review, given stability `s`
+fn fsrs_retrievability(t_days: f32, s: f32) -> f32 {
+    if s <= 0.0 {
+        return 0.0;
+    }
+    (1.0 + FSRS_FACTOR * t_days / s).powf(FSRS_DECAY)
+}
+
+/// S0(G) = w[G-1] - first-ever-review stability seed
+fn fsrs_initial_stability(review_grade: u8) -> f32 {
+    FSRS_WEIGHTS[(review_grade.clamp(1, 4) - 1) as usize].max(0.1)
+}
+
+/// D0(G) = clamp(w[4] - exp(w[5]*(G-1)) + 1, 1, 10) - first-ever-review difficulty seed
+fn fsrs_initial_difficulty(review_grade: u8) -> f32 {
+    let g = review_grade.clamp(1, 4) as f32;
+    (FSRS_WEIGHTS[4] - (FSRS_WEIGHTS[5] * (g - 1.0)).exp() + 1.0).clamp(1.0, 10.0)
+}
+
+/// D' = clamp(w[7]*D0(4) + (1-w[7])*(D - w[6]*(G-3)), 1, 10)
+fn fsrs_next_difficulty(d: f32, review_grade: u8) -> f32 {
+    let g = review_grade.clamp(1, 4) as f32;
+    let reverting_target = fsrs_initial_difficulty(4);
+    let delta = FSRS_WEIGHTS[6] * (g - 3.0);
+    (FSRS_WEIGHTS[7] * reverting_target + (1.0 - FSRS_WEIGHTS[7]) * (d - delta)).clamp(1.0, 10.0)
+}
+
+/// Successful-review stability growth (grades 2-4): bigger jumps for easier
+/// facts recalled against longer odds (low R), tempered by current difficulty
+fn fsrs_next_stability_success(s: f32, d: f32, r: f32, review_grade: u8) -> f32 {
+    let hard_penalty = if review_grade == 2 { FSRS_WEIGHTS[15] } else { 1.0 };
+    let easy_bonus = if review_grade == 4 { FSRS_WEIGHTS[16] } else { 1.0 };
+    let growth = FSRS_WEIGHTS[8].exp()
+        * (11.0 - d)
+        * s.powf(-FSRS_WEIGHTS[9])
+        * ((FSRS_WEIGHTS[10] * (1.0 - r)).exp() - 1.0)
+        * hard_penalty
+        * easy_bonus;
+    (s * (1.0 + growth)).max(0.1)
+}
+
+/// Lapse (grade 1) stability reset - smaller than a fresh seed since some
+/// memory trace survives a single miss
+fn fsrs_next_stability_lapse(s: f32, d: f32, r: f32) -> f32 {
+    (FSRS_WEIGHTS[11]
+        * d.powf(-FSRS_WEIGHTS[12])
+        * ((s + 1.0).powf(FSRS_WEIGHTS[13]) - 1.0)
+        * (FSRS_WEIGHTS[14] * (1.0 - r)).exp())
+    .max(0.1)
+}
+
+/// Bucket FSRS stability into a 0-5 mastery level for ranks/divisions
+fn calculate_mastery_level(fact: &FactMastery, _grade: u8) -> u8 {
     if fact.total_attempts == 0 {
         return 0;
     }
-    
-    // Get last 3 attempts (or all if fewer than 3)
-    let total = fact.recent_attempts.len();
-    let start_idx = if total > 3 { total - 3 } else { 0 };
-    let last_3 = &fact.recent_attempts[start_idx..];
-    
-    let fast_threshold = get_fast_threshold_ms(grade);
-    
-    // Count correct and fast attempts in last 3
-    let correct_count = last_3.iter().filter(|a| a.correct).count();
-    
-    // Grade-relative speed tiers for progressive mastery
-    // K: 20 CQPM (3s), G1-3: 30 CQPM (2s), G4: 35 CQPM (1.7s), G5+: 40 CQPM (1.5s)
-    
-    // Check speed tiers in last 3 attempts (grade-relative thresholds)
-    // L5 requires 2+ fast to reduce false positives from lucky single attempts
-    let hit_1x_count = last_3.iter().filter(|a| a.correct && a.time_ms <= fast_threshold).count();
-    let hit_2x = last_3.iter().any(|a| a.correct && a.time_ms <= fast_threshold * 2);
-    let hit_3x = last_3.iter().any(|a| a.correct && a.time_ms <= fast_threshold * 3);
-    
-    if hit_1x_count >= 2 {
-        5  // Mastered: 2+ fast in last 3 (consistent speed, not lucky)
-    } else if hit_2x {
-        4  // Close: Within 2x threshold (building speed)
-    } else if hit_3x {
-        3  // Developing: Within 3x threshold (some speed progress)
-    } else if correct_count >= 2 {
-        2  // Cyan: Learning (2+ correct but slow)
-    } else if correct_count >= 1 {
-        1  // Cyan: Practicing (at least 1 correct)
-    } else {
-        0  // Gray: All wrong (needs help)
+
+    let s = fact.stability;
+    FSRS_MASTERY_STABILITY_DAYS.iter()
+        .rposition(|&threshold| s >= threshold)
+        .map(|idx| (idx + 1) as u8)
+        .unwrap_or(0)
+}
+
+/// Simulated-day horizon for projecting review load per candidate retention
+const RETENTION_SIM_DAYS: u32 = 30;
+
+/// Recompute a player's target_retention every N problems answered - the sweep
+/// scans every one of their facts x every candidate, so it isn't run per-answer
+const RETENTION_SIM_INTERVAL_PROBLEMS: u32 = 25;
+
+/// Candidate target-retention values to sweep (70%-97%, see simulate_review_load)
+const RETENTION_SIM_CANDIDATES: [f32; 6] = [0.70, 0.78, 0.85, 0.90, 0.94, 0.97];
+
+/// Minimum fraction of facts that must still be at-or-above the candidate
+/// retention at the end of the horizon for that candidate to be viable
+const RETENTION_SIM_MIN_RETAINED_FRACTION: f32 = 0.85;
+
+/// Sweep candidate target retentions for this player's current fact set, roll
+/// each one forward over RETENTION_SIM_DAYS simulated days (a fact is "reviewed"
+/// - success branch, assumed average/good - whenever its projected
+/// retrievability sinks to the candidate), and return the candidate with the
+/// lowest daily review count that still keeps retained-fraction above the floor.
+/// Facts with no FSRS state yet (never reviewed) don't have a stability to
+/// project and are skipped.
+fn simulate_review_load(ctx: &ReducerContext, player_id: &str) -> f32 {
+    let facts: Vec<FactMastery> = ctx.db.fact_mastery()
+        .player_id()
+        .filter(&player_id.to_string())
+        .filter(|f| f.stability > 0.0)
+        .collect();
+
+    if facts.is_empty() {
+        return FSRS_DEFAULT_TARGET_RETENTION;
+    }
+
+    let mut best_retention = FSRS_DEFAULT_TARGET_RETENTION;
+    let mut best_reviews_per_day = f32::MAX;
+
+    for &candidate in RETENTION_SIM_CANDIDATES.iter() {
+        let mut total_reviews: u32 = 0;
+        let mut retained: u32 = 0;
+
+        for fact in &facts {
+            let mut stability = fact.stability;
+            let mut difficulty = fact.difficulty;
+            let mut days_since_review = 0.0f32;
+
+            for _ in 0..RETENTION_SIM_DAYS {
+                days_since_review += 1.0;
+                let r = fsrs_retrievability(days_since_review, stability);
+                if r <= candidate {
+                    total_reviews += 1;
+                    difficulty = fsrs_next_difficulty(difficulty, 3);
+                    stability = fsrs_next_stability_success(stability, difficulty, r, 3);
+                    days_since_review = 0.0;
+                }
+            }
+
+            if fsrs_retrievability(days_since_review, stability) >= candidate {
+                retained += 1;
+            }
+        }
+
+        let reviews_per_day = total_reviews as f32 / RETENTION_SIM_DAYS as f32;
+        let retained_fraction = retained as f32 / facts.len() as f32;
+
+        if retained_fraction >= RETENTION_SIM_MIN_RETAINED_FRACTION && reviews_per_day < best_reviews_per_day {
+            best_reviews_per_day = reviews_per_day;
+            best_retention = candidate;
+        }
     }
+
+    best_retention
 }
 
 // ==================== ADAPTIVE SELECTION HELPERS ====================
 
 /// Calculate weight for a fact based on mastery data and time since last seen
-fn calculate_fact_weight(fact: &FactMastery, current_time: Timestamp) -> f32 {
-    // Use server-maintained mastery level (already calculated and cached)
-    let mastery_level = fact.mastery_level;
-    
+fn calculate_fact_weight(fact: &FactMastery, current_time: Timestamp, difficulty_shift: i8, target_retention: f32) -> f32 {
+    // Use server-maintained mastery level (already calculated and cached), shifted
+    // by the boss's current phase (positive = harder bucket, negative = easier)
+    let mastery_level = (fact.mastery_level as i16 + difficulty_shift as i16).clamp(0, 5) as u8;
+
     // Bucket weights (1:7:2 ratio - ZPD dominant for optimal learning)
     // 70% ZPD (where learning happens), 20% mastered (confidence), 10% hard (stretch)
     let mut weight: f32 = match mastery_level {
@@ -3658,23 +8288,18 @@ fn calculate_fact_weight(fact: &FactMastery, current_time: Timestamp) -> f32 {
         return 10.0;
     }
     
-    // Time-based spacing boost
-    // Boost facts not seen recently to implement spaced repetition
+    // FSRS overdue boost: scale weight by how far retrievability has sunk below
+    // target retention, rather than fixed hour buckets - a fact due in 20 minutes
+    // and one due in 20 days both get picked up, but the worse one wins ties
     let current_micros = current_time.to_micros_since_unix_epoch();
     let last_seen_micros = fact.last_seen.to_micros_since_unix_epoch();
-    let ms_since = current_micros.saturating_sub(last_seen_micros) / 1000;
-    let hours_since = ms_since as f32 / (1000.0 * 60.0 * 60.0);
-    
-    // Apply forgetting curve boost
-    if hours_since >= 72.0 {
-        weight *= 2.0;  // 3+ days: double weight (needs review)
-    } else if hours_since >= 24.0 {
-        weight *= 1.5;  // 1+ day: 50% boost (due for practice)  
-    } else if hours_since >= 8.0 {
-        weight *= 1.2;  // 8+ hours: slight boost
-    }
-    // Recent (< 8 hours): no time boost
-    
+    let days_since = current_micros.saturating_sub(last_seen_micros) as f32
+        / (1000.0 * 1000.0 * 60.0 * 60.0 * 24.0);
+
+    let retrievability = fsrs_retrievability(days_since, fact.stability);
+    let overdue = (target_retention - retrievability).max(0.0);
+    weight *= 1.0 + overdue * 4.0;
+
     weight.max(0.1f32) // Keep small positive weight even for mastered facts
 }
 
@@ -3722,31 +8347,144 @@ fn calculate_division(rank: &Option<String>, mastered_count: u32, total_count: u
 }
 
 /// Get player's mastery statistics for their current grade
-/// Returns (mastered_count, total_facts) where both are filtered to the player's grade
-fn get_player_mastery_stats(ctx: &ReducerContext, player: &Player) -> (u32, u32) {
-    // Get all facts for the player's grade
+/// Returns (mastered_count, total_facts) where both are filtered to the player's grade.
+/// mastered_count is an O(1) read of Player.mastered_fact_count - a cached aggregate
+/// kept current by update_fact_mastery's per-answer delta (and fully rebuilt by
+/// recalculate_for_grade_change on a grade change) rather than a fact_mastery rescan.
+fn get_player_mastery_stats(_ctx: &ReducerContext, player: &Player) -> (u32, u32) {
+    let total_facts = get_facts_for_grade(player.grade).len() as u32;
+    (player.mastered_fact_count, total_facts)
+}
+
+/// Rebuild the cached mastery aggregate (mastered_fact_count/facts_seen_count/
+/// stability_sum) from a full fact_mastery scan, filtered to the given grade's
+/// valid fact pool. Only needed where facts are bulk-mutated outside of
+/// update_fact_mastery's per-answer delta path - grade changes, and disaster
+/// recovery restores (see restore.rs) where a player or fact_mastery export
+/// can't be trusted to carry an up-to-date aggregate.
+pub(crate) fn rebuild_mastery_aggregate(ctx: &ReducerContext, player: &mut Player) {
     let grade_facts = get_facts_for_grade(player.grade);
-    let total_facts = grade_facts.len() as u32;
-    
-    // Build set of valid fact keys for this grade
     let valid_fact_keys: std::collections::HashSet<String> = grade_facts
         .iter()
         .map(|f| f.to_key())
         .collect();
-    
-    // Count only mastered facts that exist in this grade
-    // Use server-maintained mastery_level (already calculated and fresh)
-    let mastered_count = ctx.db.fact_mastery()
+
+    let mut mastered_count = 0u32;
+    let mut facts_seen = 0u32;
+    let mut stability_sum = 0.0f32;
+
+    for fact in ctx.db.fact_mastery().player_id().filter(&player.id) {
+        if !valid_fact_keys.contains(&fact.fact_key) {
+            continue;
+        }
+        facts_seen += 1;
+        stability_sum += fact.stability;
+        if fact.mastery_level >= 5 {
+            mastered_count += 1;
+        }
+    }
+
+    player.mastered_fact_count = mastered_count;
+    player.facts_seen_count = facts_seen;
+    player.stability_sum = stability_sum;
+}
+
+// ==================== MASTERY FORECAST ====================
+
+/// Trailing window (in days) of performance_snapshot history used to estimate
+/// mastery rate - recent enough to reflect current pace, long enough to
+/// smooth out single-raid noise
+const MASTERY_FORECAST_WINDOW_DAYS: u64 = 14;
+
+/// Minimum snapshots within the window before we'll trust a rate estimate
+const MASTERY_FORECAST_MIN_SNAPSHOTS: usize = 2;
+
+/// Minimum span (in days) between the oldest and newest in-window snapshot -
+/// without this, two snapshots an hour apart would extrapolate a wild rate
+const MASTERY_FORECAST_MIN_SPAN_DAYS: f32 = 1.0;
+
+/// Recomputes and stores this player's mastery-completion forecast for one
+/// track (one row per player+track - see MasteryForecast). Burn-down estimate:
+/// regress facts_mastered_at_snapshot against timestamp over the trailing
+/// window and project forward from facts_remaining.
+#[reducer]
+pub fn refresh_mastery_forecast(ctx: &ReducerContext, track: Option<String>) -> Result<(), String> {
+    let player = get_player(ctx)?;
+
+    let (mastered_count, total_facts) = get_player_mastery_stats(ctx, &player);
+    let facts_remaining = total_facts.saturating_sub(mastered_count);
+
+    // Upsert: no multi-column unique constraint for (player_id, track), so
+    // scan-and-replace like the rest of this codebase's upsert sites
+    for existing in ctx.db.mastery_forecast().player_id().filter(&player.id) {
+        if existing.track == track {
+            ctx.db.mastery_forecast().id().delete(&existing.id);
+        }
+    }
+
+    if facts_remaining == 0 {
+        ctx.db.mastery_forecast().insert(MasteryForecast {
+            id: 0,
+            player_id: player.id.clone(),
+            track,
+            facts_remaining: 0,
+            rate_per_day: None,
+            days_remaining: None,
+            done_on: None,
+            computed_at: ctx.timestamp,
+        });
+        return Ok(());
+    }
+
+    let window_secs = MASTERY_FORECAST_WINDOW_DAYS * 86400;
+    let mut snapshots: Vec<_> = ctx.db.performance_snapshot()
         .player_id()
         .filter(&player.id)
-        .filter(|fm| {
-            fm.mastery_level >= 5 && valid_fact_keys.contains(&fm.fact_key)
-        })
-        .count() as u32;
-    
-    (mastered_count, total_facts)
-}
+        .filter(|s| s.grade == player.grade
+            && s.track == track
+            && ctx.timestamp.duration_since(s.timestamp)
+                .map(|d| d.as_secs() <= window_secs)
+                .unwrap_or(false))
+        .collect();
+    snapshots.sort_by_key(|s| s.timestamp);
 
+    let (rate_per_day, days_remaining, done_on) = if snapshots.len() < MASTERY_FORECAST_MIN_SNAPSHOTS {
+        (None, None, None)
+    } else {
+        let first = snapshots.first().unwrap();
+        let last = snapshots.last().unwrap();
+        let span_days = last.timestamp.duration_since(first.timestamp)
+            .map(|d| d.as_secs_f32() / 86400.0)
+            .unwrap_or(0.0);
+
+        if span_days < MASTERY_FORECAST_MIN_SPAN_DAYS {
+            (None, None, None)
+        } else {
+            let mastered_delta = last.facts_mastered_at_snapshot as f32 - first.facts_mastered_at_snapshot as f32;
+            let rate = mastered_delta / span_days;
+            if rate <= 0.0 {
+                (Some(rate), None, None) // stalled or regressing - not on pace
+            } else {
+                let days = facts_remaining as f32 / rate;
+                let done = ctx.timestamp + std::time::Duration::from_secs((days * 86400.0) as u64);
+                (Some(rate), Some(days), Some(done))
+            }
+        }
+    };
+
+    ctx.db.mastery_forecast().insert(MasteryForecast {
+        id: 0,
+        player_id: player.id.clone(),
+        track,
+        facts_remaining,
+        rate_per_day,
+        days_remaining,
+        done_on,
+        computed_at: ctx.timestamp,
+    });
+
+    Ok(())
+}
 
 /// Calculate player rank based on mastery percentage
 /// Returns rank name for bronze through legendary (never None)
@@ -3805,6 +8543,78 @@ fn weighted_random_selection(facts: Vec<(String, f32)>, seed: u64) -> Option<Str
 }
 
 
+/// Derive an SM-2 quality score (0-5) from correctness and response time
+/// relative to the grade's fast threshold.
+fn calculate_sm2_quality(is_correct: bool, response_ms: u32, grade: u8) -> u8 {
+    if !is_correct {
+        return 2; // Incorrect: always < 3, triggers a reset below
+    }
+    let fast_threshold = get_fast_threshold_ms(grade);
+    if response_ms <= fast_threshold {
+        5
+    } else if response_ms <= fast_threshold + 2000 {
+        4
+    } else {
+        3
+    }
+}
+
+/// Update (or create) this player's SM-2 practice schedule row for a fact.
+/// Standard SM-2 recurrence: quality < 3 resets the repetition streak and
+/// interval back to 1 day; otherwise the interval grows 1 -> 6 -> prev*ease,
+/// and the ease factor is nudged up or down by how far quality was from 5,
+/// floored at 1.3 so a fact is never scheduled further and further out.
+fn update_practice_schedule(ctx: &ReducerContext, player_id: &str, fact_key: &str, grade: u8, is_correct: bool, response_ms: u32) {
+    let quality = calculate_sm2_quality(is_correct, response_ms, grade);
+
+    let existing = ctx.db.practice_schedule()
+        .player_id()
+        .filter(&player_id.to_string())
+        .find(|p| p.fact_key == fact_key);
+
+    let (mut ease_factor, mut repetitions, prev_interval) = existing.as_ref()
+        .map(|p| (p.ease_factor, p.repetitions, p.interval_days))
+        .unwrap_or((2.5, 0, 0));
+
+    let interval_days = if quality < 3 {
+        repetitions = 0;
+        1
+    } else {
+        repetitions += 1;
+        match repetitions {
+            1 => 1,
+            2 => 6,
+            _ => (prev_interval as f32 * ease_factor).round().max(1.0) as u32,
+        }
+    };
+
+    let q = quality as f32;
+    ease_factor += 0.1 - (5.0 - q) * (0.08 + (5.0 - q) * 0.02);
+    ease_factor = ease_factor.max(1.3);
+
+    let due_at = Timestamp::from_micros_since_unix_epoch(
+        ctx.timestamp.to_micros_since_unix_epoch() + (interval_days as i64) * 24 * 60 * 60 * 1_000_000
+    );
+
+    if let Some(mut schedule) = existing {
+        schedule.ease_factor = ease_factor;
+        schedule.repetitions = repetitions;
+        schedule.interval_days = interval_days;
+        schedule.due_at = due_at;
+        ctx.db.practice_schedule().id().update(schedule);
+    } else {
+        ctx.db.practice_schedule().insert(PracticeSchedule {
+            id: 0, // auto_inc
+            player_id: player_id.to_string(),
+            fact_key: fact_key.to_string(),
+            ease_factor,
+            repetitions,
+            interval_days,
+            due_at,
+        });
+    }
+}
+
 /// Update or create a FactMastery record for a fact
 fn update_fact_mastery(
     ctx: &ReducerContext,
@@ -3817,71 +8627,108 @@ fn update_fact_mastery(
 ) {
     // Normalize the fact key
     let fact_key = normalize_fact(left, right, operation);
-    
+
     // Find existing record
     let existing = ctx.db.fact_mastery()
         .player_id()
         .filter(&player_id)
         .filter(|f| f.fact_key == fact_key)
         .next();
-    
+
+    // Looked up once and carried through both branches - also where the cached
+    // mastery aggregate (mastered_fact_count/facts_seen_count/stability_sum) gets
+    // its delta applied and persisted, instead of get_player_mastery_stats
+    // rescanning fact_mastery on every read.
+    let mut player_opt = ctx.db.player().id().find(&player_id);
+    let player_grade = player_opt.as_ref().map(|p| p.grade).unwrap_or(3);
+
     if let Some(mut fact) = existing {
+        let old_mastery_level = fact.mastery_level;
+        let old_stability = fact.stability;
+
         // Update aggregates
         fact.total_attempts = fact.total_attempts.saturating_add(1);
-        
+
         if is_correct {
             fact.total_correct = fact.total_correct.saturating_add(1);
-            
+
             // Update average response time (rolling average for correct answers)
             if fact.total_correct == 1 {
                 fact.avg_response_ms = response_ms;
             } else {
                 let count = fact.total_correct.saturating_sub(1) as u64;
                 let avg = fact.avg_response_ms as u64;
-                
+
                 if let Some(total_ms) = avg.checked_mul(count) {
                     if let Some(new_total) = total_ms.checked_add(response_ms as u64) {
                         fact.avg_response_ms = (new_total / fact.total_correct as u64).min(u32::MAX as u64) as u32;
                     }
                 }
             }
-            
+
             // Update fastest time
             if response_ms < fact.fastest_ms {
                 fact.fastest_ms = response_ms;
             }
         }
-        
+
+        // FSRS state update - elapsed time and retrievability against the
+        // stability/difficulty this fact had BEFORE this attempt
+        let elapsed_days = ctx.timestamp.to_micros_since_unix_epoch()
+            .saturating_sub(fact.last_seen.to_micros_since_unix_epoch()) as f32
+            / (1000.0 * 1000.0 * 60.0 * 60.0 * 24.0);
+        let review_grade = fsrs_review_grade(is_correct, response_ms, get_fast_threshold_ms(player_grade));
+
+        if fact.stability <= 0.0 {
+            // First-ever review for this fact (stability/difficulty not yet seeded)
+            fact.stability = fsrs_initial_stability(review_grade);
+            fact.difficulty = fsrs_initial_difficulty(review_grade);
+        } else {
+            let r = fsrs_retrievability(elapsed_days, fact.stability);
+            fact.difficulty = fsrs_next_difficulty(fact.difficulty, review_grade);
+            fact.stability = if review_grade == 1 {
+                fsrs_next_stability_lapse(fact.stability, fact.difficulty, r)
+            } else {
+                fsrs_next_stability_success(fact.stability, fact.difficulty, r, review_grade)
+            };
+        }
+
         // Update metadata
         fact.last_seen = ctx.timestamp;
-        
+
         // Add to recent attempts (maintain 100 max rolling window)
         fact.recent_attempts.push(AttemptRecord {
             time_ms: response_ms,
             correct: is_correct,
             timestamp: ctx.timestamp,
         });
-        
+
         // Keep only last 100 attempts (enough for trend analysis)
         if fact.recent_attempts.len() > 100 {
             fact.recent_attempts.remove(0);
         }
-        
-        // Get player grade for mastery calculation
-        let player_grade = ctx.db.player().id().find(&player_id)
-            .map(|p| p.grade)
-            .unwrap_or(3);
-        
+
         // Recalculate mastery level (server-authoritative)
         fact.mastery_level = calculate_mastery_level(&fact, player_grade);
-        
+
+        // Fold this attempt's before/after mastery state into the player's
+        // cached aggregate - only a ±1 delta, never a rescan
+        if let Some(ref mut player) = player_opt {
+            player.stability_sum += fact.stability - old_stability;
+            if fact.mastery_level >= 5 && old_mastery_level < 5 {
+                player.mastered_fact_count = player.mastered_fact_count.saturating_add(1);
+            } else if fact.mastery_level < 5 && old_mastery_level >= 5 {
+                player.mastered_fact_count = player.mastered_fact_count.saturating_sub(1);
+            }
+        }
+
         ctx.db.fact_mastery().id().update(fact);
+
+        update_practice_schedule(ctx, &player_id, &fact_key, player_grade, is_correct, response_ms);
     } else {
         // Create new record
-        let player_grade = ctx.db.player().id().find(&player_id)
-            .map(|p| p.grade)
-            .unwrap_or(3);
-        
+        let review_grade = fsrs_review_grade(is_correct, response_ms, get_fast_threshold_ms(player_grade));
+
         let mut new_fact = FactMastery {
             id: 0, // auto_inc
             player_id: player_id.clone(),
@@ -3897,12 +8744,30 @@ fn update_fact_mastery(
                 timestamp: ctx.timestamp,
             }],
             mastery_level: 0,  // Will be calculated below
+            stability: fsrs_initial_stability(review_grade),
+            difficulty: fsrs_initial_difficulty(review_grade),
         };
-        
+
         // Calculate initial mastery level (server-authoritative)
         new_fact.mastery_level = calculate_mastery_level(&new_fact, player_grade);
-        
+
+        // A brand new fact is always a +1 to facts_seen, and its stability starts
+        // from zero, so the delta is just the seeded value itself
+        if let Some(ref mut player) = player_opt {
+            player.facts_seen_count = player.facts_seen_count.saturating_add(1);
+            player.stability_sum += new_fact.stability;
+            if new_fact.mastery_level >= 5 {
+                player.mastered_fact_count = player.mastered_fact_count.saturating_add(1);
+            }
+        }
+
         ctx.db.fact_mastery().insert(new_fact);
+
+        update_practice_schedule(ctx, &player_id, &fact_key, player_grade, is_correct, response_ms);
+    }
+
+    if let Some(player) = player_opt {
+        ctx.db.player().id().update(player);
     }
 }
 
@@ -3943,18 +8808,38 @@ fn update_player_stats(ctx: &ReducerContext, player_id: &String, is_correct: boo
         
         player.last_played = ctx.timestamp;
         player.last_raid = ctx.timestamp;  // Track raid completion for streak
+
+        // Periodically re-tune this player's target retention against their
+        // current fact set (see simulate_review_load) - not worth the full sweep
+        // on every single answer
+        if player.total_problems % RETENTION_SIM_INTERVAL_PROBLEMS == 0 {
+            player.target_retention = simulate_review_load(ctx, player_id);
+        }
+
         ctx.db.player().id().update(player);
     }
 }
 
 fn end_raid(ctx: &ReducerContext, raid_id: u64, victory: bool) {
+    end_raid_with_killing_blow(ctx, raid_id, victory, None);
+}
+
+/// Same as end_raid, but lets the caller record who landed the final blow (victory
+/// only) so settle_raid_rewards can award the killing-blow bonus.
+fn end_raid_with_killing_blow(ctx: &ReducerContext, raid_id: u64, victory: bool, killing_blow_player_id: Option<&str>) {
     // Cancel any pending timeout for this raid
     for schedule in ctx.db.raid_timeout_schedule().iter() {
         if schedule.raid_id == raid_id {
             ctx.db.raid_timeout_schedule().id().delete(&schedule.id);
         }
     }
-    
+    cleanup_boss_skill_data(ctx, raid_id);
+    cancel_boss_tick_schedule(ctx, raid_id);
+    cancel_pause_timeout(ctx, raid_id);
+    cancel_idle_check(ctx, raid_id);
+    cancel_all_problem_timeouts(ctx, raid_id);
+    cleanup_raid_spectators(ctx, raid_id);
+
     if let Some(mut raid) = ctx.db.raid().id().find(&raid_id) {
         // Debug removed - [RAID] ended log is canonical
         
@@ -3964,7 +8849,11 @@ fn end_raid(ctx: &ReducerContext, raid_id: u64, victory: bool) {
                       raid_id, raid.state);
             return;
         }
-        
+
+        // Final replay entry for this raid's stream - see RaidEvent
+        let event_kind = if victory { RaidEventKind::Victory } else { RaidEventKind::Defeat };
+        record_raid_event(ctx, &mut raid, event_kind, killing_blow_player_id.map(|s| s.to_string()), 0);
+
         // Capture data we need before modifying raid
         let raid_started_at = raid.started_at;
         let raid_room_code = raid.room_code.clone();
@@ -4006,12 +8895,35 @@ fn end_raid(ctx: &ReducerContext, raid_id: u64, victory: bool) {
         let squad_names: Vec<&str> = raid_players.iter().map(|rp| rp.player_name.as_str()).collect();
         let is_multiplayer = raid_room_code.is_some();
         let track = raid_players.first().map(|rp| rp.track.as_deref().unwrap_or("unknown")).unwrap_or("unknown");
+
+        // Abandon tracking: only multiplayer raids count, and we need the FULL roster
+        // (including NoShow/pure-abandon players the damage/problems filter above excludes)
+        if is_multiplayer {
+            let full_roster: Vec<_> = ctx.db.raid_player()
+                .raid_id()
+                .filter(&raid_id)
+                .collect();
+            for (player_id, outcome) in classify_raid_outcomes(ctx, &full_roster) {
+                record_raid_outcome(ctx, &player_id, raid_id, outcome);
+            }
+        }
         
         // Wide event: one canonical log for raid outcome
-        log::info!("[RAID] ended raid_id={} outcome={} duration_sec={} players={} squad={:?} is_multiplayer={} total_damage={} boss_max_hp={} boss_level={} track={} total_problems={} avg_accuracy={}",
+        let total_bonus_points: u32 = raid_players.iter().map(|rp| rp.bonus_points).sum();
+        // Representative multiplier for this log line only - actual rewards below
+        // are computed per-player against their own grade/track (see active_reward_window)
+        let representative_grade = raid_players.first()
+            .and_then(|rp| ctx.db.player().id().find(&rp.player_id))
+            .map(|p| p.grade)
+            .unwrap_or(0);
+        let reward_window = active_reward_window(ctx, representative_grade, raid_players.first().and_then(|rp| rp.track.as_deref()));
+        let reward_multiplier_label = reward_window.as_ref().map(|w| w.label.as_str()).unwrap_or("none");
+        let ap_multiplier_log = reward_window.as_ref().map(|w| w.ap_multiplier).unwrap_or(1.0);
+        log::info!("[RAID] ended raid_id={} outcome={} duration_sec={} players={} squad={:?} is_multiplayer={} total_damage={} boss_max_hp={} boss_level={} track={} total_problems={} avg_accuracy={} total_bonus_points={} reward_window={} ap_multiplier={:.2}",
             raid_id, if victory { "victory" } else { "defeat" }, duration_seconds,
             raid_players.len(), squad_names, is_multiplayer,
-            total_damage, raid_boss_max_hp, raid_boss_level, track, total_problems, avg_accuracy);
+            total_damage, raid_boss_max_hp, raid_boss_level, track, total_problems, avg_accuracy, total_bonus_points,
+            reward_multiplier_label, ap_multiplier_log);
         
         for raid_player in &raid_players {
             if let Some(mut player) = ctx.db.player().id().find(&raid_player.player_id) {
@@ -4056,13 +8968,39 @@ fn end_raid(ctx: &ReducerContext, raid_id: u64, victory: bool) {
                 
                 // Update player rank
                 player.rank = new_rank.clone();
-                
+
                 // Calculate division within rank
                 let division = calculate_division(&new_rank, mastered_count, total_facts);
+                player.division = Some(division.clone());
                 
                 // Calculate commutative units for TimeBack
                 let timeback_units = calculate_mastered_units_for_timeback(ctx, &raid_player.player_id, player.grade);
-                
+
+                // Sandbagging guard: flag this session if its CQPM is suspiciously far
+                // below the established baseline, then fold it into the baseline (which
+                // only ratchets upward - see update_cqpm_baseline)
+                let sandbag_suspected = player.cqpm_baseline > 0.0
+                    && session_cqpm < player.cqpm_baseline * SANDBAG_RATIO_THRESHOLD;
+                if sandbag_suspected {
+                    log::warn!("[MASTERY] sandbag_suspected player:{} grade:{} session_cqpm:{:.1} baseline:{:.1}",
+                        &raid_player.player_id[..8.min(raid_player.player_id.len())], player.grade, session_cqpm, player.cqpm_baseline);
+                }
+                update_cqpm_baseline(ctx, &mut player, session_cqpm);
+
+                // Bounded feedback step toward/away from this grade's target CQPM -
+                // see update_difficulty_level and difficulty_level_shift
+                update_difficulty_level(&mut player, session_cqpm);
+
+                // Hidden Elo rating vs. the boss - runs for every participant
+                // (solo included), unlike update_skill_ratings below which
+                // needs 2+ players to compare against each other
+                let session_accuracy_pct = if raid_player.problems_answered > 0 {
+                    (raid_player.correct_answers as f32 * 100.0) / raid_player.problems_answered as f32
+                } else {
+                    0.0
+                };
+                update_elo_rating(&mut player, raid_boss_level, raid_boss_max_hp, victory, session_accuracy_pct);
+
                 // Record performance snapshot with rank, division, track, and raid type
                 ctx.db.performance_snapshot().insert(PerformanceSnapshot {
                     id: 0, // auto_inc
@@ -4081,6 +9019,8 @@ fn end_raid(ctx: &ReducerContext, raid_id: u64, victory: bool) {
                     timeback_units_at_snapshot: timeback_units,
                     boss_level: raid_boss_level,
                     victory: Some(victory),
+                    sandbag_suspected,
+                    elo_rating_at_snapshot: player.elo_rating,
                 });
                 
                 // Track Master achievement: 3× solo wins on goal boss
@@ -4098,9 +9038,18 @@ fn end_raid(ctx: &ReducerContext, raid_id: u64, victory: bool) {
                     
                     if goal_boss_wins == 3 {
                         log::info!("[TRACK_MASTER] player=\"{}\" player_id={} grade={} track={} boss={} email={}",
-                            player.name, &raid_player.player_id[..8.min(raid_player.player_id.len())], 
-                            player.grade, raid_player.track.as_deref().unwrap_or("unknown"), 
+                            player.name, &raid_player.player_id[..8.min(raid_player.player_id.len())],
+                            player.grade, raid_player.track.as_deref().unwrap_or("unknown"),
                             raid_boss_level, player.email.as_deref().unwrap_or(""));
+                        ctx.db.announcement().insert(Announcement {
+                            id: 0,
+                            kind: AnnouncementKind::TrackMaster,
+                            player_name: player.name.clone(),
+                            message: format!("{} became a Track Master on boss {}!", player.name, raid_boss_level),
+                            grade: player.grade,
+                            track: raid_player.track.clone(),
+                            timestamp: ctx.timestamp,
+                        });
                     }
                 }
                 
@@ -4110,13 +9059,22 @@ fn end_raid(ctx: &ReducerContext, raid_id: u64, victory: bool) {
                 
                 // Log first raid ever (new player milestone!)
                 if was_first_raid {
-                    log::info!("[FIRST_RAID] player=\"{}\" grade={} track={}", 
+                    log::info!("[FIRST_RAID] player=\"{}\" grade={} track={}",
                         player.name, player.grade, raid_player.track.as_deref().unwrap_or("unknown"));
+                    ctx.db.announcement().insert(Announcement {
+                        id: 0,
+                        kind: AnnouncementKind::FirstRaid,
+                        player_name: player.name.clone(),
+                        message: format!("{} completed their first raid!", player.name),
+                        grade: player.grade,
+                        track: raid_player.track.clone(),
+                        timestamp: ctx.timestamp,
+                    });
                 }
                 
                 // Track streak (raid-based - requires daily raiding)
                 // Reset daily_raid_count if new day (handles edge case of staying connected past midnight)
-                if is_new_day(player.last_raid, ctx.timestamp) {
+                if is_new_day(&player.timezone, player.last_raid, ctx.timestamp) {
                     reset_quests_by_prefix(&mut player, "daily_");
                 }
                 let quests = parse_quests(&player.quests);
@@ -4132,11 +9090,23 @@ fn end_raid(ctx: &ReducerContext, raid_id: u64, victory: bool) {
                     let new_streak = increment_quest(&mut player, "daily_streak");
                     
                     // Log streak milestones (7, 14, 30 days)
-                    match new_streak {
-                        7 => log::info!("[STREAK] player=\"{}\" days=7 milestone=weekly", player.name),
-                        14 => log::info!("[STREAK] player=\"{}\" days=14 milestone=biweekly", player.name),
-                        30 => log::info!("[STREAK] player=\"{}\" days=30 milestone=monthly", player.name),
-                        _ => {}
+                    let streak_milestone = match new_streak {
+                        7 => Some(("weekly", 7u32)),
+                        14 => Some(("biweekly", 14u32)),
+                        30 => Some(("monthly", 30u32)),
+                        _ => None,
+                    };
+                    if let Some((label, days)) = streak_milestone {
+                        log::info!("[STREAK] player=\"{}\" days={} milestone={}", player.name, days, label);
+                        ctx.db.announcement().insert(Announcement {
+                            id: 0,
+                            kind: AnnouncementKind::StreakMilestone,
+                            player_name: player.name.clone(),
+                            message: format!("{} hit a {}-day raid streak!", player.name, days),
+                            grade: player.grade,
+                            track: raid_player.track.clone(),
+                            timestamp: ctx.timestamp,
+                        });
                     }
                 }
                 
@@ -4164,52 +9134,31 @@ fn end_raid(ctx: &ReducerContext, raid_id: u64, victory: bool) {
                 
                 // Squad bonus for multiplayer (2+ players)
                 let multiplayer_bonus = if raid_players.len() > 1 { 25 } else { 0 };
-                
+
                 // AP is predictable based on performance - no random bonus
-                // The excitement comes from the loot chest variability instead
-                let ap_earned = base_ap + multiplayer_bonus;
-                
+                // The excitement comes from the loot chest variability instead.
+                // Scaled by any active reward window (see active_reward_window) -
+                // single highest-priority window applies, windows never stack.
+                let reward_window = active_reward_window(ctx, player.grade, raid_player.track.as_deref());
+                let ap_multiplier = reward_window.as_ref().map(|w| w.ap_multiplier).unwrap_or(1.0);
+                let ap_earned = ((base_ap + multiplayer_bonus) as f32 * ap_multiplier) as u32;
+
                 player.total_ap = player.total_ap.saturating_add(ap_earned);
-                
+
+                // Fold this raid's net bonus-target score into the player's running
+                // total (see RaidPlayer.bonus_points/wrong_count) - read back by
+                // refresh_leaderboard as a tiebreaker
+                let net_bonus_score = raid_player.bonus_points as i32
+                    - (raid_player.wrong_count * BONUS_TARGET_WRONG_PENALTY) as i32;
+                player.bonus_score = player.bonus_score.saturating_add(net_bonus_score);
+
                 let player_id_hex = player.id.clone();
                 // total_ap tracking removed (was used for verbose logging)
-                
-                // Calculate and store chest bonus for this player - Robinhood style!
-                // Weighted rarity system matching client PALETTE
-                let chest_bonus = {
-                    // Define rarities with weights (must match client)
-                    let rarities = [
-                        (25,  65),  // Common: 25 AP, weight 65
-                        (50,  20),  // Uncommon: 50 AP, weight 20
-                        (75,  10),  // Rare: 75 AP, weight 10
-                        (150, 4),   // Epic: 150 AP, weight 4
-                        (300, 1),   // Legendary: 300 AP, weight 1
-                    ];
-                    
-                    // Calculate total weight
-                    let total_weight: u32 = rarities.iter().map(|(_, w)| w).sum();
-                    
-                    // Roll the dice!
-                    let mut roll = ctx.rng().gen_range(0..total_weight);
-                    let mut chosen_ap = 25; // Default to common
-                    
-                    // Find which rarity we hit
-                    for (ap, weight) in rarities.iter() {
-                        if roll < *weight {
-                            chosen_ap = *ap;
-                            break;
-                        }
-                        roll -= weight;
-                    }
-                    
-                    chosen_ap
-                };
-                
-                // Update raid_player with chest bonus
-                let mut updated_raid_player = raid_player.clone();
-                updated_raid_player.pending_chest_bonus = Some(chest_bonus);
-                update_raid_player(ctx, updated_raid_player);
-                
+
+                // Loot chest AP is no longer a random roll - settle_raid_rewards
+                // (called below, after this per-player loop) stakes each player's
+                // contribution-proportional share into raid_reward_ledger instead.
+
                 // Calculate mastery delta for TimeBack
                 let mastered_after = calculate_mastered_units_for_timeback(ctx, &player_id_hex, player.grade);
                 
@@ -4246,15 +9195,21 @@ fn end_raid(ctx: &ReducerContext, raid_id: u64, victory: bool) {
                     // Calculate XP: time × engagement (if accuracy met)
                     // Engagement scales based on how much of their personal floor they hit
                     let active_duration_minutes = active_duration_seconds as f32 / 60.0;
-                    let timeback_xp = if meets_accuracy_threshold && engagement > 0.0 {
+                    let base_timeback_xp = if meets_accuracy_threshold && engagement > 0.0 {
                         active_duration_minutes.min(2.5) * engagement
                     } else {
                         0.0
                     };
-                    
+
+                    // Scaled by any active reward window - same single
+                    // highest-priority window as ap_earned above, windows never stack
+                    let xp_window = active_reward_window(ctx, player.grade, raid_player.track.as_deref());
+                    let xp_multiplier = xp_window.as_ref().map(|w| w.xp_multiplier).unwrap_or(1.0);
+                    let timeback_xp = base_timeback_xp * xp_multiplier;
+
                     // Calculate floor for logging (matches calculate_engagement logic)
                     let floor = f32::max(2.0, player_best_cqpm * 0.25);
-                    
+
                     // Always send event to TimeBack (enables accurate accuracy/time tracking)
                     // XP = 0 when criteria not met, but attempt is still recorded
                     create_timeback_event(
@@ -4273,14 +9228,14 @@ fn end_raid(ctx: &ReducerContext, raid_id: u64, victory: bool) {
                     
                     // Wide event: one line tells the whole story for support tickets
                     if timeback_xp > 0.0 {
-                        log::info!("[XP] player=\"{}\" player_id={} outcome=earned xp={:.2} duration_min={:.1} accuracy={} cqpm={:.1} engagement={:.2} floor={:.1} best_cqpm={:.1} track={} boss={} victory={} grade={} raid_id={}",
-                            player.name, &player_id_hex[..8.min(player_id_hex.len())], timeback_xp, active_duration_minutes, accuracy, session_cqpm, engagement, floor, player_best_cqpm, raid_player.track.as_deref().unwrap_or("unknown"), raid_boss_level, victory, player.grade, raid_id);
+                        log::info!("[XP] player=\"{}\" player_id={} outcome=earned xp={:.2} duration_min={:.1} accuracy={} cqpm={:.1} engagement={:.2} floor={:.1} best_cqpm={:.1} track={} boss={} victory={} grade={} raid_id={} xp_multiplier={:.2}",
+                            player.name, &player_id_hex[..8.min(player_id_hex.len())], timeback_xp, active_duration_minutes, accuracy, session_cqpm, engagement, floor, player_best_cqpm, raid_player.track.as_deref().unwrap_or("unknown"), raid_boss_level, victory, player.grade, raid_id, xp_multiplier);
                     } else {
                         let reason = if accuracy < 80 { "low_accuracy" } else { "low_engagement" };
-                        
+
                         // Event sent with xp=0, TimeBack sees attempt for accurate tracking
-                        log::info!("[XP] player=\"{}\" player_id={} outcome=reported xp=0 reason={} accuracy={} cqpm={:.1} engagement={:.2} floor={:.1} best_cqpm={:.1} track={} boss={} victory={} grade={} raid_id={}",
-                            player.name, &player_id_hex[..8.min(player_id_hex.len())], reason, accuracy, session_cqpm, engagement, floor, player_best_cqpm, raid_player.track.as_deref().unwrap_or("unknown"), raid_boss_level, victory, player.grade, raid_id);
+                        log::info!("[XP] player=\"{}\" player_id={} outcome=reported xp=0 reason={} accuracy={} cqpm={:.1} engagement={:.2} floor={:.1} best_cqpm={:.1} track={} boss={} victory={} grade={} raid_id={} xp_multiplier={:.2}",
+                            player.name, &player_id_hex[..8.min(player_id_hex.len())], reason, accuracy, session_cqpm, engagement, floor, player_best_cqpm, raid_player.track.as_deref().unwrap_or("unknown"), raid_boss_level, victory, player.grade, raid_id, xp_multiplier);
                     }
                     }
                 }
@@ -4291,6 +9246,14 @@ fn end_raid(ctx: &ReducerContext, raid_id: u64, victory: bool) {
             }
         }
         
+        // Contribution-scored post-battle summary (the per-raid scoreboard clients
+        // render on the results screen), which also stakes each victor's claimable
+        // chest share into raid_reward_ledger for open_loot_chest
+        settle_raid_rewards(ctx, raid_id, victory, killing_blow_player_id);
+
+        // Feed this raid's relative performance into each co-raider's skill rating
+        update_skill_ratings(ctx, raid_id);
+
         // Refresh leaderboard once after all players updated (prevents stale rank display)
         // Collect unique grades (handles potential multi-grade raids, though unlikely)
         let grades_in_raid: std::collections::HashSet<u8> = raid_players.iter()
@@ -4401,6 +9364,48 @@ fn calculate_mastered_units_for_timeback(ctx: &ReducerContext, player_id: &str,
 }
 
 /// Create a TimeBack event for XP tracking
+/// Bucket width for TimebackEventQueue.due_epoch / the dispatch sweep interval
+const TIMEBACK_RETRY_BUCKET_SECS: i64 = 60;
+
+/// Floor a timestamp to its TIMEBACK_RETRY_BUCKET_SECS bucket index
+fn minute_epoch(ts: Timestamp) -> u64 {
+    tz::floor_div(ts.to_micros_since_unix_epoch(), TIMEBACK_RETRY_BUCKET_SECS * 1_000_000) as u64
+}
+
+/// Recurring sweep schedule for dispatch_due_timeback_events - same
+/// recurring-interval pattern as cleanup_schedule/scrub_schedule
+#[table(name = timeback_dispatch_schedule, scheduled(dispatch_due_timeback_events))]
+pub struct TimebackDispatchSchedule {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub scheduled_at: ScheduleAt,
+}
+
+/// Claims every unclaimed TimeBack event whose due_epoch bucket has arrived
+/// (see TimebackEventQueue.due_epoch/claimed), so the worker's subscription
+/// only has to watch `claimed && !sent` rows instead of scanning and
+/// timestamp-filtering the whole historical queue every poll.
+#[reducer]
+pub fn dispatch_due_timeback_events(ctx: &ReducerContext, _schedule: TimebackDispatchSchedule) {
+    if ctx.sender != ctx.identity() {
+        log::warn!("Client {} attempted to call dispatch_due_timeback_events", ctx.sender);
+        return;
+    }
+    let current_epoch = minute_epoch(ctx.timestamp);
+    let due: Vec<_> = ctx.db.timeback_event_queue().claimed().filter(&false)
+        .filter(|e| e.due_epoch <= current_epoch)
+        .collect();
+    let claimed_count = due.len();
+    for mut event in due {
+        event.claimed = true;
+        ctx.db.timeback_event_queue().id().update(event);
+    }
+    if claimed_count > 0 {
+        log::info!("[TIMEBACK] dispatch claimed {} due event(s) at epoch {}", claimed_count, current_epoch);
+    }
+}
+
 fn create_timeback_event(
     ctx: &ReducerContext,
     player_id: &str,
@@ -4474,6 +9479,8 @@ fn create_timeback_event(
         next_retry_at: None, // Ready to send immediately
         last_error: None,
         sent_at: None,
+        due_epoch: minute_epoch(ctx.timestamp),
+        claimed: true,
     });
 }
 
@@ -4518,115 +9525,176 @@ pub fn test_create_timeback_event(
     log::info!("🧪 Test TimeBack event created for player {} (grade {})", player_id, level);
 }
 
+/// Whether a TimeBack send failure is worth retrying
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RetryDisposition {
+    /// Transient (5xx, timeout, connection reset) - follow the backoff curve
+    Transient,
+    /// Permanent (4xx-style rejection) - no amount of retrying will fix this
+    Permanent,
+}
+
+/// Classify a TimeBack error string to decide whether it's worth retrying.
+/// Looks for HTTP-status-style substrings the worker embeds in its error
+/// messages; anything unrecognized is treated as transient (safer to retry
+/// than to silently drop a real earned-XP event).
+fn classify_error(error: &str) -> RetryDisposition {
+    let lower = error.to_lowercase();
+    let is_4xx = ["400", "401", "403", "404", "409", "410", "422"]
+        .iter()
+        .any(|code| lower.contains(code));
+    if is_4xx {
+        RetryDisposition::Permanent
+    } else {
+        RetryDisposition::Transient
+    }
+}
+
+/// Move a TimeBack event into the dead letter table, preserving its payload,
+/// last error, and attempt count, and delete it from the live queue.
+fn dead_letter_event(ctx: &ReducerContext, event: &TimebackEventQueue) {
+    ctx.db.timeback_dead_letter().insert(TimebackDeadLetter {
+        id: 0, // auto_inc
+        original_event_id: event.id,
+        player_id: event.player_id.clone(),
+        payload: event.payload.clone(),
+        attempts: event.attempts,
+        last_error: event.last_error.clone(),
+        created_at: event.created_at,
+        dead_lettered_at: ctx.timestamp,
+    });
+    ctx.db.timeback_event_queue().id().delete(&event.id);
+    log::error!("[TIMEBACK] dead_letter event:{} player:{} attempts:{} error:{}",
+        event.id, event.player_id, event.attempts, event.last_error.as_deref().unwrap_or("none"));
+}
+
 /// Mark a TimeBack event as sent (called by worker)
 /// Protected by authorization check - only authorized workers can call this
 #[reducer]
 pub fn mark_event_sent(ctx: &ReducerContext, event_id: u64, error: Option<String>) {
-    // Authorization check: only authorized workers can mark events as sent
-    if ctx.db.authorized_worker().identity().find(&ctx.sender).is_none() {
-        log::warn!("Unauthorized mark_event_sent attempt by {}", ctx.sender);
+    // Capability check: full admins pass unconditionally; scoped workers (e.g. a
+    // TimeBack sender limited to "per=5sec") need a worker_capability grant here
+    if let Err(e) = check_capability(ctx, "mark_event_sent", 2) {
+        log::warn!("Unauthorized mark_event_sent attempt by {}: {}", ctx.sender, e);
         return;
     }
-    
+
     if let Some(mut event) = ctx.db.timeback_event_queue().id().find(&event_id) {
-        if error.is_none() {
+        let Some(error_message) = error else {
             // Success
             event.sent = true;
             event.sent_at = Some(ctx.timestamp);
             event.last_error = None;
+            ctx.db.timeback_event_queue().id().update(event);
             log::info!("✅ TimeBack event {} marked as sent", event_id);
-        } else {
-            // Failed - increment attempts and set retry time
-            event.attempts = event.attempts.saturating_add(1);
-            event.last_error = error;
-            
-            // Safety check: Don't retry forever (worker should handle this, but just in case)
-            if event.attempts >= 5 {
-                event.sent = true; // Remove from queue
-                log::error!("🛑 TimeBack event {} exceeded max retries (5), marking as done", event_id);
-            } else {
-                // Exponential backoff: 1min, 2min, 4min, 8min, 16min
-                let backoff_minutes = 1u64 << event.attempts.min(4);
-                let backoff_micros = backoff_minutes * 60 * 1_000_000;
-                let current_micros = ctx.timestamp.to_micros_since_unix_epoch();
-                let next_retry_micros = current_micros + backoff_micros as i64;
-                let next_retry = Timestamp::from_micros_since_unix_epoch(next_retry_micros);
-                event.next_retry_at = Some(next_retry);
-                
-                log::warn!("❌ TimeBack event {} failed (attempt {}): {:?}", 
-                    event_id, event.attempts, event.last_error);
-            }
+            return;
+        };
+
+        // Failed - increment attempts and record the error
+        event.attempts = event.attempts.saturating_add(1);
+        event.last_error = Some(error_message.clone());
+
+        let disposition = classify_error(&error_message);
+        if disposition == RetryDisposition::Permanent {
+            log::error!("🛑 TimeBack event {} hit a permanent error, dead-lettering: {}", event_id, error_message);
+            dead_letter_event(ctx, &event);
+            return;
         }
-        
+
+        // Safety net: don't retry forever even for transient errors
+        if event.attempts >= 5 {
+            log::error!("🛑 TimeBack event {} exceeded max retries (5), dead-lettering", event_id);
+            dead_letter_event(ctx, &event);
+            return;
+        }
+
+        // Exponential backoff: base 1min, doubling per attempt, capped at 16min,
+        // scaled by a +/-50% jitter factor (derived from the reducer context's
+        // deterministic rng) so a batch of events that all failed in the same
+        // poll don't all retry at the exact same instant and hammer a
+        // recovering worker endpoint in lockstep
+        const BASE_MINUTES: u64 = 1;
+        const MAX_BACKOFF_MINUTES: u64 = 16;
+        let backoff_minutes = (BASE_MINUTES << (event.attempts - 1).min(4)).min(MAX_BACKOFF_MINUTES);
+        let backoff_micros = backoff_minutes * 60 * 1_000_000;
+        let jitter_percent = ctx.rng().gen_range(-50..=50);
+        let jittered_micros = (backoff_micros as i64) + (backoff_micros as i64 * jitter_percent / 100);
+
+        // checked_add_micros guards against i64 overflow; falling back to "retry
+        // right now" is harmless here since it only degrades to a tighter retry
+        // loop instead of corrupting next_retry_at with a wrapped timestamp
+        let next_retry = time_math::checked_add_micros(ctx.timestamp, jittered_micros).unwrap_or(ctx.timestamp);
+        event.next_retry_at = Some(next_retry);
+        event.due_epoch = minute_epoch(next_retry);
+        event.claimed = false;
+
+        log::warn!("❌ TimeBack event {} failed (attempt {}): {:?}",
+            event_id, event.attempts, event.last_error);
+
         ctx.db.timeback_event_queue().id().update(event);
     } else {
         log::error!("mark_event_sent: Event {} not found", event_id);
     }
 }
 
-fn is_new_day(last: Timestamp, current: Timestamp) -> bool {
-    // Reset at midnight PST (8am UTC)
-    // This ensures US students see reset overnight, not during homework time
-    const RESET_HOUR_UTC: u64 = 8; // midnight PST = 8am UTC
-    
-    let hour_in_micros = 60 * 60 * 1_000_000u64;
-    let day_in_micros = 24 * hour_in_micros;
-    
-    // Offset timestamps by reset hour to make midnight PST the "start" of day
-    let offset_micros = RESET_HOUR_UTC * hour_in_micros;
-    let last_offset = (last.to_micros_since_unix_epoch() as u64).saturating_sub(offset_micros);
-    let current_offset = (current.to_micros_since_unix_epoch() as u64).saturating_sub(offset_micros);
-    
-    // Now divide by day to get "day number" since reset time
-    let last_day = last_offset / day_in_micros;
-    let current_day = current_offset / day_in_micros;
-    
-    current_day > last_day
+/// Admin: reinject a dead-lettered TimeBack event after the underlying
+/// outage is resolved. Resets attempts/next_retry_at so it's picked up
+/// immediately by the worker's next poll.
+#[reducer]
+pub fn requeue_dead_letter(ctx: &ReducerContext, id: u64) -> Result<(), String> {
+    check_capability(ctx, "requeue_dead_letter", 1)
+        .map_err(|e| format!("Unauthorized requeue_dead_letter attempt by {}: {}", ctx.sender, e))?;
+
+    let dead = ctx.db.timeback_dead_letter().id().find(&id)
+        .ok_or(format!("Dead letter event {} not found", id))?;
+
+    ctx.db.timeback_event_queue().insert(TimebackEventQueue {
+        id: 0, // auto_inc
+        player_id: dead.player_id.clone(),
+        payload: dead.payload.clone(),
+        created_at: ctx.timestamp, // FIFO order from requeue time, not original enqueue
+        sent: false,
+        attempts: 0,
+        next_retry_at: None, // Ready to send immediately
+        last_error: None,
+        sent_at: None,
+        due_epoch: minute_epoch(ctx.timestamp),
+        claimed: true,
+    });
+    ctx.db.timeback_dead_letter().id().delete(&dead.id);
+
+    log::info!("[TIMEBACK] requeued dead_letter_id:{} player:{} by {}", id, dead.player_id, ctx.sender);
+    Ok(())
 }
 
-/// Calculate number of days between two timestamps (for streak tracking)
-fn calculate_days_between(last: Timestamp, current: Timestamp) -> u64 {
-    const RESET_HOUR_UTC: u64 = 8; // midnight PST = 8am UTC
-    let hour_in_micros = 60 * 60 * 1_000_000u64;
-    let day_in_micros = 24 * hour_in_micros;
-    let offset_micros = RESET_HOUR_UTC * hour_in_micros;
-    
-    let last_offset = (last.to_micros_since_unix_epoch() as u64).saturating_sub(offset_micros);
-    let current_offset = (current.to_micros_since_unix_epoch() as u64).saturating_sub(offset_micros);
-    
-    let last_day = last_offset / day_in_micros;
-    let current_day = current_offset / day_in_micros;
-    
-    current_day.saturating_sub(last_day)
+/// Has `current` crossed into a new local civil day (in `zone`) since `last`?
+/// See the tz module - this used to hardcode "midnight PST = 8am UTC", which
+/// was wrong for roughly half the year and for any player outside Pacific.
+fn is_new_day(zone: &str, last: Timestamp, current: Timestamp) -> bool {
+    tz::is_new_day(zone, last, current)
 }
 
-fn is_new_week(last: Timestamp, current: Timestamp) -> bool {
-    // Reset weekly on Monday at midnight PST (8am UTC)
-    const RESET_HOUR_UTC: u64 = 8; // midnight PST = 8am UTC
-    
-    let hour_in_micros = 60 * 60 * 1_000_000u64;
-    let day_in_micros = 24 * hour_in_micros;
-    let week_in_micros = 7 * day_in_micros;
-    
-    // Unix epoch was Thursday, we want Monday = 4 days offset
-    // Plus 8 hours to align with midnight PST
-    let days_offset = 4;
-    let total_offset_micros = (days_offset * 24 + RESET_HOUR_UTC) * hour_in_micros;
-    
-    let last_offset = (last.to_micros_since_unix_epoch() as u64).saturating_sub(total_offset_micros);
-    let current_offset = (current.to_micros_since_unix_epoch() as u64).saturating_sub(total_offset_micros);
-    
-    let last_week = last_offset / week_in_micros;
-    let current_week = current_offset / week_in_micros;
-    
-    current_week > last_week
+/// Calculate number of local civil days between two timestamps (for streak tracking)
+fn calculate_days_between(zone: &str, last: Timestamp, current: Timestamp) -> u64 {
+    tz::days_between(zone, last, current)
+}
+
+/// Has `current` crossed into a new local week (Monday-start, in `zone`) since `last`?
+fn is_new_week(zone: &str, last: Timestamp, current: Timestamp) -> bool {
+    tz::is_new_week(zone, last, current)
 }
 
 fn cleanup_raid_data(ctx: &ReducerContext, raid_id: u64) {
     // Cancel any pending scheduled reducers for this raid
     cancel_raid_timeout(ctx, raid_id);
     cancel_countdown_schedule(ctx, raid_id);
-    
+    cleanup_boss_skill_data(ctx, raid_id);
+    cancel_boss_tick_schedule(ctx, raid_id);
+    cancel_pause_timeout(ctx, raid_id);
+    cancel_idle_check(ctx, raid_id);
+    cancel_all_problem_timeouts(ctx, raid_id);
+    cleanup_raid_spectators(ctx, raid_id);
+
     // Clear all player references BEFORE deleting anything
     // This prevents dangling pointers if a player reconnects mid-cleanup
     let raid_players: Vec<_> = ctx.db.raid_player().raid_id().filter(&raid_id).collect();
@@ -4658,6 +9726,14 @@ fn cleanup_raid_data(ctx: &ReducerContext, raid_id: u64) {
         ctx.db.raid_player().id().delete(&rp.id);
     }
     
+    // Clean up this raid's world-boss event row, if it has one
+    let events: Vec<_> = ctx.db.world_boss_event().iter()
+        .filter(|e| e.raid_id == Some(raid_id))
+        .collect();
+    for event in events {
+        ctx.db.world_boss_event().id().delete(&event.id);
+    }
+
     ctx.db.raid().id().delete(&raid_id);
 }
 
@@ -2,9 +2,40 @@
 // Accept JSON arrays exported from admin panel (TypeScript SDK format)
 
 use spacetimedb::{reducer, ReducerContext, Timestamp, log, Table};
-use crate::{Player, FactMastery, PerformanceSnapshot, AttemptRecord, authorized_worker};
-use crate::{player, fact_mastery, performance_snapshot};
+use crate::{Player, FactMastery, PerformanceSnapshot, AttemptRecord, RestoreSession};
+use crate::{player, fact_mastery, performance_snapshot, restore_session};
+use crate::check_capability;
+use crate::tz;
+use crate::DIFFICULTY_LEVEL_DEFAULT;
+use crate::bitpack::BitPackedReader;
 use serde_json::Value;
+use std::collections::HashSet;
+use std::io::Read;
+
+/// Sentinel prefix marking a payload as DEFLATE-compressed (raw, no zlib/gzip
+/// wrapper) and base64-encoded, rather than plain JSON - see maybe_decompress
+const COMPRESSED_PAYLOAD_PREFIX: &str = "GZ1:";
+
+/// If `payload` starts with COMPRESSED_PAYLOAD_PREFIX, base64-decode and
+/// inflate the remainder back into the original JSON string; otherwise return
+/// it unchanged. Lets old, already-uncompressed backups keep loading while
+/// new exports can compress past the reducer argument size ceiling -
+/// recent_attempts arrays and snapshot rows compress extremely well.
+fn maybe_decompress(payload: &str) -> Result<String, String> {
+    let Some(encoded) = payload.strip_prefix(COMPRESSED_PAYLOAD_PREFIX) else {
+        return Ok(payload.to_string());
+    };
+
+    let compressed = base64::decode(encoded)
+        .map_err(|e| format!("Invalid base64 in compressed payload: {}", e))?;
+
+    let mut decoder = flate2::read::DeflateDecoder::new(&compressed[..]);
+    let mut json_data = String::new();
+    decoder.read_to_string(&mut json_data)
+        .map_err(|e| format!("Failed to inflate compressed payload: {}", e))?;
+
+    Ok(json_data)
+}
 
 /// Parse Timestamp from SDK JSON format: {"__timestamp_micros_since_unix_epoch__": "123456"}
 fn parse_timestamp_json(val: &Value) -> Result<Timestamp, String> {
@@ -31,142 +62,761 @@ fn parse_attempt_record(val: &Value) -> Result<AttemptRecord, String> {
     })
 }
 
-/// Bulk restore player table from JSON array
+/// How a bulk_restore_* reducer should treat a row whose natural key already
+/// exists in the table. Passed as a plain string since reducer args are
+/// limited to SpacetimeDB-serializable types and this isn't worth a dedicated
+/// SpacetimeType just to move across the wire.
+enum RestoreMode {
+    /// Insert every row as a new row, even if its natural key collides with
+    /// an existing one - the original behavior, kept as the default so
+    /// existing exports/tooling that don't pass a mode keep working
+    Insert,
+    /// Look up each row by its natural key (Player.id; (player_id, fact_key)
+    /// for FactMastery) and update the existing row in place instead of
+    /// inserting a duplicate
+    Upsert,
+    /// Parse and key-match every row but perform no writes
+    DryRun,
+}
+
+impl RestoreMode {
+    fn parse(mode: &str) -> Result<Self, String> {
+        match mode {
+            "" | "insert" => Ok(RestoreMode::Insert),
+            "upsert" => Ok(RestoreMode::Upsert),
+            "dry_run" => Ok(RestoreMode::DryRun),
+            other => Err(format!("Unknown restore mode '{}' (expected insert, upsert, or dry_run)", other)),
+        }
+    }
+}
+
+/// Caps how many parse errors a DryRun summary string quotes in full -
+/// a bad export can have thousands of invalid rows and the point is to show
+/// an operator enough to diagnose the problem, not reproduce the whole log
+const RESTORE_DRY_RUN_MAX_ERRORS: usize = 20;
+
+/// Running tally for a bulk_restore_* call, in any mode. Insert/Upsert only
+/// use it to log a one-line summary on success; DryRun returns it (via the
+/// Err channel - see bulk_restore_player for why) as the operator-facing
+/// validation report. `touched_players` collects the ids affected by this
+/// call so their mastery aggregate can be rebuilt once per player at the end
+/// of the batch, instead of after every single row.
+#[derive(Default)]
+struct RestoreSummary {
+    rows_valid: u32,
+    rows_would_insert: u32,
+    rows_would_update: u32,
+    errors: Vec<(usize, String)>,
+    touched_players: HashSet<String>,
+}
+
+impl RestoreSummary {
+    fn record_error(&mut self, i: usize, e: String) {
+        if self.errors.len() < RESTORE_DRY_RUN_MAX_ERRORS {
+            self.errors.push((i, e));
+        }
+    }
+
+    fn to_report(&self) -> String {
+        let mut report = format!(
+            "rows_valid={} rows_would_insert={} rows_would_update={} rows_invalid={}",
+            self.rows_valid, self.rows_would_insert, self.rows_would_update, self.errors.len()
+        );
+        for (i, e) in &self.errors {
+            report.push_str(&format!("; record {}: {}", i, e));
+        }
+        report
+    }
+}
+
+/// Parse a single player record without inserting it. Shared by
+/// bulk_restore_player, bulk_restore_player_binary, and restore_chunk.
+fn parse_player(p: &Value, i: usize) -> Result<Player, String> {
+    Ok(Player {
+        id: p.get("id").and_then(|v| v.as_str()).ok_or(format!("Player {}: missing id", i))?.to_string(),
+        name: p.get("name").and_then(|v| v.as_str()).ok_or(format!("Player {}: missing name", i))?.to_string(),
+        grade: p.get("grade").and_then(|v| v.as_u64()).ok_or(format!("Player {}: missing grade", i))? as u8,
+        rank: p.get("rank").and_then(|v| v.as_str()).map(|s| s.to_string()),
+        total_problems: p.get("totalProblems").and_then(|v| v.as_u64()).ok_or(format!("Player {}: missing totalProblems", i))? as u32,
+        total_correct: p.get("totalCorrect").and_then(|v| v.as_u64()).ok_or(format!("Player {}: missing totalCorrect", i))? as u32,
+        avg_response_ms: p.get("avgResponseMs").and_then(|v| v.as_u64()).ok_or(format!("Player {}: missing avgResponseMs", i))? as u32,
+        best_response_ms: p.get("bestResponseMs").and_then(|v| v.as_u64()).ok_or(format!("Player {}: missing bestResponseMs", i))? as u32,
+        total_raids: p.get("totalRaids").and_then(|v| v.as_u64()).ok_or(format!("Player {}: missing totalRaids", i))? as u32,
+        quests: p.get("quests").and_then(|v| v.as_str()).map(|s| s.to_string()),
+        last_played: parse_timestamp_json(p.get("lastPlayed").ok_or(format!("Player {}: missing lastPlayed", i))?)?,
+        last_raid: p.get("lastRaid").and_then(|v| parse_timestamp_json(v).ok())
+            .unwrap_or_else(|| parse_timestamp_json(p.get("lastPlayed").unwrap()).unwrap()),  // Default to last_played for old backups
+        last_weekly_reset: parse_timestamp_json(p.get("lastWeeklyReset").ok_or(format!("Player {}: missing lastWeeklyReset", i))?)?,
+        total_ap: p.get("totalAp").and_then(|v| v.as_u64()).ok_or(format!("Player {}: missing totalAp", i))? as u32,
+        in_raid_id: p.get("inRaidId").and_then(|v| v.as_str()).and_then(|s| s.parse::<u64>().ok()),
+        timeback_id: p.get("timebackId").and_then(|v| v.as_str()).map(|s| s.to_string()),
+        email: p.get("email").and_then(|v| v.as_str()).map(|s| s.to_string()),
+        abandon_score: p.get("abandonScore").and_then(|v| v.as_u64()).unwrap_or(0) as u32,
+        matchmaking_cooldown_until: p.get("matchmakingCooldownUntil").and_then(|v| parse_timestamp_json(v).ok()),
+        matchmaking_cooldown_strikes: p.get("matchmakingCooldownStrikes").and_then(|v| v.as_u64()).unwrap_or(0) as u8,
+        cqpm_baseline: p.get("cqpmBaseline").and_then(|v| v.as_f64()).unwrap_or(0.0) as f32,
+        skill_rating: p.get("skillRating").and_then(|v| v.as_f64()).unwrap_or(1000.0) as f32,
+        last_bonus_date: p.get("lastBonusDate").and_then(|v| parse_timestamp_json(v).ok()),
+        daily_bonus_streak: p.get("dailyBonusStreak").and_then(|v| v.as_u64()).unwrap_or(0) as u8,
+        bonus_score: p.get("bonusScore").and_then(|v| v.as_i64()).unwrap_or(0) as i32,
+        target_retention: p.get("targetRetention").and_then(|v| v.as_f64()).unwrap_or(0.9) as f32,
+        mastered_fact_count: p.get("masteredFactCount").and_then(|v| v.as_u64()).unwrap_or(0) as u32,
+        facts_seen_count: p.get("factsSeenCount").and_then(|v| v.as_u64()).unwrap_or(0) as u32,
+        stability_sum: p.get("stabilitySum").and_then(|v| v.as_f64()).unwrap_or(0.0) as f32,
+        division: p.get("division").and_then(|v| v.as_str()).map(|s| s.to_string()),
+        elo_rating: p.get("eloRating").and_then(|v| v.as_f64()).unwrap_or(1200.0) as f32,
+        best_elo_rating: p.get("bestEloRating").and_then(|v| v.as_f64()).unwrap_or(1200.0) as f32,
+        timezone: p.get("timezone").and_then(|v| v.as_str()).unwrap_or(tz::DEFAULT_ZONE).to_string(),
+        difficulty_level: p.get("difficultyLevel").and_then(|v| v.as_i64()).unwrap_or(DIFFICULTY_LEVEL_DEFAULT as i64) as i32,
+    })
+}
+
+/// Apply one already-parsed player row per `mode` - natural key is `id`
+/// (also the primary key), so Upsert can update in place with no extra scan.
+/// Either way, the player is queued in `summary.touched_players` so its
+/// mastered_fact_count/facts_seen_count/stability_sum get rebuilt from
+/// whatever fact_mastery rows already exist for them (once per batch, via
+/// rebuild_touched_players) rather than trusting the export's JSON/binary
+/// fields, which go stale the moment a fact_mastery backup of a different
+/// vintage is restored separately.
+fn apply_player(ctx: &ReducerContext, player: Player, mode: &RestoreMode, summary: &mut RestoreSummary) {
+    summary.rows_valid += 1;
+    let player_id = player.id.clone();
+    let exists = ctx.db.player().id().find(&player_id).is_some();
+    match mode {
+        RestoreMode::DryRun => {}
+        RestoreMode::Insert => {
+            ctx.db.player().insert(player);
+        }
+        RestoreMode::Upsert if exists => {
+            ctx.db.player().id().update(player);
+        }
+        RestoreMode::Upsert => {
+            ctx.db.player().insert(player);
+        }
+    }
+    if exists {
+        summary.rows_would_update += 1;
+    } else {
+        summary.rows_would_insert += 1;
+    }
+    if !matches!(mode, RestoreMode::DryRun) {
+        summary.touched_players.insert(player_id);
+    }
+}
+
+/// Recompute and persist the mastery aggregate for every player touched by
+/// this restore call, once each, regardless of how many rows (player or
+/// fact_mastery) affected them. No-op for a player id that doesn't exist
+/// (e.g. a fact_mastery chunk landed before its player row).
+fn rebuild_touched_players(ctx: &ReducerContext, touched: &HashSet<String>) {
+    for player_id in touched {
+        if let Some(mut player) = ctx.db.player().id().find(player_id) {
+            crate::rebuild_mastery_aggregate(ctx, &mut player);
+            ctx.db.player().id().update(player);
+        }
+    }
+}
+
+/// Bulk restore player table from JSON array. `mode` is "insert" (default,
+/// matches the original unconditional-insert behavior), "upsert" (update an
+/// existing row with the same `id` in place instead of duplicating it), or
+/// "dry_run" (validate only, write nothing).
+///
 /// Protected by authorization check - only authorized workers can call this
 #[reducer]
-pub fn bulk_restore_player(ctx: &ReducerContext, json_data: String) -> Result<(), String> {
-    // Authorization check: only authorized workers can restore data
-    if ctx.db.authorized_worker().identity().find(&ctx.sender).is_none() {
-        log::warn!("Unauthorized bulk_restore_player attempt by {}", ctx.sender);
-        return Err("Unauthorized".to_string());
-    }
-    
+pub fn bulk_restore_player(ctx: &ReducerContext, json_data: String, mode: String) -> Result<(), String> {
+    // Capability check: full admins pass unconditionally; scoped workers need
+    // a worker_capability grant for "bulk_restore_player" that satisfies its restrictions
+    check_capability(ctx, "bulk_restore_player", 2)
+        .map_err(|e| format!("Unauthorized bulk_restore_player attempt by {}: {}", ctx.sender, e))?;
+
+    let mode = RestoreMode::parse(&mode)?;
+    let json_data = maybe_decompress(&json_data)?;
     let data: Value = serde_json::from_str(&json_data)
         .map_err(|e| format!("Invalid JSON: {}", e))?;
-    
+
     let players = data.as_array()
         .ok_or("Expected JSON array of players")?;
-    
-    let mut count = 0;
+
+    let mut summary = RestoreSummary::default();
     for (i, p) in players.iter().enumerate() {
-        let player = Player {
-            id: p.get("id").and_then(|v| v.as_str()).ok_or(format!("Player {}: missing id", i))?.to_string(),
-            name: p.get("name").and_then(|v| v.as_str()).ok_or(format!("Player {}: missing name", i))?.to_string(),
-            grade: p.get("grade").and_then(|v| v.as_u64()).ok_or(format!("Player {}: missing grade", i))? as u8,
-            rank: p.get("rank").and_then(|v| v.as_str()).map(|s| s.to_string()),
-            total_problems: p.get("totalProblems").and_then(|v| v.as_u64()).ok_or(format!("Player {}: missing totalProblems", i))? as u32,
-            total_correct: p.get("totalCorrect").and_then(|v| v.as_u64()).ok_or(format!("Player {}: missing totalCorrect", i))? as u32,
-            avg_response_ms: p.get("avgResponseMs").and_then(|v| v.as_u64()).ok_or(format!("Player {}: missing avgResponseMs", i))? as u32,
-            best_response_ms: p.get("bestResponseMs").and_then(|v| v.as_u64()).ok_or(format!("Player {}: missing bestResponseMs", i))? as u32,
-            total_raids: p.get("totalRaids").and_then(|v| v.as_u64()).ok_or(format!("Player {}: missing totalRaids", i))? as u32,
-            quests: p.get("quests").and_then(|v| v.as_str()).map(|s| s.to_string()),
-            last_played: parse_timestamp_json(p.get("lastPlayed").ok_or(format!("Player {}: missing lastPlayed", i))?)?,
-            last_raid: p.get("lastRaid").and_then(|v| parse_timestamp_json(v).ok())
-                .unwrap_or_else(|| parse_timestamp_json(p.get("lastPlayed").unwrap()).unwrap()),  // Default to last_played for old backups
-            last_weekly_reset: parse_timestamp_json(p.get("lastWeeklyReset").ok_or(format!("Player {}: missing lastWeeklyReset", i))?)?,
-            total_ap: p.get("totalAp").and_then(|v| v.as_u64()).ok_or(format!("Player {}: missing totalAp", i))? as u32,
-            in_raid_id: p.get("inRaidId").and_then(|v| v.as_str()).and_then(|s| s.parse::<u64>().ok()),
-            timeback_id: p.get("timebackId").and_then(|v| v.as_str()).map(|s| s.to_string()),
-            email: p.get("email").and_then(|v| v.as_str()).map(|s| s.to_string()),
-        };
-        
-        ctx.db.player().insert(player);
-        count += 1;
+        match parse_player(p, i) {
+            Ok(player) => apply_player(ctx, player, &mode, &mut summary),
+            Err(e) if matches!(mode, RestoreMode::DryRun) => summary.record_error(i, e),
+            Err(e) => return Err(e),
+        }
     }
-    
-    log::info!("✅ Restored {} player records", count);
+
+    // DryRun never writes, so its only output is this report - returned via
+    // the Err channel since a reducer can't hand data back to the caller any
+    // other way (see check_capability's Result<(), String> callers for the
+    // same constraint elsewhere in this module)
+    if matches!(mode, RestoreMode::DryRun) {
+        return Err(format!("[DRY RUN] player: {}", summary.to_report()));
+    }
+
+    rebuild_touched_players(ctx, &summary.touched_players);
+    log::info!("✅ Restored {} player records ({})", players.len(), summary.to_report());
     Ok(())
 }
 
-/// Bulk restore fact_mastery table from JSON array
+/// Parse a single fact_mastery record without inserting it. Shared by
+/// bulk_restore_fact_mastery, bulk_restore_fact_mastery_binary, and
+/// restore_chunk.
+fn parse_fact_mastery(f: &Value, i: usize) -> Result<FactMastery, String> {
+    let recent_attempts_json = f.get("recentAttempts")
+        .and_then(|v| v.as_array())
+        .ok_or(format!("Fact {}: missing or invalid recentAttempts", i))?;
+
+    let recent_attempts: Vec<AttemptRecord> = recent_attempts_json.iter()
+        .map(|a| parse_attempt_record(a))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(FactMastery {
+        id: 0, // auto_inc
+        player_id: f.get("playerId").and_then(|v| v.as_str()).ok_or(format!("Fact {}: missing playerId", i))?.to_string(),
+        fact_key: f.get("factKey").and_then(|v| v.as_str()).ok_or(format!("Fact {}: missing factKey", i))?.to_string(),
+        total_attempts: f.get("totalAttempts").and_then(|v| v.as_u64()).ok_or(format!("Fact {}: missing totalAttempts", i))? as u32,
+        total_correct: f.get("totalCorrect").and_then(|v| v.as_u64()).ok_or(format!("Fact {}: missing totalCorrect", i))? as u32,
+        last_seen: parse_timestamp_json(f.get("lastSeen").ok_or(format!("Fact {}: missing lastSeen", i))?)?,
+        avg_response_ms: f.get("avgResponseMs").and_then(|v| v.as_u64()).ok_or(format!("Fact {}: missing avgResponseMs", i))? as u32,
+        fastest_ms: f.get("fastestMs").and_then(|v| v.as_u64()).ok_or(format!("Fact {}: missing fastestMs", i))? as u32,
+        recent_attempts,
+        mastery_level: f.get("masteryLevel").and_then(|v| v.as_u64()).ok_or(format!("Fact {}: missing masteryLevel", i))? as u8,
+        stability: f.get("stability").and_then(|v| v.as_f64()).unwrap_or(0.0) as f32,
+        difficulty: f.get("difficulty").and_then(|v| v.as_f64()).unwrap_or(0.0) as f32,
+    })
+}
+
+/// Apply one already-parsed fact_mastery row per `mode` - natural key is
+/// (player_id, fact_key), not the auto_inc `id`, so Upsert has to scan for a
+/// match and replace it (same delete-then-insert pattern as
+/// grant_worker_capability's manual uniqueness check). Also queues the
+/// owning player in `summary.touched_players` so its cached mastery
+/// aggregate gets rebuilt once per batch (see rebuild_touched_players) -
+/// restoring fact_mastery rows independently of a player backup would
+/// otherwise leave that cache silently wrong with no admin-facing way to
+/// notice.
+fn apply_fact_mastery(ctx: &ReducerContext, fact: FactMastery, mode: &RestoreMode, summary: &mut RestoreSummary) {
+    summary.rows_valid += 1;
+    let player_id = fact.player_id.clone();
+    let existing = ctx.db.fact_mastery()
+        .player_id()
+        .filter(&fact.player_id)
+        .find(|row| row.fact_key == fact.fact_key);
+    let exists = existing.is_some();
+    match mode {
+        RestoreMode::DryRun => {}
+        RestoreMode::Insert => {
+            ctx.db.fact_mastery().insert(fact);
+        }
+        RestoreMode::Upsert => {
+            if let Some(existing) = existing {
+                ctx.db.fact_mastery().id().delete(&existing.id);
+            }
+            ctx.db.fact_mastery().insert(fact);
+        }
+    }
+    if exists {
+        summary.rows_would_update += 1;
+    } else {
+        summary.rows_would_insert += 1;
+    }
+    if !matches!(mode, RestoreMode::DryRun) {
+        summary.touched_players.insert(player_id);
+    }
+}
+
+/// Bulk restore fact_mastery table from JSON array. See bulk_restore_player
+/// for what `mode` does.
+///
 /// Protected by authorization check - only authorized workers can call this
 #[reducer]
-pub fn bulk_restore_fact_mastery(ctx: &ReducerContext, json_data: String) -> Result<(), String> {
-    // Authorization check: only authorized workers can restore data
-    if ctx.db.authorized_worker().identity().find(&ctx.sender).is_none() {
-        log::warn!("Unauthorized bulk_restore_fact_mastery attempt by {}", ctx.sender);
-        return Err("Unauthorized".to_string());
-    }
-    
+pub fn bulk_restore_fact_mastery(ctx: &ReducerContext, json_data: String, mode: String) -> Result<(), String> {
+    // Capability check: full admins pass unconditionally; scoped workers need
+    // a worker_capability grant for "bulk_restore_fact_mastery" that satisfies its restrictions
+    check_capability(ctx, "bulk_restore_fact_mastery", 2)
+        .map_err(|e| format!("Unauthorized bulk_restore_fact_mastery attempt by {}: {}", ctx.sender, e))?;
+
+    let mode = RestoreMode::parse(&mode)?;
+    let json_data = maybe_decompress(&json_data)?;
     let data: Value = serde_json::from_str(&json_data)
         .map_err(|e| format!("Invalid JSON: {}", e))?;
-    
+
     let facts = data.as_array()
         .ok_or("Expected JSON array of fact_mastery records")?;
-    
-    let mut count = 0;
+
+    let mut summary = RestoreSummary::default();
     for (i, f) in facts.iter().enumerate() {
-        // Parse recent_attempts array
-        let recent_attempts_json = f.get("recentAttempts")
-            .and_then(|v| v.as_array())
-            .ok_or(format!("Fact {}: missing or invalid recentAttempts", i))?;
-        
-        let recent_attempts: Vec<AttemptRecord> = recent_attempts_json.iter()
-            .map(|a| parse_attempt_record(a))
-            .collect::<Result<Vec<_>, _>>()?;
-        
-        let fact = FactMastery {
-            id: 0, // auto_inc
-            player_id: f.get("playerId").and_then(|v| v.as_str()).ok_or(format!("Fact {}: missing playerId", i))?.to_string(),
-            fact_key: f.get("factKey").and_then(|v| v.as_str()).ok_or(format!("Fact {}: missing factKey", i))?.to_string(),
-            total_attempts: f.get("totalAttempts").and_then(|v| v.as_u64()).ok_or(format!("Fact {}: missing totalAttempts", i))? as u32,
-            total_correct: f.get("totalCorrect").and_then(|v| v.as_u64()).ok_or(format!("Fact {}: missing totalCorrect", i))? as u32,
-            last_seen: parse_timestamp_json(f.get("lastSeen").ok_or(format!("Fact {}: missing lastSeen", i))?)?,
-            avg_response_ms: f.get("avgResponseMs").and_then(|v| v.as_u64()).ok_or(format!("Fact {}: missing avgResponseMs", i))? as u32,
-            fastest_ms: f.get("fastestMs").and_then(|v| v.as_u64()).ok_or(format!("Fact {}: missing fastestMs", i))? as u32,
-            recent_attempts,
-            mastery_level: f.get("masteryLevel").and_then(|v| v.as_u64()).ok_or(format!("Fact {}: missing masteryLevel", i))? as u8,
-        };
-        
-        ctx.db.fact_mastery().insert(fact);
-        count += 1;
+        match parse_fact_mastery(f, i) {
+            Ok(fact) => apply_fact_mastery(ctx, fact, &mode, &mut summary),
+            Err(e) if matches!(mode, RestoreMode::DryRun) => summary.record_error(i, e),
+            Err(e) => return Err(e),
+        }
     }
-    
-    log::info!("✅ Restored {} fact_mastery records", count);
+
+    if matches!(mode, RestoreMode::DryRun) {
+        return Err(format!("[DRY RUN] fact_mastery: {}", summary.to_report()));
+    }
+
+    rebuild_touched_players(ctx, &summary.touched_players);
+    log::info!("✅ Restored {} fact_mastery records ({})", facts.len(), summary.to_report());
     Ok(())
 }
 
-/// Bulk restore performance_snapshot table from JSON array
+/// Parse a single performance_snapshot record without inserting it. Shared by
+/// bulk_restore_performance_snapshot, bulk_restore_performance_snapshot_binary,
+/// and restore_chunk.
+fn parse_performance_snapshot(s: &Value, i: usize) -> Result<PerformanceSnapshot, String> {
+    Ok(PerformanceSnapshot {
+        id: 0, // auto_inc
+        player_id: s.get("playerId").and_then(|v| v.as_str()).ok_or(format!("Snapshot {}: missing playerId", i))?.to_string(),
+        timestamp: parse_timestamp_json(s.get("timestamp").ok_or(format!("Snapshot {}: missing timestamp", i))?)?,
+        grade: s.get("grade").and_then(|v| v.as_u64()).ok_or(format!("Snapshot {}: missing grade", i))? as u8,
+        track: s.get("track").and_then(|v| v.as_str()).map(|s| s.to_string()),
+        rank: s.get("rank").and_then(|v| v.as_str()).map(|s| s.to_string()),
+        division: s.get("division").and_then(|v| v.as_str()).map(|s| s.to_string()),
+        facts_mastered_at_snapshot: s.get("factsMasteredAtSnapshot").and_then(|v| v.as_u64()).ok_or(format!("Snapshot {}: missing factsMasteredAtSnapshot", i))? as u32,
+        problems_attempted: s.get("problemsAttempted").and_then(|v| v.as_u64()).ok_or(format!("Snapshot {}: missing problemsAttempted", i))? as u32,
+        problems_correct: s.get("problemsCorrect").and_then(|v| v.as_u64()).ok_or(format!("Snapshot {}: missing problemsCorrect", i))? as u32,
+        session_seconds: s.get("sessionSeconds").and_then(|v| v.as_u64()).ok_or(format!("Snapshot {}: missing sessionSeconds", i))? as u32,
+        damage_dealt: s.get("damageDealt").and_then(|v| v.as_u64()).ok_or(format!("Snapshot {}: missing damageDealt", i))? as u32,
+        raid_type: s.get("raidType").and_then(|v| v.as_str()).map(|s| s.to_string()),
+        timeback_units_at_snapshot: s.get("timebackUnitsAtSnapshot").and_then(|v| v.as_u64()).unwrap_or(0) as u32,
+        boss_level: s.get("bossLevel").and_then(|v| v.as_u64()).unwrap_or(0) as u8,
+        victory: s.get("victory").and_then(|v| v.as_bool()),
+        sandbag_suspected: s.get("sandbagSuspected").and_then(|v| v.as_bool()).unwrap_or(false),
+        elo_rating_at_snapshot: s.get("eloRatingAtSnapshot").and_then(|v| v.as_f64()).unwrap_or(1200.0) as f32,
+    })
+}
+
+/// Apply one already-parsed performance_snapshot row per `mode`. Snapshots
+/// are point-in-time history with no natural key - a player can have any
+/// number of them - so there's nothing for Upsert to match against; it's
+/// treated the same as Insert here and DryRun never reports would-update.
+fn apply_performance_snapshot(ctx: &ReducerContext, snapshot: PerformanceSnapshot, mode: &RestoreMode, summary: &mut RestoreSummary) {
+    summary.rows_valid += 1;
+    summary.rows_would_insert += 1;
+    if !matches!(mode, RestoreMode::DryRun) {
+        ctx.db.performance_snapshot().insert(snapshot);
+    }
+}
+
+/// Bulk restore performance_snapshot table from JSON array. See
+/// bulk_restore_player for what `mode` does; Upsert behaves like Insert here
+/// since snapshot rows have no natural key to match against.
+///
 /// Protected by authorization check - only authorized workers can call this
 #[reducer]
-pub fn bulk_restore_performance_snapshot(ctx: &ReducerContext, json_data: String) -> Result<(), String> {
-    // Authorization check: only authorized workers can restore data
-    if ctx.db.authorized_worker().identity().find(&ctx.sender).is_none() {
-        log::warn!("Unauthorized bulk_restore_performance_snapshot attempt by {}", ctx.sender);
-        return Err("Unauthorized".to_string());
-    }
-    
+pub fn bulk_restore_performance_snapshot(ctx: &ReducerContext, json_data: String, mode: String) -> Result<(), String> {
+    // Capability check: full admins pass unconditionally; scoped workers need
+    // a worker_capability grant for "bulk_restore_performance_snapshot" that satisfies its restrictions
+    check_capability(ctx, "bulk_restore_performance_snapshot", 2)
+        .map_err(|e| format!("Unauthorized bulk_restore_performance_snapshot attempt by {}: {}", ctx.sender, e))?;
+
+    let mode = RestoreMode::parse(&mode)?;
+    let json_data = maybe_decompress(&json_data)?;
     let data: Value = serde_json::from_str(&json_data)
         .map_err(|e| format!("Invalid JSON: {}", e))?;
-    
+
     let snapshots = data.as_array()
         .ok_or("Expected JSON array of performance_snapshot records")?;
-    
-    let mut count = 0;
+
+    let mut summary = RestoreSummary::default();
     for (i, s) in snapshots.iter().enumerate() {
-        let snapshot = PerformanceSnapshot {
-            id: 0, // auto_inc
-            player_id: s.get("playerId").and_then(|v| v.as_str()).ok_or(format!("Snapshot {}: missing playerId", i))?.to_string(),
-            timestamp: parse_timestamp_json(s.get("timestamp").ok_or(format!("Snapshot {}: missing timestamp", i))?)?,
-            grade: s.get("grade").and_then(|v| v.as_u64()).ok_or(format!("Snapshot {}: missing grade", i))? as u8,
-            track: s.get("track").and_then(|v| v.as_str()).map(|s| s.to_string()),
-            rank: s.get("rank").and_then(|v| v.as_str()).map(|s| s.to_string()),
-            division: s.get("division").and_then(|v| v.as_str()).map(|s| s.to_string()),
-            facts_mastered_at_snapshot: s.get("factsMasteredAtSnapshot").and_then(|v| v.as_u64()).ok_or(format!("Snapshot {}: missing factsMasteredAtSnapshot", i))? as u32,
-            problems_attempted: s.get("problemsAttempted").and_then(|v| v.as_u64()).ok_or(format!("Snapshot {}: missing problemsAttempted", i))? as u32,
-            problems_correct: s.get("problemsCorrect").and_then(|v| v.as_u64()).ok_or(format!("Snapshot {}: missing problemsCorrect", i))? as u32,
-            session_seconds: s.get("sessionSeconds").and_then(|v| v.as_u64()).ok_or(format!("Snapshot {}: missing sessionSeconds", i))? as u32,
-            damage_dealt: s.get("damageDealt").and_then(|v| v.as_u64()).ok_or(format!("Snapshot {}: missing damageDealt", i))? as u32,
-            raid_type: s.get("raidType").and_then(|v| v.as_str()).map(|s| s.to_string()),
-            timeback_units_at_snapshot: s.get("timebackUnitsAtSnapshot").and_then(|v| v.as_u64()).unwrap_or(0) as u32,
-            boss_level: s.get("bossLevel").and_then(|v| v.as_u64()).unwrap_or(0) as u8,
-            victory: s.get("victory").and_then(|v| v.as_bool()),
-        };
-        
-        ctx.db.performance_snapshot().insert(snapshot);
+        match parse_performance_snapshot(s, i) {
+            Ok(snapshot) => apply_performance_snapshot(ctx, snapshot, &mode, &mut summary),
+            Err(e) if matches!(mode, RestoreMode::DryRun) => summary.record_error(i, e),
+            Err(e) => return Err(e),
+        }
+    }
+
+    if matches!(mode, RestoreMode::DryRun) {
+        return Err(format!("[DRY RUN] performance_snapshot: {}", summary.to_report()));
+    }
+
+    log::info!("✅ Restored {} performance_snapshot records ({})", snapshots.len(), summary.to_report());
+    Ok(())
+}
+
+// ==================== Bit-packed binary variants (see bitpack.rs) ====================
+//
+// Same tables, same authorization, same semantics as the JSON reducers above -
+// these exist so an admin panel export that's too big for a reducer's String
+// argument limit (or just wasteful as repeated-field-name JSON) can be sent as
+// a compact Vec<u8> instead. Each record is length-framed (see
+// BitPackedReader::read_record), so one corrupt/truncated record fails with a
+// clean Err instead of a panic or silently garbled rows.
+
+/// Bulk restore player table from a bit-packed binary payload. See
+/// bulk_restore_player for what `mode` does.
+///
+/// Protected by authorization check - only authorized workers can call this
+#[reducer]
+pub fn bulk_restore_player_binary(ctx: &ReducerContext, data: Vec<u8>, mode: String) -> Result<(), String> {
+    check_capability(ctx, "bulk_restore_player_binary", 2)
+        .map_err(|e| format!("Unauthorized bulk_restore_player_binary attempt by {}: {}", ctx.sender, e))?;
+
+    let mode = RestoreMode::parse(&mode)?;
+
+    let mut reader = BitPackedReader::new(&data);
+    let base = reader.read_header()?;
+
+    let mut summary = RestoreSummary::default();
+    let mut count: u64 = 0;
+    while !reader.is_empty() {
+        let i = count;
+        let player = reader.read_record(|r| {
+            Ok(Player {
+                id: r.read_string()?,
+                name: r.read_string()?,
+                grade: r.read_u8()?,
+                rank: r.read_optional_string()?,
+                total_problems: r.read_varint()? as u32,
+                total_correct: r.read_varint()? as u32,
+                avg_response_ms: r.read_varint()? as u32,
+                best_response_ms: r.read_varint()? as u32,
+                total_raids: r.read_varint()? as u32,
+                quests: r.read_optional_string()?,
+                last_played: r.read_timestamp_delta(base)?,
+                last_raid: r.read_timestamp_delta(base)?,
+                last_weekly_reset: r.read_timestamp_delta(base)?,
+                total_ap: r.read_varint()? as u32,
+                in_raid_id: r.read_optional_u64()?,
+                timeback_id: r.read_optional_string()?,
+                email: r.read_optional_string()?,
+                abandon_score: r.read_varint()? as u32,
+                matchmaking_cooldown_until: r.read_optional_timestamp_delta(base)?,
+                matchmaking_cooldown_strikes: r.read_u8()?,
+                cqpm_baseline: r.read_f32()?,
+                skill_rating: r.read_f32()?,
+                last_bonus_date: r.read_optional_timestamp_delta(base)?,
+                daily_bonus_streak: r.read_u8()?,
+                bonus_score: r.read_signed_varint()? as i32,
+                target_retention: r.read_f32()?,
+                mastered_fact_count: r.read_varint()? as u32,
+                facts_seen_count: r.read_varint()? as u32,
+                stability_sum: r.read_f32()?,
+                division: r.read_optional_string()?,
+                elo_rating: r.read_f32()?,
+                best_elo_rating: r.read_f32()?,
+                timezone: r.read_string()?,
+                difficulty_level: r.read_signed_varint()? as i32,
+            })
+        }).map_err(|e| format!("Player record {}: {}", i, e));
+
+        match player {
+            Ok(player) => apply_player(ctx, player, &mode, &mut summary),
+            Err(e) if matches!(mode, RestoreMode::DryRun) => summary.record_error(i as usize, e),
+            Err(e) => return Err(e),
+        }
         count += 1;
     }
-    
-    log::info!("✅ Restored {} performance_snapshot records", count);
+
+    if matches!(mode, RestoreMode::DryRun) {
+        return Err(format!("[DRY RUN] player (binary): {}", summary.to_report()));
+    }
+
+    rebuild_touched_players(ctx, &summary.touched_players);
+    log::info!("✅ Restored {} player records (binary, {})", count, summary.to_report());
+    Ok(())
+}
+
+/// Bulk restore fact_mastery table from a bit-packed binary payload. See
+/// bulk_restore_player for what `mode` does.
+///
+/// Protected by authorization check - only authorized workers can call this
+#[reducer]
+pub fn bulk_restore_fact_mastery_binary(ctx: &ReducerContext, data: Vec<u8>, mode: String) -> Result<(), String> {
+    check_capability(ctx, "bulk_restore_fact_mastery_binary", 2)
+        .map_err(|e| format!("Unauthorized bulk_restore_fact_mastery_binary attempt by {}: {}", ctx.sender, e))?;
+
+    let mode = RestoreMode::parse(&mode)?;
+    let mut reader = BitPackedReader::new(&data);
+    let base = reader.read_header()?;
+
+    let mut summary = RestoreSummary::default();
+    let mut count: u64 = 0;
+    while !reader.is_empty() {
+        let i = count;
+        let fact = reader.read_record(|r| {
+            let player_id = r.read_string()?;
+            let fact_key = r.read_string()?;
+            let total_attempts = r.read_varint()? as u32;
+            let total_correct = r.read_varint()? as u32;
+            let last_seen = r.read_timestamp_delta(base)?;
+            let avg_response_ms = r.read_varint()? as u32;
+            let fastest_ms = r.read_varint()? as u32;
+
+            // Don't pre-reserve capacity from attempt_count - it's an
+            // unvalidated varint from the payload, so a crafted record could
+            // claim close to u64::MAX entries and either overflow the
+            // capacity calculation or trigger a multi-GB allocation before a
+            // single byte of the (possibly tiny) actual record is read.
+            // Growing the Vec via push instead means a corrupt count just
+            // fails the next read_varint once the record's real bytes (bounded
+            // by read_record's outer length) run out.
+            let attempt_count = r.read_varint()?;
+            let mut recent_attempts = Vec::new();
+            for _ in 0..attempt_count {
+                recent_attempts.push(AttemptRecord {
+                    time_ms: r.read_varint()? as u32,
+                    correct: r.read_bool()?,
+                    timestamp: r.read_timestamp_delta(base)?,
+                });
+            }
+
+            Ok(FactMastery {
+                id: 0, // auto_inc
+                player_id,
+                fact_key,
+                total_attempts,
+                total_correct,
+                last_seen,
+                avg_response_ms,
+                fastest_ms,
+                recent_attempts,
+                mastery_level: r.read_u8()?,
+                stability: r.read_f32()?,
+                difficulty: r.read_f32()?,
+            })
+        }).map_err(|e| format!("Fact record {}: {}", i, e));
+
+        match fact {
+            Ok(fact) => apply_fact_mastery(ctx, fact, &mode, &mut summary),
+            Err(e) if matches!(mode, RestoreMode::DryRun) => summary.record_error(i as usize, e),
+            Err(e) => return Err(e),
+        }
+        count += 1;
+    }
+
+    if matches!(mode, RestoreMode::DryRun) {
+        return Err(format!("[DRY RUN] fact_mastery (binary): {}", summary.to_report()));
+    }
+
+    rebuild_touched_players(ctx, &summary.touched_players);
+    log::info!("✅ Restored {} fact_mastery records (binary, {})", count, summary.to_report());
+    Ok(())
+}
+
+/// Bulk restore performance_snapshot table from a bit-packed binary payload.
+/// See bulk_restore_player for what `mode` does (Upsert behaves like Insert
+/// here, same as bulk_restore_performance_snapshot).
+///
+/// Protected by authorization check - only authorized workers can call this
+#[reducer]
+pub fn bulk_restore_performance_snapshot_binary(ctx: &ReducerContext, data: Vec<u8>, mode: String) -> Result<(), String> {
+    check_capability(ctx, "bulk_restore_performance_snapshot_binary", 2)
+        .map_err(|e| format!("Unauthorized bulk_restore_performance_snapshot_binary attempt by {}: {}", ctx.sender, e))?;
+
+    let mode = RestoreMode::parse(&mode)?;
+    let mut reader = BitPackedReader::new(&data);
+    let base = reader.read_header()?;
+
+    let mut summary = RestoreSummary::default();
+    let mut count: u64 = 0;
+    while !reader.is_empty() {
+        let i = count;
+        let snapshot = reader.read_record(|r| {
+            Ok(PerformanceSnapshot {
+                id: 0, // auto_inc
+                player_id: r.read_string()?,
+                timestamp: r.read_timestamp_delta(base)?,
+                grade: r.read_u8()?,
+                track: r.read_optional_string()?,
+                rank: r.read_optional_string()?,
+                division: r.read_optional_string()?,
+                facts_mastered_at_snapshot: r.read_varint()? as u32,
+                problems_attempted: r.read_varint()? as u32,
+                problems_correct: r.read_varint()? as u32,
+                session_seconds: r.read_varint()? as u32,
+                damage_dealt: r.read_varint()? as u32,
+                raid_type: r.read_optional_string()?,
+                timeback_units_at_snapshot: r.read_varint()? as u32,
+                boss_level: r.read_u8()?,
+                victory: if r.read_bool()? { Some(r.read_bool()?) } else { None },
+                sandbag_suspected: r.read_bool()?,
+                elo_rating_at_snapshot: r.read_f32()?,
+            })
+        }).map_err(|e| format!("Snapshot record {}: {}", i, e));
+
+        match snapshot {
+            Ok(snapshot) => apply_performance_snapshot(ctx, snapshot, &mode, &mut summary),
+            Err(e) if matches!(mode, RestoreMode::DryRun) => summary.record_error(i as usize, e),
+            Err(e) => return Err(e),
+        }
+        count += 1;
+    }
+
+    if matches!(mode, RestoreMode::DryRun) {
+        return Err(format!("[DRY RUN] performance_snapshot (binary): {}", summary.to_report()));
+    }
+
+    log::info!("✅ Restored {} performance_snapshot records (binary, {})", count, summary.to_report());
+    Ok(())
+}
+
+// ==================== Chunked, resumable restore sessions ====================
+//
+// The bulk_restore_* reducers above need the whole table's JSON in one call,
+// which doesn't fit a large export and gives an admin no visibility into how
+// far an in-flight restore got. begin_restore/restore_chunk/finish_restore
+// split the same per-table JSON-array parsing into a sequence of numbered
+// chunks tracked by a restore_session row, so a dropped connection can resume
+// by re-sending restore_chunk for chunks_received onward instead of starting
+// the whole import over.
+
+/// Parse one chunk's JSON array and insert its rows into `table_name`,
+/// returning how many rows were inserted. Reuses the same per-record parsing
+/// as the whole-array bulk_restore_* reducers above.
+fn restore_chunk_rows(ctx: &ReducerContext, table_name: &str, json_data: &str) -> Result<u32, String> {
+    let data: Value = serde_json::from_str(json_data)
+        .map_err(|e| format!("Invalid JSON: {}", e))?;
+    let rows = data.as_array()
+        .ok_or("Expected JSON array of records")?;
+
+    // Always plain insert - a chunk is, by construction, a slice that hasn't
+    // been applied yet (restore_chunk rejects out-of-order/duplicate
+    // chunk_index before calling this), so there's no natural-key collision
+    // to upsert against.
+    let mut summary = RestoreSummary::default();
+    match table_name {
+        "player" => {
+            for (i, p) in rows.iter().enumerate() {
+                match parse_player(p, i) {
+                    Ok(player) => apply_player(ctx, player, &RestoreMode::Insert, &mut summary),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        "fact_mastery" => {
+            for (i, f) in rows.iter().enumerate() {
+                match parse_fact_mastery(f, i) {
+                    Ok(fact) => apply_fact_mastery(ctx, fact, &RestoreMode::Insert, &mut summary),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        "performance_snapshot" => {
+            for (i, s) in rows.iter().enumerate() {
+                match parse_performance_snapshot(s, i) {
+                    Ok(snapshot) => apply_performance_snapshot(ctx, snapshot, &RestoreMode::Insert, &mut summary),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        other => return Err(format!("Unknown restore table '{}'", other)),
+    }
+
+    rebuild_touched_players(ctx, &summary.touched_players);
+    Ok(rows.len() as u32)
+}
+
+/// Start (or resume) a chunked restore session for `table_name`
+/// ("player" | "fact_mastery" | "performance_snapshot"). `session_id` is
+/// client-chosen so an admin panel that loses its connection mid-upload can
+/// call begin_restore again with the same id and keep sending restore_chunk
+/// from wherever chunks_received left off, instead of restarting the import.
+#[reducer]
+pub fn begin_restore(ctx: &ReducerContext, session_id: String, table_name: String, chunks_expected: u32) -> Result<(), String> {
+    check_capability(ctx, "begin_restore", 3)
+        .map_err(|e| format!("Unauthorized begin_restore attempt by {}: {}", ctx.sender, e))?;
+
+    if !matches!(table_name.as_str(), "player" | "fact_mastery" | "performance_snapshot") {
+        return Err(format!("Unknown restore table '{}'", table_name));
+    }
+
+    if let Some(existing) = ctx.db.restore_session().session_id().find(&session_id) {
+        if existing.table_name != table_name || existing.chunks_expected != chunks_expected {
+            return Err(format!(
+                "Restore session '{}' already exists for table '{}' ({} chunks expected)",
+                session_id, existing.table_name, existing.chunks_expected
+            ));
+        }
+        // Re-sending begin_restore for the same in-flight session is a no-op -
+        // the admin panel can safely retry after a dropped connection.
+        return Ok(());
+    }
+
+    ctx.db.restore_session().insert(RestoreSession {
+        session_id,
+        table_name,
+        chunks_expected,
+        chunks_received: 0,
+        rows_restored: 0,
+        started_at: ctx.timestamp,
+        last_chunk_at: ctx.timestamp,
+        completed: false,
+    });
+
+    Ok(())
+}
+
+/// Apply one chunk of a restore session. `chunk_index` must equal the
+/// session's chunks_received (the next chunk expected); a lower index is a
+/// retry of an already-applied chunk and is accepted as a no-op, while a
+/// higher index is rejected since it would mean a chunk was skipped.
+#[reducer]
+pub fn restore_chunk(ctx: &ReducerContext, session_id: String, chunk_index: u32, json_data: String) -> Result<(), String> {
+    check_capability(ctx, "restore_chunk", 3)
+        .map_err(|e| format!("Unauthorized restore_chunk attempt by {}: {}", ctx.sender, e))?;
+
+    let mut session = ctx.db.restore_session().session_id().find(&session_id)
+        .ok_or(format!("No restore session '{}' - call begin_restore first", session_id))?;
+
+    if session.completed {
+        return Err(format!("Restore session '{}' already finished", session_id));
+    }
+
+    if chunk_index < session.chunks_received {
+        log::info!("[RESTORE] session '{}' chunk {} already applied, ignoring retry", session_id, chunk_index);
+        return Ok(());
+    }
+    if chunk_index > session.chunks_received {
+        return Err(format!(
+            "Restore session '{}': expected chunk {}, got {}",
+            session_id, session.chunks_received, chunk_index
+        ));
+    }
+
+    let json_data = maybe_decompress(&json_data)?;
+    let rows_inserted = restore_chunk_rows(ctx, &session.table_name, &json_data)?;
+
+    session.chunks_received += 1;
+    session.rows_restored += rows_inserted;
+    session.last_chunk_at = ctx.timestamp;
+    ctx.db.restore_session().session_id().update(session);
+
+    Ok(())
+}
+
+/// Mark a restore session complete once every expected chunk has landed.
+/// Rejects a session that's still missing chunks, so a premature
+/// finish_restore can't hide a dropped chunk.
+#[reducer]
+pub fn finish_restore(ctx: &ReducerContext, session_id: String) -> Result<(), String> {
+    check_capability(ctx, "finish_restore", 1)
+        .map_err(|e| format!("Unauthorized finish_restore attempt by {}: {}", ctx.sender, e))?;
+
+    let mut session = ctx.db.restore_session().session_id().find(&session_id)
+        .ok_or(format!("No restore session '{}'", session_id))?;
+
+    if session.chunks_received != session.chunks_expected {
+        return Err(format!(
+            "Restore session '{}' incomplete: {}/{} chunks received",
+            session_id, session.chunks_received, session.chunks_expected
+        ));
+    }
+
+    session.completed = true;
+    let (table_name, rows_restored, chunks_received) =
+        (session.table_name.clone(), session.rows_restored, session.chunks_received);
+    ctx.db.restore_session().session_id().update(session);
+
+    log::info!(
+        "✅ Restore session '{}' complete: {} {} rows across {} chunks",
+        session_id, rows_restored, table_name, chunks_received
+    );
     Ok(())
 }
@@ -0,0 +1,90 @@
+//! Overflow-safe Timestamp arithmetic.
+//!
+//! `Timestamp`'s underlying micros-since-epoch is an i64, so plain `+`/`-`
+//! on it (or on the result of `to_micros_since_unix_epoch()`) panics on
+//! overflow in debug builds and wraps in release - and the common shortcut
+//! of casting to u64 before a `saturating_sub` silently produces garbage for
+//! any pre-epoch timestamp (the cast wraps a negative value to a huge one).
+//! Route backoff and day/week boundary math through here instead.
+
+use spacetimedb::Timestamp;
+
+/// `t + micros`, or `None` if that would overflow `i64`.
+pub fn checked_add_micros(t: Timestamp, micros: i64) -> Option<Timestamp> {
+    t.to_micros_since_unix_epoch()
+        .checked_add(micros)
+        .map(Timestamp::from_micros_since_unix_epoch)
+}
+
+/// `t - micros`, or `None` if that would overflow `i64`.
+pub fn checked_sub_micros(t: Timestamp, micros: i64) -> Option<Timestamp> {
+    t.to_micros_since_unix_epoch()
+        .checked_sub(micros)
+        .map(Timestamp::from_micros_since_unix_epoch)
+}
+
+/// `a - b` in micros, or `None` if that would overflow `i64`.
+pub fn diff_micros(a: Timestamp, b: Timestamp) -> Option<i64> {
+    a.to_micros_since_unix_epoch()
+        .checked_sub(b.to_micros_since_unix_epoch())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts(micros: i64) -> Timestamp {
+        Timestamp::from_micros_since_unix_epoch(micros)
+    }
+
+    fn micros_of(t: Option<Timestamp>) -> Option<i64> {
+        t.map(|t| t.to_micros_since_unix_epoch())
+    }
+
+    #[test]
+    fn test_checked_add_micros_normal() {
+        assert_eq!(micros_of(checked_add_micros(ts(1_000_000), 500_000)), Some(1_500_000));
+    }
+
+    #[test]
+    fn test_checked_add_micros_negative_timestamp() {
+        // Pre-epoch timestamp plus a positive offset
+        assert_eq!(micros_of(checked_add_micros(ts(-10_000_000), 5_000_000)), Some(-5_000_000));
+    }
+
+    #[test]
+    fn test_checked_add_micros_overflow() {
+        assert_eq!(micros_of(checked_add_micros(ts(i64::MAX - 10), 100)), None);
+    }
+
+    #[test]
+    fn test_checked_add_micros_underflow() {
+        assert_eq!(micros_of(checked_add_micros(ts(i64::MIN + 10), -100)), None);
+    }
+
+    #[test]
+    fn test_checked_sub_micros_negative_timestamp() {
+        assert_eq!(micros_of(checked_sub_micros(ts(-5_000_000), 5_000_000)), Some(-10_000_000));
+    }
+
+    #[test]
+    fn test_checked_sub_micros_underflow() {
+        assert_eq!(micros_of(checked_sub_micros(ts(i64::MIN + 10), 100)), None);
+    }
+
+    #[test]
+    fn test_diff_micros_normal() {
+        assert_eq!(diff_micros(ts(10_000_000), ts(4_000_000)), Some(6_000_000));
+    }
+
+    #[test]
+    fn test_diff_micros_pre_epoch() {
+        assert_eq!(diff_micros(ts(-1_000_000), ts(-5_000_000)), Some(4_000_000));
+    }
+
+    #[test]
+    fn test_diff_micros_overflow() {
+        // MAX - MIN overflows i64
+        assert_eq!(diff_micros(ts(i64::MAX), ts(i64::MIN)), None);
+    }
+}
@@ -0,0 +1,279 @@
+//! Per-player timezone support for daily/weekly reset boundaries.
+//!
+//! The old reset logic assumed a fixed "midnight PST = 8am UTC" offset, which
+//! is wrong half the year (PDT is UTC-7) and for any player outside the
+//! Pacific zone. This module stores a small fixed table of US zones (standard
+//! offset + whether they observe the US DST rule, in effect since 2007) rather
+//! than a full IANA tzdata - good enough for "when does a US student's day
+//! roll over" without pulling in a timezone database this crate doesn't have.
+//!
+//! Everything here operates on signed microseconds-since-epoch so it behaves
+//! correctly for the (vanishingly rare but cheap-to-get-right) pre-epoch
+//! timestamp, unlike the `as u64` casts this replaced.
+
+use spacetimedb::Timestamp;
+
+const MICROS_PER_SEC: i64 = 1_000_000;
+const MICROS_PER_HOUR: i64 = 3_600 * MICROS_PER_SEC;
+const MICROS_PER_DAY: i64 = 24 * MICROS_PER_HOUR;
+
+/// (IANA name, standard (non-DST) UTC offset in hours, observes US DST rule)
+const ZONE_TABLE: &[(&str, i64, bool)] = &[
+    ("America/Los_Angeles", -8, true),
+    ("America/Denver", -7, true),
+    ("America/Phoenix", -7, false), // Arizona does not observe DST
+    ("America/Chicago", -6, true),
+    ("America/New_York", -5, true),
+    ("UTC", 0, false),
+];
+
+/// What every player had before this feature existed - keeps old behavior
+/// for players whose timezone field hasn't been set to anything else.
+pub const DEFAULT_ZONE: &str = "America/Los_Angeles";
+
+fn zone_info(zone: &str) -> (i64, bool) {
+    ZONE_TABLE
+        .iter()
+        .find(|(name, _, _)| *name == zone)
+        .map(|(_, offset, dst)| (*offset, *dst))
+        .unwrap_or((-8, true)) // unrecognized zone -> old fixed PST/PDT behavior
+}
+
+pub(crate) fn floor_div(a: i64, b: i64) -> i64 {
+    let q = a / b;
+    let r = a % b;
+    if r != 0 && (r < 0) != (b < 0) {
+        q - 1
+    } else {
+        q
+    }
+}
+
+fn floor_mod(a: i64, b: i64) -> i64 {
+    a - floor_div(a, b) * b
+}
+
+/// Civil-to-days and days-to-civil conversions (Howard Hinnant's well-known
+/// proleptic Gregorian algorithm). `days` is days since 1970-01-01.
+fn days_from_civil(y: i64, m: u32, d: u32) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = floor_div(y, 400);
+    let yoe = y - era * 400; // [0, 399]
+    let mp = (m as i64 + 9) % 12; // [0, 11], Mar = 0
+    let doy = (153 * mp + 2) / 5 + d as i64 - 1; // [0, 365]
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy; // [0, 146096]
+    era * 146_097 + doe - 719_468
+}
+
+fn civil_from_days(z: i64) -> (i64, u32, u32) {
+    let z = z + 719_468;
+    let era = floor_div(z, 146_097);
+    let doe = z - era * 146_097; // [0, 146096]
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365; // [0, 399]
+    let y = yoe + era * 400;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100); // [0, 365]
+    let mp = (5 * doy + 2) / 153; // [0, 11]
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32; // [1, 31]
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32; // [1, 12]
+    let y = if m <= 2 { y + 1 } else { y };
+    (y, m, d)
+}
+
+/// Day-of-month of the nth Sunday (1-indexed) of a given month.
+fn nth_sunday_of_month(year: i64, month: u32, nth: i64) -> u32 {
+    let first_of_month = days_from_civil(year, month, 1);
+    let first_weekday = floor_mod(first_of_month + 4, 7); // 0 = Sunday (epoch day 0 was Thursday)
+    let first_sunday = if first_weekday == 0 { 1 } else { 1 + (7 - first_weekday) };
+    (first_sunday + 7 * (nth - 1)) as u32
+}
+
+/// UTC instant (micros) that US DST begins in `year` for a zone with the
+/// given standard offset: 2nd Sunday of March, 2:00am standard time.
+fn dst_start_utc_micros(year: i64, std_offset_hours: i64) -> i64 {
+    let day = nth_sunday_of_month(year, 3, 2);
+    let days = days_from_civil(year, 3, day);
+    days * MICROS_PER_DAY + 2 * MICROS_PER_HOUR - std_offset_hours * MICROS_PER_HOUR
+}
+
+/// UTC instant (micros) that US DST ends in `year`: 1st Sunday of November,
+/// 2:00am daylight time (= 1:00am standard time - the "fall back" instant).
+fn dst_end_utc_micros(year: i64, std_offset_hours: i64) -> i64 {
+    let day = nth_sunday_of_month(year, 11, 1);
+    let days = days_from_civil(year, 11, day);
+    days * MICROS_PER_DAY + 1 * MICROS_PER_HOUR - std_offset_hours * MICROS_PER_HOUR
+}
+
+fn is_dst_active(zone: &str, utc_micros: i64) -> bool {
+    let (std_offset, observes_dst) = zone_info(zone);
+    if !observes_dst {
+        return false;
+    }
+    // Only used to pick which year's March/November transition table applies,
+    // so it doesn't need to be offset-exact near midnight on New Year's Eve.
+    let approx_day = floor_div(utc_micros + std_offset * MICROS_PER_HOUR, MICROS_PER_DAY);
+    let (year, _, _) = civil_from_days(approx_day);
+    let start = dst_start_utc_micros(year, std_offset);
+    let end = dst_end_utc_micros(year, std_offset);
+    utc_micros >= start && utc_micros < end
+}
+
+fn utc_offset_micros(zone: &str, utc_micros: i64) -> i64 {
+    let (std_offset, _) = zone_info(zone);
+    let dst_hours = if is_dst_active(zone, utc_micros) { 1 } else { 0 };
+    (std_offset + dst_hours) * MICROS_PER_HOUR
+}
+
+/// Local civil day number (days since 1970-01-01 in the player's local time).
+fn local_day_number(zone: &str, utc_micros: i64) -> i64 {
+    floor_div(utc_micros + utc_offset_micros(zone, utc_micros), MICROS_PER_DAY)
+}
+
+/// Day number (in the epoch-days domain) of the Monday that starts the local
+/// week containing `utc_micros`.
+fn local_week_start_day_number(zone: &str, utc_micros: i64) -> i64 {
+    let day = local_day_number(zone, utc_micros);
+    let weekday_mon0 = floor_mod(day + 3, 7); // epoch day 0 (Thu) -> index 3, Mon -> 0
+    day - weekday_mon0
+}
+
+/// UTC instant of local midnight for a given local day number. Transitions
+/// happen at 2am local, never at midnight, so the offset in effect at
+/// midnight is whatever was active just before the transition (if any) -
+/// not necessarily the offset at whatever instant the caller queried from.
+/// One correction pass is enough since a given day has at most one transition.
+fn day_number_to_midnight_utc_micros(zone: &str, day: i64) -> i64 {
+    let guess = day * MICROS_PER_DAY - utc_offset_micros(zone, day * MICROS_PER_DAY);
+    let offset = utc_offset_micros(zone, guess);
+    day * MICROS_PER_DAY - offset
+}
+
+/// UTC instant (micros) of local midnight for the day containing `utc_micros`.
+fn local_day_start_utc_micros(zone: &str, utc_micros: i64) -> i64 {
+    day_number_to_midnight_utc_micros(zone, local_day_number(zone, utc_micros))
+}
+
+/// UTC instant (micros) of local midnight on the Monday that starts the week
+/// containing `utc_micros`.
+fn local_week_start_utc_micros(zone: &str, utc_micros: i64) -> i64 {
+    day_number_to_midnight_utc_micros(zone, local_week_start_day_number(zone, utc_micros))
+}
+
+/// True if `current` falls on a later local civil day than `last`, in `zone`.
+pub fn is_new_day(zone: &str, last: Timestamp, current: Timestamp) -> bool {
+    local_day_number(zone, current.to_micros_since_unix_epoch())
+        > local_day_number(zone, last.to_micros_since_unix_epoch())
+}
+
+/// True if `current` falls in a later local week (Monday-start) than `last`, in `zone`.
+pub fn is_new_week(zone: &str, last: Timestamp, current: Timestamp) -> bool {
+    local_week_start_day_number(zone, current.to_micros_since_unix_epoch())
+        > local_week_start_day_number(zone, last.to_micros_since_unix_epoch())
+}
+
+/// Number of local civil days between `last` and `current` (0 if `current`
+/// isn't later, same semantics as the old saturating_sub-based helper).
+pub fn days_between(zone: &str, last: Timestamp, current: Timestamp) -> u64 {
+    let delta = local_day_number(zone, current.to_micros_since_unix_epoch())
+        - local_day_number(zone, last.to_micros_since_unix_epoch());
+    delta.max(0) as u64
+}
+
+/// UTC micros-since-epoch of local midnight for the day `current` falls on.
+pub fn today_start_micros(zone: &str, current: Timestamp) -> i64 {
+    local_day_start_utc_micros(zone, current.to_micros_since_unix_epoch())
+}
+
+/// UTC micros-since-epoch of local midnight on the Monday of the week `current` falls in.
+pub fn week_start_micros(zone: &str, current: Timestamp) -> i64 {
+    local_week_start_utc_micros(zone, current.to_micros_since_unix_epoch())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts(micros: i64) -> Timestamp {
+        Timestamp::from_micros_since_unix_epoch(micros)
+    }
+
+    #[test]
+    fn test_civil_day_roundtrip() {
+        // 2024-03-10 (the day of the US spring-forward transition that year)
+        let days = days_from_civil(2024, 3, 10);
+        assert_eq!(civil_from_days(days), (2024, 3, 10));
+        // Pre-epoch date roundtrips too
+        let days = days_from_civil(1969, 12, 31);
+        assert_eq!(days, -1);
+        assert_eq!(civil_from_days(days), (1969, 12, 31));
+    }
+
+    #[test]
+    fn test_spring_forward_day_is_23_hours() {
+        // 2024-03-10: US DST begins (01:59 PST jumps straight to 03:00 PDT).
+        // Local midnight of 2024-03-10 to local midnight of 2024-03-11 spans
+        // only 23 hours.
+        let noon_mar10 = days_from_civil(2024, 3, 10) * MICROS_PER_DAY + 12 * MICROS_PER_HOUR;
+        let noon_mar11 = days_from_civil(2024, 3, 11) * MICROS_PER_DAY + 12 * MICROS_PER_HOUR;
+        let midnight = local_day_start_utc_micros("America/Los_Angeles", noon_mar10);
+        let next_midnight = local_day_start_utc_micros("America/Los_Angeles", noon_mar11);
+        assert_eq!(next_midnight - midnight, 23 * MICROS_PER_HOUR);
+    }
+
+    #[test]
+    fn test_fall_back_day_is_25_hours() {
+        // 2024-11-03: US DST ends. Local midnight of 2024-11-03 to local
+        // midnight of 2024-11-04 spans 25 hours.
+        let noon_nov3 = days_from_civil(2024, 11, 3) * MICROS_PER_DAY + 12 * MICROS_PER_HOUR;
+        let noon_nov4 = days_from_civil(2024, 11, 4) * MICROS_PER_DAY + 12 * MICROS_PER_HOUR;
+        let midnight = local_day_start_utc_micros("America/Los_Angeles", noon_nov3);
+        let next_midnight = local_day_start_utc_micros("America/Los_Angeles", noon_nov4);
+        assert_eq!(next_midnight - midnight, 25 * MICROS_PER_HOUR);
+    }
+
+    #[test]
+    fn test_is_new_day_across_dst_transition() {
+        // On the 23-hour spring-forward day, a timestamp exactly 23 hours
+        // after local midnight is the next local day; one second earlier
+        // is still the same day.
+        let noon_mar10 = days_from_civil(2024, 3, 10) * MICROS_PER_DAY + 12 * MICROS_PER_HOUR;
+        let midnight = local_day_start_utc_micros("America/Los_Angeles", noon_mar10);
+        assert!(!is_new_day(
+            "America/Los_Angeles",
+            ts(midnight),
+            ts(midnight + 23 * MICROS_PER_HOUR - MICROS_PER_SEC)
+        ));
+        assert!(is_new_day(
+            "America/Los_Angeles",
+            ts(midnight),
+            ts(midnight + 23 * MICROS_PER_HOUR)
+        ));
+    }
+
+    #[test]
+    fn test_arizona_has_no_dst() {
+        // Phoenix stays on the same fixed offset across what would be a DST
+        // transition elsewhere - July and January agree on offset.
+        let summer = days_from_civil(2024, 7, 1) * MICROS_PER_DAY;
+        let winter = days_from_civil(2024, 1, 1) * MICROS_PER_DAY;
+        assert_eq!(
+            utc_offset_micros("America/Phoenix", summer),
+            utc_offset_micros("America/Phoenix", winter)
+        );
+    }
+
+    #[test]
+    fn test_unknown_zone_falls_back_to_old_pst_behavior() {
+        let utc_micros = days_from_civil(2024, 1, 15) * MICROS_PER_DAY + 8 * MICROS_PER_HOUR;
+        assert_eq!(local_day_number("bogus/zone", utc_micros), local_day_number("America/Los_Angeles", utc_micros));
+    }
+
+    #[test]
+    fn test_week_start_is_monday() {
+        // 1970-01-01 was a Thursday
+        let thursday = 0i64;
+        let week_start_day = local_week_start_day_number("UTC", thursday * MICROS_PER_DAY);
+        let (y, m, d) = civil_from_days(week_start_day);
+        // The Monday before 1970-01-01 is 1969-12-29
+        assert_eq!((y, m, d), (1969, 12, 29));
+    }
+}
@@ -1,58 +1,98 @@
 fn main() {
     println!("Testing Mastery Level Edge Cases\n");
-    
+
     // Test cases: (attempts, recent_responses, expected_level, description)
     let test_cases = vec![
         // Empty and very short strings
         (0, "", "Level 0", "Empty - no attempts"),
         (1, "F", "Level 1", "1 attempt - 1 correct"),
         (2, "FF", "Level 5", "2 attempts - 2 fast = Level 5"),
-        
+
         // Exactly 3 attempts (last-3 window filled)
         (3, "FFF", "Level 5", "3 Fast = Level 5!"),
         (3, "FFW", "Level 5", "2 Fast = Level 5 (2+ fast threshold)"),
         (3, "FWW", "Level 1", "1 correct = Level 1"),
         (3, "WWW", "Level 0", "0 correct = Level 0"),
-        (3, "MMM", "Level 2", "3 correct (medium) = Level 2 (accurate but slow)"),
-        
+        (3, "MMM", "Level 4", "3 correct, all medium = Level 4"),
+        (3, "SSS", "Level 2", "3 correct, all slow = Level 2 (accurate but slow)"),
+        (3, "MMS", "Level 3", "2 medium + 1 slow = Level 3 (mixed speed)"),
+        (3, "MSS", "Level 3", "1 medium + 2 slow = Level 3 (mixed speed)"),
+        (3, "FMS", "Level 4", "1 fast + 1 medium + 1 slow = Level 4"),
+        (3, "FSS", "Level 4", "1 fast + 2 slow = Level 4"),
+
         // 4-5 attempts (last 3 used)
         (4, "FFFF", "Level 5", "4/4 Fast = Level 5"),
         (4, "FWFF", "Level 5", "3/4 Fast = Level 5"),
         (4, "MMFF", "Level 5", "2 Fast = Level 5 (2+ fast threshold)"),
+        (4, "WMMS", "Level 3", "Last 3: MMS = Level 3"),
         (5, "FFFFF", "Level 5", "5/5 Fast = Level 5"),
         (5, "WFFFF", "Level 5", "4/5 Fast = Level 5"),
         (5, "WWFFF", "Level 5", "3/5 Fast = Level 5"),
         (5, "WWWFF", "Level 5", "2/5 Fast = Level 5 (2+ fast threshold)"),
-        
+        (5, "WWMSS", "Level 3", "Last 3: MSS = Level 3"),
+
         // 6-10 attempts (only last 3 used)
         (6, "WFFFFF", "Level 5", "Last 3: FFF = Level 5"),
         (7, "WWFFFFF", "Level 5", "Last 3: FFF = Level 5"),
         (8, "WWWFFFFF", "Level 5", "Last 3: FFF = Level 5"),
         (9, "WWWWFFFFF", "Level 5", "Last 3: FFF = Level 5"),
         (10, "WWWWWFFFFF", "Level 5", "Last 3: FFF = Level 5"),
-        
+
         // Exactly 10 (longer sequence)
         (10, "FFFFFWWWWW", "Level 0", "Last 3: WWW = Level 0"),
         (10, "MWFFFFFMFF", "Level 5", "Last 3: MFF = Level 5"),
-        
+        (10, "FFFFFWWWMM", "Level 3", "Last 3: WMM = Level 3 (1 miss + 2 medium)"),
+
         // More than 10 (longer sequence)
         (11, "FWWWWWFFFFF", "Level 5", "Long sequence, last 3: FFF"),
         (12, "FFWWWWWFFFFF", "Level 5", "Long sequence, last 3: FFF"),
     ];
-    
-    for (attempts, responses, expected, description) in test_cases {
-        let level = calculate_level(attempts, responses);
+
+    let mut failures = 0;
+    for (attempts, responses, expected, description) in &test_cases {
+        let level = calculate_level(*attempts, responses);
         let last_3 = get_last_3(responses);
-        let status = if level == expected { "✅" } else { "❌" };
-        
+        let status = if level == *expected { "✅" } else { "❌" };
+        if level != *expected {
+            failures += 1;
+        }
+
         println!("{} {} - {}", status, description, responses);
-        println!("   Attempts: {}, Last 3: '{}', Result: {}", 
+        println!("   Attempts: {}, Last 3: '{}', Result: {}",
             attempts, last_3, level);
-        if level != expected {
+        if level != *expected {
             println!("   ERROR: Expected {}", expected);
         }
         println!();
     }
+
+    // target_ms-driven classification of raw attempt times, feeding the same
+    // calculate_level used above
+    println!("Testing target_ms classification from raw attempt times\n");
+
+    let raw_cases: Vec<(u32, u32, Vec<(bool, u32)>, &str)> = vec![
+        (3, 7, vec![(true, 400), (true, 450), (true, 420)], "Well under 0.5x target -> all F"),
+        (3, 7, vec![(true, 900), (true, 950), (true, 890)], "Between 0.5x and 1x target -> all M"),
+        (3, 7, vec![(true, 1300), (true, 1250), (true, 1290)], "Between 1x and 1.5x target -> all S"),
+        (3, 7, vec![(false, 100), (false, 200), (true, 400)], "2 misses then 1 fast -> Level 1"),
+        (12, 12, vec![(true, 900), (true, 950), (true, 890)], "Two-digit operands raise target_ms, so the same ~900ms that's Medium for 3x7 (second case above) classifies as Fast here"),
+    ];
+
+    for (left, right, attempts, description) in &raw_cases {
+        let target_ms = target_ms_for_fact(*left, *right);
+        let responses: String = attempts.iter()
+            .map(|&(correct, time_ms)| classify_attempt(correct, time_ms, target_ms))
+            .collect();
+        let level = calculate_level(attempts.len() as u32, &responses);
+        println!("{} ({} x {}, target_ms={}) -> '{}' -> {}", description, left, right, target_ms, responses, level);
+    }
+
+    if failures > 0 {
+        println!("\n{} test case(s) FAILED", failures);
+        std::process::exit(1);
+    } else {
+        println!("\nAll test cases passed");
+    }
 }
 
 fn get_last_3(responses: &str) -> String {
@@ -64,33 +104,78 @@ fn get_last_3(responses: &str) -> String {
     }
 }
 
+fn digit_count(n: u32) -> u32 {
+    if n < 10 { 1 } else if n < 100 { 2 } else { 3 }
+}
+
+/// Per-fact target response time in milliseconds, seeded by operand digit
+/// count - a two-digit factor like 12x12 is inherently slower to recall
+/// than a single-digit one like 3x7. digit_count is always >= 1 per operand,
+/// so this is always >= 1200ms - there's no floor to clamp against.
+fn target_ms_for_fact(left: u32, right: u32) -> u32 {
+    let digits = digit_count(left) + digit_count(right);
+    // 1-digit x 1-digit facts (digits=2) target ~1200ms, plus ~400ms per
+    // extra digit of complexity beyond that baseline
+    1200 + digits.saturating_sub(2) * 400
+}
+
+/// Classify a single attempt against a fact's target_ms:
+///   incorrect                       -> W
+///   correct, time_ms <= 0.5x target -> F (fast)
+///   correct, time_ms <= target      -> M (medium)
+///   correct, time_ms <= 1.5x target -> S (slow but right)
+/// A correct answer slower than 1.5x target_ms still classifies as S -
+/// correctness, not speed, decides whether an attempt counts at all.
+fn classify_attempt(correct: bool, time_ms: u32, target_ms: u32) -> char {
+    if !correct {
+        return 'W';
+    }
+    if time_ms <= target_ms / 2 {
+        'F'
+    } else if time_ms <= target_ms {
+        'M'
+    } else {
+        'S'
+    }
+}
+
+/// Derive a 0-5 mastery level from the last 3 classified attempts (or fewer,
+/// if the fact has fewer than 3 attempts total):
+///   0 correct                                  -> Level 0
+///   1 correct                                  -> Level 1
+///   2+ correct, 2+ fast                        -> Level 5 (consistently fast, not a lucky guess)
+///   3 correct, all medium, or exactly 1 fast    -> Level 4
+///   2+ correct, all slow                       -> Level 2 (accurate but slow)
+///   everything else with 2+ correct            -> Level 3 (mixed speed)
 fn calculate_level(attempts: u32, responses: &str) -> &'static str {
     // First check: empty case
     if attempts == 0 || responses.is_empty() {
         return "Level 0";
     }
-    
+
     // Get last 3 characters (or all if fewer than 3)
     let last_3 = get_last_3(responses);
-    
-    // Count fast and correct
+
+    // Count fast/medium/slow/correct
     let correct_count = last_3.chars().filter(|&c| c != 'W').count();
     let fast_count = last_3.chars().filter(|&c| c == 'F').count();
-    
-    // Calculate level (simplified: last 3, L5 = 2+ fast)
+    let medium_count = last_3.chars().filter(|&c| c == 'M').count();
+    let slow_count = last_3.chars().filter(|&c| c == 'S').count();
+
+    if correct_count == 0 {
+        return "Level 0";
+    }
+    if correct_count == 1 {
+        return "Level 1";
+    }
     if fast_count >= 2 {
-        "Level 5"  // 2+ fast in last 3 (consistent, not lucky)
-    } else if correct_count >= 2 {
-        "Level 2"  // Accurate but slow (2+ correct)
-    } else if correct_count >= 1 {
-        "Level 1"  // Practicing
-    } else {
-        "Level 0"  // All wrong
+        return "Level 5"; // 2+ fast in last 3 (consistent, not lucky)
+    }
+    if correct_count == 3 && (medium_count == 3 || fast_count == 1) {
+        return "Level 4"; // all medium, or one fast carrying the rest
+    }
+    if slow_count == correct_count {
+        return "Level 2"; // accurate but slow
     }
-    // Note: Levels 3-4 (speed tiers) would need threshold multiplier logic
+    "Level 3" // accurate, mixed speed - faster than Level 2, not consistent enough for 4/5
 }
-
-
-
-
-